@@ -3,6 +3,18 @@ use serde::{Deserialize, Serialize};
 
 use crate::{CreationDate, OperationChecksumValue, OperationData, OperationId};
 
+/// Maximum number of operations the server accepts in one upload request.
+pub const MAX_OPERATIONS_PER_REQUEST: usize = 1000;
+
+/// Maximum total size (in bytes) of operations data the server accepts in one upload request.
+pub const MAX_OPERATIONS_DATA_PER_REQUEST: usize = 8 * 1024 * 1024;
+
+/// Maximum number of operations the server returns in one page.
+pub const MAX_OPERATIONS_PAGE_SIZE: usize = 1000;
+
+/// Operation checksum is a SHA-256 hash.
+pub const OPERATION_CHECKSUM_SIZE: usize = 32;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Operation {
@@ -12,6 +24,27 @@ pub struct Operation {
     pub checksum: OperationChecksumValue,
 }
 
+/// Opaque position in the operations list.
+///
+/// The server returns it with every page except the last one. Pass it to the next page request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, AsRef, From, Into)]
+pub struct ContinuationToken(String);
+
+impl ContinuationToken {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// One page of the user's operations.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationsPage {
+    pub operations: Vec<Operation>,
+    /// Token of the next page. `None` means this is the last page.
+    pub next: Option<ContinuationToken>,
+}
+
 #[derive(Debug, Serialize, Deserialize, AsRef, From, Into)]
 pub struct BlockChecksum(pub Vec<u8>);
 
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::OperationId;
+
 #[cfg_attr(feature = "server", derive(rocket::Responder))]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Error {
@@ -29,6 +31,109 @@ pub enum Error {
 
     #[cfg_attr(feature = "server", response(status = 401, content_type = "json"))]
     Unauthorized(String),
+
+    /// The upload would exceed the user's storage quota.
+    #[cfg_attr(feature = "server", response(status = 413, content_type = "json"))]
+    QuotaExceeded(String),
+
+    /// Uploaded operations were rejected. The body contains the JSON-encoded [OperationRejection].
+    #[cfg_attr(feature = "server", response(status = 422, content_type = "json"))]
+    OperationRejected(OperationRejection),
+
+    /// The authenticated identity sent too many requests. The body contains the JSON-encoded [RateLimited].
+    #[cfg_attr(feature = "server", response(status = 429, content_type = "json"))]
+    RateLimited(RateLimited),
+}
+
+/// The reason why the server rejected uploaded operations.
+///
+/// None of the operations are saved when the request is rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum OperationRejection {
+    /// The request contains more than `max` operations.
+    #[serde(rename_all = "camelCase")]
+    TooManyOperations { max: usize },
+    /// The total size of operations data exceeds `max` bytes.
+    #[serde(rename_all = "camelCase")]
+    TooMuchData { max: usize },
+    /// The operation checksum is not a SHA-256 hash.
+    #[serde(rename_all = "camelCase")]
+    InvalidChecksum { id: OperationId },
+    /// The operation with the same id but a different checksum already exists.
+    #[serde(rename_all = "camelCase")]
+    ChecksumMismatch { id: OperationId },
+}
+
+impl std::fmt::Display for OperationRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperationRejection::TooManyOperations { max } => {
+                write!(f, "too many operations in one request (max {max})")
+            }
+            OperationRejection::TooMuchData { max } => {
+                write!(f, "operations data is too big (max {max} bytes per request)")
+            }
+            OperationRejection::InvalidChecksum { id } => write!(f, "operation {} has invalid checksum", id.as_ref()),
+            OperationRejection::ChecksumMismatch { id } => {
+                write!(f, "operation {} already exists with a different checksum", id.as_ref())
+            }
+        }
+    }
+}
+
+/// Rate limit rejection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimited {
+    /// Number of seconds after which the request can be retried.
+    pub retry_after: u64,
+}
+
+#[cfg(feature = "server")]
+mod impl_responder {
+    use rocket::Request;
+    use rocket::response::{self, Responder};
+    use rocket::serde::json::Json;
+
+    macro_rules! impl_json_responder {
+        ($ty:ty) => {
+            impl<'r> Responder<'r, 'static> for $ty {
+                fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+                    Json(self).respond_to(req)
+                }
+            }
+        };
+    }
+
+    impl_json_responder!(super::OperationRejection);
+    impl_json_responder!(super::RateLimited);
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{OperationRejection, RateLimited};
+
+    #[test]
+    fn rejection_wire_format() {
+        let id = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let rejection = OperationRejection::ChecksumMismatch { id: id.into() };
+
+        let json = serde_json::to_string(&rejection).unwrap();
+        assert_eq!(json, format!(r#"{{"reason":"checksumMismatch","id":"{id}"}}"#));
+        assert_eq!(serde_json::from_str::<OperationRejection>(&json).unwrap(), rejection);
+
+        assert_eq!(
+            serde_json::to_string(&OperationRejection::TooManyOperations { max: 10 }).unwrap(),
+            r#"{"reason":"tooManyOperations","max":10}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<RateLimited>(r#"{"retryAfter":3}"#).unwrap(),
+            RateLimited { retry_after: 3 }
+        );
+    }
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Files garbage collection request.
+///
+/// The server can not decrypt operations, so only the client knows which files are still referenced.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileGcRequest {
+    /// Ids of all files referenced by the user's data.
+    pub live_files: Vec<Uuid>,
+}
+
+/// Files garbage collection result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileGcReport {
+    /// Number of unreferenced files waiting for the grace period to expire.
+    pub orphaned: u64,
+    /// Number of deleted files.
+    pub deleted: u64,
+}
@@ -1,13 +1,17 @@
 mod data;
 mod error;
+mod file;
+mod usage;
 mod user;
 
 pub use data::*;
 use derive_more::{AsRef, From, Into};
 pub use error::*;
+pub use file::*;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use time::serde::rfc3339;
+pub use usage::*;
 pub use user::*;
 
 #[derive(Debug, Serialize, Deserialize, AsRef, From, Copy, Clone, Into, PartialEq, Eq, Hash)]
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// User's storage usage on the sync server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    /// Number of stored operations.
+    pub operations: u64,
+    /// Total size of stored operations data in bytes.
+    pub operation_bytes: u64,
+    /// Number of stored files.
+    pub files: u64,
+    /// Total size of stored files in bytes.
+    pub file_bytes: u64,
+    /// Storage quota in bytes. `None` means the storage is unlimited.
+    pub quota: Option<u64>,
+}
+
+impl Usage {
+    /// Returns the total number of used bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.operation_bytes + self.file_bytes
+    }
+}
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+create table api_token (
+    id uuid primary key,
+    subject text not null,
+    token_hash bytea not null unique,
+    created_at timestamp with time zone not null
+);
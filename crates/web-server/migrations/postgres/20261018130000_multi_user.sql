@@ -0,0 +1,19 @@
+-- Add migration script here
+
+alter table "user" add column subject text unique;
+
+alter table operation add column user_id uuid references "user" (id);
+
+-- Operations uploaded before the multi-user support belong to the only existing user.
+-- If the user has never been initialized, the operations are kept by the legacy user without the secret key hash.
+-- It is set by the subject bound to the legacy user (`web-server bind-legacy-user <subject>`) during the first sign-in.
+insert into "user" (id, secret_key_hash)
+    select '00000000-0000-0000-0000-000000000000', ''
+    where not exists (select 1 from "user") and exists (select 1 from operation);
+update operation set user_id = (select id from "user" limit 1);
+
+alter table operation alter column user_id set not null;
+alter table operation drop constraint operation_pkey;
+alter table operation add primary key (user_id, id);
+
+create index operation_user_id_created_at_idx on operation (user_id, created_at);
@@ -0,0 +1,9 @@
+-- Add migration script here
+
+create table file (
+    id uuid not null,
+    user_id uuid not null references "user" (id),
+    size bigint not null,
+    created_at timestamp with time zone not null,
+    primary key (user_id, id)
+);
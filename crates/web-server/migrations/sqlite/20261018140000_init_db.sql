@@ -0,0 +1,26 @@
+-- Add migration script here
+
+create table "user" (
+    id blob primary key,
+    subject text unique,
+    secret_key_hash text not null
+);
+
+create table operation (
+    id blob not null,
+    user_id blob not null references "user" (id),
+    -- Always in UTC, so timestamps can be compared as strings.
+    created_at text not null,
+    data blob not null,
+    checksum blob not null,
+    primary key (user_id, id)
+);
+
+create index operation_user_id_created_at_idx on operation (user_id, created_at);
+
+create table api_token (
+    id blob primary key,
+    subject text not null,
+    token_hash blob not null unique,
+    created_at text not null
+);
@@ -0,0 +1,9 @@
+-- Add migration script here
+
+create table file (
+    id blob not null,
+    user_id blob not null references "user" (id),
+    size integer not null,
+    created_at text not null,
+    primary key (user_id, id)
+);
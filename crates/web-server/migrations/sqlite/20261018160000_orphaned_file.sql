@@ -0,0 +1,9 @@
+-- Add migration script here
+
+-- Files that are not referenced by the user's data anymore. They are deleted after the grace period.
+create table orphaned_file (
+    id blob not null,
+    user_id blob not null references "user" (id),
+    orphaned_at text not null,
+    primary key (user_id, id)
+);
@@ -0,0 +1,6 @@
+-- Operations are ordered by creation time and id. The id makes the order stable for operations with the same
+-- creation time, so the operations list can be paginated using the (created_at, id) cursor.
+
+drop index operation_user_id_created_at_idx;
+
+create index operation_user_id_created_at_id_idx on operation (user_id, created_at, id);
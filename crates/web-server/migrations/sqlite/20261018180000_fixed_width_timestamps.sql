@@ -0,0 +1,23 @@
+-- Timestamps used to be stored in the RFC 3339 format with trimmed trailing zeros of the fraction
+-- (`2026-10-18T14:00:00Z`, `2026-10-18T14:00:00.5Z`). Such strings are not ordered as the timestamps they represent.
+-- Now timestamps are stored in UTC with the fixed 9-digit fraction: `2026-10-18T14:00:00.000000000Z`.
+
+update operation set created_at = substr(created_at, 1, 19) || '.'
+    || substr(iif(substr(created_at, 20, 1) = '.', substr(created_at, 21, length(created_at) - 21), '') || '000000000', 1, 9)
+    || 'Z'
+where length(created_at) <> 30;
+
+update file set created_at = substr(created_at, 1, 19) || '.'
+    || substr(iif(substr(created_at, 20, 1) = '.', substr(created_at, 21, length(created_at) - 21), '') || '000000000', 1, 9)
+    || 'Z'
+where length(created_at) <> 30;
+
+update orphaned_file set orphaned_at = substr(orphaned_at, 1, 19) || '.'
+    || substr(iif(substr(orphaned_at, 20, 1) = '.', substr(orphaned_at, 21, length(orphaned_at) - 21), '') || '000000000', 1, 9)
+    || 'Z'
+where length(orphaned_at) <> 30;
+
+update api_token set created_at = substr(created_at, 1, 19) || '.'
+    || substr(iif(substr(created_at, 20, 1) = '.', substr(created_at, 21, length(created_at) - 21), '') || '000000000', 1, 9)
+    || 'Z'
+where length(created_at) <> 30;
@@ -0,0 +1,361 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use rocket::tokio::sync::{Mutex, RwLock};
+
+use crate::Result;
+
+/// How long the fetched keys are considered fresh.
+pub const DEFAULT_JWKS_TTL: Duration = Duration::from_secs(10 * 60);
+/// How long the expired keys can still be used when the identity provider is unreachable.
+pub const DEFAULT_JWKS_MAX_STALENESS: Duration = Duration::from_secs(24 * 60 * 60);
+/// Minimal interval between forced refreshes caused by unknown key ids.
+///
+/// It protects the identity provider (and us) from being flooded by tokens with random `kid`s.
+const MIN_FORCED_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct CachedJwks {
+    jwks: Arc<JwkSet>,
+    fetched_at: Instant,
+}
+
+/// Caches the identity provider's public keys (JWKS).
+///
+/// * Fresh keys (younger than `ttl`) are used as is.
+/// * Expired keys are still used (stale-while-revalidate) while the background task refreshes them.
+///   If the identity provider is unreachable, expired keys are used up to `max_staleness`.
+/// * If the token has an unknown key id, the keys are refreshed immediately (key rotation).
+pub struct JwksCache {
+    url: String,
+    ttl: Duration,
+    max_staleness: Duration,
+    client: reqwest::Client,
+    cached: RwLock<Option<CachedJwks>>,
+    /// Serializes fetches, so concurrent requests do not hit the identity provider at the same time.
+    fetch_lock: Mutex<()>,
+    is_refreshing: AtomicBool,
+}
+
+impl JwksCache {
+    pub fn new(url: String, ttl: Duration, max_staleness: Duration) -> Self {
+        Self {
+            url,
+            ttl,
+            max_staleness,
+            client: reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .expect("reqwest client should be built"),
+            cached: RwLock::new(None),
+            fetch_lock: Mutex::new(()),
+            is_refreshing: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the key with the given key id.
+    ///
+    /// Returns `Ok(None)` if the key does not exist even after the refresh.
+    pub async fn key(self: &Arc<Self>, k_id: &str) -> Result<Option<Jwk>> {
+        let cached = self
+            .cached
+            .read()
+            .await
+            .as_ref()
+            .map(|cached| (Arc::clone(&cached.jwks), cached.fetched_at.elapsed()));
+
+        let Some((jwks, age)) = cached else {
+            let jwks = self.refresh(None).await?;
+
+            return Ok(jwks.find(k_id).cloned());
+        };
+
+        if let Some(key) = jwks.find(k_id) {
+            if age > self.max_staleness {
+                // The keys are too old to trust them. Refresh synchronously.
+                return Ok(self.refresh(None).await?.find(k_id).cloned());
+            }
+
+            if age > self.ttl {
+                self.spawn_refresh();
+            }
+
+            return Ok(Some(key.clone()));
+        }
+
+        if age < MIN_FORCED_REFRESH_INTERVAL {
+            debug!(k_id, "Unknown key id, but the keys have been refreshed recently");
+
+            return Ok(None);
+        }
+
+        debug!(k_id, "Unknown key id. Refreshing keys...");
+
+        Ok(self.refresh(Some(k_id)).await?.find(k_id).cloned())
+    }
+
+    /// Refreshes the keys in the background.
+    fn spawn_refresh(self: &Arc<Self>) {
+        if self.is_refreshing.swap(true, Ordering::AcqRel) {
+            // Someone is already refreshing the keys.
+            return;
+        }
+
+        let cache = Arc::clone(self);
+        rocket::tokio::spawn(async move {
+            if let Err(err) = cache.refresh(None).await {
+                warn!(?err, url = cache.url, "Failed to refresh JWKS in the background");
+            }
+
+            cache.is_refreshing.store(false, Ordering::Release);
+        });
+    }
+
+    /// Fetches the keys and updates the cache.
+    ///
+    /// If the fetch fails, then the cached keys are returned (if they are not too old).
+    /// `k_id` is the key id that caused the refresh. If another task has already fetched keys
+    /// that contain it, then the fetch is skipped.
+    async fn refresh(&self, k_id: Option<&str>) -> Result<Arc<JwkSet>> {
+        let _guard = self.fetch_lock.lock().await;
+
+        // Another task may have refreshed the keys while we were waiting for the lock.
+        if let Some(cached) = self.cached.read().await.as_ref() {
+            let age = cached.fetched_at.elapsed();
+            let has_key = k_id.map(|k_id| cached.jwks.find(k_id).is_some()).unwrap_or(false);
+
+            if has_key || (k_id.is_none() && age <= self.ttl) || (k_id.is_some() && age < MIN_FORCED_REFRESH_INTERVAL) {
+                return Ok(Arc::clone(&cached.jwks));
+            }
+        }
+
+        match self.fetch().await {
+            Ok(jwks) => {
+                let jwks = Arc::new(jwks);
+
+                *self.cached.write().await = Some(CachedJwks {
+                    jwks: Arc::clone(&jwks),
+                    fetched_at: Instant::now(),
+                });
+
+                Ok(jwks)
+            }
+            Err(err) => {
+                error!(?err, url = self.url, "Failed to fetch JWKS");
+
+                match self.cached.read().await.as_ref() {
+                    Some(cached) if cached.fetched_at.elapsed() <= self.max_staleness => {
+                        warn!("Using stale JWKS because the identity provider is unreachable");
+
+                        Ok(Arc::clone(&cached.jwks))
+                    }
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<JwkSet> {
+        trace!(url = self.url, "Fetching JWKS");
+
+        Ok(self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::Mutex;
+
+    use super::JwksCache;
+
+    fn jwks(key_ids: &[&str]) -> String {
+        let keys = key_ids
+            .iter()
+            .map(|k_id| {
+                format!(
+                    r#"{{"kty":"RSA","kid":"{k_id}","alg":"RS256","use":"sig","n":"sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw","e":"AQAB"}}"#
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(r#"{{"keys":[{keys}]}}"#)
+    }
+
+    /// Tiny stand-in for the identity provider's JWKS endpoint.
+    struct JwksServer {
+        url: String,
+        body: Arc<Mutex<String>>,
+        requests: Arc<AtomicUsize>,
+        is_down: Arc<AtomicBool>,
+    }
+
+    impl JwksServer {
+        async fn start(key_ids: &[&str]) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/certs", listener.local_addr().unwrap());
+
+            let body = Arc::new(Mutex::new(jwks(key_ids)));
+            let requests = Arc::new(AtomicUsize::new(0));
+            let is_down = Arc::new(AtomicBool::new(false));
+
+            let server = Self {
+                url,
+                body: Arc::clone(&body),
+                requests: Arc::clone(&requests),
+                is_down: Arc::clone(&is_down),
+            };
+
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut buf = [0; 4096];
+                    let _ = stream.read(&mut buf).await.unwrap();
+
+                    requests.fetch_add(1, Ordering::SeqCst);
+
+                    let response = if is_down.load(Ordering::SeqCst) {
+                        String::from(
+                            "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        )
+                    } else {
+                        let body = body.lock().await.clone();
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                            body.len()
+                        )
+                    };
+
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                    let _ = stream.shutdown().await;
+                }
+            });
+
+            server
+        }
+
+        fn requests(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn fresh_keys_are_cached() {
+        let server = JwksServer::start(&["key-1"]).await;
+        let cache = Arc::new(JwksCache::new(
+            server.url.clone(),
+            Duration::from_secs(60),
+            Duration::from_secs(120),
+        ));
+
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        assert!(cache.key("key-1").await.unwrap().is_some());
+
+        assert_eq!(server.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_key_id_is_not_refreshed_too_often() {
+        let server = JwksServer::start(&["key-1"]).await;
+        let cache = Arc::new(JwksCache::new(
+            server.url.clone(),
+            Duration::from_secs(60),
+            Duration::from_secs(120),
+        ));
+
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        assert!(cache.key("random-key").await.unwrap().is_none());
+        assert!(cache.key("another-random-key").await.unwrap().is_none());
+
+        assert_eq!(server.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_key_id_triggers_refresh() {
+        let server = JwksServer::start(&["key-1"]).await;
+        let cache = Arc::new(JwksCache::new(
+            server.url.clone(),
+            Duration::from_secs(60),
+            Duration::from_secs(120),
+        ));
+
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        assert_eq!(server.requests(), 1);
+
+        // Key rotation.
+        *server.body.lock().await = jwks(&["key-2"]);
+        // Forced refreshes are rate limited. Pretend the previous fetch happened long enough ago.
+        cache.cached.write().await.as_mut().unwrap().fetched_at -= super::MIN_FORCED_REFRESH_INTERVAL;
+
+        assert!(cache.key("key-2").await.unwrap().is_some());
+        assert_eq!(server.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn stale_keys_are_used_during_outage() {
+        let server = JwksServer::start(&["key-1"]).await;
+        let cache = Arc::new(JwksCache::new(
+            server.url.clone(),
+            Duration::ZERO,
+            Duration::from_secs(120),
+        ));
+
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        server.is_down.store(true, Ordering::SeqCst);
+
+        // The keys are stale, and the identity provider is down, but the auth still works.
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(cache.key("key-1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn stale_keys_are_revalidated_in_background() {
+        let server = JwksServer::start(&["key-1"]).await;
+        let cache = Arc::new(JwksCache::new(
+            server.url.clone(),
+            Duration::ZERO,
+            Duration::from_secs(120),
+        ));
+
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        let requests = server.requests();
+
+        // Stale key is returned immediately...
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // ...and the keys are refreshed in the background.
+        assert!(server.requests() > requests);
+    }
+
+    #[tokio::test]
+    async fn too_old_keys_are_not_used() {
+        let server = JwksServer::start(&["key-1"]).await;
+        let cache = Arc::new(JwksCache::new(server.url.clone(), Duration::ZERO, Duration::ZERO));
+
+        assert!(cache.key("key-1").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        server.is_down.store(true, Ordering::SeqCst);
+
+        assert!(cache.key("key-1").await.is_err());
+    }
+}
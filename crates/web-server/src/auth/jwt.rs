@@ -0,0 +1,146 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, KeyAlgorithm};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::Deserialize;
+
+use super::Identity;
+use super::jwks::{DEFAULT_JWKS_MAX_STALENESS, JwksCache};
+use crate::{Error, Result};
+
+/// The only claims we care about. All other claims are ignored.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+/// Returns the signing algorithm of the JWK key algorithm. Encryption algorithms are not supported.
+fn signing_algorithm(algorithm: KeyAlgorithm) -> Option<Algorithm> {
+    match algorithm {
+        KeyAlgorithm::HS256 => Some(Algorithm::HS256),
+        KeyAlgorithm::HS384 => Some(Algorithm::HS384),
+        KeyAlgorithm::HS512 => Some(Algorithm::HS512),
+        KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+        KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+        KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+        KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+        KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+        KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+        KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+        KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+        KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+        KeyAlgorithm::RSA1_5
+        | KeyAlgorithm::RSA_OAEP
+        | KeyAlgorithm::RSA_OAEP_256
+        | KeyAlgorithm::UNKNOWN_ALGORITHM => None,
+    }
+}
+
+/// Returns the algorithm the key must be used with.
+///
+/// The token header is controlled by the client, so the algorithm is never taken from it. If the key does not
+/// specify the algorithm, it is derived from the key type. Symmetric keys without the algorithm are rejected.
+fn key_algorithm(key: &Jwk) -> Option<Algorithm> {
+    if let Some(algorithm) = key.common.key_algorithm {
+        return signing_algorithm(algorithm);
+    }
+
+    match &key.algorithm {
+        AlgorithmParameters::RSA(_) => Some(Algorithm::RS256),
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => Some(Algorithm::ES256),
+            EllipticCurve::P384 => Some(Algorithm::ES384),
+            _ => None,
+        },
+        AlgorithmParameters::OctetKeyPair(params) if params.curve == EllipticCurve::Ed25519 => Some(Algorithm::EdDSA),
+        AlgorithmParameters::OctetKeyPair(_) | AlgorithmParameters::OctetKey(_) => None,
+    }
+}
+
+/// Validates JWTs using the public keys from the JWKS endpoint.
+///
+/// The keys are cached. See [JwksCache] for the refresh policy.
+pub struct JwtVerifier {
+    jwks: Arc<JwksCache>,
+    audience: String,
+    issuer: Option<String>,
+}
+
+impl JwtVerifier {
+    pub fn new(jwks_url: String, jwks_ttl: Duration, audience: String, issuer: Option<String>) -> Self {
+        Self {
+            jwks: Arc::new(JwksCache::new(jwks_url, jwks_ttl, DEFAULT_JWKS_MAX_STALENESS)),
+            audience,
+            issuer,
+        }
+    }
+
+    /// Validates the token and returns the authenticated [Identity].
+    pub async fn verify(&self, token: &str) -> Result<Identity> {
+        let header = decode_header(token).map_err(|_| Error::Unauthorized("invalid authentication token header"))?;
+        let k_id = header.kid.ok_or(Error::Unauthorized(
+            "authentication token header does not contain 'kid' field",
+        ))?;
+
+        let key = self.jwks.key(&k_id).await?.ok_or(Error::Unauthorized(
+            "authentication token key id does not match any known key",
+        ))?;
+
+        let decoding_key = DecodingKey::from_jwk(&key)?;
+        let algorithm = key_algorithm(&key).ok_or(Error::Unauthorized(
+            "authentication token key algorithm is not supported",
+        ))?;
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.audience]);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let token_data = decode::<Claims>(token, &decoding_key, &validation).map_err(|err| {
+            debug!(?err, "Failed to validate authentication token");
+            Error::Unauthorized("invalid authentication token")
+        })?;
+        trace!(?token_data, "Decoded authentication token");
+
+        Ok(Identity {
+            subject: token_data.claims.sub,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::Algorithm;
+    use jsonwebtoken::jwk::Jwk;
+    use rocket::serde::json::from_str;
+
+    use super::key_algorithm;
+
+    const RSA_N: &str = "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw";
+
+    #[test]
+    fn key_algorithm_has_priority() {
+        let key: Jwk = from_str(&format!(
+            r#"{{"kty":"RSA","kid":"1","alg":"PS384","n":"{RSA_N}","e":"AQAB"}}"#
+        ))
+        .unwrap();
+
+        assert_eq!(key_algorithm(&key), Some(Algorithm::PS384));
+    }
+
+    #[test]
+    fn algorithm_is_derived_from_key_type() {
+        let key: Jwk = from_str(&format!(r#"{{"kty":"RSA","kid":"1","n":"{RSA_N}","e":"AQAB"}}"#)).unwrap();
+
+        assert_eq!(key_algorithm(&key), Some(Algorithm::RS256));
+    }
+
+    #[test]
+    fn symmetric_key_without_algorithm_is_rejected() {
+        let key: Jwk = from_str(r#"{"kty":"oct","kid":"1","k":"c2VjcmV0"}"#).unwrap();
+
+        assert_eq!(key_algorithm(&key), None);
+    }
+}
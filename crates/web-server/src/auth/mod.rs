@@ -0,0 +1,127 @@
+//! Authentication providers.
+//!
+//! The server does not implement any sign-in flow by itself. Instead, it validates the token
+//! provided by the client using one of the supported providers:
+//!
+//! * [AuthProvider::CloudflareAccess]: Cloudflare Zero Trust Access JWT from the `cf-access-jwt-assertion` header.
+//! * [AuthProvider::Oidc]: any OIDC-compatible identity provider. The JWT is taken from the `Authorization` header
+//!   and validated using the provider's JWKS.
+//! * [AuthProvider::Token]: server-issued API tokens. Only token hashes are stored in the database.
+//!
+//! The provider is selected using the `DATAANS_WEB_SERVER_AUTH_PROVIDER` env variable.
+
+mod jwks;
+mod jwt;
+
+use std::env;
+use std::time::Duration;
+
+pub use jwks::{DEFAULT_JWKS_TTL, JwksCache};
+pub use jwt::JwtVerifier;
+use rocket::http::HeaderMap;
+
+use crate::db::TokenDb;
+use crate::services::TokenService;
+use crate::{Error, Result};
+
+const AUTH_PROVIDER: &str = "DATAANS_WEB_SERVER_AUTH_PROVIDER";
+/// Optional JWKS cache TTL in seconds.
+const JWKS_TTL: &str = "DATAANS_WEB_SERVER_JWKS_TTL";
+
+const CF_TEAM_NAME: &str = "DATAANS_WEB_SERVER_CF_TEAM_NAME";
+const CF_AUD: &str = "DATAANS_WEB_CF_AUD";
+
+const OIDC_JWKS_URL: &str = "DATAANS_WEB_SERVER_OIDC_JWKS_URL";
+const OIDC_ISSUER: &str = "DATAANS_WEB_SERVER_OIDC_ISSUER";
+const OIDC_AUDIENCE: &str = "DATAANS_WEB_SERVER_OIDC_AUDIENCE";
+
+/// Cloudflare Access puts the validated JWT in this header.
+pub const CF_AUTH_HEADER_NAME: &str = "cf-access-jwt-assertion";
+/// Standard HTTP authorization header. Used for `Bearer` tokens.
+pub const AUTHORIZATION_HEADER_NAME: &str = "authorization";
+
+/// Authenticated identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Unique identifier of the authenticated subject.
+    ///
+    /// It is the `sub` claim for JWT-based providers and the token subject for API tokens.
+    pub subject: String,
+}
+
+/// Configured authentication provider.
+pub enum AuthProvider {
+    /// Cloudflare Zero Trust Access.
+    CloudflareAccess(JwtVerifier),
+    /// Generic OIDC provider with the JWKS endpoint.
+    Oidc(JwtVerifier),
+    /// Server-issued API tokens.
+    Token,
+}
+
+impl AuthProvider {
+    /// Creates the [AuthProvider] based on env variables.
+    ///
+    /// Panics if the configuration is incomplete. The server is useless without working auth.
+    pub fn from_env() -> Self {
+        let provider = env::var(AUTH_PROVIDER).unwrap_or_else(|_| String::from("cloudflare"));
+        let jwks_ttl = env::var(JWKS_TTL)
+            .map(|ttl| Duration::from_secs(ttl.parse().expect("JWKS TTL should be a number of seconds")))
+            .unwrap_or(DEFAULT_JWKS_TTL);
+
+        match provider.as_str() {
+            "cloudflare" => {
+                let team_name = env::var(CF_TEAM_NAME).expect("Cloudflare team name env var should be set");
+                let aud = env::var(CF_AUD).expect("Cloudflare AUD env var should be set");
+
+                AuthProvider::CloudflareAccess(JwtVerifier::new(
+                    format!("https://{team_name}.cloudflareaccess.com/cdn-cgi/access/certs"),
+                    jwks_ttl,
+                    aud,
+                    Some(format!("https://{team_name}.cloudflareaccess.com")),
+                ))
+            }
+            "oidc" => AuthProvider::Oidc(JwtVerifier::new(
+                env::var(OIDC_JWKS_URL).expect("OIDC JWKS URL env var should be set"),
+                jwks_ttl,
+                env::var(OIDC_AUDIENCE).expect("OIDC audience env var should be set"),
+                env::var(OIDC_ISSUER).ok(),
+            )),
+            "token" => AuthProvider::Token,
+            provider => panic!("Unsupported auth provider: {provider}. Supported values: cloudflare, oidc, token"),
+        }
+    }
+
+    /// Authenticates the request using request headers.
+    pub async fn authenticate<D: TokenDb>(
+        &self,
+        headers: &HeaderMap<'_>,
+        tokens: &TokenService<D>,
+    ) -> Result<Identity> {
+        match self {
+            AuthProvider::CloudflareAccess(verifier) => {
+                let token = headers
+                    .get_one(CF_AUTH_HEADER_NAME)
+                    .ok_or(Error::Unauthorized("missing authentication token"))?;
+
+                verifier.verify(token).await
+            }
+            AuthProvider::Oidc(verifier) => verifier.verify(bearer_token(headers)?).await,
+            AuthProvider::Token => tokens.verify(bearer_token(headers)?).await,
+        }
+    }
+}
+
+pub(crate) fn bearer_token<'h>(headers: &'h HeaderMap<'_>) -> Result<&'h str> {
+    let value = headers
+        .get_one(AUTHORIZATION_HEADER_NAME)
+        .ok_or(Error::Unauthorized("missing authentication token"))?;
+
+    value
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or(Error::Unauthorized(
+            "invalid authorization header: Bearer token expected",
+        ))
+}
@@ -0,0 +1,215 @@
+//! Server administration commands.
+//!
+//! Commands use the same env variables as the server itself (database URL, files storage, etc).
+
+use std::process::exit;
+
+use rocket::tokio::fs::File;
+use rocket::tokio::io::{BufReader, BufWriter};
+
+use crate::services::{Admin, TokenService, verify_archive};
+use crate::{connect_db, prepare_file_loader};
+
+const USAGE: &str = "Usage:
+  web-server                            Run the sync server.
+  web-server issue-token <subject>      Issue a new API token.
+  web-server export <archive>           Export all server data into the archive.
+  web-server import <archive>           Import the archive into an empty server.
+  web-server stats                      Print storage statistics of all users.
+  web-server verify [<archive>]         Check the server data consistency or the archive integrity.
+  web-server track-files                Register stored files missing in the database.
+  web-server reset-user <user> --yes    Delete all data of the user (selected by subject or id).
+  web-server bind-legacy-user <subject> Bind the user created before the multi-user support to the subject.";
+
+/// Runs the admin command and exits the process.
+pub async fn run(command: &str, mut args: impl Iterator<Item = String>) -> ! {
+    let result = match (command, args.next(), args.next()) {
+        ("issue-token", Some(subject), None) => issue_token(subject).await,
+        ("export", Some(path), None) => export(&path).await,
+        ("import", Some(path), None) => import(&path).await,
+        ("stats", None, None) => stats().await,
+        ("verify", None, None) => verify().await,
+        ("verify", Some(path), None) => verify_archive_file(&path).await,
+        ("track-files", None, None) => track_files().await,
+        ("reset-user", Some(user), Some(confirmation)) if confirmation == "--yes" => reset_user(&user).await,
+        ("reset-user", Some(_), None) => Err(String::from(
+            "This command deletes all user's data. Pass `--yes` to confirm.",
+        )),
+        ("bind-legacy-user", Some(subject), None) => bind_legacy_user(&subject).await,
+        _ => Err(String::from(USAGE)),
+    };
+
+    match result {
+        Ok(()) => exit(0),
+        Err(err) => {
+            eprintln!("{err}");
+            exit(1);
+        }
+    }
+}
+
+/// Issues a new API token and prints it to stdout.
+async fn issue_token(subject: String) -> Result<(), String> {
+    let token_service = TokenService::new(connect_db().await);
+    let token = token_service
+        .issue(subject)
+        .await
+        .map_err(|err| format!("Failed to issue a new API token: {err}"))?;
+
+    println!("{token}");
+
+    Ok(())
+}
+
+async fn export(path: &str) -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+    let file_saver = prepare_file_loader().await;
+
+    let file = File::create_new(path)
+        .await
+        .map_err(|err| format!("Failed to create the archive file: {err}"))?;
+
+    let summary = admin
+        .export(&file_saver, BufWriter::new(file))
+        .await
+        .map_err(|err| format!("Failed to export the server data: {err}"))?;
+
+    println!("Exported: {summary:?}");
+
+    Ok(())
+}
+
+async fn import(path: &str) -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+    let file_saver = prepare_file_loader().await;
+
+    let file = File::open(path)
+        .await
+        .map_err(|err| format!("Failed to open the archive file: {err}"))?;
+
+    let summary = admin
+        .import(&file_saver, BufReader::new(file))
+        .await
+        .map_err(|err| format!("Failed to import the archive: {err}"))?;
+
+    println!("Imported: {summary:?}");
+
+    Ok(())
+}
+
+async fn stats() -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+
+    let stats = admin
+        .stats()
+        .await
+        .map_err(|err| format!("Failed to collect statistics: {err}"))?;
+
+    println!(
+        "{:<36}  {:<24}  {:>10}  {:>15}  {:>8}  {:>14}",
+        "user id", "subject", "operations", "operation bytes", "files", "file bytes"
+    );
+    for user_stats in &stats {
+        let usage = &user_stats.usage;
+
+        println!(
+            "{:<36}  {:<24}  {:>10}  {:>15}  {:>8}  {:>14}",
+            user_stats.user.id,
+            user_stats.user.subject.as_deref().unwrap_or("<legacy user>"),
+            usage.operations,
+            usage.operation_bytes,
+            usage.files,
+            usage.file_bytes,
+        );
+    }
+    println!("Total users: {}", stats.len());
+
+    Ok(())
+}
+
+async fn verify() -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+    let file_saver = prepare_file_loader().await;
+
+    let report = admin
+        .verify(&file_saver)
+        .await
+        .map_err(|err| format!("Failed to verify the server data: {err}"))?;
+
+    for (user_id, id) in &report.malformed_checksums {
+        println!("Operation checksum is not a SHA-256 hash: user {user_id}, operation {id}");
+    }
+    for (user_id, id) in &report.missing_files {
+        println!("File is missing in the storage: user {user_id}, file {id}");
+    }
+    for (user_id, id) in &report.untracked_files {
+        println!("File is not tracked in the database: user {user_id}, file {id}");
+    }
+
+    if report.is_ok() {
+        println!("No problems found");
+
+        Ok(())
+    } else {
+        Err(String::from("Server data is inconsistent"))
+    }
+}
+
+async fn track_files() -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+    let file_saver = prepare_file_loader().await;
+
+    let registered = admin
+        .track_files(&file_saver)
+        .await
+        .map_err(|err| format!("Failed to register files: {err}"))?;
+
+    println!("Registered {registered} files");
+
+    Ok(())
+}
+
+async fn verify_archive_file(path: &str) -> Result<(), String> {
+    let file = File::open(path)
+        .await
+        .map_err(|err| format!("Failed to open the archive file: {err}"))?;
+
+    let summary = verify_archive(BufReader::new(file))
+        .await
+        .map_err(|err| format!("The archive is corrupted: {err}"))?;
+
+    println!("The archive is valid: {summary:?}");
+
+    Ok(())
+}
+
+async fn reset_user(user: &str) -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+    let file_saver = prepare_file_loader().await;
+
+    let usage = admin
+        .reset_user(&file_saver, user)
+        .await
+        .map_err(|err| format!("Failed to reset the user: {err}"))?;
+
+    println!(
+        "Deleted {} operations and {} files ({} bytes) of {user}",
+        usage.operations, usage.files, usage.file_bytes
+    );
+
+    Ok(())
+}
+
+async fn bind_legacy_user(subject: &str) -> Result<(), String> {
+    let admin = Admin::new(connect_db().await);
+    let file_saver = prepare_file_loader().await;
+
+    let (user_id, files) = admin
+        .bind_legacy_user(&file_saver, subject)
+        .await
+        .map_err(|err| format!("Failed to bind the legacy user: {err}"))?;
+
+    println!("The legacy user {user_id} has been bound to {subject}. Moved {files} files");
+
+    Ok(())
+}
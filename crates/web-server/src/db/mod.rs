@@ -1,9 +1,16 @@
 mod model;
 mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 pub use model::*;
 pub use postgres::PostgresDb;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteDb;
+use std::collections::HashMap;
+
 use thiserror::Error;
+use uuid::Uuid;
 
 #[derive(Error, Debug)]
 pub enum DbError {
@@ -13,34 +20,333 @@ pub enum DbError {
     #[error("{0} operation is unsupported")]
     Unsupported(&'static str),
 
-    #[error("invalid operation: {0}")]
-    InvalidOperation(&'static str),
+    #[error("operation {0} already exists with a different checksum")]
+    OperationChecksumMismatch(Uuid),
 
     #[error("user already exist")]
     UserAlreadyExist,
+
+    #[error("storage quota exceeded: {used} used + {requested} requested > {quota} quota")]
+    QuotaExceeded { used: i64, requested: i64, quota: i64 },
 }
 
 /// User database interface.
 ///
+/// Every user is bound to the authenticated identity (subject). One subject can have only one user.
+///
 /// The user's password is not actually a password. It is a hash of the encryption (secret) key.
 /// This hash is used to verify that the user provided the correct password and salt for generating
 /// the encryption key.
 pub trait UserDb: Send + Sync {
-    /// Initialize the app user.
+    /// Initialize the user for the given subject.
     ///
     /// This should be called only once, when the user is signed in for the first time.
-    async fn init(&self, user: &User) -> Result<(), DbError>;
-    /// Returns the app user.
+    async fn init(&self, subject: &str, user: &User) -> Result<(), DbError>;
+    /// Returns the user of the given subject.
     ///
-    /// If the user does not exist, returns an error.
-    async fn user(&self) -> Result<User, DbError>;
+    /// If the user does not exist or is not initialized yet, returns an error.
+    async fn user(&self, subject: &str) -> Result<User, DbError>;
 }
 
 /// Operations database interface.
+///
+/// All operations belong to the user. The user can not access operations of other users.
 pub trait OperationsDb: Send + Sync {
-    /// Returns a list of operations, skipping the first `operations_to_skip` operations.
+    /// Returns a list of the user's operations, skipping the first `operations_to_skip` operations.
+    ///
+    /// The resulting operations are ordered by creation time and id.
+    async fn operations(&self, user_id: Uuid, operations_to_skip: usize) -> Result<Vec<Operation>, DbError>;
+    /// Returns up to `limit` user's operations starting from the `cursor`.
+    ///
+    /// The resulting operations are ordered by creation time and id.
+    async fn operations_page(
+        &self,
+        user_id: Uuid,
+        cursor: OperationsCursor,
+        limit: usize,
+    ) -> Result<Vec<Operation>, DbError>;
+    /// Returns checksums of all user's operations ordered by creation time and id.
+    async fn checksums(&self, user_id: Uuid) -> Result<Vec<Vec<u8>>, DbError>;
+    /// Saves operations in one transaction.
+    ///
+    /// Re-uploading an existing operation is allowed, but its checksum must match the stored one.
+    /// Otherwise, none of the operations are saved.
+    ///
+    /// If the `quota` (in bytes) is set, the user's storage usage is checked in the same transaction.
+    /// Re-uploaded operations are not counted.
+    async fn add_operations(&self, user_id: Uuid, operations: &[Operation], quota: Option<i64>) -> Result<(), DbError>;
+}
+
+/// Removes duplicates from the uploaded operations.
+///
+/// Fails if the same operation is uploaded twice with different checksums.
+fn unique_operations(operations: &[Operation]) -> Result<Vec<&Operation>, DbError> {
+    let mut checksums: HashMap<Uuid, &[u8]> = HashMap::with_capacity(operations.len());
+    let mut unique = Vec::with_capacity(operations.len());
+
+    for operation in operations {
+        match checksums.get(&operation.id) {
+            Some(checksum) if *checksum != operation.checksum.as_slice() => {
+                return Err(DbError::OperationChecksumMismatch(operation.id));
+            }
+            Some(_) => {}
+            None => {
+                checksums.insert(operation.id, &operation.checksum);
+                unique.push(operation);
+            }
+        }
+    }
+
+    Ok(unique)
+}
+
+/// Compares re-uploaded operations with the stored ones.
+///
+/// `stored` contains `(id, checksum)` of operations that already existed before the upload.
+fn check_reuploaded_operations(operations: &[&Operation], stored: &[(Uuid, Vec<u8>)]) -> Result<(), DbError> {
+    let checksums: HashMap<Uuid, &[u8]> = operations
+        .iter()
+        .map(|operation| (operation.id, operation.checksum.as_slice()))
+        .collect();
+
+    for (id, stored_checksum) in stored {
+        warn!(%id, "Operation re-uploading detected. It is allowed but unwanted behaviour!");
+
+        if checksums
+            .get(id)
+            .is_some_and(|checksum| *checksum != stored_checksum.as_slice())
+        {
+            error!(
+                %id,
+                ?stored_checksum,
+                "Operation already exists but the provided checksum does not match the stored one",
+            );
+
+            return Err(DbError::OperationChecksumMismatch(*id));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the user's storage usage fits into the `quota` after adding `requested` bytes.
+///
+/// `used` is the usage after the change. Changes that do not increase the usage are always allowed.
+fn check_quota(used: i64, requested: i64, quota: Option<i64>) -> Result<(), DbError> {
+    match quota {
+        Some(quota) if requested > 0 && used > quota => Err(DbError::QuotaExceeded {
+            used: used - requested,
+            requested,
+            quota,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Files database interface.
+///
+/// The file content is stored by the [FileSaver](crate::services::FileSaver). The database only tracks file sizes.
+pub trait FilesDb: Send + Sync {
+    /// Saves the uploaded file. If the file already exists, its size is updated.
+    ///
+    /// If the `quota` (in bytes) is set, the user's storage usage is checked in the same transaction.
+    /// Only the size difference of the existing file is counted.
+    async fn add_file(&self, user_id: Uuid, file: &File, quota: Option<i64>) -> Result<(), DbError>;
+    /// Returns the stored size of the file, or `None` if the file is not registered.
+    async fn file_size(&self, user_id: Uuid, id: Uuid) -> Result<Option<i64>, DbError>;
+    /// Removes the file and its orphan mark.
+    async fn remove_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError>;
+
+    /// Returns the user's files marked as orphaned.
+    async fn orphaned_files(&self, user_id: Uuid) -> Result<Vec<OrphanedFile>, DbError>;
+    /// Marks the file as orphaned.
+    async fn add_orphaned_file(&self, user_id: Uuid, file: &OrphanedFile) -> Result<(), DbError>;
+    /// Removes the orphan mark. The file is referenced again or does not exist anymore.
+    async fn remove_orphaned_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError>;
+}
+
+/// Storage usage database interface.
+pub trait UsageDb: Send + Sync {
+    /// Returns the user's storage usage.
+    async fn usage(&self, user_id: Uuid) -> Result<Usage, DbError>;
+}
+
+/// API tokens database interface.
+///
+/// The token itself is never stored. Only its SHA-256 hash.
+pub trait TokenDb: Send + Sync {
+    /// Saves a newly issued token.
+    async fn add_token(&self, token: &ApiToken) -> Result<(), DbError>;
+    /// Returns the token by its hash.
+    ///
+    /// If the token does not exist, returns an error.
+    async fn token_by_hash(&self, token_hash: &[u8]) -> Result<ApiToken, DbError>;
+}
+
+/// Server administration database interface.
+///
+/// It is used only by the admin CLI commands and has access to the data of all users.
+pub trait AdminDb: Send + Sync {
+    /// Returns all users.
+    async fn users(&self) -> Result<Vec<UserAccount>, DbError>;
+    /// Restores the user exactly as it was exported.
+    async fn add_user(&self, user: &UserAccount) -> Result<(), DbError>;
+    /// Removes the user together with all user's operations and files records.
+    async fn remove_user(&self, user_id: Uuid) -> Result<(), DbError>;
+    /// Binds the user without a subject (created before the multi-user support) to the subject.
+    ///
+    /// If the user does not exist or already has a subject, returns an error.
+    async fn bind_user(&self, user_id: Uuid, subject: &str) -> Result<(), DbError>;
+    /// Returns all user's files.
+    async fn files(&self, user_id: Uuid) -> Result<Vec<File>, DbError>;
+    /// Returns all issued API tokens.
+    async fn tokens(&self) -> Result<Vec<ApiToken>, DbError>;
+    /// Restores all records exported from another server in one transaction.
     ///
-    /// The resulting operations are ordered by creation time.
-    async fn operations(&self, operations_to_skip: usize) -> Result<Vec<Operation>, DbError>;
-    async fn add_operations(&self, operations: &[Operation]) -> Result<(), DbError>;
+    /// Either all records are saved or none of them.
+    async fn restore(&self, data: &ServerData) -> Result<(), DbError>;
+}
+
+/// Database connection pool statistics.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// Number of open connections.
+    pub size: u32,
+    /// Number of idle connections.
+    pub idle: usize,
+}
+
+/// Database backend.
+///
+/// The backend is selected by the database URL scheme: `postgres://` (or `postgresql://`) or `sqlite://`.
+pub enum Db {
+    Postgres(PostgresDb),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteDb),
+}
+
+macro_rules! dispatch {
+    ($db:expr, $inner:ident => $call:expr) => {
+        match $db {
+            Db::Postgres($inner) => $call,
+            #[cfg(feature = "sqlite")]
+            Db::Sqlite($inner) => $call,
+        }
+    };
+}
+
+impl Db {
+    /// Checks the database connectivity.
+    pub async fn ping(&self) -> Result<(), DbError> {
+        dispatch!(self, db => db.ping().await)
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        dispatch!(self, db => db.pool_stats())
+    }
+}
+
+impl UserDb for Db {
+    async fn init(&self, subject: &str, user: &User) -> Result<(), DbError> {
+        dispatch!(self, db => db.init(subject, user).await)
+    }
+
+    async fn user(&self, subject: &str) -> Result<User, DbError> {
+        dispatch!(self, db => db.user(subject).await)
+    }
+}
+
+impl OperationsDb for Db {
+    async fn operations(&self, user_id: Uuid, operations_to_skip: usize) -> Result<Vec<Operation>, DbError> {
+        dispatch!(self, db => db.operations(user_id, operations_to_skip).await)
+    }
+
+    async fn add_operations(&self, user_id: Uuid, operations: &[Operation], quota: Option<i64>) -> Result<(), DbError> {
+        dispatch!(self, db => db.add_operations(user_id, operations, quota).await)
+    }
+
+    async fn operations_page(
+        &self,
+        user_id: Uuid,
+        cursor: OperationsCursor,
+        limit: usize,
+    ) -> Result<Vec<Operation>, DbError> {
+        dispatch!(self, db => db.operations_page(user_id, cursor, limit).await)
+    }
+
+    async fn checksums(&self, user_id: Uuid) -> Result<Vec<Vec<u8>>, DbError> {
+        dispatch!(self, db => db.checksums(user_id).await)
+    }
+}
+
+impl FilesDb for Db {
+    async fn add_file(&self, user_id: Uuid, file: &File, quota: Option<i64>) -> Result<(), DbError> {
+        dispatch!(self, db => db.add_file(user_id, file, quota).await)
+    }
+
+    async fn file_size(&self, user_id: Uuid, id: Uuid) -> Result<Option<i64>, DbError> {
+        dispatch!(self, db => db.file_size(user_id, id).await)
+    }
+
+    async fn remove_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError> {
+        dispatch!(self, db => db.remove_file(user_id, id).await)
+    }
+
+    async fn orphaned_files(&self, user_id: Uuid) -> Result<Vec<OrphanedFile>, DbError> {
+        dispatch!(self, db => db.orphaned_files(user_id).await)
+    }
+
+    async fn add_orphaned_file(&self, user_id: Uuid, file: &OrphanedFile) -> Result<(), DbError> {
+        dispatch!(self, db => db.add_orphaned_file(user_id, file).await)
+    }
+
+    async fn remove_orphaned_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError> {
+        dispatch!(self, db => db.remove_orphaned_file(user_id, id).await)
+    }
+}
+
+impl UsageDb for Db {
+    async fn usage(&self, user_id: Uuid) -> Result<Usage, DbError> {
+        dispatch!(self, db => db.usage(user_id).await)
+    }
+}
+
+impl TokenDb for Db {
+    async fn add_token(&self, token: &ApiToken) -> Result<(), DbError> {
+        dispatch!(self, db => db.add_token(token).await)
+    }
+
+    async fn token_by_hash(&self, token_hash: &[u8]) -> Result<ApiToken, DbError> {
+        dispatch!(self, db => db.token_by_hash(token_hash).await)
+    }
+}
+
+impl AdminDb for Db {
+    async fn users(&self) -> Result<Vec<UserAccount>, DbError> {
+        dispatch!(self, db => db.users().await)
+    }
+
+    async fn add_user(&self, user: &UserAccount) -> Result<(), DbError> {
+        dispatch!(self, db => db.add_user(user).await)
+    }
+
+    async fn remove_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        dispatch!(self, db => db.remove_user(user_id).await)
+    }
+
+    async fn bind_user(&self, user_id: Uuid, subject: &str) -> Result<(), DbError> {
+        dispatch!(self, db => db.bind_user(user_id, subject).await)
+    }
+
+    async fn files(&self, user_id: Uuid) -> Result<Vec<File>, DbError> {
+        dispatch!(self, db => db.files(user_id).await)
+    }
+
+    async fn tokens(&self) -> Result<Vec<ApiToken>, DbError> {
+        dispatch!(self, db => db.tokens().await)
+    }
+
+    async fn restore(&self, data: &ServerData) -> Result<(), DbError> {
+        dispatch!(self, db => db.restore(data).await)
+    }
 }
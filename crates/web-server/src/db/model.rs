@@ -11,8 +11,69 @@ pub struct Operation {
     pub checksum: Vec<u8>,
 }
 
+/// Position in the user's operations list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationsCursor {
+    /// Skip the first `N` operations.
+    Skip(usize),
+    /// Operations after the given one.
+    After { created_at: OffsetDateTime, id: Uuid },
+}
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
     pub secret_key_hash: String,
 }
+
+/// The user with its subject.
+///
+/// The user created before the multi-user support may have no subject.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct UserAccount {
+    pub id: Uuid,
+    pub subject: Option<String>,
+    pub secret_key_hash: String,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub subject: String,
+    pub token_hash: Vec<u8>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct File {
+    pub id: Uuid,
+    /// File size in bytes.
+    pub size: i64,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Usage {
+    pub operations: i64,
+    pub operation_bytes: i64,
+    pub files: i64,
+    pub file_bytes: i64,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct OrphanedFile {
+    pub id: Uuid,
+    /// The time when the file was found unreferenced for the first time.
+    pub orphaned_at: OffsetDateTime,
+}
+
+/// Database records of all users restored from the server archive.
+///
+/// Operations and files are paired with their owners' ids.
+#[derive(Debug, Default)]
+pub struct ServerData {
+    pub tokens: Vec<ApiToken>,
+    pub users: Vec<UserAccount>,
+    pub operations: Vec<(Uuid, Operation)>,
+    pub files: Vec<(Uuid, File)>,
+}
@@ -1,8 +1,14 @@
+use std::collections::HashSet;
+
 use sqlx::{PgConnection, PgPool};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use super::model::*;
-use super::{DbError, OperationsDb, UserDb};
+use super::{
+    AdminDb, DbError, FilesDb, OperationsDb, PoolStats, TokenDb, UsageDb, UserDb, check_quota,
+    check_reuploaded_operations, unique_operations,
+};
 
 pub struct PostgresDb {
     pool: PgPool,
@@ -12,69 +18,154 @@ impl PostgresDb {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
-}
 
-impl PostgresDb {
-    async fn operation_by_id(&self, operation_id: Uuid, connection: &mut PgConnection) -> Result<Operation, DbError> {
-        let operation = sqlx::query_as("select id, created_at, data, checksum from operation where id = $1")
-            .bind(operation_id)
-            .fetch_one(&mut *connection)
+    pub async fn ping(&self) -> Result<(), DbError> {
+        sqlx::query("select 1").execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        }
+    }
+
+    /// Returns the user's storage usage in bytes.
+    ///
+    /// The user is locked until the end of the transaction. So, concurrent uploads of the same user
+    /// can not exceed the quota together.
+    async fn lock_storage_usage(connection: &mut PgConnection, user_id: Uuid) -> Result<i64, DbError> {
+        sqlx::query("select id from \"user\" where id = $1 for update")
+            .bind(user_id)
+            .execute(&mut *connection)
             .await?;
 
-        Ok(operation)
+        let used = sqlx::query_scalar(
+            "select \
+                (select coalesce(sum(length(data)), 0)::bigint from operation where user_id = $1) + \
+                (select coalesce(sum(size), 0)::bigint from file where user_id = $1)",
+        )
+        .bind(user_id)
+        .fetch_one(&mut *connection)
+        .await?;
+
+        Ok(used)
     }
 }
 
 impl OperationsDb for PostgresDb {
-    async fn operations(&self, operations_to_skip: usize) -> Result<Vec<Operation>, DbError> {
-        let operations =
-            sqlx::query_as("select id, created_at, data, checksum from operation order by created_at offset $1")
+    async fn operations(&self, user_id: Uuid, operations_to_skip: usize) -> Result<Vec<Operation>, DbError> {
+        let operations = sqlx::query_as(
+            "select id, created_at, data, checksum from operation where user_id = $1 order by created_at, id offset $2",
+        )
+        .bind(user_id)
+        .bind(i64::try_from(operations_to_skip).expect("usize -> i64 conversion should not fail"))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(operations)
+    }
+
+    async fn operations_page(
+        &self,
+        user_id: Uuid,
+        cursor: OperationsCursor,
+        limit: usize,
+    ) -> Result<Vec<Operation>, DbError> {
+        let limit = i64::try_from(limit).expect("usize -> i64 conversion should not fail");
+
+        let operations: Vec<Operation> = match cursor {
+            OperationsCursor::Skip(operations_to_skip) => {
+                sqlx::query_as(
+                    "select id, created_at, data, checksum from operation where user_id = $1 \
+                        order by created_at, id offset $2 limit $3",
+                )
+                .bind(user_id)
                 .bind(i64::try_from(operations_to_skip).expect("usize -> i64 conversion should not fail"))
+                .bind(limit)
                 .fetch_all(&self.pool)
-                .await?;
+                .await?
+            }
+            OperationsCursor::After { created_at, id } => {
+                sqlx::query_as(
+                    "select id, created_at, data, checksum from operation where user_id = $1 and (created_at, id) > ($2, $3) \
+                        order by created_at, id limit $4",
+                )
+                .bind(user_id)
+                .bind(created_at)
+                .bind(id)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?
+            }
+        };
 
         Ok(operations)
     }
 
-    async fn add_operations(&self, operations: &[Operation]) -> Result<(), DbError> {
+    async fn checksums(&self, user_id: Uuid) -> Result<Vec<Vec<u8>>, DbError> {
+        let checksums = sqlx::query_scalar("select checksum from operation where user_id = $1 order by created_at, id")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(checksums)
+    }
+
+    async fn add_operations(&self, user_id: Uuid, operations: &[Operation], quota: Option<i64>) -> Result<(), DbError> {
+        let operations = unique_operations(operations)?;
+
+        let ids: Vec<Uuid> = operations.iter().map(|operation| operation.id).collect();
+        let created_at: Vec<OffsetDateTime> = operations.iter().map(|operation| operation.created_at).collect();
+        let data: Vec<&[u8]> = operations.iter().map(|operation| operation.data.as_slice()).collect();
+        let checksums: Vec<&[u8]> = operations
+            .iter()
+            .map(|operation| operation.checksum.as_slice())
+            .collect();
+
         let mut transaction = self.pool.begin().await?;
 
-        // TODO: replace with `join_all`.
-        for operation in operations {
-            if let Ok(existing_operation) = self.operation_by_id(operation.id, &mut transaction).await {
-                warn!(
-                    "Operation ({}) reuploading detected. It is allowed but unwanted behaviour!",
-                    operation.id
-                );
-
-                if existing_operation.checksum != operation.checksum {
-                    error!(
-                        "Operation with id ({}) always exists but provided operation checksum ({:?}) do not match existing operation checksum ({:?}).",
-                        operation.id, operation.checksum, existing_operation.checksum
-                    );
-
-                    return Err(DbError::InvalidOperation(
-                        "operation with the same id but different checksum already exists",
-                    ));
-                }
-            } else {
-                let Operation {
-                    id,
-                    created_at,
-                    data,
-                    checksum,
-                } = operation;
-
-                sqlx::query!(
-                    "insert into operation (id, created_at, data, checksum) values ($1, $2, $3, $4)",
-                    id,
-                    created_at,
-                    data,
-                    checksum
-                )
-                .execute(&mut *transaction)
-                .await?;
-            }
+        let inserted: HashSet<Uuid> = sqlx::query_scalar::<_, Uuid>(
+            "insert into operation (id, user_id, created_at, data, checksum) \
+                select id, $1, created_at, data, checksum \
+                from unnest($2::uuid[], $3::timestamptz[], $4::bytea[], $5::bytea[]) as t(id, created_at, data, checksum) \
+                on conflict (user_id, id) do nothing \
+                returning id",
+        )
+        .bind(user_id)
+        .bind(&ids)
+        .bind(&created_at)
+        .bind(&data)
+        .bind(&checksums)
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .collect();
+
+        if inserted.len() < ids.len() {
+            let reuploaded: Vec<Uuid> = ids.into_iter().filter(|id| !inserted.contains(id)).collect();
+
+            let stored: Vec<(Uuid, Vec<u8>)> =
+                sqlx::query_as("select id, checksum from operation where user_id = $1 and id = any($2)")
+                    .bind(user_id)
+                    .bind(&reuploaded)
+                    .fetch_all(&mut *transaction)
+                    .await?;
+
+            check_reuploaded_operations(&operations, &stored)?;
+        }
+
+        if quota.is_some() {
+            let requested = operations
+                .iter()
+                .filter(|operation| inserted.contains(&operation.id))
+                .map(|operation| i64::try_from(operation.data.len()).expect("operation size should fit in i64"))
+                .sum();
+            let used = Self::lock_storage_usage(&mut transaction, user_id).await?;
+
+            check_quota(used, requested, quota)?;
         }
 
         transaction.commit().await?;
@@ -84,35 +175,342 @@ impl OperationsDb for PostgresDb {
 }
 
 impl UserDb for PostgresDb {
-    async fn init(&self, user: &User) -> Result<(), DbError> {
+    async fn init(&self, subject: &str, user: &User) -> Result<(), DbError> {
         let mut transaction = self.pool.begin().await?;
 
-        let existing_user = sqlx::query_as::<_, User>("select id, secret_key_hash from \"user\" limit 1")
+        let existing_user = sqlx::query_as::<_, User>("select id, secret_key_hash from \"user\" where subject = $1")
+            .bind(subject)
             .fetch_optional(&mut *transaction)
             .await?;
 
-        if existing_user.is_some() {
-            return Err(DbError::UserAlreadyExist);
+        match existing_user {
+            // The legacy user can be created by the migration without the secret key hash.
+            // The bound subject sets it during the first sign-in.
+            Some(existing_user) if existing_user.secret_key_hash.is_empty() => {
+                sqlx::query("update \"user\" set secret_key_hash = $1 where id = $2")
+                    .bind(&user.secret_key_hash)
+                    .bind(existing_user.id)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            Some(_) => return Err(DbError::UserAlreadyExist),
+            None => {
+                sqlx::query("insert into \"user\" (id, subject, secret_key_hash) values ($1, $2, $3)")
+                    .bind(user.id)
+                    .bind(subject)
+                    .bind(&user.secret_key_hash)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
         }
 
-        sqlx::query!(
-            "insert into \"user\" (id, secret_key_hash) values ($1, $2)",
-            user.id,
-            user.secret_key_hash
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn user(&self, subject: &str) -> Result<User, DbError> {
+        // The user without the secret key hash is not initialized yet.
+        let user = sqlx::query_as::<_, User>(
+            "select id, secret_key_hash from \"user\" where subject = $1 and secret_key_hash <> ''",
+        )
+        .bind(subject)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+impl FilesDb for PostgresDb {
+    async fn add_file(&self, user_id: Uuid, file: &File, quota: Option<i64>) -> Result<(), DbError> {
+        let File { id, size, created_at } = file;
+
+        let mut transaction = self.pool.begin().await?;
+
+        let stored_size: Option<i64> = sqlx::query_scalar("select size from file where user_id = $1 and id = $2")
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        sqlx::query(
+            "insert into file (id, user_id, size, created_at) values ($1, $2, $3, $4) \
+                on conflict (user_id, id) do update set size = excluded.size",
         )
+        .bind(id)
+        .bind(user_id)
+        .bind(size)
+        .bind(created_at)
         .execute(&mut *transaction)
         .await?;
 
+        if quota.is_some() {
+            let used = Self::lock_storage_usage(&mut transaction, user_id).await?;
+
+            check_quota(used, size - stored_size.unwrap_or_default(), quota)?;
+        }
+
         transaction.commit().await?;
 
         Ok(())
     }
 
-    async fn user(&self) -> Result<User, DbError> {
-        let user = sqlx::query_as::<_, User>("select id, secret_key_hash from \"user\"")
+    async fn file_size(&self, user_id: Uuid, id: Uuid) -> Result<Option<i64>, DbError> {
+        let size = sqlx::query_scalar("select size from file where user_id = $1 and id = $2")
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(size)
+    }
+
+    async fn remove_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("delete from orphaned_file where user_id = $1 and id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("delete from file where user_id = $1 and id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn orphaned_files(&self, user_id: Uuid) -> Result<Vec<OrphanedFile>, DbError> {
+        let files = sqlx::query_as("select id, orphaned_at from orphaned_file where user_id = $1")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(files)
+    }
+
+    async fn add_orphaned_file(&self, user_id: Uuid, file: &OrphanedFile) -> Result<(), DbError> {
+        let OrphanedFile { id, orphaned_at } = file;
+
+        sqlx::query("insert into orphaned_file (id, user_id, orphaned_at) values ($1, $2, $3) on conflict do nothing")
+            .bind(id)
+            .bind(user_id)
+            .bind(orphaned_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_orphaned_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError> {
+        sqlx::query("delete from orphaned_file where user_id = $1 and id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl UsageDb for PostgresDb {
+    async fn usage(&self, user_id: Uuid) -> Result<Usage, DbError> {
+        let usage = sqlx::query_as(
+            "select \
+                (select count(*) from operation where user_id = $1) as operations, \
+                (select coalesce(sum(length(data)), 0)::bigint from operation where user_id = $1) as operation_bytes, \
+                (select count(*) from file where user_id = $1) as files, \
+                (select coalesce(sum(size), 0)::bigint from file where user_id = $1) as file_bytes",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(usage)
+    }
+}
+
+impl TokenDb for PostgresDb {
+    async fn add_token(&self, token: &ApiToken) -> Result<(), DbError> {
+        let ApiToken {
+            id,
+            subject,
+            token_hash,
+            created_at,
+        } = token;
+
+        sqlx::query("insert into api_token (id, subject, token_hash, created_at) values ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(subject)
+            .bind(token_hash)
+            .bind(created_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn token_by_hash(&self, token_hash: &[u8]) -> Result<ApiToken, DbError> {
+        let token = sqlx::query_as("select id, subject, token_hash, created_at from api_token where token_hash = $1")
+            .bind(token_hash)
             .fetch_one(&self.pool)
             .await?;
 
-        Ok(user)
+        Ok(token)
+    }
+}
+
+impl AdminDb for PostgresDb {
+    async fn users(&self) -> Result<Vec<UserAccount>, DbError> {
+        let users = sqlx::query_as("select id, subject, secret_key_hash from \"user\" order by subject")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(users)
+    }
+
+    async fn add_user(&self, user: &UserAccount) -> Result<(), DbError> {
+        let UserAccount {
+            id,
+            subject,
+            secret_key_hash,
+        } = user;
+
+        sqlx::query("insert into \"user\" (id, subject, secret_key_hash) values ($1, $2, $3)")
+            .bind(id)
+            .bind(subject)
+            .bind(secret_key_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let mut transaction = self.pool.begin().await?;
+
+        for query in [
+            "delete from orphaned_file where user_id = $1",
+            "delete from file where user_id = $1",
+            "delete from operation where user_id = $1",
+            "delete from \"user\" where id = $1",
+        ] {
+            sqlx::query(query).bind(user_id).execute(&mut *transaction).await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn bind_user(&self, user_id: Uuid, subject: &str) -> Result<(), DbError> {
+        let result = sqlx::query("update \"user\" set subject = $1 where id = $2 and subject is null")
+            .bind(subject)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound.into());
+        }
+
+        Ok(())
+    }
+
+    async fn files(&self, user_id: Uuid) -> Result<Vec<File>, DbError> {
+        let files = sqlx::query_as("select id, size, created_at from file where user_id = $1 order by created_at")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(files)
+    }
+
+    async fn tokens(&self) -> Result<Vec<ApiToken>, DbError> {
+        let tokens = sqlx::query_as("select id, subject, token_hash, created_at from api_token order by created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    async fn restore(&self, data: &ServerData) -> Result<(), DbError> {
+        let ServerData {
+            tokens,
+            users,
+            operations,
+            files,
+        } = data;
+
+        let mut transaction = self.pool.begin().await?;
+
+        for ApiToken {
+            id,
+            subject,
+            token_hash,
+            created_at,
+        } in tokens
+        {
+            sqlx::query("insert into api_token (id, subject, token_hash, created_at) values ($1, $2, $3, $4)")
+                .bind(id)
+                .bind(subject)
+                .bind(token_hash)
+                .bind(created_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for UserAccount {
+            id,
+            subject,
+            secret_key_hash,
+        } in users
+        {
+            sqlx::query("insert into \"user\" (id, subject, secret_key_hash) values ($1, $2, $3)")
+                .bind(id)
+                .bind(subject)
+                .bind(secret_key_hash)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for (
+            user_id,
+            Operation {
+                id,
+                created_at,
+                data,
+                checksum,
+            },
+        ) in operations
+        {
+            sqlx::query("insert into operation (id, user_id, created_at, data, checksum) values ($1, $2, $3, $4, $5)")
+                .bind(id)
+                .bind(user_id)
+                .bind(created_at)
+                .bind(data)
+                .bind(checksum)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for (user_id, File { id, size, created_at }) in files {
+            sqlx::query("insert into file (id, user_id, size, created_at) values ($1, $2, $3, $4)")
+                .bind(id)
+                .bind(user_id)
+                .bind(size)
+                .bind(created_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
     }
 }
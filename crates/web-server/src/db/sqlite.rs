@@ -0,0 +1,540 @@
+use std::collections::HashSet;
+
+use sqlx::{QueryBuilder, SqliteConnection, SqlitePool};
+use time::{OffsetDateTime, UtcOffset};
+use uuid::Uuid;
+
+use super::model::*;
+use super::{
+    AdminDb, DbError, FilesDb, OperationsDb, PoolStats, TokenDb, UsageDb, UserDb, check_quota,
+    check_reuploaded_operations, unique_operations,
+};
+
+/// Number of rows inserted (or selected by id) in one query.
+///
+/// SQLite limits the number of bound parameters in one query.
+const BATCH_SIZE: usize = 500;
+
+/// Formats the timestamp for storing in the database.
+///
+/// SQLite stores timestamps as strings, and the default RFC 3339 format trims trailing zeros of the fraction.
+/// The fixed-width UTC format keeps the string order equal to the time order, so `order by created_at` and
+/// the `(created_at, id)` cursor work correctly.
+fn timestamp(value: OffsetDateTime) -> String {
+    let value = value.to_offset(UtcOffset::UTC);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        value.year(),
+        u8::from(value.month()),
+        value.day(),
+        value.hour(),
+        value.minute(),
+        value.second(),
+        value.nanosecond(),
+    )
+}
+
+pub struct SqliteDb {
+    pool: SqlitePool,
+}
+
+impl SqliteDb {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ping(&self) -> Result<(), DbError> {
+        sqlx::query("select 1").execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        }
+    }
+
+    /// Returns the user's storage usage in bytes.
+    ///
+    /// SQLite allows only one write transaction at a time. So, the usage can not be changed concurrently
+    /// when it is requested after the write.
+    async fn storage_usage(connection: &mut SqliteConnection, user_id: Uuid) -> Result<i64, DbError> {
+        let used = sqlx::query_scalar(
+            "select \
+                (select coalesce(sum(length(data)), 0) from operation where user_id = ?1) + \
+                (select coalesce(sum(size), 0) from file where user_id = ?1)",
+        )
+        .bind(user_id)
+        .fetch_one(&mut *connection)
+        .await?;
+
+        Ok(used)
+    }
+}
+
+impl OperationsDb for SqliteDb {
+    async fn operations(&self, user_id: Uuid, operations_to_skip: usize) -> Result<Vec<Operation>, DbError> {
+        // SQLite requires the `limit` clause before `offset`. `-1` means no limit.
+        let operations = sqlx::query_as(
+            "select id, created_at, data, checksum from operation where user_id = ? order by created_at, id limit -1 offset ?",
+        )
+        .bind(user_id)
+        .bind(i64::try_from(operations_to_skip).expect("usize -> i64 conversion should not fail"))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(operations)
+    }
+
+    async fn operations_page(
+        &self,
+        user_id: Uuid,
+        cursor: OperationsCursor,
+        limit: usize,
+    ) -> Result<Vec<Operation>, DbError> {
+        let limit = i64::try_from(limit).expect("usize -> i64 conversion should not fail");
+
+        let operations: Vec<Operation> = match cursor {
+            OperationsCursor::Skip(operations_to_skip) => {
+                sqlx::query_as(
+                    "select id, created_at, data, checksum from operation where user_id = ? \
+                        order by created_at, id limit ? offset ?",
+                )
+                .bind(user_id)
+                .bind(limit)
+                .bind(i64::try_from(operations_to_skip).expect("usize -> i64 conversion should not fail"))
+                .fetch_all(&self.pool)
+                .await?
+            }
+            OperationsCursor::After { created_at, id } => sqlx::query_as(
+                "select id, created_at, data, checksum from operation where user_id = ? and (created_at, id) > (?, ?) \
+                        order by created_at, id limit ?",
+            )
+            .bind(user_id)
+            .bind(timestamp(created_at))
+            .bind(id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?,
+        };
+
+        Ok(operations)
+    }
+
+    async fn checksums(&self, user_id: Uuid) -> Result<Vec<Vec<u8>>, DbError> {
+        let checksums = sqlx::query_scalar("select checksum from operation where user_id = ? order by created_at, id")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(checksums)
+    }
+
+    async fn add_operations(&self, user_id: Uuid, operations: &[Operation], quota: Option<i64>) -> Result<(), DbError> {
+        let operations = unique_operations(operations)?;
+
+        let mut transaction = self.pool.begin().await?;
+
+        let mut inserted = HashSet::with_capacity(operations.len());
+        for batch in operations.chunks(BATCH_SIZE) {
+            let mut query = QueryBuilder::new("insert into operation (id, user_id, created_at, data, checksum) ");
+            query.push_values(batch, |mut row, operation| {
+                row.push_bind(operation.id)
+                    .push_bind(user_id)
+                    .push_bind(timestamp(operation.created_at))
+                    .push_bind(&operation.data)
+                    .push_bind(&operation.checksum);
+            });
+            query.push(" on conflict (user_id, id) do nothing returning id");
+
+            inserted.extend(query.build_query_scalar::<Uuid>().fetch_all(&mut *transaction).await?);
+        }
+
+        if inserted.len() < operations.len() {
+            let reuploaded: Vec<Uuid> = operations
+                .iter()
+                .map(|operation| operation.id)
+                .filter(|id| !inserted.contains(id))
+                .collect();
+
+            let mut stored: Vec<(Uuid, Vec<u8>)> = Vec::new();
+            for batch in reuploaded.chunks(BATCH_SIZE) {
+                let mut query = QueryBuilder::new("select id, checksum from operation where user_id = ");
+                query.push_bind(user_id).push(" and id in (");
+                let mut ids = query.separated(", ");
+                for id in batch {
+                    ids.push_bind(id);
+                }
+                ids.push_unseparated(")");
+
+                stored.extend(
+                    query
+                        .build_query_as::<(Uuid, Vec<u8>)>()
+                        .fetch_all(&mut *transaction)
+                        .await?,
+                );
+            }
+
+            check_reuploaded_operations(&operations, &stored)?;
+        }
+
+        if quota.is_some() {
+            let requested = operations
+                .iter()
+                .filter(|operation| inserted.contains(&operation.id))
+                .map(|operation| i64::try_from(operation.data.len()).expect("operation size should fit in i64"))
+                .sum();
+            let used = Self::storage_usage(&mut transaction, user_id).await?;
+
+            check_quota(used, requested, quota)?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+impl UserDb for SqliteDb {
+    async fn init(&self, subject: &str, user: &User) -> Result<(), DbError> {
+        let mut transaction = self.pool.begin().await?;
+
+        let existing_user = sqlx::query_as::<_, User>("select id, secret_key_hash from \"user\" where subject = ?")
+            .bind(subject)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        match existing_user {
+            // The legacy user (e.g. imported from the PostgreSQL database) can have no secret key hash.
+            // The bound subject sets it during the first sign-in.
+            Some(existing_user) if existing_user.secret_key_hash.is_empty() => {
+                sqlx::query("update \"user\" set secret_key_hash = ? where id = ?")
+                    .bind(&user.secret_key_hash)
+                    .bind(existing_user.id)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            Some(_) => return Err(DbError::UserAlreadyExist),
+            None => {
+                sqlx::query("insert into \"user\" (id, subject, secret_key_hash) values (?, ?, ?)")
+                    .bind(user.id)
+                    .bind(subject)
+                    .bind(&user.secret_key_hash)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn user(&self, subject: &str) -> Result<User, DbError> {
+        // The user without the secret key hash is not initialized yet.
+        let user = sqlx::query_as::<_, User>(
+            "select id, secret_key_hash from \"user\" where subject = ? and secret_key_hash <> ''",
+        )
+        .bind(subject)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+impl FilesDb for SqliteDb {
+    async fn add_file(&self, user_id: Uuid, file: &File, quota: Option<i64>) -> Result<(), DbError> {
+        let File { id, size, created_at } = file;
+
+        let mut transaction = self.pool.begin().await?;
+
+        let stored_size: Option<i64> = sqlx::query_scalar("select size from file where user_id = ? and id = ?")
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        sqlx::query(
+            "insert into file (id, user_id, size, created_at) values (?, ?, ?, ?) \
+                on conflict (user_id, id) do update set size = excluded.size",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(size)
+        .bind(timestamp(*created_at))
+        .execute(&mut *transaction)
+        .await?;
+
+        if quota.is_some() {
+            let used = Self::storage_usage(&mut transaction, user_id).await?;
+
+            check_quota(used, size - stored_size.unwrap_or_default(), quota)?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn file_size(&self, user_id: Uuid, id: Uuid) -> Result<Option<i64>, DbError> {
+        let size = sqlx::query_scalar("select size from file where user_id = ? and id = ?")
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(size)
+    }
+
+    async fn remove_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("delete from orphaned_file where user_id = ? and id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("delete from file where user_id = ? and id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn orphaned_files(&self, user_id: Uuid) -> Result<Vec<OrphanedFile>, DbError> {
+        let files = sqlx::query_as("select id, orphaned_at from orphaned_file where user_id = ?")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(files)
+    }
+
+    async fn add_orphaned_file(&self, user_id: Uuid, file: &OrphanedFile) -> Result<(), DbError> {
+        let OrphanedFile { id, orphaned_at } = file;
+
+        sqlx::query("insert into orphaned_file (id, user_id, orphaned_at) values (?, ?, ?) on conflict do nothing")
+            .bind(id)
+            .bind(user_id)
+            .bind(timestamp(*orphaned_at))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_orphaned_file(&self, user_id: Uuid, id: Uuid) -> Result<(), DbError> {
+        sqlx::query("delete from orphaned_file where user_id = ? and id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl UsageDb for SqliteDb {
+    async fn usage(&self, user_id: Uuid) -> Result<Usage, DbError> {
+        let usage = sqlx::query_as(
+            "select \
+                (select count(*) from operation where user_id = ?1) as operations, \
+                (select coalesce(sum(length(data)), 0) from operation where user_id = ?1) as operation_bytes, \
+                (select count(*) from file where user_id = ?1) as files, \
+                (select coalesce(sum(size), 0) from file where user_id = ?1) as file_bytes",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(usage)
+    }
+}
+
+impl TokenDb for SqliteDb {
+    async fn add_token(&self, token: &ApiToken) -> Result<(), DbError> {
+        let ApiToken {
+            id,
+            subject,
+            token_hash,
+            created_at,
+        } = token;
+
+        sqlx::query("insert into api_token (id, subject, token_hash, created_at) values (?, ?, ?, ?)")
+            .bind(id)
+            .bind(subject)
+            .bind(token_hash)
+            .bind(timestamp(*created_at))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn token_by_hash(&self, token_hash: &[u8]) -> Result<ApiToken, DbError> {
+        let token = sqlx::query_as("select id, subject, token_hash, created_at from api_token where token_hash = ?")
+            .bind(token_hash)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(token)
+    }
+}
+
+impl AdminDb for SqliteDb {
+    async fn users(&self) -> Result<Vec<UserAccount>, DbError> {
+        let users = sqlx::query_as("select id, subject, secret_key_hash from \"user\" order by subject")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(users)
+    }
+
+    async fn add_user(&self, user: &UserAccount) -> Result<(), DbError> {
+        let UserAccount {
+            id,
+            subject,
+            secret_key_hash,
+        } = user;
+
+        sqlx::query("insert into \"user\" (id, subject, secret_key_hash) values (?, ?, ?)")
+            .bind(id)
+            .bind(subject)
+            .bind(secret_key_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let mut transaction = self.pool.begin().await?;
+
+        for query in [
+            "delete from orphaned_file where user_id = ?",
+            "delete from file where user_id = ?",
+            "delete from operation where user_id = ?",
+            "delete from \"user\" where id = ?",
+        ] {
+            sqlx::query(query).bind(user_id).execute(&mut *transaction).await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn bind_user(&self, user_id: Uuid, subject: &str) -> Result<(), DbError> {
+        let result = sqlx::query("update \"user\" set subject = ? where id = ? and subject is null")
+            .bind(subject)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound.into());
+        }
+
+        Ok(())
+    }
+
+    async fn files(&self, user_id: Uuid) -> Result<Vec<File>, DbError> {
+        let files = sqlx::query_as("select id, size, created_at from file where user_id = ? order by created_at")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(files)
+    }
+
+    async fn tokens(&self) -> Result<Vec<ApiToken>, DbError> {
+        let tokens = sqlx::query_as("select id, subject, token_hash, created_at from api_token order by created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    async fn restore(&self, data: &ServerData) -> Result<(), DbError> {
+        let ServerData {
+            tokens,
+            users,
+            operations,
+            files,
+        } = data;
+
+        let mut transaction = self.pool.begin().await?;
+
+        for ApiToken {
+            id,
+            subject,
+            token_hash,
+            created_at,
+        } in tokens
+        {
+            sqlx::query("insert into api_token (id, subject, token_hash, created_at) values (?, ?, ?, ?)")
+                .bind(id)
+                .bind(subject)
+                .bind(token_hash)
+                .bind(timestamp(*created_at))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for UserAccount {
+            id,
+            subject,
+            secret_key_hash,
+        } in users
+        {
+            sqlx::query("insert into \"user\" (id, subject, secret_key_hash) values (?, ?, ?)")
+                .bind(id)
+                .bind(subject)
+                .bind(secret_key_hash)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for (
+            user_id,
+            Operation {
+                id,
+                created_at,
+                data,
+                checksum,
+            },
+        ) in operations
+        {
+            sqlx::query("insert into operation (id, user_id, created_at, data, checksum) values (?, ?, ?, ?, ?)")
+                .bind(id)
+                .bind(user_id)
+                .bind(timestamp(*created_at))
+                .bind(data)
+                .bind(checksum)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for (user_id, File { id, size, created_at }) in files {
+            sqlx::query("insert into file (id, user_id, size, created_at) values (?, ?, ?, ?)")
+                .bind(id)
+                .bind(user_id)
+                .bind(size)
+                .bind(timestamp(*created_at))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
+use std::time::Duration;
+
+use rocket::data::ByteUnit;
 use thiserror::Error;
+use web_api_types::{OperationRejection, RateLimited};
 
 use crate::db::DbError;
 
@@ -25,6 +29,25 @@ pub enum Error {
     #[error("unauthorized: {0}")]
     Unauthorized(&'static str),
 
+    #[error("user is not initialized")]
+    UserNotInitialized,
+
+    #[error("storage quota exceeded: {used} used + {requested} requested > {quota} quota")]
+    QuotaExceeded {
+        used: ByteUnit,
+        requested: ByteUnit,
+        quota: ByteUnit,
+    },
+
+    #[error("operations rejected: {0}")]
+    OperationRejected(OperationRejection),
+
+    #[error("rate limit exceeded: retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
+
+    #[error("archive error: {0}")]
+    Archive(String),
+
     #[error("reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
 
@@ -34,10 +57,21 @@ pub enum Error {
 
 impl From<DbError> for Error {
     fn from(err: DbError) -> Self {
-        if let DbError::SqlxError(sqlx::Error::RowNotFound) = err {
-            Self::NotFound
-        } else {
-            Self::DbError(err)
+        match err {
+            DbError::SqlxError(sqlx::Error::RowNotFound) => Self::NotFound,
+            DbError::OperationChecksumMismatch(id) => {
+                Self::OperationRejected(OperationRejection::ChecksumMismatch { id: id.into() })
+            }
+            DbError::QuotaExceeded { used, requested, quota } => {
+                let bytes = |value: i64| ByteUnit::from(u64::try_from(value).unwrap_or_default());
+
+                Self::QuotaExceeded {
+                    used: bytes(used),
+                    requested: bytes(requested),
+                    quota: bytes(quota),
+                }
+            }
+            err => Self::DbError(err),
         }
     }
 }
@@ -59,7 +93,18 @@ impl From<Error> for web_api_types::Error {
             Error::InvalidData(_) => Self::InvalidData(error.to_string()),
             Error::Io(_) => Self::Internal("internal IO error".into()),
             Error::FileSaver(_) => Self::Internal("internal file saver error".into()),
+            Error::Archive(err) => {
+                error!(err);
+                Self::Internal("archive error".into())
+            }
             Error::Unauthorized(err) => Self::Unauthorized(err.into()),
+            Error::UserNotInitialized => Self::AccessDenied(error.to_string()),
+            Error::QuotaExceeded { .. } => Self::QuotaExceeded(error.to_string()),
+            Error::OperationRejected(rejection) => Self::OperationRejected(rejection),
+            Error::RateLimited { retry_after } => Self::RateLimited(RateLimited {
+                // Round up, so the client does not retry too early.
+                retry_after: retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0),
+            }),
             Error::Reqwest(err) => {
                 error!(?err);
                 Self::Internal("failed to fetch".into())
@@ -8,9 +8,12 @@ compile_error!(
     "The dev feature is enabled, which is not intended for production use. Please disable it before deploying."
 );
 
+pub mod auth;
+mod cli;
 pub mod db;
 mod error;
 mod logging;
+mod metrics;
 mod routes;
 pub mod services;
 
@@ -21,23 +24,41 @@ pub use error::{Error, Result};
 use rocket::routes;
 use sqlx::postgres::PgPoolOptions;
 
-use crate::db::PostgresDb;
-use crate::services::{Data as DataService, UserService};
+use crate::auth::AuthProvider;
+use crate::db::{Db, PostgresDb};
+use crate::metrics::{Metrics, MetricsFairing};
+use crate::services::{
+    DEFAULT_FILE_GC_GRACE_PERIOD, DEFAULT_RATE_LIMIT, Data as DataService, FilesGc, RateLimiter, TokenService,
+    UsageService, UserService,
+};
 
-const CF_TEAM_NAME: &str = "DATAANS_WEB_SERVER_CF_TEAM_NAME";
-const CF_AUD: &str = "DATAANS_WEB_CF_AUD";
 const DATABASE_URL: &str = "DATAANS_WEB_SERVER_DATABASE_URL";
+/// Optional per-user storage quota. For example, `10 GiB`.
+const USER_QUOTA: &str = "DATAANS_WEB_SERVER_USER_QUOTA";
+/// Optional orphaned files grace period in seconds.
+const FILE_GC_GRACE_PERIOD: &str = "DATAANS_WEB_SERVER_FILE_GC_GRACE_PERIOD";
+/// Optional number of operation uploads allowed per minute for one identity. `0` disables the rate limiting.
+const RATE_LIMIT: &str = "DATAANS_WEB_SERVER_RATE_LIMIT";
+/// Optional Bearer token for the `/metrics` endpoint. The endpoint is disabled if the token is not set.
+const METRICS_TOKEN: &str = "DATAANS_WEB_SERVER_METRICS_TOKEN";
 
 pub struct State<D, S> {
-    pub cf_team_name: String,
-    pub cf_aud: String,
+    pub auth: AuthProvider,
+    pub db: Arc<D>,
+    pub metrics: Metrics,
+    /// Token required to read metrics. Metrics are disabled if it is not set.
+    pub metrics_token: Option<String>,
     pub data_service: DataService<D>,
     pub user_service: UserService<D>,
+    pub token_service: TokenService<D>,
+    pub usage_service: UsageService<D>,
+    pub files_gc: FilesGc<D>,
+    pub rate_limiter: RateLimiter,
     pub file_saver: S,
 }
 
 #[cfg(feature = "fs")]
-pub type WebServerState = State<PostgresDb, crate::services::Fs>;
+pub type WebServerState = State<Db, crate::services::Fs>;
 
 #[cfg(feature = "fs")]
 async fn prepare_file_loader() -> crate::services::Fs {
@@ -57,42 +78,135 @@ async fn prepare_file_loader() -> crate::services::Fs {
     crate::services::Fs::new(files_dir)
 }
 
-#[cfg(feature = "tigris")]
-async fn prepare_file_loader() -> crate::services::Tigris {
-    use aws_config::{BehaviorVersion, load_defaults};
+#[cfg(feature = "s3")]
+async fn prepare_file_loader() -> crate::services::S3 {
+    use crate::services::{S3, S3Config, S3Credentials};
 
     const BUCKET_NAME: &str = "DATAANS_WEB_SERVER_S3_BUCKET";
+    const ENDPOINT_URL: &str = "DATAANS_WEB_SERVER_S3_ENDPOINT_URL";
+    const REGION: &str = "DATAANS_WEB_SERVER_S3_REGION";
+    const FORCE_PATH_STYLE: &str = "DATAANS_WEB_SERVER_S3_FORCE_PATH_STYLE";
+    const ACCESS_KEY_ID: &str = "DATAANS_WEB_SERVER_S3_ACCESS_KEY_ID";
+    const SECRET_ACCESS_KEY: &str = "DATAANS_WEB_SERVER_S3_SECRET_ACCESS_KEY";
 
-    crate::services::Tigris::new(
-        load_defaults(BehaviorVersion::latest()).await,
-        env::var(BUCKET_NAME).expect("S3 bucket name env var should be set"),
-    )
+    let credentials = match (env::var(ACCESS_KEY_ID), env::var(SECRET_ACCESS_KEY)) {
+        (Ok(access_key_id), Ok(secret_access_key)) => Some(S3Credentials {
+            access_key_id,
+            secret_access_key,
+        }),
+        (Err(_), Err(_)) => None,
+        _ => panic!("both {ACCESS_KEY_ID} and {SECRET_ACCESS_KEY} env vars should be set"),
+    };
+
+    S3::new(S3Config {
+        bucket: env::var(BUCKET_NAME).expect("S3 bucket name env var should be set"),
+        endpoint_url: env::var(ENDPOINT_URL).ok(),
+        region: env::var(REGION).ok(),
+        force_path_style: env::var(FORCE_PATH_STYLE)
+            .map(|value| value.parse().expect("S3 force path style should be `true` or `false`"))
+            .unwrap_or_default(),
+        credentials,
+    })
+    .await
 }
 
-#[cfg(feature = "tigris")]
-pub type WebServerState = State<PostgresDb, crate::services::Tigris>;
+#[cfg(feature = "s3")]
+pub type WebServerState = State<Db, crate::services::S3>;
 
-impl WebServerState {
-    pub async fn new() -> WebServerState {
-        let cf_team_name = env::var(CF_TEAM_NAME).expect("Cloudflare team name env var should be set");
-        let cf_aud = env::var(CF_AUD).expect("Cloudflare AUD env var should be set");
+async fn connect_postgres(database_url: &str) -> PostgresDb {
+    let pool = PgPoolOptions::new()
+        .max_connections(16)
+        .min_connections(1)
+        .acquire_timeout(std::time::Duration::from_secs(3))
+        .connect_lazy(database_url)
+        .expect("can not connect to postgresql db");
+
+    sqlx::migrate!("./migrations/postgres")
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
+    PostgresDb::new(pool)
+}
+
+#[cfg(feature = "sqlite")]
+async fn connect_sqlite(database_url: &str) -> Db {
+    use std::str::FromStr;
 
-        let pool = PgPoolOptions::new()
-            .max_connections(16)
-            .min_connections(1)
-            .acquire_timeout(std::time::Duration::from_secs(3))
-            .connect_lazy(&env::var(DATABASE_URL).expect("database url env var should be set"))
-            .expect("can not connect to postgresql db");
+    use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
 
-        sqlx::migrate!().run(&pool).await.expect("Failed to run migrations");
+    let options = SqliteConnectOptions::from_str(database_url)
+        .expect("invalid sqlite database url")
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(4)
+        .acquire_timeout(std::time::Duration::from_secs(3))
+        .connect_with(options)
+        .await
+        .expect("can not connect to sqlite db");
+
+    sqlx::migrate!("./migrations/sqlite")
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
+    Db::Sqlite(crate::db::SqliteDb::new(pool))
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn connect_sqlite(_database_url: &str) -> Db {
+    panic!("SQLite support is disabled. Rebuild the server with the `sqlite` feature enabled")
+}
 
-        let db = Arc::new(PostgresDb::new(pool));
+async fn connect_db() -> Arc<Db> {
+    let database_url = env::var(DATABASE_URL).expect("database url env var should be set");
+
+    let db = if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        Db::Postgres(connect_postgres(&database_url).await)
+    } else if database_url.starts_with("sqlite:") {
+        connect_sqlite(&database_url).await
+    } else {
+        panic!("Unsupported database url. Supported schemes: postgres://, postgresql://, sqlite://")
+    };
+
+    Arc::new(db)
+}
+
+impl WebServerState {
+    pub async fn new() -> WebServerState {
+        let auth = AuthProvider::from_env();
+        let db = connect_db().await;
+        let quota = env::var(USER_QUOTA)
+            .ok()
+            .map(|quota| quota.parse().expect("user quota should be a valid size, e.g. `10 GiB`"));
+        let grace_period = env::var(FILE_GC_GRACE_PERIOD)
+            .map(|period| {
+                std::time::Duration::from_secs(period.parse().expect("grace period should be a number of seconds"))
+            })
+            .unwrap_or(DEFAULT_FILE_GC_GRACE_PERIOD);
+        let rate_limit = env::var(RATE_LIMIT)
+            .map(|limit| {
+                std::num::NonZeroU32::new(
+                    limit
+                        .parse()
+                        .expect("rate limit should be a number of requests per minute"),
+                )
+            })
+            .unwrap_or(Some(DEFAULT_RATE_LIMIT));
 
         Self {
-            cf_team_name,
-            cf_aud,
+            auth,
+            db: Arc::clone(&db),
+            metrics: Metrics::default(),
+            metrics_token: env::var(METRICS_TOKEN).ok().filter(|token| !token.is_empty()),
             data_service: DataService::new(Arc::clone(&db)),
             user_service: UserService::new(Arc::clone(&db)),
+            token_service: TokenService::new(Arc::clone(&db)),
+            usage_service: UsageService::new(Arc::clone(&db), quota),
+            files_gc: FilesGc::new(Arc::clone(&db), grace_period),
+            rate_limiter: RateLimiter::new(rate_limit),
             file_saver: prepare_file_loader().await,
         }
     }
@@ -102,20 +216,47 @@ impl WebServerState {
 async fn main() -> std::result::Result<(), Box<rocket::Error>> {
     logging::init_tracing();
 
+    // `env::Args` is not `Send`, so it can not be held across `.await`.
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if let Some((command, args)) = args.split_first() {
+        cli::run(command, args.iter().cloned()).await;
+    }
+
     let state = WebServerState::new().await;
 
     let _rocket = rocket::build()
         .manage(state)
+        .attach(MetricsFairing)
         .mount(
             "/data",
-            routes![routes::blocks, routes::operations, routes::add_operations,],
+            routes![
+                routes::blocks,
+                routes::operations,
+                routes::operations_page,
+                routes::add_operations,
+            ],
+        )
+        .mount(
+            "/file",
+            routes![
+                routes::upload,
+                routes::download,
+                routes::exists,
+                routes::collect_garbage
+            ],
         )
-        .mount("/file", routes![routes::upload, routes::download, routes::exists,])
         .mount("/user", routes![routes::get_user, routes::init_user,])
+        .mount("/usage", routes![routes::get_usage])
         .mount(
             "/health",
-            routes![routes::health, routes::health_auth, routes::cf_token],
+            routes![
+                routes::health,
+                routes::health_ready,
+                routes::health_auth,
+                routes::cf_token
+            ],
         )
+        .mount("/", routes![routes::metrics])
         .launch()
         .await?;
 
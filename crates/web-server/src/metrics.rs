@@ -0,0 +1,283 @@
+//! Server metrics in the Prometheus text format.
+//!
+//! Metrics are collected in memory and rendered on every `/metrics` request. They are reset on server restart.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+
+use crate::WebServerState;
+use crate::db::PoolStats;
+
+/// Upper bounds (in seconds) of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Route label of requests that did not match any route. It keeps the number of label values bounded.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+#[derive(Default)]
+struct RouteLatency {
+    /// Number of requests in every bucket. The last one is the `+Inf` bucket.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl RouteLatency {
+    fn observe(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Requests {
+    /// Number of requests by `(method, route, status)`.
+    counts: BTreeMap<(String, String, u16), u64>,
+    /// Request latencies by `(method, route)`.
+    latencies: BTreeMap<(String, String), RouteLatency>,
+}
+
+/// Server metrics.
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<Requests>,
+    operations_stored: AtomicU64,
+    uploaded_bytes: AtomicU64,
+    downloaded_bytes: AtomicU64,
+    auth_failures: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_request(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        let mut requests = self.requests.lock().expect("metrics mutex should not be poisoned");
+
+        *requests
+            .counts
+            .entry((method.to_owned(), route.to_owned(), status))
+            .or_default() += 1;
+        requests
+            .latencies
+            .entry((method.to_owned(), route.to_owned()))
+            .or_default()
+            .observe(latency);
+    }
+
+    pub fn operations_stored(&self, count: usize) {
+        self.operations_stored.fetch_add(
+            u64::try_from(count).expect("usize -> u64 conversion should not fail"),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn uploaded(&self, bytes: u64) {
+        self.uploaded_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn downloaded(&self, bytes: u64) {
+        self.downloaded_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn auth_failure(&self) {
+        self.auth_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn render(&self, pool: PoolStats) -> String {
+        let mut out = String::new();
+
+        {
+            let requests = self.requests.lock().expect("metrics mutex should not be poisoned");
+
+            header(
+                &mut out,
+                "dataans_http_requests_total",
+                "counter",
+                "Total number of HTTP requests.",
+            );
+            for ((method, route, status), count) in &requests.counts {
+                let _ = writeln!(
+                    out,
+                    "dataans_http_requests_total{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {count}",
+                    escape(route)
+                );
+            }
+
+            header(
+                &mut out,
+                "dataans_http_request_duration_seconds",
+                "histogram",
+                "HTTP request latency in seconds.",
+            );
+            for ((method, route), latency) in &requests.latencies {
+                let labels = format!("method=\"{method}\",route=\"{}\"", escape(route));
+
+                let mut cumulative = 0;
+                for (bound, count) in LATENCY_BUCKETS.iter().zip(&latency.buckets) {
+                    cumulative += count;
+                    let _ = writeln!(
+                        out,
+                        "dataans_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {cumulative}"
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "dataans_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                    latency.count
+                );
+                let _ = writeln!(
+                    out,
+                    "dataans_http_request_duration_seconds_sum{{{labels}}} {}",
+                    latency.sum
+                );
+                let _ = writeln!(
+                    out,
+                    "dataans_http_request_duration_seconds_count{{{labels}}} {}",
+                    latency.count
+                );
+            }
+        }
+
+        for (name, kind, help, value) in [
+            (
+                "dataans_operations_stored_total",
+                "counter",
+                "Total number of operations uploaded by users.",
+                self.operations_stored.load(Ordering::Relaxed),
+            ),
+            (
+                "dataans_uploaded_bytes_total",
+                "counter",
+                "Total number of uploaded bytes (operations data and files).",
+                self.uploaded_bytes.load(Ordering::Relaxed),
+            ),
+            (
+                "dataans_downloaded_bytes_total",
+                "counter",
+                "Total number of downloaded bytes (operations data and files).",
+                self.downloaded_bytes.load(Ordering::Relaxed),
+            ),
+            (
+                "dataans_auth_failures_total",
+                "counter",
+                "Total number of failed authentication attempts.",
+                self.auth_failures.load(Ordering::Relaxed),
+            ),
+            (
+                "dataans_db_pool_connections",
+                "gauge",
+                "Number of open database connections.",
+                u64::from(pool.size),
+            ),
+            (
+                "dataans_db_pool_idle_connections",
+                "gauge",
+                "Number of idle database connections.",
+                u64::try_from(pool.idle).expect("usize -> u64 conversion should not fail"),
+            ),
+        ] {
+            header(&mut out, name, kind, help);
+            let _ = writeln!(out, "{name} {value}");
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escapes the label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Request start time.
+struct RequestStart(Instant);
+
+/// Records the count and latency of every request.
+pub struct MetricsFairing;
+
+#[rocket::async_trait]
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(state) = req.rocket().state::<WebServerState>() else {
+            return;
+        };
+
+        let latency = req.local_cache(|| RequestStart(Instant::now())).0.elapsed();
+        let route = req
+            .route()
+            .map(|route| route.uri.path().to_string())
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_owned());
+
+        state
+            .metrics
+            .record_request(req.method().as_str(), &route, res.status().code, latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Metrics;
+    use crate::db::PoolStats;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+
+        metrics.record_request("GET", "/data/block", 200, Duration::from_millis(3));
+        metrics.record_request("GET", "/data/block", 200, Duration::from_millis(30));
+        metrics.record_request("GET", "/data/block", 401, Duration::from_secs(20));
+        metrics.operations_stored(3);
+        metrics.uploaded(1024);
+        metrics.auth_failure();
+
+        let rendered = metrics.render(PoolStats { size: 4, idle: 3 });
+
+        for line in [
+            "dataans_http_requests_total{method=\"GET\",route=\"/data/block\",status=\"200\"} 2",
+            "dataans_http_requests_total{method=\"GET\",route=\"/data/block\",status=\"401\"} 1",
+            "dataans_http_request_duration_seconds_bucket{method=\"GET\",route=\"/data/block\",le=\"0.005\"} 1",
+            "dataans_http_request_duration_seconds_bucket{method=\"GET\",route=\"/data/block\",le=\"0.05\"} 2",
+            "dataans_http_request_duration_seconds_bucket{method=\"GET\",route=\"/data/block\",le=\"10\"} 2",
+            "dataans_http_request_duration_seconds_bucket{method=\"GET\",route=\"/data/block\",le=\"+Inf\"} 3",
+            "dataans_http_request_duration_seconds_count{method=\"GET\",route=\"/data/block\"} 3",
+            "dataans_operations_stored_total 3",
+            "dataans_uploaded_bytes_total 1024",
+            "dataans_downloaded_bytes_total 0",
+            "dataans_auth_failures_total 1",
+            "dataans_db_pool_connections 4",
+            "dataans_db_pool_idle_connections 3",
+        ] {
+            assert!(rendered.lines().any(|rendered_line| rendered_line == line), "{line}");
+        }
+    }
+}
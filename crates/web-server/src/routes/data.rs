@@ -1,25 +1,79 @@
 use rocket::serde::json::Json;
 use rocket::{State, get, post};
-use web_api_types::{Blocks, Operation, Result};
+use web_api_types::{Blocks, Operation, OperationsPage, Result};
 
 use crate::WebServerState;
 use crate::routes::UserContext;
 
 #[get("/block?<items_per_block>")]
-pub async fn blocks(_u: UserContext, server: &State<WebServerState>, items_per_block: usize) -> Result<Json<Blocks>> {
-    Ok(Json(server.data_service.blocks(items_per_block).await?))
+pub async fn blocks(u: UserContext, server: &State<WebServerState>, items_per_block: usize) -> Result<Json<Blocks>> {
+    Ok(Json(server.data_service.blocks(u.user_id()?, items_per_block).await?))
 }
 
+/// Returns all operations after the first `operations_to_skip` operations.
+///
+/// It is kept for older app versions. Use the paginated [operations_page] instead.
 #[get("/operation?<operations_to_skip>")]
 pub async fn operations(
-    _u: UserContext,
+    u: UserContext,
     server: &State<WebServerState>,
     operations_to_skip: usize,
 ) -> Result<Json<Vec<Operation>>> {
-    Ok(Json(server.data_service.operations(operations_to_skip).await?))
+    let operations = server.data_service.operations(u.user_id()?, operations_to_skip).await?;
+    server.metrics.downloaded(data_size(&operations));
+
+    Ok(Json(operations))
+}
+
+/// Returns one page of operations.
+///
+/// The first page starts after `operations_to_skip` operations. Next pages are requested using the `continuation` token
+/// from the previous page.
+#[get("/operation/page?<operations_to_skip>&<continuation>&<limit>")]
+pub async fn operations_page(
+    u: UserContext,
+    server: &State<WebServerState>,
+    operations_to_skip: Option<usize>,
+    continuation: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Json<OperationsPage>> {
+    let page = server
+        .data_service
+        .operations_page(
+            u.user_id()?,
+            operations_to_skip.unwrap_or_default(),
+            continuation,
+            limit,
+        )
+        .await?;
+    server.metrics.downloaded(data_size(&page.operations));
+
+    Ok(Json(page))
 }
 
 #[post("/operation", data = "<data>")]
-pub async fn add_operations(_u: UserContext, server: &State<WebServerState>, data: Json<Vec<Operation>>) -> Result<()> {
-    Ok(server.data_service.add_operations(data.into_inner()).await?)
+pub async fn add_operations(u: UserContext, server: &State<WebServerState>, data: Json<Vec<Operation>>) -> Result<()> {
+    let user_id = u.user_id()?;
+    server.rate_limiter.check(&u.identity.subject)?;
+    let operations = data.into_inner();
+
+    let bytes = data_size(&operations);
+    let count = operations.len();
+
+    server
+        .data_service
+        .add_operations(user_id, operations, server.usage_service.quota())
+        .await?;
+    server.metrics.operations_stored(count);
+    server.metrics.uploaded(bytes);
+
+    Ok(())
+}
+
+/// Returns the total size of operations data.
+fn data_size(operations: &[Operation]) -> u64 {
+    operations
+        .iter()
+        .map(|operation| operation.data.as_ref().len() as u64)
+        .sum()
 }
@@ -1,24 +1,90 @@
-use rocket::data::{Data, ToByteUnit};
+use std::convert::Infallible;
+
+use rocket::data::{ByteUnit, Data};
 use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
 use rocket::serde::json::Json;
 use rocket::{Response, State, get, post};
 use uuid::Uuid;
-use web_api_types::Result;
+use web_api_types::{FileGcReport, FileGcRequest, Result};
 
-use crate::WebServerState;
 use crate::routes::UserContext;
 use crate::services::FileSaver;
+use crate::{Error, WebServerState};
+
+/// Max size of the uploaded file.
+const MAX_FILE_SIZE: ByteUnit = ByteUnit::Gigabyte(2);
+
+/// The `Content-Length` request header value.
+pub struct ContentLength(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ContentLength {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ContentLength(
+            req.headers()
+                .get_one("Content-Length")
+                .and_then(|value| value.parse().ok()),
+        ))
+    }
+}
 
 #[post("/<id>", data = "<data>")]
-pub async fn upload(_u: UserContext, server: &State<WebServerState>, id: Uuid, data: Data<'_>) -> Result<()> {
-    server.file_saver.save_file(id, data.open(2.gigabytes())).await?;
+pub async fn upload(
+    u: UserContext,
+    server: &State<WebServerState>,
+    id: Uuid,
+    content_length: ContentLength,
+    data: Data<'_>,
+) -> Result<()> {
+    let user_id = u.user_id()?;
+
+    let limit = match content_length.0 {
+        Some(size) if size > MAX_FILE_SIZE.as_u64() => {
+            return Err(Error::InvalidData("file size").into());
+        }
+        Some(size) => ByteUnit::from(size),
+        // The file size must be known in advance to check the quota.
+        None if server.usage_service.quota().is_some() => {
+            return Err(Error::InvalidData("request: the Content-Length header is required").into());
+        }
+        None => MAX_FILE_SIZE,
+    };
+
+    // The file is rejected before receiving it if it does not fit into the quota.
+    let size = server
+        .usage_service
+        .upload_file(&server.file_saver, user_id, id, content_length.0, data.open(limit))
+        .await?;
+    server.metrics.uploaded(size);
 
     Ok(())
 }
 
+/// Deletes files that are not referenced by the user's data anymore.
+///
+/// Files are deleted only after the grace period. See [FilesGc](crate::services::FilesGc) for more details.
+#[post("/gc", data = "<data>")]
+pub async fn collect_garbage(
+    u: UserContext,
+    server: &State<WebServerState>,
+    data: Json<FileGcRequest>,
+) -> Result<Json<FileGcReport>> {
+    let FileGcRequest { live_files } = data.into_inner();
+
+    Ok(Json(
+        server
+            .files_gc
+            .collect(&server.file_saver, u.user_id()?, &live_files)
+            .await?,
+    ))
+}
+
 #[get("/<id>/exists")]
-pub async fn exists(_u: UserContext, server: &State<WebServerState>, id: Uuid) -> Result<Json<bool>> {
-    Ok(Json(server.file_saver.exists(id).await?))
+pub async fn exists(u: UserContext, server: &State<WebServerState>, id: Uuid) -> Result<Json<bool>> {
+    Ok(Json(server.file_saver.exists(u.user_id()?, id).await?))
 }
 
 pub struct Resp<'r>(Response<'r>);
@@ -30,14 +96,17 @@ impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Resp<'o> {
 }
 
 #[get("/<id>")]
-pub async fn download(_u: UserContext, server: &State<WebServerState>, id: Uuid) -> Result<Resp<'_>> {
-    let (size, data) = server.file_saver.open_file(id).await?;
+pub async fn download(u: UserContext, server: &State<WebServerState>, id: Uuid) -> Result<Resp<'_>> {
+    let (size, data) = server.file_saver.open_file(u.user_id()?, id).await?;
 
     let mut response_builder = Response::build();
     response_builder.status(Status::Ok).header(ContentType::Binary);
 
     if let Some(size) = size {
         response_builder.header(Header::new("Content-Length", size.to_string()));
+        server
+            .metrics
+            .downloaded(u64::try_from(size).expect("usize -> u64 conversion should not fail"));
     }
 
     Ok(Resp(response_builder.streamed_body(data).finalize()))
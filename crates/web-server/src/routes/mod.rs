@@ -1,31 +1,58 @@
 mod data;
 mod file;
+mod usage;
 mod user;
 
-use std::str::FromStr;
-
 pub use data::*;
 pub use file::*;
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
-use rocket::get;
 use rocket::http::{ContentType, Status};
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::response::{self, Responder, Response};
-use serde::Deserialize;
+use rocket::{State, get};
+use sha2::{Digest, Sha256};
+pub use usage::*;
 pub use user::*;
 use uuid::Uuid;
 
+use crate::auth::{Identity, bearer_token};
+use crate::services::FileSaver;
 use crate::{Error, WebServerState};
 
-pub const AUTH_HEADER_NAME: &str = "cf-access-jwt-assertion";
-
 #[get("/")]
 pub fn health() -> &'static str {
     "ok"
 }
 
+/// Readiness check for container orchestrators.
+///
+/// The server is ready when the database and the files storage are reachable.
+#[get("/ready")]
+pub async fn health_ready(server: &State<WebServerState>) -> (Status, &'static str) {
+    if let Err(err) = server.db.ping().await {
+        error!(?err, "Readiness check failed: the database is unreachable");
+
+        return (Status::ServiceUnavailable, "database is unreachable");
+    }
+
+    if let Err(err) = server.file_saver.health_check().await {
+        error!(?err, "Readiness check failed: the files storage is unreachable");
+
+        return (Status::ServiceUnavailable, "files storage is unreachable");
+    }
+
+    (Status::Ok, "ready")
+}
+
+/// Server metrics in the Prometheus text format.
+#[get("/metrics")]
+pub fn metrics(_access: MetricsAccess, server: &State<WebServerState>) -> (ContentType, String) {
+    (ContentType::Plain, server.metrics.render(server.db.pool_stats()))
+}
+
 #[get("/auth")]
-pub fn health_auth(_u: UserContext) -> &'static str {
+pub fn health_auth(u: UserContext) -> &'static str {
+    trace!(subject = %u.identity.subject, "Auth health check");
+
     "auth_ok"
 }
 
@@ -46,30 +73,16 @@ pub async fn cf_token(_u: UserContext) -> AuthorizationPage {
     AuthorizationPage(include_str!("../../authorize.html"))
 }
 
-#[derive(Debug)]
-pub struct UserContext;
+/// Access to the server metrics.
+///
+/// Metrics are disabled unless the metrics token is configured. The token must be passed as a Bearer token.
+pub struct MetricsAccess;
 
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for UserContext {
+impl<'r> FromRequest<'r> for MetricsAccess {
     type Error = Error;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        #![cfg_attr(feature = "dev", allow(unreachable_code))]
-        #![cfg_attr(feature = "dev", allow(unused_variables))]
-
-        // Only for local development!
-        #[cfg(feature = "dev")]
-        {
-            return Outcome::Success(UserContext);
-        }
-
-        let Some(token) = req.headers().get_one(AUTH_HEADER_NAME) else {
-            return Outcome::Error((
-                Status::Unauthorized,
-                Error::Unauthorized("missing authentication token"),
-            ));
-        };
-
         let state = match req
             .rocket()
             .state::<WebServerState>()
@@ -78,83 +91,79 @@ impl<'r> FromRequest<'r> for UserContext {
             Ok(state) => state,
             Err(err) => return Outcome::Error((Status::InternalServerError, err)),
         };
-        if let Err(err) = verify_auth_token(&state.cf_team_name, &state.cf_aud, token).await {
-            return Outcome::Error((Status::Unauthorized, err));
-        }
 
-        Outcome::Success(UserContext)
+        let Some(metrics_token) = state.metrics_token.as_deref() else {
+            return Outcome::Error((Status::NotFound, Error::NotFound));
+        };
+
+        // Hashes have the same length, so the comparison does not leak the token length.
+        match bearer_token(req.headers()) {
+            Ok(token) if Sha256::digest(token) == Sha256::digest(metrics_token) => Outcome::Success(MetricsAccess),
+            Ok(_) => Outcome::Error((Status::Unauthorized, Error::Unauthorized("invalid metrics token"))),
+            Err(err) => Outcome::Error((Status::Unauthorized, err)),
+        }
     }
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct CloudflareKey {
-    kid: String,
-    kty: String,
-    alg: String,
-    #[serde(rename = "use")]
-    key_use: String,
-    e: String,
-    n: String,
+/// Authenticated user context.
+///
+/// Any route that requires authentication must have this request guard.
+#[derive(Debug)]
+pub struct UserContext {
+    pub identity: Identity,
+    /// Id of the user bound to the authenticated identity.
+    ///
+    /// It is `None` when the user is not initialized yet.
+    pub user_id: Option<Uuid>,
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct CloudflarePublicCert {
-    kid: String,
-    cert: String,
+impl UserContext {
+    /// Returns the id of the authenticated user.
+    ///
+    /// Fails if the user is not initialized yet.
+    pub fn user_id(&self) -> Result<Uuid, Error> {
+        self.user_id.ok_or(Error::UserNotInitialized)
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct CloudflareCerts {
-    keys: Vec<CloudflareKey>,
-    public_cert: CloudflarePublicCert,
-    public_certs: Vec<CloudflarePublicCert>,
-}
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UserContext {
+    type Error = Error;
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct Claims {
-    aud: Vec<String>,
-    country: String,
-    email: String,
-    exp: i64,
-    iat: i64,
-    identity_nonce: String,
-    iss: String,
-    nbf: i64,
-    sub: Uuid,
-    #[serde(rename = "type")]
-    claim_type: String,
-}
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let state = match req
+            .rocket()
+            .state::<WebServerState>()
+            .ok_or_else(|| Error::Internal("missing Rocket state"))
+        {
+            Ok(state) => state,
+            Err(err) => return Outcome::Error((Status::InternalServerError, err)),
+        };
+
+        // Only for local development!
+        #[cfg(feature = "dev")]
+        let identity = Identity {
+            subject: String::from("dev"),
+        };
 
-async fn verify_auth_token(team_name: &str, aud: &str, token: &str) -> Result<(), Error> {
-    let header = decode_header(token).map_err(|_| Error::Unauthorized("invalid authentication token header"))?;
-    let k_id = header
-        .kid
-        .ok_or_else(|| Error::Unauthorized("authentication token header does not contain 'kid' field"))?;
-
-    let certs = reqwest::get(format!("https://{team_name}.cloudflareaccess.com/cdn-cgi/access/certs"))
-        .await?
-        .error_for_status()?
-        .json::<CloudflareCerts>()
-        .await?;
-
-    let key = certs
-        .keys
-        .iter()
-        .find(|k| k.kid == k_id)
-        .ok_or_else(|| Error::Unauthorized("authentication token key id does not match any Cloudflare key"))?;
-    let decoding_key = DecodingKey::from_rsa_components(&key.n, &key.e)?;
-    let mut validation = Validation::new(
-        Algorithm::from_str(&key.alg)
-            .map_err(|_| Error::Unauthorized("invalid algorithm in authentication token header"))?,
-    );
-    validation.set_audience(&[aud]);
-
-    let token_data = decode::<Claims>(token, &decoding_key, &validation)?;
-    trace!(?token_data, "Decoded authentication token");
-
-    Ok(())
+        #[cfg(not(feature = "dev"))]
+        let identity = match state.auth.authenticate(req.headers(), &state.token_service).await {
+            Ok(identity) => identity,
+            Err(err) => {
+                debug!(?err, "Failed to authenticate the request");
+                state.metrics.auth_failure();
+
+                return Outcome::Error((Status::Unauthorized, err));
+            }
+        };
+
+        match state.user_service.user_id(&identity.subject).await {
+            Ok(user_id) => Outcome::Success(UserContext { identity, user_id }),
+            Err(err) => {
+                error!(?err, "Failed to resolve the authenticated user");
+
+                Outcome::Error((Status::InternalServerError, err))
+            }
+        }
+    }
 }
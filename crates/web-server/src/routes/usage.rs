@@ -0,0 +1,11 @@
+use rocket::serde::json::Json;
+use rocket::{State, get};
+use web_api_types::{Result, Usage};
+
+use crate::WebServerState;
+use crate::routes::UserContext;
+
+#[get("/")]
+pub async fn get_usage(u: UserContext, server: &State<WebServerState>) -> Result<Json<Usage>> {
+    Ok(Json(server.usage_service.usage(u.user_id()?).await?))
+}
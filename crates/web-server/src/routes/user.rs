@@ -6,11 +6,11 @@ use crate::WebServerState;
 use crate::routes::UserContext;
 
 #[get("/")]
-pub async fn get_user(_u: UserContext, server: &State<WebServerState>) -> Result<Option<Json<User>>> {
-    Ok(server.user_service.user().await?.map(Json))
+pub async fn get_user(u: UserContext, server: &State<WebServerState>) -> Result<Option<Json<User>>> {
+    Ok(server.user_service.user(&u.identity.subject).await?.map(Json))
 }
 
 #[post("/", data = "<data>")]
-pub async fn init_user(_u: UserContext, server: &State<WebServerState>, data: Json<User>) -> Result<()> {
-    Ok(server.user_service.init(data.into_inner()).await?)
+pub async fn init_user(u: UserContext, server: &State<WebServerState>, data: Json<User>) -> Result<()> {
+    Ok(server.user_service.init(&u.identity.subject, data.into_inner()).await?)
 }
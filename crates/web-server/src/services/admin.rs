@@ -0,0 +1,379 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rocket::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, copy, sink};
+use time::OffsetDateTime;
+use uuid::Uuid;
+use web_api_types::OPERATION_CHECKSUM_SIZE;
+
+use crate::db::{AdminDb, DbError, File, FilesDb, Operation, OperationsDb, ServerData, Usage, UsageDb, UserAccount};
+use crate::services::{ArchiveReader, ArchiveSummary, ArchiveWriter, FileSaver, Record};
+use crate::{Error, Result};
+
+/// Reads all archive records into `data` saving files into the storage.
+///
+/// Saved files are added to `data` as soon as they are saved. So, the caller can delete them on failure.
+async fn read_archive<S: FileSaver>(
+    archive: &mut ArchiveReader<impl AsyncRead + Unpin>,
+    file_saver: &S,
+    data: &mut ServerData,
+) -> Result<()> {
+    while let Some(record) = archive.next_record().await? {
+        match record {
+            Record::Token(token) => data.tokens.push(token),
+            Record::User(user) => data.users.push(user),
+            Record::Operation {
+                user_id,
+                id,
+                created_at,
+                checksum,
+            } => {
+                let mut operation_data = Vec::new();
+                archive.read_to_end(&mut operation_data).await?;
+
+                data.operations.push((
+                    user_id,
+                    Operation {
+                        id,
+                        created_at,
+                        data: operation_data,
+                        checksum,
+                    },
+                ));
+            }
+            Record::File {
+                user_id,
+                id,
+                created_at,
+            } => {
+                let size = file_saver.save_file(user_id, id, &mut *archive).await?;
+
+                if let Err(err) = archive.finish_record().await {
+                    file_saver.delete_file(user_id, id).await?;
+
+                    return Err(err);
+                }
+
+                data.files.push((
+                    user_id,
+                    File {
+                        id,
+                        size: i64::try_from(size).map_err(|_| Error::InvalidData("file size"))?,
+                        created_at,
+                    },
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// User's storage statistics.
+#[derive(Debug)]
+pub struct UserStats {
+    pub user: UserAccount,
+    pub usage: Usage,
+}
+
+/// Server data consistency report.
+///
+/// Operations are end-to-end encrypted, so the server can not find which files they reference.
+/// Instead, the server checks that every uploaded file (tracked in the database) is present in the storage.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// `(user id, operation id)` of operations with checksums of a wrong size.
+    ///
+    /// The checksum is a hash of the plaintext operation computed by the client. The server can not recompute it,
+    /// so only the checksum size is checked.
+    pub malformed_checksums: Vec<(Uuid, Uuid)>,
+    /// `(user id, file id)` of uploaded files missing in the storage.
+    pub missing_files: Vec<(Uuid, Uuid)>,
+    /// `(user id, file id)` of stored files unknown to the database.
+    pub untracked_files: Vec<(Uuid, Uuid)>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.malformed_checksums.is_empty() && self.missing_files.is_empty() && self.untracked_files.is_empty()
+    }
+}
+
+/// Server administration: backup, restore, and inspection.
+pub struct Admin<D> {
+    db: Arc<D>,
+}
+
+impl<D> Admin<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db }
+    }
+}
+
+impl<D: AdminDb + OperationsDb + FilesDb + UsageDb> Admin<D> {
+    /// Exports all server data into the archive.
+    ///
+    /// Files missing in the storage are skipped.
+    #[instrument(err, skip(self, file_saver, writer))]
+    pub async fn export<S: FileSaver>(
+        &self,
+        file_saver: &S,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<ArchiveSummary> {
+        let mut archive = ArchiveWriter::new(writer).await?;
+
+        for token in self.db.tokens().await? {
+            archive.write(&Record::Token(token), 0, [].as_slice()).await?;
+        }
+
+        for user in self.db.users().await? {
+            let user_id = user.id;
+            archive.write(&Record::User(user), 0, [].as_slice()).await?;
+
+            for operation in self.db.operations(user_id, 0).await? {
+                let Operation {
+                    id,
+                    created_at,
+                    data,
+                    checksum,
+                } = operation;
+
+                archive
+                    .write(
+                        &Record::Operation {
+                            user_id,
+                            id,
+                            created_at,
+                            checksum,
+                        },
+                        u64::try_from(data.len()).expect("usize -> u64 conversion should not fail"),
+                        data.as_slice(),
+                    )
+                    .await?;
+            }
+
+            for File { id, size, created_at } in self.db.files(user_id).await? {
+                let (stored_size, reader) = match file_saver.open_file(user_id, id).await {
+                    Ok(file) => file,
+                    Err(err) => {
+                        warn!(?err, %user_id, %id, "Failed to open the file. Skipping it");
+                        continue;
+                    }
+                };
+                let size = match stored_size {
+                    Some(size) => u64::try_from(size).expect("usize -> u64 conversion should not fail"),
+                    None => u64::try_from(size).map_err(|_| Error::InvalidData("file size"))?,
+                };
+
+                archive
+                    .write(
+                        &Record::File {
+                            user_id,
+                            id,
+                            created_at,
+                        },
+                        size,
+                        std::pin::pin!(reader),
+                    )
+                    .await?;
+            }
+        }
+
+        archive.finish().await
+    }
+
+    /// Imports the archive created by [Admin::export].
+    ///
+    /// The server must be empty. Files are saved into the storage while the archive is read, and database records
+    /// are restored at the end in one transaction. If the import fails, saved files are deleted, so the server
+    /// stays empty.
+    #[instrument(err, skip(self, file_saver, reader))]
+    pub async fn import<S: FileSaver>(&self, file_saver: &S, reader: impl AsyncRead + Unpin) -> Result<ArchiveSummary> {
+        if !self.db.users().await?.is_empty() {
+            return Err(Error::Archive(
+                "the server already has users. Import is allowed only into an empty server".into(),
+            ));
+        }
+
+        let mut archive = ArchiveReader::new(reader).await?;
+        let mut data = ServerData::default();
+
+        let result = match read_archive(&mut archive, file_saver, &mut data).await {
+            Ok(()) => self.db.restore(&data).await.map_err(Error::from),
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            for (user_id, file) in &data.files {
+                if let Err(err) = file_saver.delete_file(*user_id, file.id).await {
+                    warn!(?err, %user_id, id = %file.id, "Failed to delete the imported file");
+                }
+            }
+
+            return Err(err);
+        }
+
+        Ok(archive.summary())
+    }
+
+    /// Returns storage statistics of all users.
+    pub async fn stats(&self) -> Result<Vec<UserStats>> {
+        let mut stats = Vec::new();
+
+        for user in self.db.users().await? {
+            let usage = self.db.usage(user.id).await?;
+
+            stats.push(UserStats { user, usage });
+        }
+
+        Ok(stats)
+    }
+
+    /// Checks the consistency of the database and the files storage.
+    #[instrument(err, skip(self, file_saver))]
+    pub async fn verify<S: FileSaver>(&self, file_saver: &S) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+
+        for user in self.db.users().await? {
+            let user_id = user.id;
+
+            report.malformed_checksums.extend(
+                self.db
+                    .operations(user_id, 0)
+                    .await?
+                    .into_iter()
+                    .filter(|operation| operation.checksum.len() != OPERATION_CHECKSUM_SIZE)
+                    .map(|operation| (user_id, operation.id)),
+            );
+
+            let tracked: HashSet<Uuid> = self.db.files(user_id).await?.into_iter().map(|file| file.id).collect();
+            let stored: HashSet<Uuid> = file_saver.list_files(user_id).await?.into_iter().collect();
+
+            report
+                .missing_files
+                .extend(tracked.difference(&stored).map(|id| (user_id, *id)));
+            report
+                .untracked_files
+                .extend(stored.difference(&tracked).map(|id| (user_id, *id)));
+        }
+
+        Ok(report)
+    }
+
+    /// Registers stored files unknown to the database.
+    ///
+    /// Files uploaded before the storage usage tracking are not counted in the user's usage until they are registered.
+    /// Returns the number of registered files.
+    #[instrument(err, skip(self, file_saver))]
+    pub async fn track_files<S: FileSaver>(&self, file_saver: &S) -> Result<usize> {
+        let mut registered = 0;
+
+        for user in self.db.users().await? {
+            let user_id = user.id;
+            let tracked: HashSet<Uuid> = self.db.files(user_id).await?.into_iter().map(|file| file.id).collect();
+
+            for id in file_saver.list_files(user_id).await? {
+                if tracked.contains(&id) {
+                    continue;
+                }
+
+                let (size, reader) = file_saver.open_file(user_id, id).await?;
+                let size = match size {
+                    Some(size) => u64::try_from(size).expect("usize -> u64 conversion should not fail"),
+                    None => copy(&mut std::pin::pin!(reader), &mut sink()).await?,
+                };
+
+                self.db
+                    .add_file(
+                        user_id,
+                        &File {
+                            id,
+                            size: i64::try_from(size).map_err(|_| Error::InvalidData("file size"))?,
+                            created_at: OffsetDateTime::now_utc(),
+                        },
+                        None,
+                    )
+                    .await?;
+                registered += 1;
+            }
+        }
+
+        Ok(registered)
+    }
+
+    /// Binds the user created before the multi-user support to the subject.
+    ///
+    /// Legacy files (stored outside of users' namespaces) are moved into the user's namespace first.
+    /// So, the command can be safely repeated if it fails in the middle.
+    /// Returns the id of the bound user and the number of moved files.
+    #[instrument(err, skip(self, file_saver))]
+    pub async fn bind_legacy_user<S: FileSaver>(&self, file_saver: &S, subject: &str) -> Result<(Uuid, usize)> {
+        let users = self.db.users().await?;
+
+        if users.iter().any(|user| user.subject.as_deref() == Some(subject)) {
+            return Err(DbError::UserAlreadyExist.into());
+        }
+        let user_id = users
+            .into_iter()
+            .find(|user| user.subject.is_none())
+            .ok_or(Error::NotFound)?
+            .id;
+
+        let legacy_files = file_saver.list_legacy_files().await?;
+        for &id in &legacy_files {
+            let size = file_saver.adopt_legacy_file(user_id, id).await?;
+
+            self.db
+                .add_file(
+                    user_id,
+                    &File {
+                        id,
+                        size: i64::try_from(size).map_err(|_| Error::InvalidData("file size"))?,
+                        created_at: OffsetDateTime::now_utc(),
+                    },
+                    None,
+                )
+                .await?;
+        }
+
+        self.db.bind_user(user_id, subject).await?;
+
+        info!(%subject, %user_id, files = legacy_files.len(), "Legacy user has been bound");
+
+        Ok((user_id, legacy_files.len()))
+    }
+
+    /// Deletes all data of the user with the given subject or id.
+    ///
+    /// The user created before the multi-user support has no subject, so it can be selected only by its id.
+    /// Its legacy files (stored outside of users' namespaces) are deleted too.
+    /// The user can initialize the sync again from scratch. Issued API tokens stay valid.
+    /// Returns the usage of the deleted user.
+    #[instrument(err, skip(self, file_saver))]
+    pub async fn reset_user<S: FileSaver>(&self, file_saver: &S, user: &str) -> Result<Usage> {
+        let user_id = Uuid::parse_str(user).ok();
+        let user = self
+            .db
+            .users()
+            .await?
+            .into_iter()
+            .find(|account| account.subject.as_deref() == Some(user) || Some(account.id) == user_id)
+            .ok_or(Error::NotFound)?;
+
+        let usage = self.db.usage(user.id).await?;
+
+        if user.subject.is_none() {
+            for id in file_saver.list_legacy_files().await? {
+                file_saver.adopt_legacy_file(user.id, id).await?;
+            }
+        }
+        for id in file_saver.list_files(user.id).await? {
+            file_saver.delete_file(user.id, id).await?;
+        }
+        self.db.remove_user(user.id).await?;
+
+        info!(subject = ?user.subject, user_id = %user.id, "User has been reset");
+
+        Ok(usage)
+    }
+}
@@ -0,0 +1,366 @@
+//! Server data archive.
+//!
+//! The archive contains all users, API tokens, operations, and files of the server. Operations and files
+//! are end-to-end encrypted, so the archive is as safe as the server database and storage.
+//!
+//! # Format
+//!
+//! ```text
+//! archive = MAGIC record* end
+//! record  = header_len: u32 | header: JSON | body_len: u64 | body | sha256(header | body)
+//! end     = 0: u32
+//! ```
+//!
+//! All integers are big-endian. The header is a JSON-encoded [Record]. The body is the operation data or
+//! the file content. Every record has its own checksum, so a corrupted record is detected as soon as it is read.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use rocket::serde::json::serde_json;
+use rocket::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, Take, copy, sink};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::db::{ApiToken, UserAccount};
+use crate::{Error, Result};
+
+const MAGIC: &[u8] = b"DATAANS-SERVER-ARCHIVE-V1\n";
+const CHECKSUM_SIZE: usize = 32;
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+/// Headers are small JSON objects. The limit prevents huge allocations when reading a corrupted archive.
+const MAX_HEADER_SIZE: u32 = 64 * 1024;
+
+/// Archive record header.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Record {
+    /// API token. The record has no body.
+    Token(ApiToken),
+    /// User. The record has no body.
+    User(UserAccount),
+    /// User's operation. The body is the encrypted operation data.
+    Operation {
+        user_id: Uuid,
+        id: Uuid,
+        created_at: OffsetDateTime,
+        checksum: Vec<u8>,
+    },
+    /// User's file. The body is the encrypted file content.
+    File {
+        user_id: Uuid,
+        id: Uuid,
+        created_at: OffsetDateTime,
+    },
+}
+
+/// Number of archived objects.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub users: u64,
+    pub tokens: u64,
+    pub operations: u64,
+    pub files: u64,
+    pub file_bytes: u64,
+}
+
+impl ArchiveSummary {
+    fn count(&mut self, record: &Record, body_len: u64) {
+        match record {
+            Record::Token(_) => self.tokens += 1,
+            Record::User(_) => self.users += 1,
+            Record::Operation { .. } => self.operations += 1,
+            Record::File { .. } => {
+                self.files += 1;
+                self.file_bytes += body_len;
+            }
+        }
+    }
+}
+
+pub struct ArchiveWriter<W> {
+    writer: W,
+    summary: ArchiveSummary,
+}
+
+impl<W: AsyncWrite + Unpin> ArchiveWriter<W> {
+    pub async fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(MAGIC).await?;
+
+        Ok(Self {
+            writer,
+            summary: ArchiveSummary::default(),
+        })
+    }
+
+    /// Writes the record.
+    ///
+    /// The `body` must contain exactly `body_len` bytes.
+    pub async fn write(&mut self, record: &Record, body_len: u64, mut body: impl AsyncRead + Unpin) -> Result<()> {
+        let header = serde_json::to_vec(record).map_err(|err| Error::Archive(err.to_string()))?;
+        let mut hasher = Sha256::new();
+        hasher.update(&header);
+
+        let header_len = u32::try_from(header.len())
+            .ok()
+            .filter(|len| *len <= MAX_HEADER_SIZE)
+            .ok_or_else(|| Error::Archive("record header is too big".into()))?;
+
+        self.writer.write_u32(header_len).await?;
+        self.writer.write_all(&header).await?;
+        self.writer.write_u64(body_len).await?;
+
+        let mut buf = vec![0; COPY_BUFFER_SIZE];
+        let mut written = 0;
+        loop {
+            let len = body.read(&mut buf).await?;
+            if len == 0 {
+                break;
+            }
+
+            hasher.update(&buf[..len]);
+            self.writer.write_all(&buf[..len]).await?;
+            written += u64::try_from(len).expect("usize -> u64 conversion should not fail");
+        }
+
+        if written != body_len {
+            return Err(Error::Archive(format!(
+                "record body size mismatch: expected {body_len} bytes but got {written} bytes"
+            )));
+        }
+
+        self.writer.write_all(&hasher.finalize()).await?;
+        self.summary.count(record, body_len);
+
+        Ok(())
+    }
+
+    /// Writes the end of the archive and returns the summary of written records.
+    pub async fn finish(mut self) -> Result<ArchiveSummary> {
+        self.writer.write_u32(0).await?;
+        self.writer.flush().await?;
+
+        Ok(self.summary)
+    }
+}
+
+/// Archive reader.
+///
+/// The body of the current record is read from the reader itself (it implements [AsyncRead]).
+pub struct ArchiveReader<R> {
+    reader: Take<R>,
+    hasher: Sha256,
+    in_record: bool,
+    summary: ArchiveSummary,
+}
+
+impl<R: AsyncRead + Unpin> ArchiveReader<R> {
+    pub async fn new(mut reader: R) -> Result<Self> {
+        let mut magic = vec![0; MAGIC.len()];
+        reader.read_exact(&mut magic).await?;
+
+        if magic != MAGIC {
+            return Err(Error::Archive("not a Dataans server archive".into()));
+        }
+
+        Ok(Self {
+            reader: reader.take(0),
+            hasher: Sha256::new(),
+            in_record: false,
+            summary: ArchiveSummary::default(),
+        })
+    }
+
+    /// Returns the next record or `None` at the end of the archive.
+    ///
+    /// The previous record is verified before reading the next one.
+    pub async fn next_record(&mut self) -> Result<Option<Record>> {
+        self.finish_record().await?;
+
+        let reader = self.reader.get_mut();
+
+        let header_len = reader.read_u32().await?;
+        if header_len == 0 {
+            return Ok(None);
+        }
+        if header_len > MAX_HEADER_SIZE {
+            return Err(Error::Archive(format!("record header is too big: {header_len} bytes")));
+        }
+
+        let mut header = vec![0; usize::try_from(header_len).expect("u32 -> usize conversion should not fail")];
+        reader.read_exact(&mut header).await?;
+        let body_len = reader.read_u64().await?;
+
+        let record = serde_json::from_slice(&header).map_err(|err| Error::Archive(err.to_string()))?;
+
+        self.hasher.update(&header);
+        self.reader.set_limit(body_len);
+        self.in_record = true;
+        self.summary.count(&record, body_len);
+
+        Ok(Some(record))
+    }
+
+    /// Skips the rest of the current record body and verifies the record checksum.
+    pub async fn finish_record(&mut self) -> Result<()> {
+        if !self.in_record {
+            return Ok(());
+        }
+
+        copy(self, &mut sink()).await?;
+        self.in_record = false;
+
+        if self.reader.limit() != 0 {
+            return Err(Error::Archive("unexpected end of the archive".into()));
+        }
+
+        let mut checksum = [0; CHECKSUM_SIZE];
+        self.reader.get_mut().read_exact(&mut checksum).await?;
+
+        if checksum[..] != self.hasher.finalize_reset()[..] {
+            return Err(Error::Archive("record checksum mismatch".into()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the summary of records read so far.
+    pub fn summary(&self) -> ArchiveSummary {
+        self.summary
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ArchiveReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+
+        ready!(Pin::new(&mut this.reader).poll_read(cx, buf))?;
+        this.hasher.update(&buf.filled()[filled..]);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads the whole archive and verifies checksums of all records.
+pub async fn verify_archive(reader: impl AsyncRead + Unpin) -> Result<ArchiveSummary> {
+    let mut archive = ArchiveReader::new(reader).await?;
+
+    while archive.next_record().await?.is_some() {}
+
+    Ok(archive.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::io::AsyncReadExt;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    use super::{ArchiveReader, ArchiveWriter, Record, verify_archive};
+    use crate::db::UserAccount;
+
+    async fn archive(user_id: Uuid) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        let mut writer = ArchiveWriter::new(&mut data).await.unwrap();
+        writer
+            .write(
+                &Record::User(UserAccount {
+                    id: user_id,
+                    subject: Some("alice".into()),
+                    secret_key_hash: "hash".into(),
+                }),
+                0,
+                [].as_slice(),
+            )
+            .await
+            .unwrap();
+        writer
+            .write(
+                &Record::File {
+                    user_id,
+                    id: Uuid::new_v4(),
+                    created_at: OffsetDateTime::now_utc(),
+                },
+                9,
+                b"tbt files".as_slice(),
+            )
+            .await
+            .unwrap();
+
+        let summary = writer.finish().await.unwrap();
+        assert_eq!((summary.users, summary.files, summary.file_bytes), (1, 1, 9));
+
+        data
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let user_id = Uuid::new_v4();
+        let data = archive(user_id).await;
+
+        let mut reader = ArchiveReader::new(data.as_slice()).await.unwrap();
+
+        let Some(Record::User(user)) = reader.next_record().await.unwrap() else {
+            panic!("user record expected");
+        };
+        assert_eq!(user.id, user_id);
+        assert_eq!(user.subject.as_deref(), Some("alice"));
+
+        let Some(Record::File { user_id: owner, .. }) = reader.next_record().await.unwrap() else {
+            panic!("file record expected");
+        };
+        assert_eq!(owner, user_id);
+        let mut content = Vec::new();
+        (&mut reader).read_to_end(&mut content).await.unwrap();
+        assert_eq!(content, b"tbt files");
+
+        assert!(reader.next_record().await.unwrap().is_none());
+        assert_eq!(reader.summary().file_bytes, 9);
+    }
+
+    #[tokio::test]
+    async fn corrupted_record() {
+        let mut data = archive(Uuid::new_v4()).await;
+
+        let position = data.windows(9).position(|window| window == b"tbt files").unwrap();
+        data[position] = b'T';
+
+        assert!(verify_archive(data.as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn truncated_archive() {
+        let data = archive(Uuid::new_v4()).await;
+
+        assert!(verify_archive(data.as_slice()).await.is_ok());
+        assert!(verify_archive(&data[..data.len() - 10]).await.is_err());
+        assert!(verify_archive(b"not an archive".as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_header() {
+        let mut data = b"DATAANS-SERVER-ARCHIVE-V1\n".to_vec();
+        data.extend_from_slice(&u32::MAX.to_be_bytes());
+
+        let mut reader = ArchiveReader::new(data.as_slice()).await.unwrap();
+        assert!(reader.next_record().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn body_size_mismatch() {
+        let mut data = Vec::new();
+        let mut writer = ArchiveWriter::new(&mut data).await.unwrap();
+
+        let record = Record::File {
+            user_id: Uuid::new_v4(),
+            id: Uuid::new_v4(),
+            created_at: OffsetDateTime::now_utc(),
+        };
+
+        assert!(writer.write(&record, 10, b"tbt files".as_slice()).await.is_err());
+    }
+}
@@ -1,10 +1,77 @@
 use std::sync::Arc;
 
+use rocket::data::ByteUnit;
 use sha2::{Digest, Sha256};
-use web_api_types::{BlockChecksum, Blocks, Operation};
+use time::OffsetDateTime;
+use uuid::Uuid;
+use web_api_types::{
+    BlockChecksum, Blocks, ContinuationToken, MAX_OPERATIONS_DATA_PER_REQUEST, MAX_OPERATIONS_PAGE_SIZE,
+    MAX_OPERATIONS_PER_REQUEST, OPERATION_CHECKSUM_SIZE, Operation, OperationRejection, OperationsPage,
+};
 
-use crate::Result;
-use crate::db::{Operation as OperationModel, OperationsDb};
+use crate::db::{Operation as OperationModel, OperationsCursor, OperationsDb};
+use crate::services::quota_bytes;
+use crate::{Error, Result};
+
+/// Checks the uploaded operations before saving them.
+fn validate_operations(operations: &[Operation]) -> Result<()> {
+    if operations.len() > MAX_OPERATIONS_PER_REQUEST {
+        return Err(Error::OperationRejected(OperationRejection::TooManyOperations {
+            max: MAX_OPERATIONS_PER_REQUEST,
+        }));
+    }
+
+    let data_size: usize = operations.iter().map(|operation| operation.data.as_ref().len()).sum();
+    if data_size > MAX_OPERATIONS_DATA_PER_REQUEST {
+        return Err(Error::OperationRejected(OperationRejection::TooMuchData {
+            max: MAX_OPERATIONS_DATA_PER_REQUEST,
+        }));
+    }
+
+    if let Some(operation) = operations
+        .iter()
+        .find(|operation| operation.checksum.as_ref().len() != OPERATION_CHECKSUM_SIZE)
+    {
+        return Err(Error::OperationRejected(OperationRejection::InvalidChecksum {
+            id: operation.id,
+        }));
+    }
+
+    Ok(())
+}
+
+fn to_api_operation(operation: OperationModel) -> Operation {
+    Operation {
+        id: operation.id.into(),
+        created_at: operation.created_at.into(),
+        data: operation.data.into(),
+        checksum: operation.checksum.into(),
+    }
+}
+
+/// Encodes the position after the operation: `<created at unix timestamp in nanoseconds>.<operation id>`.
+fn encode_continuation_token(operation: &OperationModel) -> ContinuationToken {
+    ContinuationToken::from(format!(
+        "{}.{}",
+        operation.created_at.unix_timestamp_nanos(),
+        operation.id.simple()
+    ))
+}
+
+fn decode_continuation_token(token: &str) -> Result<OperationsCursor> {
+    let invalid_token = || Error::InvalidData("continuation token");
+
+    let (created_at, id) = token.split_once('.').ok_or_else(invalid_token)?;
+
+    Ok(OperationsCursor::After {
+        created_at: created_at
+            .parse()
+            .ok()
+            .and_then(|nanos| OffsetDateTime::from_unix_timestamp_nanos(nanos).ok())
+            .ok_or_else(invalid_token)?,
+        id: Uuid::try_parse(id).map_err(|_| invalid_token())?,
+    })
+}
 
 pub struct Data<D> {
     db: Arc<D>,
@@ -17,16 +84,21 @@ impl<D> Data<D> {
 }
 
 impl<D: OperationsDb> Data<D> {
-    pub async fn blocks(&self, items_per_block: usize) -> Result<Blocks> {
-        let operations = self.db.operations(0).await?;
+    pub async fn blocks(&self, user_id: Uuid, items_per_block: usize) -> Result<Blocks> {
+        if items_per_block == 0 {
+            return Err(Error::InvalidData("items per block"));
+        }
 
-        let blocks = operations
+        // Only checksums are needed. Operations data can be huge.
+        let checksums = self.db.checksums(user_id).await?;
+
+        let blocks = checksums
             .chunks(items_per_block)
-            .map(|operations| {
+            .map(|checksums| {
                 let mut hasher = Sha256::new();
 
-                for operation in operations {
-                    hasher.update(&operation.checksum);
+                for checksum in checksums {
+                    hasher.update(checksum);
                 }
 
                 BlockChecksum::from(hasher.finalize().to_vec())
@@ -36,21 +108,57 @@ impl<D: OperationsDb> Data<D> {
         Ok(Blocks::from(blocks))
     }
 
-    pub async fn operations(&self, operations_to_skip: usize) -> Result<Vec<Operation>> {
-        let operations = self.db.operations(operations_to_skip).await?;
+    pub async fn operations(&self, user_id: Uuid, operations_to_skip: usize) -> Result<Vec<Operation>> {
+        let operations = self.db.operations(user_id, operations_to_skip).await?;
 
-        Ok(operations
-            .into_iter()
-            .map(|operation| Operation {
-                id: operation.id.into(),
-                created_at: operation.created_at.into(),
-                data: operation.data.into(),
-                checksum: operation.checksum.into(),
-            })
-            .collect())
+        Ok(operations.into_iter().map(to_api_operation).collect())
     }
 
-    pub async fn add_operations(&self, operations: Vec<Operation>) -> Result<()> {
+    /// Returns one page of the user's operations.
+    ///
+    /// The first page starts after `operations_to_skip` operations. Next pages start from the `continuation` token.
+    pub async fn operations_page(
+        &self,
+        user_id: Uuid,
+        operations_to_skip: usize,
+        continuation: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<OperationsPage> {
+        let limit = limit
+            .unwrap_or(MAX_OPERATIONS_PAGE_SIZE)
+            .clamp(1, MAX_OPERATIONS_PAGE_SIZE);
+        let cursor = match continuation {
+            Some(token) => decode_continuation_token(token)?,
+            None => OperationsCursor::Skip(operations_to_skip),
+        };
+
+        // One extra operation tells whether the next page exists.
+        let mut operations = self.db.operations_page(user_id, cursor, limit + 1).await?;
+
+        let next = if operations.len() > limit {
+            operations.truncate(limit);
+            operations.last().map(encode_continuation_token)
+        } else {
+            None
+        };
+
+        Ok(OperationsPage {
+            operations: operations.into_iter().map(to_api_operation).collect(),
+            next,
+        })
+    }
+
+    /// Saves uploaded operations.
+    ///
+    /// The whole upload is rejected if any of the operations is invalid or new operations do not fit into the `quota`.
+    pub async fn add_operations(
+        &self,
+        user_id: Uuid,
+        operations: Vec<Operation>,
+        quota: Option<ByteUnit>,
+    ) -> Result<()> {
+        validate_operations(&operations)?;
+
         let operations_models: Vec<OperationModel> = operations
             .into_iter()
             .map(|operation| OperationModel {
@@ -61,7 +169,9 @@ impl<D: OperationsDb> Data<D> {
             })
             .collect();
 
-        self.db.add_operations(&operations_models).await?;
+        self.db
+            .add_operations(user_id, &operations_models, quota_bytes(quota))
+            .await?;
 
         Ok(())
     }
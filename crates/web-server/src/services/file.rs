@@ -5,22 +5,47 @@ use uuid::Uuid;
 
 use crate::Result;
 
+/// User files storage.
+///
+/// Every user has its own files namespace. The user can not access files of other users
+/// even when the file id is known.
 pub trait FileSaver: Send + Sync {
-    async fn save_file(&self, id: Uuid, reader: impl AsyncRead + Unpin) -> Result<()>;
-    async fn open_file(&self, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)>;
-    async fn exists(&self, id: Uuid) -> Result<bool>;
+    /// Saves the file and returns the number of saved bytes.
+    async fn save_file(&self, user_id: Uuid, id: Uuid, reader: impl AsyncRead + Unpin) -> Result<u64>;
+    async fn open_file(&self, user_id: Uuid, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)>;
+    async fn exists(&self, user_id: Uuid, id: Uuid) -> Result<bool>;
+    /// Deletes the file. Deleting a non-existent file is not an error.
+    async fn delete_file(&self, user_id: Uuid, id: Uuid) -> Result<()>;
+    /// Returns ids of all user's files.
+    async fn list_files(&self, user_id: Uuid) -> Result<Vec<Uuid>>;
+    /// Returns ids of files stored before the multi-user support.
+    ///
+    /// Such files are stored by their id (`<file id>`) outside of any user's namespace.
+    async fn list_legacy_files(&self) -> Result<Vec<Uuid>>;
+    /// Moves the legacy file into the user's namespace and returns its size.
+    async fn adopt_legacy_file(&self, user_id: Uuid, id: Uuid) -> Result<u64>;
+    /// Checks that the storage is reachable.
+    async fn health_check(&self) -> Result<()>;
+}
+
+/// Returns the file key (relative path) inside the storage: `<user id>/<file id>`.
+fn file_key(user_id: Uuid, id: Uuid) -> String {
+    format!("{user_id}/{id}")
 }
 
 #[cfg(feature = "fs")]
 mod fs {
     use std::path::PathBuf;
 
-    use rocket::tokio::fs::File;
+    use std::io::ErrorKind;
+
+    use rocket::tokio::fs::{File, create_dir_all, metadata, read_dir, remove_file, rename};
     use rocket::tokio::io::{AsyncRead, copy};
     use uuid::Uuid;
 
-    use crate::Result;
+    use super::file_key;
     use crate::services::FileSaver;
+    use crate::{Error, Result};
 
     #[derive(Debug)]
     pub struct Fs {
@@ -31,27 +56,35 @@ mod fs {
         pub fn new(dest: PathBuf) -> Self {
             Self { dest }
         }
+
+        fn file_path(&self, user_id: Uuid, id: Uuid) -> PathBuf {
+            self.dest.join(file_key(user_id, id))
+        }
+
+        fn user_dir(&self, user_id: Uuid) -> PathBuf {
+            self.dest.join(user_id.to_string())
+        }
     }
 
     impl FileSaver for Fs {
         #[instrument(ret, skip(reader))]
-        async fn save_file(&self, id: Uuid, mut reader: impl AsyncRead + Unpin) -> Result<()> {
-            let mut file = File::create(self.dest.join(id.to_string())).await?;
+        async fn save_file(&self, user_id: Uuid, id: Uuid, mut reader: impl AsyncRead + Unpin) -> Result<u64> {
+            create_dir_all(self.user_dir(user_id)).await?;
 
-            copy(&mut reader, &mut file).await?;
+            let mut file = File::create(self.file_path(user_id, id)).await?;
 
-            Ok(())
+            Ok(copy(&mut reader, &mut file).await?)
         }
 
         #[instrument(err)]
-        async fn exists(&self, id: Uuid) -> Result<bool> {
-            Ok(self.dest.join(id.to_string()).exists())
+        async fn exists(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
+            Ok(self.file_path(user_id, id).exists())
         }
 
         #[instrument(err)]
-        async fn open_file(&self, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)> {
+        async fn open_file(&self, user_id: Uuid, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)> {
             // TODO: use buf reader.
-            let data = File::open(self.dest.join(id.to_string())).await?;
+            let data = File::open(self.file_path(user_id, id)).await?;
             let size = data
                 .metadata()
                 .await
@@ -60,80 +93,337 @@ mod fs {
 
             Ok((size, data))
         }
+
+        #[instrument(err)]
+        async fn delete_file(&self, user_id: Uuid, id: Uuid) -> Result<()> {
+            match remove_file(self.file_path(user_id, id)).await {
+                Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            }
+        }
+
+        #[instrument(err)]
+        async fn list_files(&self, user_id: Uuid) -> Result<Vec<Uuid>> {
+            let mut entries = match read_dir(self.user_dir(user_id)).await {
+                Ok(entries) => entries,
+                // The user has not uploaded any files yet.
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(err) => return Err(err.into()),
+            };
+
+            let mut files = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                match entry.file_name().to_str().map(Uuid::parse_str) {
+                    Some(Ok(id)) => files.push(id),
+                    _ => warn!(path = ?entry.path(), "Unexpected file in the user's files directory"),
+                }
+            }
+
+            Ok(files)
+        }
+
+        #[instrument(err)]
+        async fn list_legacy_files(&self) -> Result<Vec<Uuid>> {
+            let mut entries = read_dir(&self.dest).await?;
+
+            let mut files = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                // Users' directories are also named by ids.
+                if !entry.file_type().await?.is_file() {
+                    continue;
+                }
+
+                if let Some(Ok(id)) = entry.file_name().to_str().map(Uuid::parse_str) {
+                    files.push(id);
+                }
+            }
+
+            Ok(files)
+        }
+
+        #[instrument(err)]
+        async fn adopt_legacy_file(&self, user_id: Uuid, id: Uuid) -> Result<u64> {
+            create_dir_all(self.user_dir(user_id)).await?;
+
+            let path = self.file_path(user_id, id);
+            rename(self.dest.join(id.to_string()), &path).await?;
+
+            Ok(metadata(path).await?.len())
+        }
+
+        #[instrument(err)]
+        async fn health_check(&self) -> Result<()> {
+            if metadata(&self.dest).await?.is_dir() {
+                Ok(())
+            } else {
+                Err(Error::FileSaver(format!("{:?} is not a directory", self.dest)))
+            }
+        }
     }
 }
 
 #[cfg(feature = "fs")]
 pub use fs::Fs;
 
-#[cfg(feature = "tigris")]
-mod tigris {
+#[cfg(feature = "s3")]
+mod s3 {
     use std::fmt;
 
-    use aws_config::SdkConfig;
+    use aws_config::{BehaviorVersion, Region};
     use aws_sdk_s3::Client;
+    use aws_sdk_s3::config::{Builder as S3ConfigBuilder, Credentials};
+    use aws_sdk_s3::primitives::ByteStream;
+    use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
     use rocket::tokio::io::{AsyncRead, AsyncReadExt};
     use uuid::Uuid;
 
+    use super::file_key;
     use crate::services::FileSaver;
     use crate::{Error, Result};
 
-    pub struct Tigris {
+    /// Size of the multipart upload part.
+    ///
+    /// The file is uploaded part by part, so the server never keeps more than one part in memory.
+    /// S3 requires every part except the last one to be at least 5 MiB.
+    const PART_SIZE: usize = 8 * 1024 * 1024;
+
+    /// S3 access key.
+    #[derive(Clone)]
+    pub struct S3Credentials {
+        pub access_key_id: String,
+        pub secret_access_key: String,
+    }
+
+    impl fmt::Debug for S3Credentials {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("S3Credentials")
+                .field("access_key_id", &self.access_key_id)
+                .field("secret_access_key", &"<...>")
+                .finish()
+        }
+    }
+
+    /// S3-compatible object storage configuration.
+    ///
+    /// Every unset value falls back to the standard AWS configuration (`AWS_*` env variables, profile files, etc).
+    #[derive(Debug, Clone, Default)]
+    pub struct S3Config {
+        pub bucket: String,
+        /// Custom endpoint URL. For example, `http://localhost:9000` for MinIO.
+        pub endpoint_url: Option<String>,
+        pub region: Option<String>,
+        /// Use path-style addressing (`<endpoint>/<bucket>/<key>`) instead of virtual-hosted-style.
+        ///
+        /// Most self-hosted storages (MinIO, Garage) require it.
+        pub force_path_style: bool,
+        pub credentials: Option<S3Credentials>,
+    }
+
+    /// Any S3-compatible object storage: AWS S3, Tigris, Cloudflare R2, MinIO, Garage, etc.
+    pub struct S3 {
         client: Client,
         bucket: String,
     }
 
-    impl fmt::Debug for Tigris {
+    impl fmt::Debug for S3 {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            f.debug_struct("Tigris")
+            f.debug_struct("S3")
                 .field("client", &"<...>")
                 .field("bucket", &self.bucket)
                 .finish()
         }
     }
 
-    impl Tigris {
-        pub fn new(config: SdkConfig, bucket: String) -> Self {
+    impl S3 {
+        pub async fn new(config: S3Config) -> Self {
+            let S3Config {
+                bucket,
+                endpoint_url,
+                region,
+                force_path_style,
+                credentials,
+            } = config;
+
+            let mut loader = aws_config::defaults(BehaviorVersion::latest());
+            if let Some(endpoint_url) = endpoint_url {
+                loader = loader.endpoint_url(endpoint_url);
+            }
+            if let Some(region) = region {
+                loader = loader.region(Region::new(region));
+            }
+            if let Some(S3Credentials {
+                access_key_id,
+                secret_access_key,
+            }) = credentials
+            {
+                loader = loader.credentials_provider(Credentials::new(
+                    access_key_id,
+                    secret_access_key,
+                    None,
+                    None,
+                    "dataans",
+                ));
+            }
+
+            let s3_config = S3ConfigBuilder::from(&loader.load().await)
+                .force_path_style(force_path_style)
+                .build();
+
             Self {
-                client: Client::new(&config),
+                client: Client::from_conf(s3_config),
                 bucket,
             }
         }
+
+        /// Uploads the file using the multipart upload.
+        ///
+        /// `part` is the first part of the file. It is already read from the `reader`.
+        async fn upload_parts(
+            &self,
+            key: &str,
+            upload_id: &str,
+            mut part: Vec<u8>,
+            reader: &mut (impl AsyncRead + Unpin),
+        ) -> Result<u64> {
+            let mut size = 0;
+            let mut completed_parts = Vec::new();
+
+            while !part.is_empty() {
+                let part_number = i32::try_from(completed_parts.len() + 1).expect("parts number should fit in i32");
+                size += u64::try_from(part.len()).expect("usize -> u64 conversion should not fail");
+
+                let uploaded_part = self
+                    .client
+                    .upload_part()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(part))
+                    .send()
+                    .await
+                    .map_err(|err| {
+                        error!(?err, part_number, "Failed to upload file part to S3");
+                        Error::FileSaver(err.to_string())
+                    })?;
+
+                completed_parts.push(
+                    CompletedPart::builder()
+                        .part_number(part_number)
+                        .set_e_tag(uploaded_part.e_tag)
+                        .build(),
+                );
+
+                part = read_part(reader).await?;
+            }
+
+            self.client
+                .complete_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(completed_parts))
+                        .build(),
+                )
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(?err, "Failed to complete multipart upload");
+                    Error::FileSaver(err.to_string())
+                })?;
+
+            Ok(size)
+        }
     }
 
-    impl FileSaver for Tigris {
+    /// Reads the next file part. The returned part is empty when the reader is exhausted.
+    async fn read_part(reader: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>> {
+        let mut part = Vec::with_capacity(PART_SIZE);
+        reader
+            .take(u64::try_from(PART_SIZE).expect("usize -> u64 conversion should not fail"))
+            .read_to_end(&mut part)
+            .await?;
+
+        Ok(part)
+    }
+
+    impl FileSaver for S3 {
         #[instrument(ret, skip(reader))]
-        async fn save_file(&self, id: Uuid, mut reader: impl AsyncRead + Unpin) -> Result<()> {
-            let mut data = Vec::new();
-            reader.read_to_end(&mut data).await?;
+        async fn save_file(&self, user_id: Uuid, id: Uuid, mut reader: impl AsyncRead + Unpin) -> Result<u64> {
+            let key = file_key(user_id, id);
+            let part = read_part(&mut reader).await?;
 
-            let object = self
+            if part.len() < PART_SIZE {
+                // The file is small enough to be uploaded with a single request.
+                let size = u64::try_from(part.len()).expect("usize -> u64 conversion should not fail");
+
+                let object = self
+                    .client
+                    .put_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .body(ByteStream::from(part))
+                    .send()
+                    .await
+                    .map_err(|err| {
+                        error!(?err, "Failed to save file to S3");
+                        Error::FileSaver(err.to_string())
+                    })?;
+
+                trace!(?object, "File has been saved to S3");
+
+                return Ok(size);
+            }
+
+            let upload = self
                 .client
-                .put_object()
+                .create_multipart_upload()
                 .bucket(&self.bucket)
-                .key(id.to_string())
-                // I hate the `aws_sdk_s3` body API.
-                // https://github.com/awslabs/aws-sdk-rust/discussions/361
-                .body(data.into())
+                .key(&key)
                 .send()
                 .await
                 .map_err(|err| {
-                    error!(?err, "Failed to save file to S3");
+                    error!(?err, "Failed to start multipart upload");
                     Error::FileSaver(err.to_string())
                 })?;
+            let upload_id = upload
+                .upload_id
+                .ok_or_else(|| Error::FileSaver("S3 did not return the multipart upload id".into()))?;
 
-            trace!(?object, "File has been saved to S3");
+            match self.upload_parts(&key, &upload_id, part, &mut reader).await {
+                Ok(size) => {
+                    trace!(size, "File has been saved to S3");
 
-            Ok(())
+                    Ok(size)
+                }
+                Err(err) => {
+                    // Uploaded parts are stored (and billed) until the upload is aborted.
+                    if let Err(abort_err) = self
+                        .client
+                        .abort_multipart_upload()
+                        .bucket(&self.bucket)
+                        .key(&key)
+                        .upload_id(&upload_id)
+                        .send()
+                        .await
+                    {
+                        error!(?abort_err, "Failed to abort multipart upload");
+                    }
+
+                    Err(err)
+                }
+            }
         }
 
         #[instrument(err)]
-        async fn exists(&self, id: Uuid) -> Result<bool> {
+        async fn exists(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
             let head_object = self
                 .client
                 .head_object()
                 .bucket(&self.bucket)
-                .key(id.to_string())
+                .key(file_key(user_id, id))
                 .send()
                 .await;
 
@@ -154,12 +444,12 @@ mod tigris {
         }
 
         #[instrument(err)]
-        async fn open_file(&self, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)> {
+        async fn open_file(&self, user_id: Uuid, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)> {
             let object = self
                 .client
                 .get_object()
                 .bucket(&self.bucket)
-                .key(id.to_string())
+                .key(file_key(user_id, id))
                 .send()
                 .await
                 .map_err(|err| {
@@ -173,8 +463,381 @@ mod tigris {
 
             Ok((size, data))
         }
+
+        #[instrument(err)]
+        async fn delete_file(&self, user_id: Uuid, id: Uuid) -> Result<()> {
+            // S3 does not fail when the object does not exist.
+            self.client
+                .delete_object()
+                .bucket(&self.bucket)
+                .key(file_key(user_id, id))
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(?err, "Failed to delete file from S3");
+                    Error::FileSaver(err.to_string())
+                })?;
+
+            Ok(())
+        }
+
+        #[instrument(err)]
+        async fn list_files(&self, user_id: Uuid) -> Result<Vec<Uuid>> {
+            let mut pages = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(format!("{user_id}/"))
+                .into_paginator()
+                .send();
+
+            let mut files = Vec::new();
+            while let Some(page) = pages.next().await {
+                let page = page.map_err(|err| {
+                    error!(?err, "Failed to list files in S3");
+                    Error::FileSaver(err.to_string())
+                })?;
+
+                for object in page.contents() {
+                    let key = object.key().unwrap_or_default();
+
+                    match key.rsplit('/').next().map(Uuid::parse_str) {
+                        Some(Ok(id)) => files.push(id),
+                        _ => warn!(key, "Unexpected object in the user's files prefix"),
+                    }
+                }
+            }
+
+            Ok(files)
+        }
+
+        #[instrument(err)]
+        async fn list_legacy_files(&self) -> Result<Vec<Uuid>> {
+            // Users' namespaces are returned as common prefixes, so the contents are only top-level objects.
+            let mut pages = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .delimiter("/")
+                .into_paginator()
+                .send();
+
+            let mut files = Vec::new();
+            while let Some(page) = pages.next().await {
+                let page = page.map_err(|err| {
+                    error!(?err, "Failed to list files in S3");
+                    Error::FileSaver(err.to_string())
+                })?;
+
+                files.extend(
+                    page.contents()
+                        .iter()
+                        .filter_map(|object| Uuid::parse_str(object.key().unwrap_or_default()).ok()),
+                );
+            }
+
+            Ok(files)
+        }
+
+        #[instrument(err)]
+        async fn adopt_legacy_file(&self, user_id: Uuid, id: Uuid) -> Result<u64> {
+            let key = file_key(user_id, id);
+
+            self.client
+                .copy_object()
+                .bucket(&self.bucket)
+                .copy_source(format!("{}/{id}", self.bucket))
+                .key(&key)
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(?err, "Failed to copy the legacy file in S3");
+                    Error::FileSaver(err.to_string())
+                })?;
+
+            let object = self
+                .client
+                .head_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .send()
+                .await
+                .map_err(|err| Error::FileSaver(err.to_string()))?;
+
+            self.client
+                .delete_object()
+                .bucket(&self.bucket)
+                .key(id.to_string())
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(?err, "Failed to delete the legacy file from S3");
+                    Error::FileSaver(err.to_string())
+                })?;
+
+            Ok(object
+                .content_length
+                .and_then(|len| u64::try_from(len).ok())
+                .unwrap_or_default())
+        }
+
+        #[instrument(err)]
+        async fn health_check(&self) -> Result<()> {
+            self.client
+                .head_bucket()
+                .bucket(&self.bucket)
+                .send()
+                .await
+                .map_err(|err| Error::FileSaver(err.to_string()))?;
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use aws_sdk_s3::Client;
+        use aws_sdk_s3::operation::abort_multipart_upload::AbortMultipartUploadOutput;
+        use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadOutput;
+        use aws_sdk_s3::operation::copy_object::CopyObjectOutput;
+        use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadOutput;
+        use aws_sdk_s3::operation::delete_object::DeleteObjectOutput;
+        use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+        use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
+        use aws_sdk_s3::operation::put_object::PutObjectOutput;
+        use aws_sdk_s3::operation::upload_part::{UploadPartError, UploadPartOutput};
+        use aws_sdk_s3::types::error::NotFound;
+        use aws_sdk_s3::types::{CommonPrefix, Object};
+        use aws_smithy_mocks::{RuleMode, mock, mock_client};
+        use uuid::Uuid;
+
+        use super::{PART_SIZE, S3, file_key};
+        use crate::services::FileSaver;
+
+        const BUCKET: &str = "dataans-test";
+
+        fn s3(client: Client) -> S3 {
+            S3 {
+                client,
+                bucket: BUCKET.to_owned(),
+            }
+        }
+
+        #[tokio::test]
+        async fn s3_small_file_is_put_at_once() {
+            let (user_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+            let key = file_key(user_id, id);
+
+            let put_object = mock!(Client::put_object)
+                .match_requests(move |req| req.bucket() == Some(BUCKET) && req.key() == Some(key.as_str()))
+                .then_output(|| PutObjectOutput::builder().build());
+            let create_multipart_upload = mock!(Client::create_multipart_upload)
+                .then_output(|| CreateMultipartUploadOutput::builder().upload_id("upload").build());
+            let s3 = s3(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                [&put_object, &create_multipart_upload]
+            ));
+
+            assert_eq!(s3.save_file(user_id, id, b"alice data".as_slice()).await.unwrap(), 10);
+            assert_eq!(put_object.num_calls(), 1);
+            assert_eq!(create_multipart_upload.num_calls(), 0);
+        }
+
+        #[tokio::test]
+        async fn s3_multipart_upload() {
+            let (user_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+            let data = vec![7u8; PART_SIZE * 2 + PART_SIZE / 2];
+
+            let create_multipart_upload = mock!(Client::create_multipart_upload)
+                .then_output(|| CreateMultipartUploadOutput::builder().upload_id("upload").build());
+            let upload_part = mock!(Client::upload_part)
+                .match_requests(|req| req.upload_id() == Some("upload"))
+                .then_output(|| UploadPartOutput::builder().e_tag("etag").build());
+            let complete_multipart_upload = mock!(Client::complete_multipart_upload)
+                .match_requests(|req| {
+                    req.multipart_upload()
+                        .map(|upload| upload.parts().iter().map(|part| part.part_number()).collect::<Vec<_>>())
+                        == Some(vec![Some(1), Some(2), Some(3)])
+                })
+                .then_output(|| CompleteMultipartUploadOutput::builder().build());
+            let s3 = s3(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                [&create_multipart_upload, &upload_part, &complete_multipart_upload]
+            ));
+
+            assert_eq!(
+                s3.save_file(user_id, id, data.as_slice()).await.unwrap(),
+                data.len() as u64
+            );
+            assert_eq!(upload_part.num_calls(), 3);
+            assert_eq!(complete_multipart_upload.num_calls(), 1);
+        }
+
+        #[tokio::test]
+        async fn s3_failed_multipart_upload_is_aborted() {
+            let data = vec![7u8; PART_SIZE + 1];
+
+            let create_multipart_upload = mock!(Client::create_multipart_upload)
+                .then_output(|| CreateMultipartUploadOutput::builder().upload_id("upload").build());
+            let upload_part = mock!(Client::upload_part).then_error(|| UploadPartError::unhandled("connection reset"));
+            let abort_multipart_upload = mock!(Client::abort_multipart_upload)
+                .match_requests(|req| req.upload_id() == Some("upload"))
+                .then_output(|| AbortMultipartUploadOutput::builder().build());
+            let s3 = s3(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                [&create_multipart_upload, &upload_part, &abort_multipart_upload]
+            ));
+
+            assert!(
+                s3.save_file(Uuid::new_v4(), Uuid::new_v4(), data.as_slice())
+                    .await
+                    .is_err()
+            );
+            assert_eq!(abort_multipart_upload.num_calls(), 1);
+        }
+
+        #[tokio::test]
+        async fn s3_files_are_isolated() {
+            let (alice, bob, id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+            let alice_key = file_key(alice, id);
+
+            let head_alice_file = mock!(Client::head_object)
+                .match_requests(move |req| req.key() == Some(alice_key.as_str()))
+                .then_output(|| HeadObjectOutput::builder().content_length(10).build());
+            let head_object =
+                mock!(Client::head_object).then_error(|| HeadObjectError::NotFound(NotFound::builder().build()));
+            let list_objects = mock!(Client::list_objects_v2)
+                .match_requests(move |req| req.prefix() == Some(format!("{alice}/").as_str()))
+                .then_output(move || {
+                    ListObjectsV2Output::builder()
+                        .contents(Object::builder().key(file_key(alice, id)).build())
+                        .contents(Object::builder().key(format!("{alice}/unexpected")).build())
+                        .build()
+                });
+            let s3 = s3(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                [&head_alice_file, &head_object, &list_objects]
+            ));
+
+            assert!(s3.exists(alice, id).await.unwrap());
+            assert!(!s3.exists(bob, id).await.unwrap());
+            assert_eq!(s3.list_files(alice).await.unwrap(), vec![id]);
+        }
+
+        #[tokio::test]
+        async fn s3_legacy_files_are_adopted() {
+            let (user_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+            let key = file_key(user_id, id);
+
+            let list_objects = mock!(Client::list_objects_v2)
+                .match_requests(|req| req.delimiter() == Some("/"))
+                .then_output(move || {
+                    ListObjectsV2Output::builder()
+                        .contents(Object::builder().key(id.to_string()).build())
+                        .contents(Object::builder().key("README").build())
+                        .common_prefixes(CommonPrefix::builder().prefix(format!("{user_id}/")).build())
+                        .build()
+                });
+            let copy_object = mock!(Client::copy_object)
+                .match_requests({
+                    let key = key.clone();
+                    move |req| {
+                        req.copy_source() == Some(format!("{BUCKET}/{id}").as_str()) && req.key() == Some(key.as_str())
+                    }
+                })
+                .then_output(|| CopyObjectOutput::builder().build());
+            let head_object = mock!(Client::head_object)
+                .match_requests(move |req| req.key() == Some(key.as_str()))
+                .then_output(|| HeadObjectOutput::builder().content_length(3).build());
+            let delete_object = mock!(Client::delete_object)
+                .match_requests(move |req| req.key() == Some(id.to_string().as_str()))
+                .then_output(|| DeleteObjectOutput::builder().build());
+            let s3 = s3(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                [&list_objects, &copy_object, &head_object, &delete_object]
+            ));
+
+            assert_eq!(s3.list_legacy_files().await.unwrap(), vec![id]);
+            assert_eq!(s3.adopt_legacy_file(user_id, id).await.unwrap(), 3);
+            assert_eq!((copy_object.num_calls(), delete_object.num_calls()), (1, 1));
+        }
     }
 }
 
-#[cfg(feature = "tigris")]
-pub use tigris::Tigris;
+#[cfg(feature = "s3")]
+pub use s3::{S3, S3Config, S3Credentials};
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use rocket::tokio::io::AsyncReadExt;
+    use uuid::Uuid;
+
+    use super::{FileSaver, Fs};
+
+    #[tokio::test]
+    async fn files_are_isolated() {
+        let dest = std::env::temp_dir().join(format!("dataans-files-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dest).unwrap();
+        let fs = Fs::new(dest.clone());
+
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let file_id = Uuid::new_v4();
+
+        assert_eq!(
+            fs.save_file(alice, file_id, b"alice data".as_slice()).await.unwrap(),
+            10
+        );
+
+        assert!(fs.exists(alice, file_id).await.unwrap());
+        assert!(!fs.exists(bob, file_id).await.unwrap());
+        assert!(fs.open_file(bob, file_id).await.is_err());
+
+        // Bob's file with the same id must not overwrite Alice's file.
+        fs.save_file(bob, file_id, b"bob data".as_slice()).await.unwrap();
+
+        let (_, reader) = fs.open_file(alice, file_id).await.unwrap();
+        let mut reader = std::pin::pin!(reader);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"alice data");
+
+        assert_eq!(fs.list_files(alice).await.unwrap(), vec![file_id]);
+        assert!(fs.list_files(Uuid::new_v4()).await.unwrap().is_empty());
+
+        fs.delete_file(bob, file_id).await.unwrap();
+        assert!(!fs.exists(bob, file_id).await.unwrap());
+        assert!(fs.exists(alice, file_id).await.unwrap());
+        // Deleting a non-existent file is not an error.
+        fs.delete_file(bob, file_id).await.unwrap();
+
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn legacy_files_are_adopted() {
+        let dest = std::env::temp_dir().join(format!("dataans-files-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dest).unwrap();
+        let fs = Fs::new(dest.clone());
+
+        let (user_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+        std::fs::write(dest.join(id.to_string()), b"tbt").unwrap();
+        // Users' directories are not legacy files.
+        fs.save_file(user_id, Uuid::new_v4(), b"alice data".as_slice())
+            .await
+            .unwrap();
+
+        assert_eq!(fs.list_legacy_files().await.unwrap(), vec![id]);
+        assert_eq!(fs.adopt_legacy_file(user_id, id).await.unwrap(), 3);
+
+        assert!(fs.exists(user_id, id).await.unwrap());
+        assert!(fs.list_legacy_files().await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use uuid::Uuid;
+use web_api_types::FileGcReport;
+
+use crate::Result;
+use crate::db::{FilesDb, OrphanedFile};
+use crate::services::FileSaver;
+
+/// Default time between finding the file unreferenced and deleting it.
+pub const DEFAULT_FILE_GC_GRACE_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Files garbage collector.
+///
+/// The server can not decrypt operations, so the client sends ids of all referenced (live) files.
+/// Other files are marked as orphaned and deleted only if they stay unreferenced during the grace period.
+/// The grace period protects files uploaded by other devices whose operations are not synced yet.
+pub struct FilesGc<D> {
+    db: Arc<D>,
+    grace_period: Duration,
+}
+
+impl<D> FilesGc<D> {
+    pub fn new(db: Arc<D>, grace_period: Duration) -> Self {
+        Self { db, grace_period }
+    }
+}
+
+impl<D: FilesDb> FilesGc<D> {
+    #[instrument(err, skip(self, file_saver, live_files), fields(live_files = live_files.len()))]
+    pub async fn collect<S: FileSaver>(
+        &self,
+        file_saver: &S,
+        user_id: Uuid,
+        live_files: &[Uuid],
+    ) -> Result<FileGcReport> {
+        let live_files: HashSet<Uuid> = live_files.iter().copied().collect();
+        let unreferenced: HashSet<Uuid> = file_saver
+            .list_files(user_id)
+            .await?
+            .into_iter()
+            .filter(|id| !live_files.contains(id))
+            .collect();
+
+        let mut orphaned = HashMap::new();
+        for OrphanedFile { id, orphaned_at } in self.db.orphaned_files(user_id).await? {
+            if unreferenced.contains(&id) {
+                orphaned.insert(id, orphaned_at);
+            } else {
+                // The file is referenced again or does not exist anymore.
+                self.db.remove_orphaned_file(user_id, id).await?;
+            }
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let mut report = FileGcReport::default();
+
+        for id in unreferenced {
+            match orphaned.get(&id) {
+                Some(orphaned_at) if now - *orphaned_at >= self.grace_period => {
+                    debug!(%id, "Deleting orphaned file");
+
+                    file_saver.delete_file(user_id, id).await?;
+                    self.db.remove_file(user_id, id).await?;
+
+                    report.deleted += 1;
+                }
+                Some(_) => report.orphaned += 1,
+                None => {
+                    self.db
+                        .add_orphaned_file(user_id, &OrphanedFile { id, orphaned_at: now })
+                        .await?;
+
+                    report.orphaned += 1;
+                }
+            }
+        }
+
+        info!(%user_id, ?report, "Files garbage collection finished");
+
+        Ok(report)
+    }
+}
@@ -1,7 +1,22 @@
+mod admin;
+mod archive;
 mod data;
 mod file;
+mod gc;
+mod rate_limit;
+mod token;
+mod usage;
 mod user;
 
+pub use admin::*;
+pub use archive::*;
 pub use data::*;
 pub use file::*;
+pub use gc::*;
+pub use rate_limit::*;
+pub use token::*;
+pub use usage::*;
 pub use user::*;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// Default number of operation uploads allowed per minute for one identity.
+pub const DEFAULT_RATE_LIMIT: NonZeroU32 = NonZeroU32::new(60).expect("rate limit should not be zero");
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Per-identity rate limiter.
+///
+/// Every authenticated identity (subject) has its own token bucket. The bucket holds up to `requests_per_minute`
+/// requests and is refilled evenly during the minute. So, short bursts are allowed, but the average rate is limited.
+pub struct RateLimiter {
+    /// `None` means the rate limiting is disabled.
+    requests_per_minute: Option<NonZeroU32>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: Option<NonZeroU32>) -> Self {
+        Self {
+            requests_per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes one request from the subject's bucket.
+    ///
+    /// Fails with [Error::RateLimited] if the bucket is empty.
+    pub fn check(&self, subject: &str) -> Result<()> {
+        let Some(requests_per_minute) = self.requests_per_minute else {
+            return Ok(());
+        };
+
+        let capacity = f64::from(requests_per_minute.get());
+        let refill_per_second = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().expect("rate limiter mutex should not be poisoned");
+        let bucket = buckets.entry(subject.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_second).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+
+            Ok(())
+        } else {
+            let retry_after = Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_second);
+            warn!(subject, ?retry_after, "Rate limit exceeded");
+
+            Err(Error::RateLimited { retry_after })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+    use std::time::Duration;
+
+    use super::RateLimiter;
+    use crate::Error;
+
+    #[test]
+    fn limits_every_identity_separately() {
+        let limiter = RateLimiter::new(NonZeroU32::new(2));
+
+        limiter.check("alice").unwrap();
+        limiter.check("alice").unwrap();
+
+        let Err(Error::RateLimited { retry_after }) = limiter.check("alice") else {
+            panic!("rate limit error expected");
+        };
+        assert!(retry_after > Duration::from_secs(29) && retry_after <= Duration::from_secs(30));
+
+        limiter.check("bob").unwrap();
+    }
+
+    #[test]
+    fn disabled() {
+        let limiter = RateLimiter::new(None);
+
+        for _ in 0..1000 {
+            limiter.check("alice").unwrap();
+        }
+    }
+}
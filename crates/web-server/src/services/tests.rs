@@ -0,0 +1,826 @@
+//! Service-level tests.
+//!
+//! Every test runs against all database backends. PostgreSQL tests need a running PostgreSQL instance:
+//! `DATABASE_URL=<postgres url> cargo test -- --ignored`.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rocket::data::ByteUnit;
+use rocket::tokio::io::{AsyncRead, AsyncReadExt};
+use time::OffsetDateTime;
+use uuid::Uuid;
+use web_api_types::{ContinuationToken, MAX_OPERATIONS_PER_REQUEST, Operation, OperationRejection, User};
+
+use crate::db::{AdminDb, Db, UserAccount};
+use crate::services::{Admin, Data, FileSaver, FilesGc, TokenService, UsageService, UserService};
+use crate::{Error, Result};
+
+/// In-memory [FileSaver].
+///
+/// Legacy files (stored before the multi-user support) belong to the nil user id.
+#[derive(Default)]
+struct MemoryFiles(Mutex<HashMap<(Uuid, Uuid), Vec<u8>>>);
+
+impl FileSaver for MemoryFiles {
+    async fn save_file(&self, user_id: Uuid, id: Uuid, mut reader: impl AsyncRead + Unpin) -> Result<u64> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await?;
+
+        let size = u64::try_from(content.len()).unwrap();
+        self.0.lock().unwrap().insert((user_id, id), content);
+
+        Ok(size)
+    }
+
+    async fn open_file(&self, user_id: Uuid, id: Uuid) -> Result<(Option<usize>, impl AsyncRead + Send)> {
+        let content = self
+            .0
+            .lock()
+            .unwrap()
+            .get(&(user_id, id))
+            .cloned()
+            .ok_or(Error::NotFound)?;
+
+        Ok((Some(content.len()), Cursor::new(content)))
+    }
+
+    async fn exists(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
+        Ok(self.0.lock().unwrap().contains_key(&(user_id, id)))
+    }
+
+    async fn delete_file(&self, user_id: Uuid, id: Uuid) -> Result<()> {
+        self.0.lock().unwrap().remove(&(user_id, id));
+
+        Ok(())
+    }
+
+    async fn list_files(&self, user_id: Uuid) -> Result<Vec<Uuid>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|(owner, _)| *owner == user_id)
+            .map(|(_, id)| *id)
+            .collect())
+    }
+
+    async fn list_legacy_files(&self) -> Result<Vec<Uuid>> {
+        self.list_files(Uuid::nil()).await
+    }
+
+    async fn adopt_legacy_file(&self, user_id: Uuid, id: Uuid) -> Result<u64> {
+        let mut files = self.0.lock().unwrap();
+        let content = files.remove(&(Uuid::nil(), id)).ok_or(Error::NotFound)?;
+        let size = u64::try_from(content.len()).unwrap();
+        files.insert((user_id, id), content);
+
+        Ok(size)
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn operation(checksum: u8) -> Operation {
+    Operation {
+        id: Uuid::new_v4().into(),
+        created_at: OffsetDateTime::now_utc().into(),
+        data: vec![checksum; 16].into(),
+        checksum: vec![checksum; 32].into(),
+    }
+}
+
+fn checksums(operations: Vec<Operation>) -> Vec<Vec<u8>> {
+    operations
+        .into_iter()
+        .map(|operation| operation.checksum.into())
+        .collect()
+}
+
+async fn init_user(users: &UserService<Db>, subject: &str) -> Uuid {
+    let id = Uuid::new_v4();
+
+    users
+        .init(
+            subject,
+            User {
+                id: id.into(),
+                secret_key_hash: format!("{subject}-hash").into(),
+            },
+        )
+        .await
+        .unwrap();
+
+    id
+}
+
+async fn user_is_bound_to_subject(db: Arc<Db>) {
+    let users = UserService::new(db);
+
+    let alice = init_user(&users, "alice").await;
+    let bob = init_user(&users, "bob").await;
+
+    assert_eq!(users.user_id("alice").await.unwrap(), Some(alice));
+    assert_eq!(users.user_id("bob").await.unwrap(), Some(bob));
+    assert_eq!(users.user_id("eve").await.unwrap(), None);
+
+    let second_init = users
+        .init(
+            "alice",
+            User {
+                id: Uuid::new_v4().into(),
+                secret_key_hash: String::from("hash").into(),
+            },
+        )
+        .await;
+    assert!(second_init.is_err());
+    assert_eq!(users.user_id("alice").await.unwrap(), Some(alice));
+}
+
+async fn legacy_user_is_bound_explicitly(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let usage = UsageService::new(Arc::clone(&db), None);
+    let admin = Admin::new(Arc::clone(&db));
+    let files = MemoryFiles::default();
+
+    let legacy = Uuid::new_v4();
+    db.add_user(&UserAccount {
+        id: legacy,
+        subject: None,
+        secret_key_hash: String::from("legacy-hash"),
+    })
+    .await
+    .unwrap();
+    let file_id = Uuid::new_v4();
+    files.save_file(Uuid::nil(), file_id, b"tbt".as_slice()).await.unwrap();
+    init_user(&users, "bob").await;
+
+    // The legacy user is never bound implicitly.
+    assert_eq!(users.user_id("alice").await.unwrap(), None);
+    assert!(admin.bind_legacy_user(&files, "bob").await.is_err());
+
+    assert_eq!(admin.bind_legacy_user(&files, "alice").await.unwrap(), (legacy, 1));
+    assert_eq!(users.user_id("alice").await.unwrap(), Some(legacy));
+    assert!(files.exists(legacy, file_id).await.unwrap());
+    assert!(files.list_legacy_files().await.unwrap().is_empty());
+    assert_eq!(usage.usage(legacy).await.unwrap().file_bytes, 3);
+
+    assert!(matches!(
+        admin.bind_legacy_user(&files, "eve").await,
+        Err(Error::NotFound)
+    ));
+}
+
+async fn legacy_user_is_reset_by_id(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let admin = Admin::new(Arc::clone(&db));
+    let files = MemoryFiles::default();
+
+    let legacy = Uuid::new_v4();
+    db.add_user(&UserAccount {
+        id: legacy,
+        subject: None,
+        secret_key_hash: String::from("legacy-hash"),
+    })
+    .await
+    .unwrap();
+    files
+        .save_file(Uuid::nil(), Uuid::new_v4(), b"tbt".as_slice())
+        .await
+        .unwrap();
+    let alice = init_user(&users, "alice").await;
+
+    admin.reset_user(&files, &legacy.to_string()).await.unwrap();
+
+    let remaining: Vec<Uuid> = db.users().await.unwrap().into_iter().map(|user| user.id).collect();
+    assert_eq!(remaining, vec![alice]);
+    assert!(files.list_legacy_files().await.unwrap().is_empty());
+}
+
+async fn uninitialized_legacy_user(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let admin = Admin::new(Arc::clone(&db));
+
+    // The migration creates the legacy user without the secret key hash if the user has never been initialized.
+    db.add_user(&UserAccount {
+        id: Uuid::nil(),
+        subject: None,
+        secret_key_hash: String::new(),
+    })
+    .await
+    .unwrap();
+    admin.bind_legacy_user(&MemoryFiles::default(), "alice").await.unwrap();
+
+    assert_eq!(users.user_id("alice").await.unwrap(), None);
+
+    init_user(&users, "alice").await;
+    assert_eq!(users.user_id("alice").await.unwrap(), Some(Uuid::nil()));
+    assert_eq!(
+        users.user("alice").await.unwrap().unwrap().secret_key_hash.as_ref(),
+        "alice-hash"
+    );
+}
+
+async fn operations_are_isolated(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+    let bob = init_user(&users, "bob").await;
+
+    data.add_operations(alice, vec![operation(1)], None).await.unwrap();
+    data.add_operations(bob, vec![operation(2), operation(3)], None)
+        .await
+        .unwrap();
+
+    assert_eq!(checksums(data.operations(alice, 0).await.unwrap()), vec![vec![1; 32]]);
+    assert_eq!(
+        checksums(data.operations(bob, 0).await.unwrap()),
+        vec![vec![2; 32], vec![3; 32]]
+    );
+    assert_eq!(checksums(data.operations(bob, 1).await.unwrap()), vec![vec![3; 32]]);
+
+    let blocks = async |user_id: Uuid| -> Vec<Vec<u8>> {
+        let blocks: Vec<_> = data.blocks(user_id, 256).await.unwrap().into();
+        blocks.into_iter().map(Into::into).collect()
+    };
+    assert_ne!(blocks(alice).await, blocks(bob).await);
+}
+
+async fn same_operation_id_does_not_collide(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+    let bob = init_user(&users, "bob").await;
+
+    let alice_operation = operation(1);
+    let mut bob_operation = operation(2);
+    bob_operation.id = alice_operation.id;
+
+    data.add_operations(alice, vec![alice_operation], None).await.unwrap();
+    // Bob must not learn anything about Alice's operation, even its existence.
+    data.add_operations(bob, vec![bob_operation], None).await.unwrap();
+
+    assert_eq!(checksums(data.operations(alice, 0).await.unwrap()), vec![vec![1; 32]]);
+    assert_eq!(checksums(data.operations(bob, 0).await.unwrap()), vec![vec![2; 32]]);
+}
+
+async fn operations_reupload(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+
+    let first = operation(1);
+    let first_id = first.id;
+    data.add_operations(alice, vec![first], None).await.unwrap();
+
+    // Re-uploading the same operation is allowed.
+    let mut same = operation(1);
+    same.id = first_id;
+    data.add_operations(alice, vec![same, operation(2)], None)
+        .await
+        .unwrap();
+    assert_eq!(checksums(data.operations(alice, 0).await.unwrap()).len(), 2);
+
+    // The whole upload is rejected when any operation conflicts with the stored one.
+    let mut conflicting = operation(3);
+    conflicting.id = first_id;
+    assert!(matches!(
+        data.add_operations(alice, vec![operation(4), conflicting], None).await,
+        Err(Error::OperationRejected(OperationRejection::ChecksumMismatch { id })) if id == first_id
+    ));
+
+    // The same operation twice in one upload with different checksums.
+    let duplicate = operation(5);
+    let mut conflicting = operation(6);
+    conflicting.id = duplicate.id;
+    assert!(matches!(
+        data.add_operations(alice, vec![duplicate, conflicting], None).await,
+        Err(Error::OperationRejected(OperationRejection::ChecksumMismatch { .. }))
+    ));
+
+    assert_eq!(
+        checksums(data.operations(alice, 0).await.unwrap()),
+        vec![vec![1; 32], vec![2; 32]]
+    );
+}
+
+async fn invalid_operations_are_rejected(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+
+    let operations = (0..=MAX_OPERATIONS_PER_REQUEST).map(|_| operation(1)).collect();
+    assert!(matches!(
+        data.add_operations(alice, operations, None).await,
+        Err(Error::OperationRejected(OperationRejection::TooManyOperations { .. }))
+    ));
+
+    let mut invalid = operation(2);
+    invalid.checksum = vec![2; 16].into();
+    assert!(matches!(
+        data.add_operations(alice, vec![operation(3), invalid], None).await,
+        Err(Error::OperationRejected(OperationRejection::InvalidChecksum { .. }))
+    ));
+
+    assert!(data.operations(alice, 0).await.unwrap().is_empty());
+}
+
+async fn operations_are_ordered_by_creation_time(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+
+    let mut first = operation(1);
+    first.created_at = (OffsetDateTime::now_utc() - Duration::from_secs(60)).into();
+    let second = operation(2);
+
+    data.add_operations(alice, vec![second, first], None).await.unwrap();
+
+    assert_eq!(
+        checksums(data.operations(alice, 0).await.unwrap()),
+        vec![vec![1; 32], vec![2; 32]]
+    );
+}
+
+async fn operations_pagination(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+
+    let now = OffsetDateTime::now_utc();
+    let operations = (1..=5)
+        .map(|checksum| {
+            let mut operation = operation(checksum);
+            // Two operations with the same creation time must not be skipped or duplicated.
+            operation.created_at = (now - Duration::from_secs(60 * u64::from(6 - checksum.min(4)))).into();
+            operation
+        })
+        .collect();
+    data.add_operations(alice, operations, None).await.unwrap();
+    let all = checksums(data.operations(alice, 0).await.unwrap());
+
+    let mut pages = Vec::new();
+    let mut continuation = None;
+    loop {
+        let page = data
+            .operations_page(alice, 1, continuation.as_ref().map(ContinuationToken::as_str), Some(2))
+            .await
+            .unwrap();
+        pages.push(checksums(page.operations));
+
+        match page.next {
+            Some(next) => continuation = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2]);
+    assert_eq!(pages.concat(), all[1..]);
+
+    let last_page = data.operations_page(alice, 4, None, None).await.unwrap();
+    assert_eq!(checksums(last_page.operations), all[4..]);
+    assert!(last_page.next.is_none());
+
+    assert!(matches!(
+        data.operations_page(alice, 0, Some("invalid"), None).await,
+        Err(Error::InvalidData(_))
+    ));
+}
+
+async fn subsecond_timestamps_are_ordered(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(db);
+
+    let alice = init_user(&users, "alice").await;
+
+    // Whole seconds and fractions with trailing zeros must be ordered by time, not by their shortest text form.
+    let second = OffsetDateTime::now_utc().replace_nanosecond(0).unwrap();
+    let operations = [
+        (5, second + Duration::from_secs(1)),
+        (4, second + Duration::from_millis(500)),
+        (2, second + Duration::from_nanos(123_450_000)),
+        (1, second),
+        (3, second + Duration::from_nanos(123_456_789)),
+    ]
+    .into_iter()
+    .map(|(checksum, created_at)| {
+        let mut operation = operation(checksum);
+        operation.created_at = created_at.into();
+        operation
+    })
+    .collect();
+    data.add_operations(alice, operations, None).await.unwrap();
+
+    let expected = (1..=5).map(|checksum| vec![checksum; 32]).collect::<Vec<_>>();
+    assert_eq!(checksums(data.operations(alice, 0).await.unwrap()), expected);
+
+    let mut pages = Vec::new();
+    let mut continuation = None;
+    loop {
+        let page = data
+            .operations_page(alice, 0, continuation.as_ref().map(ContinuationToken::as_str), Some(2))
+            .await
+            .unwrap();
+        pages.push(checksums(page.operations));
+
+        match page.next {
+            Some(next) => continuation = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(pages.concat(), expected);
+}
+
+async fn api_tokens(db: Arc<Db>) {
+    let tokens = TokenService::new(db);
+
+    let token = tokens.issue(String::from("alice")).await.unwrap();
+
+    assert_eq!(tokens.verify(&token).await.unwrap().subject, "alice");
+    assert!(matches!(
+        tokens.verify("dataans_unknown").await,
+        Err(Error::Unauthorized(_))
+    ));
+}
+
+async fn usage_and_quota(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(Arc::clone(&db));
+    let usage = UsageService::new(db, Some(ByteUnit::Byte(100)));
+
+    let alice = init_user(&users, "alice").await;
+    let bob = init_user(&users, "bob").await;
+
+    data.add_operations(alice, vec![operation(1), operation(2)], None)
+        .await
+        .unwrap();
+    usage.add_file(alice, Uuid::new_v4(), 40).await.unwrap();
+
+    let file_id = Uuid::new_v4();
+    usage.add_file(bob, file_id, 10).await.unwrap();
+    // Re-uploaded file replaces the previous one.
+    usage.add_file(bob, file_id, 20).await.unwrap();
+
+    let alice_usage = usage.usage(alice).await.unwrap();
+    assert_eq!(alice_usage.operations, 2);
+    assert_eq!(alice_usage.operation_bytes, 32);
+    assert_eq!(alice_usage.files, 1);
+    assert_eq!(alice_usage.file_bytes, 40);
+    assert_eq!(alice_usage.quota, Some(100));
+
+    let bob_usage = usage.usage(bob).await.unwrap();
+    assert_eq!(
+        (bob_usage.operations, bob_usage.files, bob_usage.file_bytes),
+        (0, 1, 20)
+    );
+
+    usage.check_quota(alice, 28).await.unwrap();
+    assert!(matches!(
+        usage.check_quota(alice, 29).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+    usage.check_quota(bob, 80).await.unwrap();
+
+    // The quota is enforced when the data is saved.
+    assert!(matches!(
+        usage.add_file(alice, Uuid::new_v4(), 29).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+    let big_operation = Operation {
+        data: vec![3; 29].into(),
+        ..operation(3)
+    };
+    assert!(matches!(
+        data.add_operations(alice, vec![big_operation], usage.quota()).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+    assert_eq!(usage.usage(alice).await.unwrap().total_bytes(), 72);
+
+    // Re-uploaded data is not counted again.
+    usage.add_file(bob, file_id, 20).await.unwrap();
+    usage.add_file(bob, Uuid::new_v4(), 80).await.unwrap();
+    usage.add_file(bob, file_id, 20).await.unwrap();
+    assert!(matches!(
+        usage.add_file(bob, file_id, 21).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+
+    let new_operation = operation(4);
+    let mut same = operation(4);
+    same.id = new_operation.id;
+    data.add_operations(alice, vec![new_operation], usage.quota())
+        .await
+        .unwrap();
+    data.add_operations(alice, vec![same], usage.quota()).await.unwrap();
+    assert_eq!(usage.usage(alice).await.unwrap().total_bytes(), 88);
+    assert!(
+        data.add_operations(alice, vec![operation(6)], usage.quota())
+            .await
+            .is_err()
+    );
+}
+
+async fn reuploaded_file_is_checked_against_quota(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let usage = UsageService::new(db, Some(ByteUnit::Byte(100)));
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+
+    let upload = |id, content: Vec<u8>| {
+        let size = u64::try_from(content.len()).unwrap();
+        let usage = &usage;
+        let files = &files;
+
+        async move {
+            usage
+                .upload_file(files, alice, id, Some(size), Cursor::new(content))
+                .await
+        }
+    };
+
+    let file_id = Uuid::new_v4();
+    assert_eq!(upload(file_id, vec![1; 20]).await.unwrap(), 20);
+    upload(Uuid::new_v4(), vec![2; 80]).await.unwrap();
+
+    // The same size fits even when the storage is full.
+    upload(file_id, vec![3; 20]).await.unwrap();
+    assert!(matches!(
+        upload(file_id, vec![4; 21]).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+    assert!(matches!(
+        upload(Uuid::new_v4(), vec![5; 1]).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+
+    // The stored file is not overwritten by the rejected one.
+    let (_, mut reader) = files.open_file(alice, file_id).await.unwrap();
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content).await.unwrap();
+    assert_eq!(content, vec![3; 20]);
+    assert_eq!(files.list_files(alice).await.unwrap().len(), 2);
+    assert_eq!(usage.usage(alice).await.unwrap().file_bytes, 100);
+}
+
+async fn untracked_files_are_registered(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let usage = UsageService::new(Arc::clone(&db), None);
+    let admin = Admin::new(db);
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+    let (tracked, untracked) = (Uuid::new_v4(), Uuid::new_v4());
+    let size = files.save_file(alice, tracked, b"tbt".as_slice()).await.unwrap();
+    usage.add_file(alice, tracked, size).await.unwrap();
+    // The file uploaded before the usage tracking.
+    files.save_file(alice, untracked, b"dataans".as_slice()).await.unwrap();
+
+    assert_eq!(admin.track_files(&files).await.unwrap(), 1);
+    assert_eq!(admin.track_files(&files).await.unwrap(), 0);
+
+    let alice_usage = usage.usage(alice).await.unwrap();
+    assert_eq!((alice_usage.files, alice_usage.file_bytes), (2, 10));
+    assert!(admin.verify(&files).await.unwrap().is_ok());
+}
+
+async fn orphaned_files_survive_grace_period(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let gc = FilesGc::new(db, Duration::from_secs(60 * 60));
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+    let (live, dead) = (Uuid::new_v4(), Uuid::new_v4());
+    files.save_file(alice, live, [].as_slice()).await.unwrap();
+    files.save_file(alice, dead, [].as_slice()).await.unwrap();
+
+    let report = gc.collect(&files, alice, &[live]).await.unwrap();
+    assert_eq!((report.orphaned, report.deleted), (1, 0));
+
+    let report = gc.collect(&files, alice, &[live]).await.unwrap();
+    assert_eq!((report.orphaned, report.deleted), (1, 0));
+
+    // The file is referenced again.
+    let report = gc.collect(&files, alice, &[live, dead]).await.unwrap();
+    assert_eq!((report.orphaned, report.deleted), (0, 0));
+
+    assert!(files.exists(alice, dead).await.unwrap());
+}
+
+async fn orphaned_files_are_deleted(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let usage = UsageService::new(Arc::clone(&db), None);
+    let gc = FilesGc::new(db, Duration::ZERO);
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+    let bob = init_user(&users, "bob").await;
+
+    let (live, dead) = (Uuid::new_v4(), Uuid::new_v4());
+    for id in [live, dead] {
+        files.save_file(alice, id, [].as_slice()).await.unwrap();
+        usage.add_file(alice, id, 10).await.unwrap();
+    }
+    let bob_file = Uuid::new_v4();
+    files.save_file(bob, bob_file, [].as_slice()).await.unwrap();
+
+    // The first run only marks the file as orphaned.
+    let report = gc.collect(&files, alice, &[live]).await.unwrap();
+    assert_eq!((report.orphaned, report.deleted), (1, 0));
+
+    let report = gc.collect(&files, alice, &[live]).await.unwrap();
+    assert_eq!((report.orphaned, report.deleted), (0, 1));
+
+    assert!(files.exists(alice, live).await.unwrap());
+    assert!(!files.exists(alice, dead).await.unwrap());
+    assert!(files.exists(bob, bob_file).await.unwrap());
+
+    let alice_usage = usage.usage(alice).await.unwrap();
+    assert_eq!((alice_usage.files, alice_usage.file_bytes), (1, 10));
+}
+
+async fn export_and_import(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(Arc::clone(&db));
+    let usage = UsageService::new(Arc::clone(&db), None);
+    let admin = Admin::new(Arc::clone(&db));
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+    let bob = init_user(&users, "bob").await;
+
+    data.add_operations(alice, vec![operation(1), operation(2)], None)
+        .await
+        .unwrap();
+    data.add_operations(bob, vec![operation(3)], None).await.unwrap();
+    let file_id = Uuid::new_v4();
+    let size = files.save_file(alice, file_id, b"tbt files".as_slice()).await.unwrap();
+    usage.add_file(alice, file_id, size).await.unwrap();
+
+    let mut archive = Vec::new();
+    let summary = admin.export(&files, &mut archive).await.unwrap();
+    assert_eq!(
+        (summary.users, summary.operations, summary.files, summary.file_bytes),
+        (2, 3, 1, 9)
+    );
+
+    // Import is allowed only into an empty server.
+    assert!(admin.import(&files, archive.as_slice()).await.is_err());
+
+    admin.reset_user(&files, "alice").await.unwrap();
+    admin.reset_user(&files, "bob").await.unwrap();
+    assert_eq!(users.user_id("alice").await.unwrap(), None);
+    assert!(data.operations(alice, 0).await.unwrap().is_empty());
+    assert!(!files.exists(alice, file_id).await.unwrap());
+
+    let restored_files = MemoryFiles::default();
+    assert_eq!(
+        admin.import(&restored_files, archive.as_slice()).await.unwrap(),
+        summary
+    );
+
+    assert_eq!(users.user_id("alice").await.unwrap(), Some(alice));
+    assert_eq!(users.user_id("bob").await.unwrap(), Some(bob));
+    assert_eq!(
+        checksums(data.operations(alice, 0).await.unwrap()),
+        vec![vec![1; 32], vec![2; 32]]
+    );
+    assert_eq!(checksums(data.operations(bob, 0).await.unwrap()), vec![vec![3; 32]]);
+    assert_eq!(
+        restored_files.0.lock().unwrap().get(&(alice, file_id)).unwrap(),
+        b"tbt files"
+    );
+    assert_eq!(usage.usage(alice).await.unwrap().file_bytes, 9);
+    assert!(admin.verify(&restored_files).await.unwrap().is_ok());
+}
+
+async fn failed_import_is_rolled_back(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let data = Data::new(Arc::clone(&db));
+    let usage = UsageService::new(Arc::clone(&db), None);
+    let admin = Admin::new(Arc::clone(&db));
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+    data.add_operations(alice, vec![operation(1)], None).await.unwrap();
+    let file_id = Uuid::new_v4();
+    let size = files.save_file(alice, file_id, b"tbt files".as_slice()).await.unwrap();
+    usage.add_file(alice, file_id, size).await.unwrap();
+
+    let mut archive = Vec::new();
+    admin.export(&files, &mut archive).await.unwrap();
+    admin.reset_user(&files, "alice").await.unwrap();
+
+    // The end of the archive is cut off, so the import fails after all records are read.
+    let restored_files = MemoryFiles::default();
+    assert!(
+        admin
+            .import(&restored_files, &archive[..archive.len() - 1])
+            .await
+            .is_err()
+    );
+
+    assert!(db.users().await.unwrap().is_empty());
+    assert!(data.operations(alice, 0).await.unwrap().is_empty());
+    assert!(restored_files.0.lock().unwrap().is_empty());
+}
+
+async fn verify_finds_inconsistencies(db: Arc<Db>) {
+    let users = UserService::new(Arc::clone(&db));
+    let usage = UsageService::new(Arc::clone(&db), None);
+    let admin = Admin::new(db);
+    let files = MemoryFiles::default();
+
+    let alice = init_user(&users, "alice").await;
+
+    let (missing, untracked) = (Uuid::new_v4(), Uuid::new_v4());
+    usage.add_file(alice, missing, 10).await.unwrap();
+    files.save_file(alice, untracked, [].as_slice()).await.unwrap();
+
+    let report = admin.verify(&files).await.unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.missing_files, vec![(alice, missing)]);
+    assert_eq!(report.untracked_files, vec![(alice, untracked)]);
+    assert!(report.malformed_checksums.is_empty());
+}
+
+macro_rules! backend_tests {
+    ($($name:ident),* $(,)?) => {
+        #[cfg(feature = "sqlite")]
+        mod sqlite {
+            use std::sync::Arc;
+
+            use sqlx::sqlite::SqlitePoolOptions;
+
+            use crate::db::{Db, SqliteDb};
+
+            async fn db() -> Arc<Db> {
+                // Every in-memory SQLite connection has its own database. So, the pool must have only one connection.
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(1)
+                    .connect("sqlite::memory:")
+                    .await
+                    .unwrap();
+                sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+
+                Arc::new(Db::Sqlite(SqliteDb::new(pool)))
+            }
+
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    super::$name(db().await).await;
+                }
+            )*
+        }
+
+        mod postgres {
+            use std::sync::Arc;
+
+            use sqlx::PgPool;
+
+            use crate::db::{Db, PostgresDb};
+
+            $(
+                #[ignore = "requires PostgreSQL"]
+                #[sqlx::test(migrations = "./migrations/postgres")]
+                async fn $name(pool: PgPool) {
+                    super::$name(Arc::new(Db::Postgres(PostgresDb::new(pool)))).await;
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    user_is_bound_to_subject,
+    legacy_user_is_bound_explicitly,
+    legacy_user_is_reset_by_id,
+    uninitialized_legacy_user,
+    operations_are_isolated,
+    same_operation_id_does_not_collide,
+    operations_reupload,
+    invalid_operations_are_rejected,
+    operations_are_ordered_by_creation_time,
+    operations_pagination,
+    subsecond_timestamps_are_ordered,
+    api_tokens,
+    usage_and_quota,
+    reuploaded_file_is_checked_against_quota,
+    untracked_files_are_registered,
+    orphaned_files_survive_grace_period,
+    orphaned_files_are_deleted,
+    export_and_import,
+    failed_import_is_rolled_back,
+    verify_finds_inconsistencies,
+);
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::auth::Identity;
+use crate::db::{ApiToken, DbError, TokenDb};
+use crate::{Error, Result};
+
+/// Prefix of every issued API token. It makes tokens easy to recognize (e.g. by secret scanners).
+const TOKEN_PREFIX: &str = "dataans_";
+
+pub struct TokenService<D> {
+    db: Arc<D>,
+}
+
+impl<D> TokenService<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db }
+    }
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+impl<D: TokenDb> TokenService<D> {
+    /// Issues a new API token for the given subject.
+    ///
+    /// Returns the plain token. It is the only moment when the token is visible: only its hash is saved.
+    pub async fn issue(&self, subject: String) -> Result<String> {
+        // Two random UUIDs give us more than 240 bits of entropy. It is more than enough for the API token.
+        let token = format!("{TOKEN_PREFIX}{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+        self.db
+            .add_token(&ApiToken {
+                id: Uuid::new_v4(),
+                subject,
+                token_hash: hash_token(&token),
+                created_at: OffsetDateTime::now_utc(),
+            })
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Verifies the API token and returns the corresponding [Identity].
+    pub async fn verify(&self, token: &str) -> Result<Identity> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Err(Error::Unauthorized("invalid API token format"));
+        }
+
+        match self.db.token_by_hash(&hash_token(token)).await {
+            Ok(ApiToken { subject, .. }) => Ok(Identity { subject }),
+            Err(DbError::SqlxError(sqlx::Error::RowNotFound)) => Err(Error::Unauthorized("unknown API token")),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use rocket::data::ByteUnit;
+use rocket::tokio::io::AsyncRead;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use web_api_types::Usage;
+
+use crate::db::{File as FileModel, FilesDb, Usage as UsageModel, UsageDb};
+use crate::services::FileSaver;
+use crate::{Error, Result};
+
+/// Tracks the user's storage usage and enforces the storage quota.
+pub struct UsageService<D> {
+    db: Arc<D>,
+    /// Per-user storage quota. `None` means the storage is unlimited.
+    quota: Option<ByteUnit>,
+}
+
+impl<D> UsageService<D> {
+    pub fn new(db: Arc<D>, quota: Option<ByteUnit>) -> Self {
+        Self { db, quota }
+    }
+
+    /// Returns the configured per-user storage quota.
+    pub fn quota(&self) -> Option<ByteUnit> {
+        self.quota
+    }
+}
+
+/// Converts the quota into the database representation.
+pub(crate) fn quota_bytes(quota: Option<ByteUnit>) -> Option<i64> {
+    quota.map(|quota| i64::try_from(quota.as_u64()).unwrap_or(i64::MAX))
+}
+
+fn to_u64(value: i64) -> u64 {
+    u64::try_from(value).expect("count and size values should not be negative")
+}
+
+impl<D: UsageDb + FilesDb> UsageService<D> {
+    pub async fn usage(&self, user_id: Uuid) -> Result<Usage> {
+        let UsageModel {
+            operations,
+            operation_bytes,
+            files,
+            file_bytes,
+        } = self.db.usage(user_id).await?;
+
+        Ok(Usage {
+            operations: to_u64(operations),
+            operation_bytes: to_u64(operation_bytes),
+            files: to_u64(files),
+            file_bytes: to_u64(file_bytes),
+            quota: self.quota.map(ByteUnit::as_u64),
+        })
+    }
+
+    /// Checks that `bytes` more bytes fit into the user's storage quota.
+    ///
+    /// It is only a cheap check before receiving the data. The quota is enforced when the data is saved
+    /// (see [UsageService::add_file] and [Data::add_operations](crate::services::Data::add_operations)).
+    pub async fn check_quota(&self, user_id: Uuid, bytes: u64) -> Result<()> {
+        let Some(quota) = self.quota else {
+            return Ok(());
+        };
+
+        let used = self.usage(user_id).await?.total_bytes();
+
+        if used.saturating_add(bytes) > quota.as_u64() {
+            warn!(%user_id, used, bytes, %quota, "Storage quota exceeded");
+
+            return Err(Error::QuotaExceeded {
+                used: used.into(),
+                requested: bytes.into(),
+                quota,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Saves and registers the uploaded file.
+    ///
+    /// If the file `size` is known in advance, the file is checked against the quota before it is stored.
+    /// It also applies to re-uploaded files: the stored content must not be overwritten by the file that does not fit.
+    /// The new file that fails to register is deleted. Returns the number of saved bytes.
+    pub async fn upload_file<S: FileSaver>(
+        &self,
+        file_saver: &S,
+        user_id: Uuid,
+        id: Uuid,
+        size: Option<u64>,
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<u64> {
+        if let Some(size) = size {
+            self.check_file_quota(user_id, id, size).await?;
+        }
+
+        let existed = file_saver.exists(user_id, id).await?;
+
+        let size = file_saver.save_file(user_id, id, reader).await?;
+        if let Err(err) = self.add_file(user_id, id, size).await {
+            // The new file is not tracked, so it must not be stored either.
+            if !existed {
+                file_saver.delete_file(user_id, id).await?;
+            }
+
+            return Err(err);
+        }
+
+        Ok(size)
+    }
+
+    /// Checks that the file of the given `size` fits into the user's storage quota.
+    ///
+    /// Re-uploaded file replaces the stored one, so only the size difference is counted.
+    async fn check_file_quota(&self, user_id: Uuid, id: Uuid, size: u64) -> Result<()> {
+        if self.quota.is_none() {
+            return Ok(());
+        }
+
+        let stored_size = self.db.file_size(user_id, id).await?.map(to_u64).unwrap_or_default();
+
+        match size.checked_sub(stored_size) {
+            Some(bytes) if bytes > 0 => self.check_quota(user_id, bytes).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Registers the uploaded file.
+    ///
+    /// Fails if the file does not fit into the user's storage quota. Re-uploaded file replaces the previous one,
+    /// so only the size difference is counted.
+    pub async fn add_file(&self, user_id: Uuid, id: Uuid, size: u64) -> Result<()> {
+        self.db
+            .add_file(
+                user_id,
+                &FileModel {
+                    id,
+                    size: i64::try_from(size).map_err(|_| Error::InvalidData("file size"))?,
+                    created_at: OffsetDateTime::now_utc(),
+                },
+                quota_bytes(self.quota),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
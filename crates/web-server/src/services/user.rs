@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use uuid::Uuid;
 use web_api_types::User;
 
 use crate::Result;
@@ -16,21 +17,24 @@ impl<D> UserService<D> {
 }
 
 impl<D: UserDb> UserService<D> {
-    pub async fn init(&self, user: User) -> Result<()> {
+    pub async fn init(&self, subject: &str, user: User) -> Result<()> {
         let User { id, secret_key_hash } = user;
 
         self.db
-            .init(&UserModel {
-                id: id.into(),
-                secret_key_hash: secret_key_hash.into(),
-            })
+            .init(
+                subject,
+                &UserModel {
+                    id: id.into(),
+                    secret_key_hash: secret_key_hash.into(),
+                },
+            )
             .await?;
 
         Ok(())
     }
 
-    pub async fn user(&self) -> Result<Option<User>> {
-        match self.db.user().await {
+    pub async fn user(&self, subject: &str) -> Result<Option<User>> {
+        match self.db.user(subject).await {
             Ok(user) => {
                 let UserModel { id, secret_key_hash } = user;
 
@@ -43,4 +47,9 @@ impl<D: UserDb> UserService<D> {
             Err(err) => Err(err.into()),
         }
     }
+
+    /// Returns the id of the user bound to the given subject.
+    pub async fn user_id(&self, subject: &str) -> Result<Option<Uuid>> {
+        Ok(self.user(subject).await?.map(|user| user.id.into()))
+    }
 }
@@ -16,6 +16,9 @@ pub const DATA_EVENT: &str = "data-event";
 /// An event name for any status-updates.
 pub const STATUS_UPDATE_EVENT: &str = "status-update-event";
 
+/// An event name for the [FileImportProgress].
+pub const FILE_IMPORT_PROGRESS_EVENT: &str = "file-import-progress";
+
 /// An event related to the user context.
 ///
 /// It includes sign in, sign out, and related events.
@@ -59,4 +62,19 @@ pub enum StatusUpdateEvent {
     SyncSuccessful,
     /// Synchronization failed.
     SyncFailed(String),
+    /// The authorization token expires soon (or has already expired) and cannot be renewed silently.
+    ///
+    /// Contains the amount of minutes left.
+    AuthTokenExpiresSoon(u64),
+}
+
+/// Progress of the file import from the local file system.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FileImportProgress {
+    /// ID of the file being imported.
+    pub file_id: FileId,
+    /// Amount of already processed bytes.
+    pub imported: u64,
+    /// File size in bytes.
+    pub total: u64,
 }
@@ -1,8 +1,16 @@
+use std::collections::HashSet;
 use std::fmt;
+use std::path::Path;
 
+use schemars::JsonSchema;
+use schemars::schema::RootSchema;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use time::serde::rfc3339;
+use uuid::Uuid;
 
-use crate::note::OwnedNote;
+use crate::DEFAULT_SPACE_AVATAR_PATH;
+use crate::note::{File, FileStatus, OwnedNote};
 use crate::space::OwnedSpace;
 
 /// Schema version.
@@ -11,18 +19,21 @@ pub enum SchemaVersion {
     /// V1.
     #[default]
     V1,
+    /// V2.
+    V2,
 }
 
 impl SchemaVersion {
     /// Returns slice that contains all possible schema versions.
     pub fn variants() -> &'static [SchemaVersion] {
-        &[SchemaVersion::V1]
+        &[SchemaVersion::V1, SchemaVersion::V2]
     }
 
     /// Returns [SchemaVersion] variant name.
     pub fn variant_name(&self) -> &'static str {
         match self {
             SchemaVersion::V1 => "V1",
+            SchemaVersion::V2 => "V2",
         }
     }
 
@@ -32,6 +43,7 @@ impl SchemaVersion {
     pub fn _from_str(value: &str) -> Self {
         match value {
             "V1" => SchemaVersion::V1,
+            "V2" => SchemaVersion::V2,
             _ => panic!("Invalid export schema version: {value}"),
         }
     }
@@ -43,26 +55,412 @@ impl fmt::Display for SchemaVersion {
     }
 }
 
+/// Options of the archive export.
+///
+/// The archive contains the app data in the json format and all files (attachments and space avatars).
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct ArchiveExportOption {
+    /// Passphrase to encrypt the archive with. The archive is not encrypted if it is `None`.
+    pub passphrase: Option<String>,
+}
+
+// The passphrase must not appear in logs.
+impl fmt::Debug for ArchiveExportOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchiveExportOption")
+            .field("encrypted", &self.passphrase.is_some())
+            .finish()
+    }
+}
+
 /// Json data export schema and the data itself.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "version")]
 pub enum Schema {
     /// V1.
     V1(SchemaV1),
+    /// V2.
+    V2(SchemaV2),
+}
+
+impl Schema {
+    /// Upgrades the data to the latest schema version.
+    pub fn into_latest(self) -> SchemaV2 {
+        match self {
+            Schema::V1(schema_v1) => schema_v1.into(),
+            Schema::V2(schema_v2) => schema_v2,
+        }
+    }
+}
+
+/// Returns the JSON Schema document of the json data export format (all versions).
+pub fn json_schema() -> RootSchema {
+    schemars::schema_for!(Schema)
 }
 
 /// App data. V1.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SchemaV1 {
     /// App data.
     pub data: Vec<Space>,
 }
 
 /// Space data.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "SpaceData")]
 pub struct Space {
     /// Space info.
     pub space: OwnedSpace,
     /// Spaces notes.
     pub notes: Vec<OwnedNote>,
 }
+
+/// App data. V2.
+///
+/// Records are stored in flat lists in the same way as in the local database. Unlike [SchemaV1], it keeps deleted
+/// items (so deletions are not lost during the import), files timestamps, and files upload status.
+///
+/// New fields must have default values (`#[serde(default)]`), so files exported by older app versions stay valid.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchemaV2 {
+    /// Spaces.
+    pub spaces: Vec<SpaceV2>,
+    /// Notes of all spaces.
+    pub notes: Vec<NoteV2>,
+    /// Note attachments and space avatars.
+    pub files: Vec<FileV2>,
+}
+
+/// Space. V2.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct SpaceV2 {
+    /// Space id.
+    pub id: Uuid,
+    /// Space name.
+    pub name: String,
+    /// Avatar file id.
+    pub avatar_id: Uuid,
+    /// Creation date.
+    #[serde(with = "rfc3339")]
+    #[schemars(with = "DateTime")]
+    pub created_at: OffsetDateTime,
+    /// Update date. For deleted spaces, it is the deletion date.
+    #[serde(with = "rfc3339")]
+    #[schemars(with = "DateTime")]
+    pub updated_at: OffsetDateTime,
+    /// Whether the space is deleted.
+    pub is_deleted: bool,
+}
+
+/// Note. V2.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct NoteV2 {
+    /// Note id.
+    pub id: Uuid,
+    /// Note text in MD format.
+    pub text: String,
+    /// Id of the space this note belongs to.
+    pub space_id: Uuid,
+    /// Creation date.
+    #[serde(with = "rfc3339")]
+    #[schemars(with = "DateTime")]
+    pub created_at: OffsetDateTime,
+    /// Update date. For deleted notes, it is the deletion date.
+    #[serde(with = "rfc3339")]
+    #[schemars(with = "DateTime")]
+    pub updated_at: OffsetDateTime,
+    /// Whether the note is deleted.
+    pub is_deleted: bool,
+    /// Ids of attached files.
+    pub files: Vec<Uuid>,
+}
+
+/// File. V2.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct FileV2 {
+    /// File id.
+    pub id: Uuid,
+    /// The original file name.
+    pub name: String,
+    /// File name in the files directory. The default space avatar has the app asset path instead.
+    pub path: String,
+    /// Creation date.
+    #[serde(with = "rfc3339")]
+    #[schemars(with = "DateTime")]
+    pub created_at: OffsetDateTime,
+    /// Update date. For deleted files, it is the deletion date.
+    #[serde(with = "rfc3339")]
+    #[schemars(with = "DateTime")]
+    pub updated_at: OffsetDateTime,
+    /// Whether the file is deleted.
+    pub is_deleted: bool,
+    /// Whether the file has been uploaded to the sync server.
+    pub is_uploaded: bool,
+    /// Hex-encoded SHA-256 hash of the file content. Files created before the deduplication support do not have it.
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// RFC 3339 date and time. Used only for the JSON Schema generation.
+struct DateTime;
+
+impl JsonSchema for DateTime {
+    fn schema_name() -> String {
+        "DateTime".to_owned()
+    }
+
+    fn json_schema(generator: &mut schemars::r#gen::SchemaGenerator) -> schemars::schema::Schema {
+        let mut schema = String::json_schema(generator).into_object();
+        schema.format = Some("date-time".to_owned());
+
+        schema.into()
+    }
+}
+
+/// Returns the name of the file in the files directory. The default space avatar path is kept as is.
+fn file_path(path: &Path) -> String {
+    if path == Path::new(DEFAULT_SPACE_AVATAR_PATH) {
+        return DEFAULT_SPACE_AVATAR_PATH.to_owned();
+    }
+
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl From<SchemaV1> for SchemaV2 {
+    /// V1 does not have files timestamps, so files get timestamps of the space or note they belong to.
+    fn from(schema_v1: SchemaV1) -> Self {
+        let mut schema_v2 = SchemaV2::default();
+        let mut file_ids = HashSet::new();
+
+        for Space { space, notes } in schema_v1.data {
+            let created_at = *space.created_at.as_ref();
+            let updated_at = *space.updated_at.as_ref();
+            let avatar_path = Path::new(space.avatar.path());
+
+            if file_ids.insert(space.avatar.id()) {
+                schema_v2.files.push(FileV2 {
+                    id: space.avatar.id(),
+                    name: avatar_path
+                        .file_name()
+                        .unwrap_or(avatar_path.as_os_str())
+                        .to_string_lossy()
+                        .into_owned(),
+                    path: file_path(avatar_path),
+                    created_at,
+                    updated_at,
+                    is_deleted: false,
+                    is_uploaded: false,
+                    hash: None,
+                });
+            }
+
+            schema_v2.spaces.push(SpaceV2 {
+                id: space.id.inner(),
+                name: space.name.as_ref().to_owned(),
+                avatar_id: space.avatar.id(),
+                created_at,
+                updated_at,
+                is_deleted: false,
+            });
+
+            for note in notes {
+                let created_at = *note.created_at.as_ref();
+                let updated_at = *note.updated_at.as_ref();
+
+                for File { id, name, path, status } in &note.files {
+                    if file_ids.insert(*id.as_ref()) {
+                        schema_v2.files.push(FileV2 {
+                            id: *id.as_ref(),
+                            name: name.clone(),
+                            path: file_path(path),
+                            created_at,
+                            updated_at,
+                            is_deleted: false,
+                            is_uploaded: matches!(
+                                status,
+                                FileStatus::ExistAndUploaded | FileStatus::NotExistAndUploaded
+                            ),
+                            hash: None,
+                        });
+                    }
+                }
+
+                schema_v2.notes.push(NoteV2 {
+                    id: note.id.inner(),
+                    text: note.text.as_ref().to_owned(),
+                    space_id: note.space_id.inner(),
+                    created_at,
+                    updated_at,
+                    is_deleted: false,
+                    files: note.files.iter().map(|file| *file.id.as_ref()).collect(),
+                });
+            }
+        }
+
+        schema_v2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use time::macros::datetime;
+    use uuid::uuid;
+
+    use super::*;
+    use crate::note::{FileId, Note};
+    use crate::space::{Avatar, Space as SpaceInfo};
+
+    fn schema_v1() -> SchemaV1 {
+        let space_id = uuid!("0d7a5f5e-9b1a-4cbe-9d34-4a3c3a8a0b11");
+        let file = File {
+            id: FileId::from(uuid!("a7b3c1d2-5e6f-4a8b-9c0d-1e2f3a4b5c6d")),
+            name: "photo.png".into(),
+            path: "/home/user/.local/share/dataans/files/a7b3c1d2_photo.png".into(),
+            status: FileStatus::NotExistAndUploaded,
+        };
+
+        SchemaV1 {
+            data: vec![Space {
+                space: SpaceInfo {
+                    id: space_id.into(),
+                    name: "Work".into(),
+                    created_at: datetime!(2026-01-02 10:00 UTC).into(),
+                    updated_at: datetime!(2026-01-03 10:00 UTC).into(),
+                    avatar: Avatar::new(crate::DEFAULT_SPACE_AVATAR_ID.into(), DEFAULT_SPACE_AVATAR_PATH),
+                },
+                notes: vec![
+                    Note {
+                        id: uuid!("5c1e6b9a-3f0d-4e2a-8b7c-6d5e4f3a2b1c").into(),
+                        text: "![](a7b3c1d2_photo.png)".into(),
+                        created_at: datetime!(2026-02-01 08:30:15.5 +2).into(),
+                        updated_at: datetime!(2026-02-01 09:00 +2).into(),
+                        space_id: space_id.into(),
+                        files: vec![file.clone()],
+                    },
+                    Note {
+                        id: uuid!("9e8d7c6b-5a4f-4e3d-8c2b-1a0f9e8d7c6b").into(),
+                        text: "Same photo".into(),
+                        created_at: datetime!(2026-03-01 12:00 UTC).into(),
+                        updated_at: datetime!(2026-03-01 12:00 UTC).into(),
+                        space_id: space_id.into(),
+                        files: vec![file],
+                    },
+                ],
+            }],
+        }
+    }
+
+    /// Serializes the data, deserializes it back, and serializes it again. Both json documents must be identical.
+    fn assert_round_trip(schema: Schema) {
+        let exported = serde_json::to_string(&schema).unwrap();
+        let imported: Schema = serde_json::from_str(&exported).unwrap();
+
+        assert_eq!(serde_json::to_string(&imported).unwrap(), exported);
+    }
+
+    #[test]
+    fn v1_round_trip() {
+        assert_round_trip(Schema::V1(schema_v1()));
+    }
+
+    #[test]
+    fn v2_round_trip() {
+        let mut schema_v2 = SchemaV2::from(schema_v1());
+        schema_v2.notes[1].is_deleted = true;
+        schema_v2.files[1].hash = Some("5feceb66ffc86f38d952786c6d696c79c2dbc239dd4e91b46729d73a27fb57e9".into());
+
+        let exported = serde_json::to_string(&Schema::V2(schema_v2.clone())).unwrap();
+        assert!(exported.starts_with(r#"{"version":"V2","spaces":["#));
+        assert!(exported.contains(r#""created_at":"2026-02-01T08:30:15.5+02:00""#));
+
+        let Schema::V2(imported) = serde_json::from_str(&exported).unwrap() else {
+            panic!("V2 schema expected");
+        };
+        assert_eq!(imported, schema_v2);
+
+        assert_round_trip(Schema::V2(schema_v2));
+    }
+
+    #[test]
+    fn v2_without_optional_fields() {
+        let schema: Schema = serde_json::from_str(
+            r#"{"version":"V2","spaces":[],"notes":[],"files":[{"id":"a7b3c1d2-5e6f-4a8b-9c0d-1e2f3a4b5c6d","name":"a.png","path":"a.png","created_at":"2026-01-02T10:00:00Z","updated_at":"2026-01-02T10:00:00Z","is_deleted":false,"is_uploaded":true}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(schema.into_latest().files[0].hash, None);
+    }
+
+    #[test]
+    fn v1_upgrade() {
+        let schema_v2 = Schema::V1(schema_v1()).into_latest();
+
+        assert_eq!(
+            schema_v2.spaces,
+            vec![SpaceV2 {
+                id: uuid!("0d7a5f5e-9b1a-4cbe-9d34-4a3c3a8a0b11"),
+                name: "Work".into(),
+                avatar_id: crate::DEFAULT_SPACE_AVATAR_ID,
+                created_at: datetime!(2026-01-02 10:00 UTC),
+                updated_at: datetime!(2026-01-03 10:00 UTC),
+                is_deleted: false,
+            }]
+        );
+        assert_eq!(
+            schema_v2.files,
+            vec![
+                FileV2 {
+                    id: crate::DEFAULT_SPACE_AVATAR_ID,
+                    name: "default_space_avatar.png".into(),
+                    path: DEFAULT_SPACE_AVATAR_PATH.into(),
+                    created_at: datetime!(2026-01-02 10:00 UTC),
+                    updated_at: datetime!(2026-01-03 10:00 UTC),
+                    is_deleted: false,
+                    is_uploaded: false,
+                    hash: None,
+                },
+                // The file attached to two notes is exported once.
+                FileV2 {
+                    id: uuid!("a7b3c1d2-5e6f-4a8b-9c0d-1e2f3a4b5c6d"),
+                    name: "photo.png".into(),
+                    path: "a7b3c1d2_photo.png".into(),
+                    created_at: datetime!(2026-02-01 08:30:15.5 +2),
+                    updated_at: datetime!(2026-02-01 09:00 +2),
+                    is_deleted: false,
+                    is_uploaded: true,
+                    hash: None,
+                },
+            ]
+        );
+        assert_eq!(schema_v2.notes.len(), 2);
+        assert!(
+            schema_v2
+                .notes
+                .iter()
+                .all(|note| note.files == [uuid!("a7b3c1d2-5e6f-4a8b-9c0d-1e2f3a4b5c6d")])
+        );
+        assert_eq!(schema_v2.notes[0].text, "![](a7b3c1d2_photo.png)");
+    }
+
+    /// The JSON Schema document is generated from the types. Run the test with `UPDATE_EXPORT_SCHEMA=1` to regenerate it.
+    #[test]
+    fn json_schema_document() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../doc/export_schema.json");
+        let schema = serde_json::to_string_pretty(&json_schema()).unwrap() + "\n";
+
+        if env::var_os("UPDATE_EXPORT_SCHEMA").is_some() {
+            fs::write(&path, &schema).unwrap();
+        }
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            schema,
+            "The export JSON Schema is outdated. Run tests with `UPDATE_EXPORT_SCHEMA=1` to regenerate it."
+        );
+    }
+}
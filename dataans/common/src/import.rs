@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Options of the Markdown directory import.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct MdImportConfig {
+    /// Split every Markdown file into separate notes by its top-level headings.
+    ///
+    /// Otherwise, every file becomes one note.
+    pub split_by_headings: bool,
+}
+
+/// An item that has not been imported.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct SkippedItem {
+    /// Path of the skipped file or the file that contains the skipped item.
+    pub path: PathBuf,
+    /// Human-readable reason.
+    pub reason: String,
+}
+
+/// Result of the data import.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ImportReport {
+    /// Amount of created spaces.
+    pub spaces: u64,
+    /// Amount of created notes.
+    pub notes: u64,
+    /// Amount of imported attachments.
+    pub files: u64,
+    /// Items that have not been imported.
+    pub skipped: Vec<SkippedItem>,
+}
@@ -7,6 +7,8 @@ pub mod error;
 pub mod event;
 /// Contains schema definitions for data export.
 pub mod export;
+/// Contains data import options and reports.
+pub mod import;
 /// All possible frontend keybindings definitions.
 pub mod key_bindings;
 /// Contains all note-related structures.
@@ -21,12 +23,13 @@ use std::fmt;
 use std::path::PathBuf;
 
 use derive_more::{AsRef, From, Into};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::{Uuid, uuid};
 pub use web_api_types as common_api_types;
 
-use crate::export::SchemaVersion;
+use crate::export::{ArchiveExportOption, SchemaVersion};
 
 /// Name of the custom tauri plugin.
 pub const APP_PLUGIN_NAME: &str = "dataans";
@@ -37,6 +40,10 @@ pub const APP_PLUGIN_NAME: &str = "dataans";
 pub const DEFAULT_SPACE_AVATAR_ID: Uuid = uuid!("54d49bda-644e-44a9-a1ad-4a8fa5f368a5");
 /// Default space avatar file path.
 pub const DEFAULT_SPACE_AVATAR_PATH: &str = "/public/default_space_avatar.png";
+/// Default size (in pixels) of the image thumbnail side.
+///
+/// Thumbnails are used to render images in the notes list.
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 512;
 
 /// Dataans app theme.
 ///
@@ -95,6 +102,41 @@ pub struct App {
     /// Base path for the all app data: config file, user files, DB, etc.
     #[serde(default)]
     pub base_path: String,
+    /// Additional named vaults.
+    ///
+    /// The default vault (named [DEFAULT_VAULT_NAME]) always exists and lives in the [App::base_path].
+    #[serde(default)]
+    pub vaults: Vec<Vault>,
+}
+
+/// The name of the default vault.
+pub const DEFAULT_VAULT_NAME: &str = "default";
+
+/// Vault configuration.
+///
+/// Every vault is a separate notebook with its own database, files, user profile, and sync server.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Vault {
+    /// Unique vault name.
+    pub name: String,
+    /// Vault data directory.
+    ///
+    /// If empty, then `<base path>/vaults/<name>` is used.
+    #[serde(default)]
+    pub path: String,
+    /// Global shortcut that switches to this vault and toggles the app visibility.
+    #[serde(default)]
+    pub toggle: String,
+}
+
+/// Information about configured vaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct VaultsInfo {
+    /// Names of all available vaults (including the default one).
+    pub names: Vec<String>,
+    /// The name of the currently opened vault.
+    pub active: String,
 }
 
 fn hide_taskbar_icon() -> bool {
@@ -125,13 +167,17 @@ pub struct Config {
     pub app: App,
 }
 
+/// [OffsetDateTime] serialized as a tuple: year, ordinal day, hour, minute, second, nanosecond, and offset hours,
+/// minutes, seconds. It is the default `time` crate serialization format. Used only for the JSON Schema generation.
+type TimeTuple = (i32, u16, u8, u8, u8, u32, i8, i8, i8);
+
 /// Date and time when the item was created.
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, From, Into, AsRef, PartialOrd, Ord)]
-pub struct CreationDate(OffsetDateTime);
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, From, Into, AsRef, PartialOrd, Ord, JsonSchema)]
+pub struct CreationDate(#[schemars(with = "TimeTuple")] OffsetDateTime);
 
 /// Date and time when the item was updated.
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, From, Into, AsRef, PartialOrd, Ord)]
-pub struct UpdateDate(OffsetDateTime);
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, From, Into, AsRef, PartialOrd, Ord, JsonSchema)]
+pub struct UpdateDate(#[schemars(with = "TimeTuple")] OffsetDateTime);
 
 /// Option that describes how to export notes.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
@@ -143,6 +189,9 @@ pub enum NotesExportOption {
     FilePerSpace,
     /// For each note a separate file will be created. All these files will be grouped by folders which represent spaces.
     FilePerNote,
+    /// Obsidian-compatible vault: a file per note with YAML front matter, grouped by space folders. Attachments are
+    /// copied into the `attachments/` folder and note links point to them.
+    Obsidian,
 }
 
 impl NotesExportOption {
@@ -152,6 +201,7 @@ impl NotesExportOption {
             NotesExportOption::OneFile,
             NotesExportOption::FilePerSpace,
             NotesExportOption::FilePerNote,
+            NotesExportOption::Obsidian,
         ]
     }
 
@@ -161,6 +211,7 @@ impl NotesExportOption {
             NotesExportOption::OneFile => "OneFile",
             NotesExportOption::FilePerSpace => "FilePerSpace",
             NotesExportOption::FilePerNote => "FilePerNote",
+            NotesExportOption::Obsidian => "Obsidian",
         }
     }
 
@@ -170,6 +221,7 @@ impl NotesExportOption {
             NotesExportOption::OneFile => "One file",
             NotesExportOption::FilePerSpace => "File per space",
             NotesExportOption::FilePerNote => "File per note",
+            NotesExportOption::Obsidian => "Obsidian vault",
         }
     }
 
@@ -181,6 +233,7 @@ impl NotesExportOption {
             "OneFile" => NotesExportOption::OneFile,
             "FilePerSpace" => NotesExportOption::FilePerSpace,
             "FilePerNote" => NotesExportOption::FilePerNote,
+            "Obsidian" => NotesExportOption::Obsidian,
             _ => panic!("Invalid NotesExportOption value: {value}"),
         }
     }
@@ -199,6 +252,8 @@ pub enum DataExportConfig {
     Md(NotesExportOption),
     /// Json export format with its options.
     Json(SchemaVersion),
+    /// Zip archive with the json data and all files.
+    Archive(ArchiveExportOption),
 }
 
 impl DataExportConfig {
@@ -207,6 +262,7 @@ impl DataExportConfig {
         &[
             DataExportConfig::Md(NotesExportOption::OneFile),
             DataExportConfig::Json(SchemaVersion::V1),
+            DataExportConfig::Archive(ArchiveExportOption { passphrase: None }),
         ]
     }
 
@@ -217,6 +273,7 @@ impl DataExportConfig {
         match value {
             "Md" => DataExportConfig::Md(Default::default()),
             "Json" => DataExportConfig::Json(Default::default()),
+            "Archive" => DataExportConfig::Archive(Default::default()),
             _ => panic!("Invalid DataExportConfig variant name: {value}"),
         }
     }
@@ -226,6 +283,7 @@ impl DataExportConfig {
         match self {
             DataExportConfig::Md(_) => "Md",
             DataExportConfig::Json(_) => "Json",
+            DataExportConfig::Archive(_) => "Archive",
         }
     }
 }
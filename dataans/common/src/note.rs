@@ -3,6 +3,7 @@ use std::fmt::Display;
 use std::path::{Path, PathBuf};
 
 use derive_more::derive::{AsRef, From, Into};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -10,7 +11,8 @@ use crate::space::{Id as SpaceId, Space};
 use crate::{CreationDate, UpdateDate};
 
 /// Represent a note ID.
-#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq, JsonSchema)]
+#[schemars(rename = "NoteId")]
 pub struct Id(Uuid);
 
 impl Id {
@@ -39,7 +41,7 @@ impl Display for Id {
 }
 
 /// Represent a note text.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq, JsonSchema)]
 pub struct MdText<'text>(Cow<'text, str>);
 
 impl Display for MdText<'_> {
@@ -76,7 +78,7 @@ impl AsRef<str> for MdText<'_> {
 }
 
 /// File status.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, JsonSchema)]
 pub enum FileStatus {
     /// File exists and has been uploaded.
     ExistAndUploaded,
@@ -110,11 +112,11 @@ impl FileStatus {
 }
 
 /// File ID.
-#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, Eq, PartialEq, From, Into, AsRef)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, Eq, PartialEq, From, Into, AsRef, JsonSchema)]
 pub struct FileId(Uuid);
 
 /// Represents an uploaded file.
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, JsonSchema)]
 pub struct File {
     /// The unique file id.
     pub id: FileId,
@@ -127,7 +129,7 @@ pub struct File {
 }
 
 /// Represent one note.
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, JsonSchema)]
 pub struct Note<'text> {
     /// Note id.
     pub id: Id,
@@ -170,6 +172,9 @@ pub struct NoteFull<'text, 'space_name, 'space_avatar> {
     pub space: Space<'space_name, 'space_avatar>,
     /// Attached files.
     pub files: Vec<File>,
+    /// Attached files whose text matches the search query.
+    #[serde(default)]
+    pub matched_files: Vec<FileId>,
 }
 
 /// Owned version of the [NoteFull] type.
@@ -201,3 +206,36 @@ pub struct UpdateNote<'text> {
     /// Attached files.
     pub files: Vec<File>,
 }
+
+/// Result of the local files integrity check.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct FilesReport {
+    /// Registered files that do not exist in the files directory.
+    pub missing: Vec<File>,
+    /// Registered files whose size or content does not match the saved size or content hash.
+    ///
+    /// Files created before the deduplication support do not have the hash, so only their size is checked.
+    pub corrupted: Vec<File>,
+    /// Files in the files directory that are not registered in the database.
+    ///
+    /// Paths are relative to the files directory.
+    pub orphaned: Vec<PathBuf>,
+}
+
+impl FilesReport {
+    /// Returns `true` if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupted.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// Repair action for a problem found by the files integrity check.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum FileRepair {
+    /// Downloads the missing or corrupted file from the sync server.
+    Redownload(FileId),
+    /// Registers the orphaned file and attaches it to a new note in the "Recovered" space.
+    Recover(PathBuf),
+    /// Deletes the orphaned file.
+    Delete(PathBuf),
+}
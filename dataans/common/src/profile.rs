@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use derive_more::{AsRef, From, Into};
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -5,7 +7,7 @@ use url::Url;
 /// Authorization token.
 ///
 /// This token is used to authenticate the user with the backend server.
-#[derive(Debug, Serialize, Deserialize, AsRef, From, Into, Clone)]
+#[derive(Debug, Serialize, Deserialize, AsRef, From, Into, Clone, PartialEq, Eq)]
 pub struct AuthorizationToken(String);
 
 /// Key derivation salt (nonce).
@@ -37,6 +39,58 @@ pub enum SyncMode {
     // Push,
 }
 
+/// Authentication scheme.
+///
+/// It represents how the app authenticates to the sync server. It must match the auth provider configured on the server.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum AuthScheme {
+    /// Cloudflare Zero Trust Access. The token is taken from the `CF_Authorization` cookie.
+    #[default]
+    CloudflareAccess,
+    /// API token issued by the sync server.
+    ApiToken,
+    /// Access token (JWT) issued by any OIDC-compatible identity provider.
+    Oidc,
+}
+
+impl AuthScheme {
+    /// Returns slice that contains all possible auth schemes.
+    pub fn variants() -> &'static [AuthScheme] {
+        &[AuthScheme::CloudflareAccess, AuthScheme::ApiToken, AuthScheme::Oidc]
+    }
+
+    /// Returns [AuthScheme] variant name.
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            AuthScheme::CloudflareAccess => "CloudflareAccess",
+            AuthScheme::ApiToken => "ApiToken",
+            AuthScheme::Oidc => "Oidc",
+        }
+    }
+
+    /// Returns pretty name of [AuthScheme].
+    pub fn pretty(&self) -> &'static str {
+        match self {
+            AuthScheme::CloudflareAccess => "Cloudflare Access",
+            AuthScheme::ApiToken => "API token",
+            AuthScheme::Oidc => "OIDC token",
+        }
+    }
+}
+
+impl FromStr for AuthScheme {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "CloudflareAccess" => Ok(AuthScheme::CloudflareAccess),
+            "ApiToken" => Ok(AuthScheme::ApiToken),
+            "Oidc" => Ok(AuthScheme::Oidc),
+            _ => Err(format!("Invalid auth scheme: {value}")),
+        }
+    }
+}
+
 /// Synchronization configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sync {
@@ -53,6 +107,11 @@ pub struct Sync {
 pub struct UserProfile {
     /// Authorization token.
     pub auth_token: AuthorizationToken,
+    /// Authentication scheme of the [UserProfile::auth_token].
+    ///
+    /// Profiles created before the auth scheme was introduced always use Cloudflare Access.
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
     /// Secret key.
     pub secret_key: SecretKey,
     /// Key derivation salt (nonce).
@@ -71,4 +130,36 @@ pub struct UserProfile {
 pub struct UserContext {
     /// Synchronization configuration.
     pub sync_config: Sync,
+    /// Authentication scheme of the signed-in user.
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
+}
+
+/// Key derivation function parameters.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Key derivation algorithm name.
+    pub algorithm: String,
+    /// Iterations count.
+    pub iterations: u32,
+}
+
+/// Recovery kit.
+///
+/// Contains everything (except the password) the user needs to restore access to the backup on a new device.
+/// The password is never included.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveryKit {
+    /// The synchronization server URL.
+    pub sync_server: WebServerUrl,
+    /// Authentication scheme used by the sync server.
+    pub auth_scheme: AuthScheme,
+    /// Key derivation salt (unique passphrase).
+    pub salt: Salt,
+    /// Encryption key derivation parameters.
+    pub kdf: KdfParams,
+    /// Short encryption key fingerprint.
+    ///
+    /// It allows to check whether the entered password is correct without contacting the server.
+    pub key_fingerprint: String,
 }
@@ -1,14 +1,16 @@
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::note::{File, FileId};
+use crate::note::{File, FileId, Id as NoteId};
 use crate::{CreationDate, UpdateDate};
 
 /// Represent a space ID.
-#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq, Hash, JsonSchema)]
+#[schemars(rename = "SpaceId")]
 pub struct Id(Uuid);
 
 impl Id {
@@ -37,7 +39,7 @@ impl From<Id> for Uuid {
 }
 
 /// Represents a space name.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq, JsonSchema)]
 pub struct Name<'name>(Cow<'name, str>);
 
 impl From<String> for Name<'static> {
@@ -74,7 +76,7 @@ impl AsRef<str> for Name<'_> {
 }
 
 /// Represents space avatar file name.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq, JsonSchema)]
 pub struct Avatar<'avatar> {
     id: FileId,
     path: Cow<'avatar, str>,
@@ -116,7 +118,7 @@ impl From<File> for Avatar<'_> {
 /// Represents a space.
 ///
 /// Space - a collection of notes.
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, JsonSchema)]
 pub struct Space<'name, 'avatar> {
     /// Space ID.
     pub id: Id,
@@ -164,3 +166,12 @@ pub struct DeleteSpace {
     /// Space ID.
     pub id: Id,
 }
+
+/// A file attached to a note in the space.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SpaceFile {
+    /// ID of the note the file is attached to.
+    pub note_id: NoteId,
+    /// Attached file.
+    pub file: File,
+}
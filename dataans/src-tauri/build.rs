@@ -16,8 +16,15 @@ fn main() {
             "search_notes",
             "export_app_data",
             "import_app_data",
+            "import_md_dir",
+            "import_telegram",
             "upload_file",
+            "import_file",
             "delete_file",
+            "thumbnail",
+            "space_files",
+            "verify_files",
+            "repair_files",
             "save_file_as",
             "gen_random_avatar",
             "pick_avatar",
@@ -25,8 +32,13 @@ fn main() {
             "profile",
             "set_sync_options",
             "full_sync",
+            "sync_usage",
             "sign_in",
             "sign_out",
+            "export_recovery_kit",
+            "import_recovery_kit",
+            "list_vaults",
+            "switch_vault",
         ]),
     ))
     .expect("Tauri app build should not fail")
@@ -0,0 +1,5 @@
+-- Content hash for files deduplication.
+
+ALTER TABLE files ADD COLUMN hash TEXT;
+
+CREATE INDEX IF NOT EXISTS files_hash_idx ON files(hash);
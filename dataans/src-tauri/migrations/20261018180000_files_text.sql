@@ -0,0 +1,7 @@
+-- Text extracted from attachments for the notes search.
+-- It is a local cache: every device extracts the text on its own, so the table is never synchronized.
+
+CREATE TABLE IF NOT EXISTS files_text(
+  file_id BLOB NOT NULL PRIMARY KEY REFERENCES files(id),
+  text TEXT NOT NULL
+);
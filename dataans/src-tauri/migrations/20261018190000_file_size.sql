@@ -0,0 +1,4 @@
+-- Size of the local file for the files integrity check.
+-- It is local data: every device saves the size of its own copy, so the column is never synchronized.
+
+ALTER TABLE files ADD COLUMN size INTEGER;
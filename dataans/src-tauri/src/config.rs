@@ -7,6 +7,7 @@ use common::{Config, Theme};
 use tauri::{AppHandle, Manager, Runtime};
 
 use super::{CONFIG_FILE_NAME, CONFIGS_DIR, FILES_DIR};
+use crate::dataans::DataansState;
 
 /// Reads the config file.
 ///
@@ -45,7 +46,18 @@ pub fn load_config_inner<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Config
 #[instrument(ret, skip(app_handle))]
 #[tauri::command]
 pub fn config<R: Runtime>(app_handle: AppHandle<R>) -> CommandResult<Config> {
-    Ok(load_config_inner(&app_handle)?)
+    let mut config = load_config_inner(&app_handle)?;
+
+    // The frontend uses the base path to locate user files. User files belong to the opened vault.
+    if let Some(state) = app_handle.try_state::<DataansState>() {
+        config.app.base_path = state
+            .active_vault_path()
+            .to_str()
+            .expect("Bro, wtf, use UTF-8 paths")
+            .to_owned();
+    }
+
+    Ok(config)
 }
 
 /// Loads the [Theme] object from the corresponding theme file.
@@ -94,12 +106,20 @@ pub fn open_config_file_folder(app_handle: AppHandle) {
     info!(?open_config_file_folder_result);
 }
 
+/// Returns the files directory of the opened vault.
+fn files_dir<R: Runtime>(app_handle: &AppHandle<R>) -> PathBuf {
+    let vault_path = match app_handle.try_state::<DataansState>() {
+        Some(state) => state.active_vault_path(),
+        None => app_handle.path().app_data_dir().unwrap_or_default(),
+    };
+
+    vault_path.join(FILES_DIR)
+}
+
 #[instrument]
 #[tauri::command]
 pub fn reveal(app_handle: AppHandle, path: PathBuf) {
-    let path_resolver = app_handle.path();
-    let files_dir = path_resolver.app_data_dir().unwrap_or_default().join(FILES_DIR);
-    let file = files_dir.join(path);
+    let file = files_dir(&app_handle).join(path);
 
     let reveal_note_file_result = opener::reveal(&file);
     info!(?reveal_note_file_result);
@@ -108,9 +128,7 @@ pub fn reveal(app_handle: AppHandle, path: PathBuf) {
 #[instrument]
 #[tauri::command]
 pub fn open(app_handle: AppHandle, path: PathBuf) {
-    let path_resolver = app_handle.path();
-    let files_dir = path_resolver.app_data_dir().unwrap_or_default().join(FILES_DIR);
-    let file = files_dir.join(path);
+    let file = files_dir(&app_handle).join(path);
 
     let open_note_file_result = opener::open(&file);
     info!(?open_note_file_result);
@@ -0,0 +1,373 @@
+//! Backup archive format.
+//!
+//! The archive is a zip file with the following entries:
+//!
+//! * `manifest.json`: the archive format version and encryption parameters. It is never encrypted.
+//! * `data.json`: the app data in the json export format ([common::export::Schema]).
+//! * `files/<name>`: note attachments and space avatars from the files directory.
+//!
+//! When the archive is protected with a passphrase, the content of every entry (except the manifest) is encrypted
+//! separately using [encrypt_data]. The encryption key is derived from the passphrase and the random salt stored in
+//! the manifest. The manifest also contains the key fingerprint, so the wrong passphrase is detected before
+//! decrypting the data.
+//!
+//! Entries that are loaded into memory (the app data and encrypted files) are limited to [MAX_IN_MEMORY_ENTRY_SIZE].
+//! Other files are streamed and limited to [MAX_FILE_ENTRY_SIZE]. The reader never trusts the declared entry size and
+//! stops reading as soon as the limit is exceeded, so a malicious archive can not exhaust the memory or the disk.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use common::profile::KdfParams;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::dataans::DataansError;
+use crate::dataans::crypto::{
+    EncryptionKey, KDF_ALGORITHM, PBKDF2_ITERATIONS, decrypt_data, derive_encryption_key, encrypt_data, generate_salt,
+    key_fingerprint,
+};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATA_ENTRY: &str = "data.json";
+const FILES_DIR_ENTRY: &str = "files/";
+
+/// Current archive format version.
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Maximum size (in bytes) of the entry that is loaded into memory.
+const MAX_IN_MEMORY_ENTRY_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Maximum size (in bytes) of the streamed file entry.
+const MAX_FILE_ENTRY_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    encryption: Option<Encryption>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Encryption {
+    kdf: KdfParams,
+    salt: String,
+    key_fingerprint: String,
+}
+
+/// Checks whether the name is a plain file name, so the file can not be written outside the files directory.
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', ':'])
+}
+
+/// Copies at most `limit` bytes from the reader. Returns an error if the reader has more data.
+fn copy_limited(reader: impl Read, writer: &mut impl Write, limit: u64) -> Result<(), DataansError> {
+    if io::copy(&mut reader.take(limit.saturating_add(1)), writer)? > limit {
+        return Err(DataansError::InvalidArchive("archive entry is too large"));
+    }
+
+    Ok(())
+}
+
+/// Writes the backup archive.
+pub struct ArchiveWriter {
+    zip: ZipWriter<File>,
+    key: Option<EncryptionKey>,
+}
+
+impl ArchiveWriter {
+    /// Creates a new archive. The archive content is encrypted if the passphrase is provided.
+    pub fn create(path: &Path, passphrase: Option<&str>) -> Result<Self, DataansError> {
+        let (key, encryption) = match passphrase {
+            Some(passphrase) => {
+                let salt = generate_salt();
+                let key = derive_encryption_key(passphrase.as_bytes(), salt.as_bytes())?;
+                let encryption = Encryption {
+                    kdf: KdfParams {
+                        algorithm: KDF_ALGORITHM.to_owned(),
+                        iterations: PBKDF2_ITERATIONS,
+                    },
+                    salt,
+                    key_fingerprint: key_fingerprint(&key),
+                };
+
+                (Some(key), Some(encryption))
+            }
+            None => (None, None),
+        };
+
+        Self::new(File::create(path)?, key, encryption)
+    }
+
+    fn new(file: File, key: Option<EncryptionKey>, encryption: Option<Encryption>) -> Result<Self, DataansError> {
+        let mut zip = ZipWriter::new(file);
+
+        zip.start_file(MANIFEST_ENTRY, SimpleFileOptions::default())?;
+        serde_json::to_writer(
+            &mut zip,
+            &Manifest {
+                version: ARCHIVE_VERSION,
+                encryption,
+            },
+        )?;
+
+        Ok(Self { zip, key })
+    }
+
+    fn options(&self, size: u64) -> SimpleFileOptions {
+        // Encrypted data can not be compressed.
+        let compression_method = if self.key.is_some() {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+
+        SimpleFileOptions::default()
+            .compression_method(compression_method)
+            .large_file(size >= u64::from(u32::MAX))
+    }
+
+    fn write_entry(&mut self, name: &str, data: &[u8]) -> Result<(), DataansError> {
+        let data = match &self.key {
+            Some(key) => encrypt_data(data, key)?,
+            None => data.to_vec(),
+        };
+        let size = u64::try_from(data.len()).expect("usize -> u64 conversion should not fail");
+        if size > MAX_IN_MEMORY_ENTRY_SIZE {
+            return Err(DataansError::InvalidArchive("archive entry is too large"));
+        }
+
+        let options = self.options(size);
+        self.zip.start_file(name, options)?;
+        self.zip.write_all(&data)?;
+
+        Ok(())
+    }
+
+    /// Writes the app data (json export schema).
+    pub fn add_data(&mut self, data: &[u8]) -> Result<(), DataansError> {
+        self.write_entry(DATA_ENTRY, data)
+    }
+
+    /// Writes the file from the files directory.
+    ///
+    /// Not encrypted files are streamed into the archive. Encrypted files are loaded into memory, because every file
+    /// is encrypted as a whole.
+    pub fn add_file(&mut self, name: &str, path: &Path) -> Result<(), DataansError> {
+        if !is_file_name(name) {
+            return Err(DataansError::InvalidArchive("invalid file name"));
+        }
+        let entry_name = format!("{FILES_DIR_ENTRY}{name}");
+
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        if self.key.is_some() {
+            if size > MAX_IN_MEMORY_ENTRY_SIZE {
+                return Err(DataansError::InvalidArchive("file is too large to be encrypted"));
+            }
+
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+
+            return self.write_entry(&entry_name, &data);
+        }
+
+        if size > MAX_FILE_ENTRY_SIZE {
+            return Err(DataansError::InvalidArchive("file is too large"));
+        }
+        let options = self.options(size);
+        self.zip.start_file(entry_name, options)?;
+        io::copy(&mut file, &mut self.zip)?;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), DataansError> {
+        self.zip.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Reads the backup archive created by [ArchiveWriter].
+pub struct ArchiveReader {
+    zip: ZipArchive<File>,
+    key: Option<EncryptionKey>,
+}
+
+impl ArchiveReader {
+    /// Opens the archive. The passphrase is required only if the archive is encrypted.
+    pub fn open(path: &Path, passphrase: Option<&str>) -> Result<Self, DataansError> {
+        let mut zip = ZipArchive::new(File::open(path)?)?;
+
+        let manifest: Manifest = match zip.by_name(MANIFEST_ENTRY) {
+            Ok(manifest) => serde_json::from_reader(manifest)?,
+            Err(zip::result::ZipError::FileNotFound) => {
+                return Err(DataansError::InvalidArchive("manifest not found"));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if manifest.version != ARCHIVE_VERSION {
+            return Err(DataansError::InvalidArchive("unsupported archive version"));
+        }
+
+        let key = match manifest.encryption {
+            Some(encryption) => {
+                if encryption.kdf.algorithm != KDF_ALGORITHM || encryption.kdf.iterations != PBKDF2_ITERATIONS {
+                    return Err(DataansError::InvalidArchive("unsupported key derivation parameters"));
+                }
+
+                let passphrase = passphrase.ok_or(DataansError::ArchivePassphraseRequired)?;
+                let key = derive_encryption_key(passphrase.as_bytes(), encryption.salt.as_bytes())?;
+                if key_fingerprint(&key) != encryption.key_fingerprint {
+                    return Err(DataansError::InvalidArchivePassphrase);
+                }
+
+                Some(key)
+            }
+            None => None,
+        };
+
+        Ok(Self { zip, key })
+    }
+
+    fn read_entry(&mut self, name: &str) -> Result<Vec<u8>, DataansError> {
+        let entry = self.zip.by_name(name)?;
+        if entry.size() > MAX_IN_MEMORY_ENTRY_SIZE {
+            return Err(DataansError::InvalidArchive("archive entry is too large"));
+        }
+
+        let mut data = Vec::new();
+        copy_limited(entry, &mut data, MAX_IN_MEMORY_ENTRY_SIZE)?;
+
+        match &self.key {
+            Some(key) => Ok(decrypt_data(&data, key)?),
+            None => Ok(data),
+        }
+    }
+
+    /// Returns the app data (json export schema).
+    pub fn data(&mut self) -> Result<Vec<u8>, DataansError> {
+        self.read_entry(DATA_ENTRY)
+    }
+
+    /// Returns names of the files in the archive.
+    pub fn file_names(&self) -> Vec<String> {
+        self.zip
+            .file_names()
+            .filter_map(|name| name.strip_prefix(FILES_DIR_ENTRY))
+            .filter(|name| is_file_name(name))
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+
+    /// Extracts the file into the destination path. Existing files are never overwritten.
+    pub fn extract_file(&mut self, name: &str, destination: &Path) -> Result<(), DataansError> {
+        let entry_name = format!("{FILES_DIR_ENTRY}{name}");
+        let mut file = OpenOptions::new().write(true).create_new(true).open(destination)?;
+
+        let result = if self.key.is_some() {
+            self.read_entry(&entry_name).and_then(|data| Ok(file.write_all(&data)?))
+        } else {
+            self.zip
+                .by_name(&entry_name)
+                .map_err(DataansError::from)
+                .and_then(|entry| {
+                    let size = entry.size();
+                    if size > MAX_FILE_ENTRY_SIZE {
+                        return Err(DataansError::InvalidArchive("archive entry is too large"));
+                    }
+
+                    // The declared size can not be trusted, so the entry is also read up to it.
+                    copy_limited(entry, &mut file, size)
+                })
+        };
+
+        if result.is_err() {
+            drop(file);
+            if let Err(err) = fs::remove_file(destination) {
+                warn!(?err, ?destination, "Failed to remove partially extracted file");
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use uuid::Uuid;
+    use zip::ZipArchive;
+
+    use super::{ArchiveReader, ArchiveWriter, Encryption, KdfParams, copy_limited, is_file_name};
+    use crate::dataans::crypto::{EncryptionKey, KDF_ALGORITHM, PBKDF2_ITERATIONS, key_fingerprint};
+
+    fn round_trip(key: Option<EncryptionKey>) {
+        let dir = std::env::temp_dir().join(format!("dataans-archive-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let source = dir.join("source.txt");
+        fs::write(&source, b"attachment content").unwrap();
+        let archive_path = dir.join("backup.zip");
+
+        let encryption = key.as_ref().map(|key| Encryption {
+            kdf: KdfParams {
+                algorithm: KDF_ALGORITHM.to_owned(),
+                iterations: PBKDF2_ITERATIONS,
+            },
+            salt: "salt".to_owned(),
+            key_fingerprint: key_fingerprint(key),
+        });
+        let mut writer = ArchiveWriter::new(File::create(&archive_path).unwrap(), key, encryption).unwrap();
+        writer.add_data(br#"{"version":"V1","data":[]}"#).unwrap();
+        writer.add_file("a1_note.txt", &source).unwrap();
+        assert!(writer.add_file("../escape.txt", &source).is_err());
+        writer.finish().unwrap();
+
+        let mut reader = ArchiveReader {
+            zip: ZipArchive::new(File::open(&archive_path).unwrap()).unwrap(),
+            key,
+        };
+        assert_eq!(reader.data().unwrap(), br#"{"version":"V1","data":[]}"#);
+        assert_eq!(reader.file_names(), vec!["a1_note.txt".to_owned()]);
+
+        let destination = dir.join("restored.txt");
+        reader.extract_file("a1_note.txt", &destination).unwrap();
+        assert_eq!(fs::read(&destination).unwrap(), b"attachment content");
+        // Existing files are not overwritten.
+        assert!(reader.extract_file("a1_note.txt", &destination).is_err());
+        assert!(destination.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plain_archive() {
+        round_trip(None);
+    }
+
+    #[test]
+    fn encrypted_archive() {
+        round_trip(Some((*b"oeifvncpfiejnvdjpvnwifvj12345678").into()));
+    }
+
+    #[test]
+    fn entry_size_limit() {
+        let mut data = Vec::new();
+        copy_limited(b"12345".as_slice(), &mut data, 5).unwrap();
+        assert_eq!(data, b"12345");
+
+        assert!(copy_limited(b"123456".as_slice(), &mut Vec::new(), 5).is_err());
+    }
+
+    #[test]
+    fn file_names() {
+        assert!(is_file_name("54d49bda_photo.png"));
+        assert!(!is_file_name(".."));
+        assert!(!is_file_name("nested/file.png"));
+        assert!(!is_file_name("C:file.png"));
+    }
+}
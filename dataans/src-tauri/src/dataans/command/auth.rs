@@ -1,6 +1,6 @@
 use common::error::{CommandResult, CommandResultEmpty};
 use common::event::{USER_CONTEXT_EVENT, UserContextEvent};
-use common::profile::{Sync, SyncMode, UserContext, UserProfile};
+use common::profile::{AuthScheme, Sync, SyncMode, UserContext, UserProfile};
 use phraze::cli::ListChoice;
 use phraze::generate_a_passphrase;
 use tauri::{AppHandle, Emitter, Manager, Runtime, State};
@@ -8,7 +8,11 @@ use url::Url;
 use uuid::Uuid;
 use web_api_types::User;
 
-use crate::dataans::crypto::{EncryptionKey, derive_encryption_key, hash_encryption_key, verify_encryption_key_hash};
+use crate::dataans::command::sync::spawn_full_sync;
+use crate::dataans::crypto::{
+    EncryptionKey, derive_encryption_key, hash_encryption_key, key_fingerprint as encryption_key_fingerprint,
+    verify_encryption_key_hash,
+};
 use crate::dataans::sync::SyncError;
 use crate::dataans::sync::client::Client;
 use crate::dataans::{DataansError, DataansState};
@@ -21,11 +25,13 @@ pub fn emit_user_context<R: Runtime>(app: &AppHandle<R>, user_context: UserConte
 
 #[tauri::command]
 pub async fn profile(state: State<'_, DataansState>) -> CommandResult<Option<UserContext>> {
+    let state = state.vault();
     Ok(state.web_service.user_context().await?)
 }
 
 #[tauri::command]
 pub async fn sign_out<R: Runtime>(app: AppHandle<R>, state: State<'_, DataansState>) -> CommandResultEmpty {
+    let state = state.vault();
     state.web_service.sign_out().await?;
 
     app.emit(USER_CONTEXT_EVENT, UserContextEvent::SignedOut)
@@ -48,10 +54,12 @@ pub async fn sign_in<R: Runtime>(
     url: Url,
     password: Option<String>,
     salt: Option<String>,
+    auth_scheme: Option<AuthScheme>,
+    key_fingerprint: Option<String>,
 ) -> CommandResultEmpty {
-    trace!(?token, "Setting CF token");
+    let state = state.vault();
 
-    let (secret_key, salt, sync_config) = match (password, salt) {
+    let (secret_key, salt, sync_config, auth_scheme) = match (password, salt) {
         (Some(password), Some(salt)) => {
             // The user wants to sign in on a new device.
 
@@ -66,18 +74,22 @@ pub async fn sign_in<R: Runtime>(
                     url: url.into(),
                     mode: SyncMode::Manual,
                 },
+                // The CF-Auth window does not pass the auth scheme.
+                auth_scheme.unwrap_or_default(),
             )
         }
         (None, None) => {
             // The user wants to re-authenticate (previous session token is expired).
+            // The CF-Auth window does not pass the auth scheme, so the scheme of the signed-in user is used.
 
             let UserProfile {
                 auth_token: _,
+                auth_scheme,
                 secret_key,
                 sync_config,
                 salt,
             } = state.web_service.load_user_profile().await?;
-            (secret_key, salt, sync_config)
+            (secret_key, salt, sync_config, auth_scheme)
         }
         (Some(password), None) => {
             // The very first sign-in. Basically, it is a local sign up.
@@ -96,17 +108,34 @@ pub async fn sign_in<R: Runtime>(
                     url: url.into(),
                     mode: SyncMode::Manual,
                 },
+                auth_scheme.unwrap_or_default(),
             )
         }
         (None, Some(_salt)) => {
             return Err(DataansError::InvalidCredentials("salt present, but password does not").into());
         }
     };
+    trace!(?token, ?auth_scheme, "Setting auth token");
     let auth_token = token.into();
     let encryption_key =
         EncryptionKey::try_from(secret_key.as_ref().as_slice()).expect("secret key length is always correct");
 
-    let client = Client::new(sync_config.url.as_ref().clone(), encryption_key, &auth_token).map_err(|err| {
+    // The key fingerprint is provided when the user signs in using the recovery kit.
+    if let Some(key_fingerprint) = key_fingerprint
+        && key_fingerprint != encryption_key_fingerprint(&encryption_key)
+    {
+        return Err(
+            DataansError::InvalidCredentials("password does not match the recovery kit key fingerprint").into(),
+        );
+    }
+
+    let client = Client::new(
+        sync_config.url.as_ref().clone(),
+        encryption_key,
+        &auth_token,
+        auth_scheme,
+    )
+    .map_err(|err| {
         error!(?err, "Failed to create sync client");
         DataansError::from(err)
     })?;
@@ -167,6 +196,7 @@ pub async fn sign_in<R: Runtime>(
 
     let profile = UserProfile {
         auth_token,
+        auth_scheme,
         salt,
         secret_key,
         sync_config: sync_config.clone(),
@@ -174,14 +204,26 @@ pub async fn sign_in<R: Runtime>(
 
     state.web_service.authorize(profile).await?;
 
-    emit_user_context(&app, UserContext { sync_config })?;
+    emit_user_context(
+        &app,
+        UserContext {
+            sync_config,
+            auth_scheme,
+        },
+    )?;
 
     if let Some(window) = app.webview_windows().get(crate::window::CF_WINDOW_TITLE) {
         window.close().map_err(DataansError::from)?;
         window.destroy().map_err(DataansError::from)?;
-    } else {
+    } else if auth_scheme == AuthScheme::CloudflareAccess {
         warn!("CF-Auth windows not found.");
     }
 
+    if state.web_service.take_interrupted_sync() {
+        info!("Resuming the interrupted sync...");
+
+        spawn_full_sync(app, &state)?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,70 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use common::DEFAULT_SPACE_AVATAR_PATH;
+use common::export::{ArchiveExportOption, Schema, SchemaV2};
+use uuid::Uuid;
+
+use crate::dataans::DataansError;
+use crate::dataans::archive::ArchiveWriter;
+use crate::dataans::command::export::json::schema_v2;
+use crate::dataans::db::OperationDb;
+
+/// Returns names of the files (note attachments and space avatars) in the app data.
+///
+/// Deleted files are not restored during the import, so they are not exported.
+fn referenced_files(schema_v2: &SchemaV2) -> BTreeSet<String> {
+    schema_v2
+        .files
+        .iter()
+        .filter(|file| !file.is_deleted)
+        // The default avatar is the app asset.
+        .filter(|file| file.path != DEFAULT_SPACE_AVATAR_PATH)
+        .filter_map(|file| Path::new(&file.path).file_name().and_then(|name| name.to_str()))
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+pub async fn export<O: OperationDb>(
+    option: &ArchiveExportOption,
+    backups_dir: &Path,
+    operation_db: &O,
+    files_path: &Path,
+) -> Result<(), DataansError> {
+    let schema = schema_v2(operation_db).await?;
+
+    let archive_path = backups_dir.join(format!("dataans-backup-{}.zip", Uuid::new_v4()));
+    let files = referenced_files(&schema);
+    let data = serde_json::to_vec(&Schema::V2(schema))?;
+
+    // The key derivation and the zip I/O are blocking.
+    let passphrase = option.passphrase.clone();
+    let files_path = files_path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_archive(&archive_path, passphrase.as_deref(), &data, files, &files_path))
+        .await?
+}
+
+fn write_archive(
+    archive_path: &Path,
+    passphrase: Option<&str>,
+    data: &[u8],
+    files: BTreeSet<String>,
+    files_path: &Path,
+) -> Result<(), DataansError> {
+    let mut archive = ArchiveWriter::create(archive_path, passphrase)?;
+    archive.add_data(data)?;
+
+    for name in files {
+        let path = files_path.join(&name);
+
+        // Files can be not downloaded from the sync server yet.
+        if !path.is_file() {
+            warn!(?name, "File does not exist locally and can not be exported");
+            continue;
+        }
+
+        archive.add_file(&name, &path)?;
+    }
+
+    archive.finish()
+}
@@ -1,46 +1,171 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
 
-use common::export::{Schema, SchemaV1, SchemaVersion, Space};
+use common::export::{FileV2, NoteV2, Schema, SchemaV1, SchemaV2, SchemaVersion, Space, SpaceV2};
 use common::space::OwnedSpace;
 use futures::future::try_join_all;
 use uuid::Uuid;
 
 use crate::dataans::DataansError;
-use crate::dataans::db::Db;
+use crate::dataans::db::model::{File as FileModel, Note as NoteModel, Space as SpaceModel};
+use crate::dataans::db::{Db, OperationDb};
 use crate::dataans::service::note::NoteService;
 
-pub async fn export_v1<D: Db>(
-    backups_dir: &Path,
-    spaces: Vec<OwnedSpace>,
+/// Collects the app data in the json export format.
+///
+/// Spaces and notes are sorted by the creation date, so exporting the same data always gives the same file.
+pub async fn schema_v1<D: Db>(
+    mut spaces: Vec<OwnedSpace>,
     note_service: &NoteService<D>,
-) -> Result<(), DataansError> {
-    let backup_file_path = backups_dir.join(format!("dataans-backup-{}.json", Uuid::new_v4()));
-    let backup_file = File::create(&backup_file_path)?;
+) -> Result<SchemaV1, DataansError> {
+    spaces.sort_by_key(|space| (*space.created_at.as_ref(), space.id.inner()));
 
-    let data = Schema::V1(SchemaV1 {
+    Ok(SchemaV1 {
         data: try_join_all(spaces.into_iter().map(|space| async move {
             let space_id = space.id;
-            Result::<Space, DataansError>::Ok(Space {
-                space,
-                notes: note_service.space_notes(space_id).await?,
-            })
+            let mut notes = note_service.space_notes(space_id).await?;
+            notes.sort_by_key(|note| (*note.created_at.as_ref(), note.id.inner()));
+
+            Result::<Space, DataansError>::Ok(Space { space, notes })
         }))
         .await?,
-    });
+    })
+}
 
-    serde_json::to_writer(backup_file, &data)?;
+/// Collects all local records (including deleted ones) in the json export format V2.
+///
+/// Records are sorted by the creation date, so exporting the same data always gives the same file.
+pub async fn schema_v2<O: OperationDb>(operation_db: &O) -> Result<SchemaV2, DataansError> {
+    let mut notes_files: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (note_id, file_id) in operation_db.notes_files().await? {
+        notes_files.entry(note_id).or_default().push(file_id);
+    }
+
+    let mut spaces = operation_db
+        .all_spaces()
+        .await?
+        .into_iter()
+        .map(|space| {
+            let SpaceModel {
+                id,
+                name,
+                avatar_id,
+                created_at,
+                updated_at,
+                is_deleted,
+            } = space;
+
+            SpaceV2 {
+                id,
+                name,
+                avatar_id,
+                created_at,
+                updated_at,
+                is_deleted,
+            }
+        })
+        .collect::<Vec<_>>();
+    spaces.sort_by_key(|space| (space.created_at, space.id));
+
+    let mut notes = operation_db
+        .all_notes()
+        .await?
+        .into_iter()
+        .map(|note| {
+            let NoteModel {
+                id,
+                text,
+                created_at,
+                updated_at,
+                space_id,
+                is_deleted,
+            } = note;
+
+            let mut files = notes_files.remove(&id).unwrap_or_default();
+            files.sort();
+
+            NoteV2 {
+                id,
+                text,
+                space_id,
+                created_at,
+                updated_at,
+                is_deleted,
+                files,
+            }
+        })
+        .collect::<Vec<_>>();
+    notes.sort_by_key(|note| (note.created_at, note.id));
+
+    let mut files = operation_db
+        .all_files()
+        .await?
+        .into_iter()
+        .map(|file| {
+            let FileModel {
+                id,
+                name,
+                path,
+                created_at,
+                updated_at,
+                is_deleted,
+                is_uploaded,
+                hash,
+                size: _,
+            } = file;
+
+            FileV2 {
+                id,
+                name,
+                path,
+                created_at,
+                updated_at,
+                is_deleted,
+                is_uploaded,
+                hash,
+            }
+        })
+        .collect::<Vec<_>>();
+    files.sort_by_key(|file| (file.created_at, file.id));
+
+    Ok(SchemaV2 { spaces, notes, files })
+}
+
+fn write_schema(backups_dir: &Path, schema: &Schema) -> Result<(), DataansError> {
+    let backup_file_path = backups_dir.join(format!("dataans-backup-{}.json", Uuid::new_v4()));
+    let backup_file = File::create(&backup_file_path)?;
+
+    serde_json::to_writer(backup_file, schema)?;
 
     Ok(())
 }
 
-pub async fn export<D: Db>(
+pub async fn export_v1<D: Db>(
+    backups_dir: &Path,
+    spaces: Vec<OwnedSpace>,
+    note_service: &NoteService<D>,
+) -> Result<(), DataansError> {
+    let data = schema_v1(spaces, note_service).await?;
+
+    write_schema(backups_dir, &Schema::V1(data))
+}
+
+pub async fn export_v2<O: OperationDb>(backups_dir: &Path, operation_db: &O) -> Result<(), DataansError> {
+    let data = schema_v2(operation_db).await?;
+
+    write_schema(backups_dir, &Schema::V2(data))
+}
+
+pub async fn export<D: Db, O: OperationDb>(
     version: SchemaVersion,
     backups_dir: &Path,
     spaces: Vec<OwnedSpace>,
     note_service: &NoteService<D>,
+    operation_db: &O,
 ) -> Result<(), DataansError> {
     match version {
         SchemaVersion::V1 => export_v1(backups_dir, spaces, note_service).await,
+        SchemaVersion::V2 => export_v2(backups_dir, operation_db).await,
     }
 }
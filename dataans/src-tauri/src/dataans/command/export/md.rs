@@ -10,6 +10,7 @@ use time::OffsetDateTime;
 use time::macros::format_description;
 use uuid::Uuid;
 
+use super::obsidian;
 use crate::dataans::DataansError;
 use crate::dataans::db::Db;
 use crate::dataans::service::note::NoteService;
@@ -139,6 +140,7 @@ pub async fn export<D: Db>(
     backups_dir: &Path,
     spaces: Vec<OwnedSpace>,
     note_service: &NoteService<D>,
+    files_path: &Path,
 ) -> Result<(), DataansError> {
     match notes_export_option {
         NotesExportOption::OneFile => {
@@ -171,6 +173,7 @@ pub async fn export<D: Db>(
             }))
             .await?;
         }
+        NotesExportOption::Obsidian => obsidian::export(backups_dir, spaces, note_service, files_path).await?,
     }
 
     Ok(())
@@ -1,5 +1,7 @@
-mod json;
+mod archive;
+pub mod json;
 mod md;
+mod obsidian;
 
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -11,7 +13,7 @@ use time::OffsetDateTime;
 use time::macros::format_description;
 
 use crate::BACKUPS_DIR;
-use crate::dataans::{DataansError, DataansState};
+use crate::dataans::{DataansError, DataansState, VaultState};
 
 fn prepare_backups_dir(base_path: &Path) -> Result<PathBuf, DataansError> {
     let backups_dir = base_path.join(BACKUPS_DIR);
@@ -34,16 +36,39 @@ fn prepare_backups_dir(base_path: &Path) -> Result<PathBuf, DataansError> {
     Ok(backups_dir)
 }
 
-async fn export_data(state: State<'_, DataansState>, export_config: DataExportConfig) -> Result<PathBuf, DataansError> {
+async fn export_data(state: &VaultState, export_config: DataExportConfig) -> Result<PathBuf, DataansError> {
     let backups_dir = prepare_backups_dir(&state.base_path)?;
     let spaces = state.space_service.spaces().await?;
 
     match export_config {
         DataExportConfig::Md(notes_export_option) => {
-            md::export(&notes_export_option, &backups_dir, spaces, &state.note_service).await?
+            md::export(
+                &notes_export_option,
+                &backups_dir,
+                spaces,
+                &state.note_service,
+                &state.files_path,
+            )
+            .await?
         }
         DataExportConfig::Json(schema_version) => {
-            json::export(schema_version, &backups_dir, spaces, &state.note_service).await?
+            json::export(
+                schema_version,
+                &backups_dir,
+                spaces,
+                &state.note_service,
+                state.operation_logger.as_ref(),
+            )
+            .await?
+        }
+        DataExportConfig::Archive(archive_option) => {
+            archive::export(
+                &archive_option,
+                &backups_dir,
+                state.operation_logger.as_ref(),
+                &state.files_path,
+            )
+            .await?
         }
     }
 
@@ -56,5 +81,6 @@ pub async fn export_app_data(
     state: State<'_, DataansState>,
     export_config: DataExportConfig,
 ) -> CommandResult<PathBuf> {
-    Ok(export_data(state, export_config).await?)
+    let state = state.vault();
+    Ok(export_data(&state, export_config).await?)
 }
@@ -0,0 +1,383 @@
+//! Obsidian-compatible Markdown export.
+//!
+//! Every space becomes a folder and every note becomes a separate `.md` file with YAML front matter. Attachments are
+//! copied into the shared `attachments/` folder and note links are rewritten to point to the copies. The resulting
+//! directory can be opened as an Obsidian vault or in any other Markdown tool.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use common::note::{File as NoteFile, OwnedNote};
+use common::space::OwnedSpace;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use uuid::Uuid;
+
+use crate::dataans::DataansError;
+use crate::dataans::db::Db;
+use crate::dataans::service::note::NoteService;
+
+/// Directory inside the export directory with copied attachments.
+const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Maximum length (in chars) of the note file name derived from the note text.
+const MAX_TITLE_LEN: usize = 60;
+
+/// Characters that are not allowed in file names on some platforms or break Obsidian links.
+const FORBIDDEN_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|', '#', '^', '[', ']'];
+
+/// File names that are reserved for devices on Windows.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "COM¹",
+    "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²",
+    "LPT³",
+];
+
+/// Characters that must be percent-encoded in Markdown link destinations.
+const LINK_ESCAPED_CHARS: &[char] = &[' ', '%', '(', ')', '<', '>', '[', ']', '#', '?', '^', '|'];
+
+pub async fn export<D: Db>(
+    backups_dir: &Path,
+    spaces: Vec<OwnedSpace>,
+    note_service: &NoteService<D>,
+    files_path: &Path,
+) -> Result<(), DataansError> {
+    let attachments_dir = backups_dir.join(ATTACHMENTS_DIR);
+    fs::create_dir(&attachments_dir)?;
+
+    let mut exporter = Exporter {
+        files_path,
+        attachments_dir: &attachments_dir,
+        copied_attachments: HashSet::new(),
+    };
+
+    let mut space_dir_names = HashSet::from([ATTACHMENTS_DIR.to_owned()]);
+    for space in spaces {
+        let space_dir_name = unique_name(
+            sanitize_file_name(space.name.as_ref()),
+            space.id.inner(),
+            &mut space_dir_names,
+        );
+        let space_dir = backups_dir.join(space_dir_name);
+        fs::create_dir(&space_dir)?;
+
+        let mut note_names = HashSet::new();
+        for note in note_service.space_notes(space.id).await? {
+            let note_name = unique_name(note_title(note.text.as_ref()), note.id.inner(), &mut note_names);
+            let note_md = exporter.note_md(&note, &space)?;
+
+            fs::write(space_dir.join(format!("{note_name}.md")), note_md)?;
+        }
+    }
+
+    Ok(())
+}
+
+struct Exporter<'path> {
+    files_path: &'path Path,
+    attachments_dir: &'path Path,
+    /// Names of attachments that have already been copied.
+    copied_attachments: HashSet<String>,
+}
+
+impl Exporter<'_> {
+    fn note_md(&mut self, note: &OwnedNote, space: &OwnedSpace) -> Result<String, DataansError> {
+        let mut md = String::new();
+
+        md.push_str("---\n");
+        writeln!(md, "id: {}", note.id.inner()).expect("writing to String should not fail");
+        writeln!(md, "space: {}", yaml_string(space.name.as_ref())?).expect("writing to String should not fail");
+        writeln!(md, "space_id: {}", space.id.inner()).expect("writing to String should not fail");
+        writeln!(md, "created: {}", format_time(note.created_at.as_ref())?).expect("writing to String should not fail");
+        writeln!(md, "updated: {}", format_time(note.updated_at.as_ref())?).expect("writing to String should not fail");
+        md.push_str("---\n\n");
+
+        // Attachments referenced in the note text are not listed once more below the text.
+        let mut linked_files = HashSet::new();
+        let mut copy_result = Ok(());
+        let text = rewrite_links(note.text.as_ref(), |destination| {
+            if !is_local_file_name(destination) || !self.files_path.join(destination).is_file() {
+                return None;
+            }
+
+            if let Err(err) = self.copy_attachment(destination) {
+                copy_result = Err(err);
+                return None;
+            }
+            linked_files.insert(destination.to_owned());
+
+            Some(attachment_link(destination))
+        });
+        copy_result?;
+        md.push_str(text.trim_end());
+        md.push('\n');
+
+        let files = note
+            .files
+            .iter()
+            .filter(|file| !file_name(file).is_some_and(|name| linked_files.contains(name)))
+            .collect::<Vec<_>>();
+        if !files.is_empty() {
+            md.push_str("\n## Attachments\n\n");
+        }
+        for file in files {
+            let NoteFile { name, .. } = file;
+
+            match file_name(file).filter(|file_name| self.files_path.join(file_name).is_file()) {
+                Some(file_name) => {
+                    self.copy_attachment(file_name)?;
+
+                    let embed = if is_image(file_name) { "!" } else { "" };
+                    writeln!(
+                        md,
+                        "- {embed}[{}]({})",
+                        escape_link_text(name),
+                        attachment_link(file_name)
+                    )
+                    .expect("writing to String should not fail");
+                }
+                None => {
+                    warn!(?file, "Attachment file does not exist locally and can not be exported");
+
+                    writeln!(md, "- {} (the file is not downloaded)", escape_link_text(name))
+                        .expect("writing to String should not fail");
+                }
+            }
+        }
+
+        Ok(md)
+    }
+
+    fn copy_attachment(&mut self, file_name: &str) -> Result<(), DataansError> {
+        if self.copied_attachments.insert(file_name.to_owned()) {
+            fs::copy(self.files_path.join(file_name), self.attachments_dir.join(file_name))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name of the local file in the files directory.
+fn file_name(file: &NoteFile) -> Option<&str> {
+    file.path.file_name().and_then(|name| name.to_str())
+}
+
+/// Checks whether the link destination is a file name inside the files directory (e.g. a pasted image).
+fn is_local_file_name(destination: &str) -> bool {
+    !destination.is_empty()
+        && destination != "."
+        && destination != ".."
+        && !destination.contains(['/', '\\', ':', '?', '#'])
+}
+
+fn is_image(file_name: &str) -> bool {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    matches!(
+        extension.as_str(),
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "svg" | "avif"
+    )
+}
+
+/// Returns the link to the copied attachment relative to the note file.
+fn attachment_link(file_name: &str) -> String {
+    let mut link = format!("../{ATTACHMENTS_DIR}/");
+
+    for c in file_name.chars() {
+        if LINK_ESCAPED_CHARS.contains(&c) {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                write!(link, "%{byte:02X}").expect("writing to String should not fail");
+            }
+        } else {
+            link.push(c);
+        }
+    }
+
+    link
+}
+
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+/// Rewrites destinations of Markdown links and images. Fenced code blocks are left untouched.
+///
+/// `rewrite` receives the link destination and returns the new one. `None` means the link must be kept as is.
+fn rewrite_links(text: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_code_block = false;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            result.push_str(line);
+        } else if in_code_block {
+            result.push_str(line);
+        } else {
+            rewrite_line_links(line, &mut rewrite, &mut result);
+        }
+    }
+
+    result
+}
+
+fn rewrite_line_links(line: &str, rewrite: &mut impl FnMut(&str) -> Option<String>, out: &mut String) {
+    let mut rest = line;
+
+    while let Some(start) = rest.find("](") {
+        let (before, after) = rest.split_at(start + 2);
+        out.push_str(before);
+
+        let (destination_len, destination) = if let Some(wrapped) = after.strip_prefix('<') {
+            // `[text](<path with spaces.png>)`
+            match wrapped.find('>') {
+                Some(end) => (end + 2, &wrapped[..end]),
+                None => (0, ""),
+            }
+        } else {
+            let end = after
+                .find(|c: char| c == ')' || c.is_whitespace())
+                .unwrap_or(after.len());
+
+            (end, &after[..end])
+        };
+
+        match rewrite(destination) {
+            Some(new_destination) if destination_len > 0 => out.push_str(&new_destination),
+            _ => out.push_str(&after[..destination_len]),
+        }
+        rest = &after[destination_len..];
+    }
+
+    out.push_str(rest);
+}
+
+/// Derives the note file name from the first non-empty line of the note text.
+fn note_title(text: &str) -> String {
+    let title = text
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['#', '>', '-', '*', '+']).trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    let title = sanitize_file_name(&title.chars().take(MAX_TITLE_LEN).collect::<String>());
+
+    if title.is_empty() { "Untitled".to_owned() } else { title }
+}
+
+/// Replaces characters that are not allowed in file names and trims the result.
+fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if FORBIDDEN_FILE_NAME_CHARS.contains(&c) || c.is_control() {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+
+    // Hidden files are ignored by Obsidian, and trailing dots and spaces are not allowed on Windows.
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = name.trim_start_matches(['.', ' ']).trim_end_matches(['.', ' ']);
+
+    // Windows reserves device names even with extensions (`CON.md`, `NUL.tar.gz`).
+    let stem_len = name.find('.').unwrap_or(name.len());
+    let (stem, extension) = name.split_at(stem_len);
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem.trim_end()))
+    {
+        format!("{stem}_{extension}")
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Returns the name that is not used yet. File systems can be case-insensitive, so names are compared ignoring case.
+fn unique_name(name: String, id: Uuid, used_names: &mut HashSet<String>) -> String {
+    let name = if name.is_empty() { "Untitled".to_owned() } else { name };
+
+    if used_names.insert(name.to_lowercase()) {
+        return name;
+    }
+
+    let name = format!("{name} {}", id.simple());
+    used_names.insert(name.to_lowercase());
+
+    name
+}
+
+/// Formats the string as a double-quoted YAML scalar. JSON strings are valid YAML.
+fn yaml_string(value: &str) -> Result<String, DataansError> {
+    Ok(serde_json::to_string(value)?)
+}
+
+fn format_time(time: &OffsetDateTime) -> Result<String, DataansError> {
+    Ok(time.format(&Rfc3339)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use uuid::uuid;
+
+    use super::{attachment_link, note_title, rewrite_links, sanitize_file_name, unique_name};
+
+    #[test]
+    fn links_rewriting() {
+        let text =
+            "![](image.png) and [doc](<my doc.pdf> \"title\")\n[site](https://example.com)\n```\n![](image.png)\n```\n";
+
+        let rewritten = rewrite_links(text, |destination| match destination {
+            "image.png" | "my doc.pdf" => Some(attachment_link(destination)),
+            _ => None,
+        });
+
+        assert_eq!(
+            rewritten,
+            "![](../attachments/image.png) and [doc](../attachments/my%20doc.pdf \"title\")\n[site](https://example.com)\n```\n![](image.png)\n```\n"
+        );
+    }
+
+    #[test]
+    fn unclosed_link() {
+        assert_eq!(rewrite_links("[a](<b", |_| Some("c".into())), "[a](<b");
+    }
+
+    #[test]
+    fn note_titles() {
+        assert_eq!(note_title("\n# Plans: 2026/10\nbody"), "Plans 2026 10");
+        assert_eq!(note_title("..."), "Untitled");
+        assert_eq!(note_title(&"a".repeat(100)).len(), 60);
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(sanitize_file_name(" .hidden  name?. "), "hidden name");
+        assert_eq!(sanitize_file_name("name . . "), "name");
+        assert_eq!(sanitize_file_name("con"), "con_");
+        assert_eq!(sanitize_file_name("Nul.tar.gz"), "Nul_.tar.gz");
+        assert_eq!(sanitize_file_name("COM1 "), "COM1_");
+        assert_eq!(sanitize_file_name("Console"), "Console");
+
+        let id = uuid!("54d49bda-644e-44a9-a1ad-4a8fa5f368a5");
+        let mut used = HashSet::from(["attachments".to_owned()]);
+        assert_eq!(unique_name("Notes".into(), id, &mut used), "Notes");
+        assert_eq!(
+            unique_name("notes".into(), id, &mut used),
+            "notes 54d49bda644e44a9a1ad4a8fa5f368a5"
+        );
+        assert_eq!(
+            unique_name("Attachments".into(), id, &mut used),
+            "Attachments 54d49bda644e44a9a1ad4a8fa5f368a5"
+        );
+    }
+}
@@ -1,33 +1,198 @@
+use std::path::PathBuf;
+
 use common::error::{CommandError, CommandResult, CommandResultEmpty};
-use common::note::File;
+use common::event::{DATA_EVENT, DataEvent, FILE_IMPORT_PROGRESS_EVENT, FileImportProgress};
+use common::note::{CreateNoteOwned, File, FileRepair, FilesReport};
+use common::space::{Avatar, CreateSpaceOwned, Id as SpaceId, SpaceFile};
+use common::{DEFAULT_SPACE_AVATAR_ID, DEFAULT_SPACE_AVATAR_PATH};
 use futures::channel::oneshot;
-use tauri::{AppHandle, Runtime, State};
+use tauri::{AppHandle, Emitter, Runtime, State};
 use tauri_plugin_dialog::{DialogExt, FilePath};
 use uuid::Uuid;
 
-use crate::dataans::DataansState;
+use crate::dataans::command::sync::sync_client;
+use crate::dataans::{DataansError, DataansState, VaultState};
+
+/// Name of the space that contains notes with recovered files.
+const RECOVERED_SPACE_NAME: &str = "Recovered";
 
 #[instrument(ret, skip(state, data))]
 #[tauri::command]
 pub async fn upload_file(state: State<'_, DataansState>, id: Uuid, name: String, data: Vec<u8>) -> CommandResult<File> {
+    let state = state.vault();
     Ok(state.file_service.upload_file(id, name, &data).await?)
 }
 
+/// Imports the file from the local file system.
+///
+/// Unlike [upload_file], the file content is not sent over IPC. The import progress is reported using
+/// [FILE_IMPORT_PROGRESS_EVENT] events.
+#[instrument(ret, skip(app, state))]
+#[tauri::command]
+pub async fn import_file<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, DataansState>,
+    id: Uuid,
+    path: PathBuf,
+) -> CommandResult<File> {
+    let state = state.vault();
+    let file_id = id.into();
+
+    Ok(state
+        .file_service
+        .import_file(id, &path, |imported, total| {
+            if let Err(err) = app.emit(
+                FILE_IMPORT_PROGRESS_EVENT,
+                FileImportProgress {
+                    file_id,
+                    imported,
+                    total,
+                },
+            ) {
+                warn!(?err, "Failed to emit the file import progress event");
+            }
+        })
+        .await?)
+}
+
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn delete_file(state: State<'_, DataansState>, id: Uuid) -> CommandResultEmpty {
+    let state = state.vault();
     Ok(state.file_service.delete_file(id).await?)
 }
 
+#[instrument(ret, skip(state))]
+#[tauri::command]
+pub async fn thumbnail(state: State<'_, DataansState>, file_id: Uuid, size: u32) -> CommandResult<Option<PathBuf>> {
+    let state = state.vault();
+    Ok(state.file_service.thumbnail(file_id, size).await?)
+}
+
+/// Returns files attached to the notes of the space.
+#[instrument(ret, skip(state))]
+#[tauri::command]
+pub async fn space_files(state: State<'_, DataansState>, space_id: SpaceId) -> CommandResult<Vec<SpaceFile>> {
+    let state = state.vault();
+    Ok(state.file_service.space_files(space_id).await?)
+}
+
+/// Checks the integrity of local files.
+#[instrument(ret, skip(state))]
+#[tauri::command]
+pub async fn verify_files(state: State<'_, DataansState>) -> CommandResult<FilesReport> {
+    let state = state.vault();
+    Ok(state.file_service.verify_files().await?)
+}
+
+/// Applies the repairs and returns the updated files integrity report.
+#[instrument(ret, skip(app, state))]
+#[tauri::command]
+pub async fn repair_files<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, DataansState>,
+    repairs: Vec<FileRepair>,
+) -> CommandResult<FilesReport> {
+    let state = state.vault();
+    Ok(apply_file_repairs(&app, &state, repairs).await?)
+}
+
+async fn apply_file_repairs<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &VaultState,
+    repairs: Vec<FileRepair>,
+) -> Result<FilesReport, DataansError> {
+    // The sync client is created only if some files need to be downloaded.
+    let mut client = None;
+    let mut recovered_files = Vec::new();
+
+    for repair in repairs {
+        match repair {
+            FileRepair::Redownload(file_id) => {
+                if client.is_none() {
+                    client = Some(sync_client(&state.web_service)?);
+                }
+                let client = client.as_ref().expect("sync client should be initialized");
+
+                let file = state.file_service.redownload_file(client, file_id.into()).await?;
+                app.emit(DATA_EVENT, DataEvent::FileStatusUpdated(file.id, file.status))?;
+            }
+            FileRepair::Recover(path) => recovered_files.push(state.file_service.recover_file(&path).await?),
+            FileRepair::Delete(path) => state.file_service.delete_orphaned_file(&path).await?,
+        }
+    }
+
+    if !recovered_files.is_empty() {
+        add_recovered_note(app, state, recovered_files).await?;
+    }
+
+    state.file_service.verify_files().await
+}
+
+/// Attaches recovered files to a new note in the "Recovered" space. The space is created if it does not exist.
+async fn add_recovered_note<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &VaultState,
+    files: Vec<File>,
+) -> Result<(), DataansError> {
+    let existing_space = state
+        .space_service
+        .spaces()
+        .await?
+        .into_iter()
+        .find(|space| space.name.as_ref() == RECOVERED_SPACE_NAME);
+    let space_id = match existing_space {
+        Some(space) => space.id,
+        None => {
+            let space = state
+                .space_service
+                .create_space(CreateSpaceOwned {
+                    id: Uuid::new_v4().into(),
+                    name: RECOVERED_SPACE_NAME.into(),
+                    avatar: Avatar::new(DEFAULT_SPACE_AVATAR_ID.into(), DEFAULT_SPACE_AVATAR_PATH),
+                })
+                .await?;
+            let space_id = space.id;
+
+            app.emit(DATA_EVENT, DataEvent::SpaceAdded(space))?;
+
+            space_id
+        }
+    };
+
+    let text = files.iter().fold(
+        String::from("Files recovered by the files integrity check:\n"),
+        |mut text, file| {
+            text.push_str(&format!("\n- {}", file.name));
+            text
+        },
+    );
+
+    let note = state
+        .note_service
+        .create_note(CreateNoteOwned {
+            id: Uuid::new_v4().into(),
+            text: text.into(),
+            space_id,
+            files,
+        })
+        .await?;
+    app.emit(DATA_EVENT, DataEvent::NoteAdded(note))?;
+
+    Ok(())
+}
+
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn gen_random_avatar(state: State<'_, DataansState>) -> CommandResult<File> {
+    let state = state.vault();
     Ok(state.file_service.gen_random_avatar().await?)
 }
 
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn pick_avatar<R: Runtime>(app: AppHandle<R>, state: State<'_, DataansState>) -> CommandResult<Option<File>> {
+    let state = state.vault();
     let (tx, rx) = oneshot::channel();
 
     tauri::async_runtime::spawn(async move {
@@ -74,6 +239,7 @@ pub async fn pick_avatar<R: Runtime>(app: AppHandle<R>, state: State<'_, Dataans
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn handle_clipboard_image(state: State<'_, DataansState>) -> CommandResult<File> {
+    let state = state.vault();
     Ok(state.file_service.handle_clipboard_image().await?)
 }
 
@@ -83,6 +249,7 @@ pub async fn save_file_as<R: Runtime>(
     state: State<'_, DataansState>,
     file: File,
 ) -> CommandResultEmpty {
+    let state = state.vault();
     let (tx, rx) = oneshot::channel();
 
     let file_name = file.name.clone();
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use common::export::Schema;
+use tauri::{Emitter, Runtime};
+
+use crate::dataans::archive::ArchiveReader;
+use crate::dataans::command::import::json::import_schema;
+use crate::dataans::db::{Db, OperationDb};
+use crate::dataans::service::note::NoteService;
+use crate::dataans::service::space::SpaceService;
+use crate::dataans::{DataansError, FileService};
+
+pub async fn import<D: Db, O: OperationDb, R: Runtime, E: Emitter<R>>(
+    emitter: &E,
+    archive_path: &Path,
+    passphrase: Option<&str>,
+    files_path: &Path,
+    file_service: &FileService<D>,
+    space_service: &SpaceService<D>,
+    note_service: &NoteService<D>,
+    operation_db: &O,
+) -> Result<(), DataansError> {
+    // The key derivation and the zip I/O are blocking.
+    let archive_path = archive_path.to_path_buf();
+    let passphrase = passphrase.map(ToOwned::to_owned);
+    let files_path = files_path.to_path_buf();
+    let data = tokio::task::spawn_blocking(move || extract_archive(&archive_path, passphrase.as_deref(), &files_path))
+        .await??;
+
+    let schema: Schema = serde_json::from_slice(&data)?;
+
+    import_schema(schema, emitter, file_service, space_service, note_service, operation_db).await
+}
+
+/// Extracts the archive files into the files directory and returns the app data.
+fn extract_archive(archive_path: &Path, passphrase: Option<&str>, files_path: &Path) -> Result<Vec<u8>, DataansError> {
+    let mut archive = ArchiveReader::open(archive_path, passphrase)?;
+
+    // Files must be restored before they are registered, so notes never point to missing files.
+    for name in archive.file_names() {
+        let destination = files_path.join(&name);
+
+        if destination.exists() {
+            debug!(?name, "File already exists");
+            continue;
+        }
+
+        archive.extract_file(&name, &destination)?;
+    }
+
+    archive.data()
+}
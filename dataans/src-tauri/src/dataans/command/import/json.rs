@@ -1,15 +1,18 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::path::Path;
 
+use common::DEFAULT_SPACE_AVATAR_PATH;
 use common::event::{DATA_EVENT, DataEvent};
-use common::export::{Schema, SchemaV1};
-use common::note::{CreateNoteOwned, Note, UpdateNote};
-use common::space::{CreateSpaceOwned, Space, UpdateSpace};
+use common::export::{FileV2, NoteV2, Schema, SchemaV2, SpaceV2};
+use common::note::{CreateNoteOwned, UpdateNote};
+use common::space::{Avatar, CreateSpaceOwned, DeleteSpace, UpdateSpace};
 use futures::future::try_join_all;
 use serde_json;
 use tauri::{Emitter, Runtime};
+use uuid::Uuid;
 
-use crate::dataans::db::Db;
+use crate::dataans::db::{Db, OperationDb};
 use crate::dataans::service::note::{NoteService, NoteServiceError};
 use crate::dataans::service::space::{SpaceService, SpaceServiceError};
 use crate::dataans::{DataansError, FileService};
@@ -20,119 +23,493 @@ fn emit_data_event<R: Runtime, E: Emitter<R>>(emitter: &E, event: DataEvent) ->
     Ok(())
 }
 
-pub async fn import_v1<D: Db, R: Runtime, E: Emitter<R>>(
-    schema_v1: SchemaV1,
+/// Imports the app data of any schema version. Older versions are upgraded to the latest one first.
+pub async fn import_schema<D: Db, O: OperationDb, R: Runtime, E: Emitter<R>>(
+    schema: Schema,
     emitter: &E,
     file_service: &FileService<D>,
     space_service: &SpaceService<D>,
     note_service: &NoteService<D>,
+    operation_db: &O,
 ) -> Result<(), DataansError> {
-    let space_futures = schema_v1.data.into_iter().map(|space_data| async move {
-        let space = space_data.space;
-        let notes = space_data.notes;
-
-        let Space {
-            id,
-            name,
-            created_at: _,
-            updated_at,
-            avatar,
-        } = space;
-
-        match space_service.space_by_id(space.id).await {
-            Err(SpaceServiceError::NotFound) => {
+    import_v2(
+        schema.into_latest(),
+        emitter,
+        file_service,
+        space_service,
+        note_service,
+        operation_db,
+    )
+    .await
+}
+
+/// Imports the app data.
+///
+/// New items are created with their exported dates. Existing items are updated only if the imported ones are newer.
+/// Deleted items are deleted locally if the local ones are older than the deletion, or created as deleted records
+/// if they do not exist locally. Locally deleted items and deleted files are never restored.
+pub async fn import_v2<D: Db, O: OperationDb, R: Runtime, E: Emitter<R>>(
+    schema_v2: SchemaV2,
+    emitter: &E,
+    file_service: &FileService<D>,
+    space_service: &SpaceService<D>,
+    note_service: &NoteService<D>,
+    operation_db: &O,
+) -> Result<(), DataansError> {
+    let SchemaV2 { spaces, notes, files } = schema_v2;
+
+    // All local records including deleted ones.
+    let local_spaces = operation_db
+        .all_spaces()
+        .await?
+        .into_iter()
+        .map(|space| space.id)
+        .collect::<HashSet<_>>();
+    let local_notes = operation_db
+        .all_notes()
+        .await?
+        .into_iter()
+        .map(|note| note.id)
+        .collect::<HashSet<_>>();
+    let local_files = operation_db
+        .all_files()
+        .await?
+        .into_iter()
+        .map(|file| (file.id, file.is_deleted))
+        .collect::<HashMap<_, _>>();
+
+    // Files must be registered before spaces and notes, because they refer to them.
+    try_join_all(
+        files
+            .iter()
+            .filter(|file| !local_files.contains_key(&file.id))
+            .map(|file| async move {
+                file_service.register_exported_file(file).await?;
+
+                if !file.is_deleted {
+                    emit_data_event(
+                        emitter,
+                        DataEvent::FileAdded(file_service.file_by_id(file.id.into()).await?),
+                    )?;
+                }
+
+                Ok::<(), DataansError>(())
+            }),
+    )
+    .await?;
+
+    let files = files
+        .into_iter()
+        .filter(|file| !file.is_deleted && local_files.get(&file.id) != Some(&true))
+        .map(|file| (file.id, file))
+        .collect::<HashMap<_, _>>();
+
+    try_join_all(spaces.into_iter().map(|space| {
+        let is_local = local_spaces.contains(&space.id);
+
+        import_space(space, is_local, &files, emitter, file_service, space_service)
+    }))
+    .await?;
+
+    try_join_all(notes.into_iter().map(|note| {
+        let is_local = local_notes.contains(&note.id);
+
+        import_note(note, is_local, &files, emitter, file_service, note_service)
+    }))
+    .await?;
+
+    Ok(())
+}
+
+/// Imports the space.
+///
+/// `is_local` tells whether the space exists in the local database (including deleted spaces).
+async fn import_space<D: Db, R: Runtime, E: Emitter<R>>(
+    space: SpaceV2,
+    is_local: bool,
+    files: &HashMap<Uuid, FileV2>,
+    emitter: &E,
+    file_service: &FileService<D>,
+    space_service: &SpaceService<D>,
+) -> Result<(), DataansError> {
+    let local_space = match space_service.space_by_id(space.id.into()).await {
+        Ok(local_space) => Some(local_space),
+        Err(SpaceServiceError::NotFound) => None,
+        Err(err) => return Err(DataansError::from(err)),
+    };
+
+    if space.is_deleted {
+        match local_space {
+            Some(local_space) if *local_space.updated_at.as_ref() < space.updated_at => {
+                space_service.delete_space(DeleteSpace { id: space.id.into() }).await?;
+
+                emit_data_event(emitter, DataEvent::SpaceDeleted(space.id.into()))?;
+            }
+            None if !is_local => space_service.create_deleted_space(&space).await?,
+            _ => {}
+        }
+
+        return Ok(());
+    }
+
+    if local_space.is_none() && is_local {
+        // The space has been deleted locally.
+        return Ok(());
+    }
+
+    let SpaceV2 {
+        id,
+        name,
+        avatar_id,
+        created_at,
+        updated_at,
+        is_deleted: _,
+    } = space;
+
+    let avatar_path = files
+        .get(&avatar_id)
+        .map(|file| file.path.clone())
+        .unwrap_or_else(|| DEFAULT_SPACE_AVATAR_PATH.to_owned());
+    let avatar = Avatar::new(avatar_id.into(), avatar_path);
+
+    // Spaces can not be created without the avatar file.
+    file_service.register_avatar(&avatar).await?;
+
+    match local_space {
+        None => {
+            space_service
+                .create_space_with_dates(
+                    CreateSpaceOwned {
+                        id: id.into(),
+                        name: name.into(),
+                        avatar,
+                    },
+                    created_at,
+                    updated_at,
+                )
+                .await?;
+
+            emit_data_event(
+                emitter,
+                DataEvent::SpaceAdded(space_service.space_by_id(id.into()).await?),
+            )?;
+        }
+        Some(local_space) => {
+            if *local_space.updated_at.as_ref() < updated_at {
                 space_service
-                    .create_space(CreateSpaceOwned { id, name, avatar })
+                    .update_space(UpdateSpace {
+                        id: id.into(),
+                        name: name.into(),
+                        avatar,
+                    })
                     .await?;
 
                 emit_data_event(
                     emitter,
-                    DataEvent::SpaceAdded(space_service.space_by_id(space.id).await?),
+                    DataEvent::SpaceUpdated(space_service.space_by_id(id.into()).await?),
                 )?;
             }
-            Ok(space) => {
-                if space.updated_at < updated_at {
-                    space_service.update_space(UpdateSpace { id, name, avatar }).await?;
+        }
+    }
 
-                    emit_data_event(
-                        emitter,
-                        DataEvent::SpaceAdded(space_service.space_by_id(space.id).await?),
-                    )?;
-                }
+    Ok(())
+}
+
+/// Imports the note.
+///
+/// `is_local` tells whether the note exists in the local database (including deleted notes).
+async fn import_note<D: Db, R: Runtime, E: Emitter<R>>(
+    note: NoteV2,
+    is_local: bool,
+    files: &HashMap<Uuid, FileV2>,
+    emitter: &E,
+    file_service: &FileService<D>,
+    note_service: &NoteService<D>,
+) -> Result<(), DataansError> {
+    let local_note = match note_service.note_by_id(note.id.into()).await {
+        Ok(local_note) => Some(local_note),
+        Err(NoteServiceError::NotFound) => None,
+        Err(err) => return Err(DataansError::from(err)),
+    };
+
+    if note.is_deleted {
+        match local_note {
+            Some(local_note) if *local_note.updated_at.as_ref() < note.updated_at => {
+                note_service.delete_note(note.id.into()).await?;
+
+                emit_data_event(emitter, DataEvent::NoteDeleted(local_note.space_id, note.id.into()))?;
             }
-            Err(err) => return Err(DataansError::from(err)),
+            None if !is_local => note_service.create_deleted_note(&note).await?,
+            _ => {}
         }
 
-        let note_futures = notes.into_iter().map(|note| async move {
-            let Note {
-                id,
-                text,
-                created_at: _,
-                updated_at,
-                files,
-                space_id,
-            } = note;
-
-            let files_futures = files.clone().into_iter().map(|file| async move {
-                if file_service.file_by_id(file.id).await.is_err() {
-                    let file_id = file.id;
-                    file_service.register_file(file).await?;
-
-                    emit_data_event(emitter, DataEvent::FileAdded(file_service.file_by_id(file_id).await?))?;
-                }
+        return Ok(());
+    }
 
-                Ok::<(), DataansError>(())
-            });
-
-            try_join_all(files_futures).await?;
-
-            match note_service.note_by_id(note.id).await {
-                Err(NoteServiceError::NotFound) => {
-                    note_service
-                        .create_note(CreateNoteOwned {
-                            id,
-                            text,
-                            space_id,
-                            files,
-                        })
-                        .await?;
-
-                    emit_data_event(emitter, DataEvent::NoteAdded(note_service.note_by_id(note.id).await?))?;
-                }
-                Ok(note) => {
-                    if note.updated_at < updated_at {
-                        note_service.update_note(UpdateNote { id, text, files }).await?;
+    if local_note.is_none() && is_local {
+        // The note has been deleted locally (e.g. together with its space).
+        return Ok(());
+    }
 
-                        emit_data_event(emitter, DataEvent::NoteUpdated(note_service.note_by_id(note.id).await?))?;
-                    }
-                }
-                Err(err) => return Err(DataansError::from(err)),
-            }
+    let NoteV2 {
+        id,
+        text,
+        space_id,
+        created_at,
+        updated_at,
+        is_deleted: _,
+        files: note_files,
+    } = note;
 
-            Ok::<(), DataansError>(())
-        });
+    let note_files = try_join_all(
+        note_files
+            .into_iter()
+            // Deleted files are not restored.
+            .filter(|file_id| files.contains_key(file_id))
+            .map(|file_id| file_service.file_by_id(file_id.into())),
+    )
+    .await?;
 
-        try_join_all(note_futures).await?;
+    match local_note {
+        None => {
+            note_service
+                .create_note_with_dates(
+                    CreateNoteOwned {
+                        id: id.into(),
+                        text: text.into(),
+                        space_id: space_id.into(),
+                        files: note_files,
+                    },
+                    created_at,
+                    updated_at,
+                )
+                .await?;
 
-        Ok::<(), DataansError>(())
-    });
+            emit_data_event(emitter, DataEvent::NoteAdded(note_service.note_by_id(id.into()).await?))?;
+        }
+        Some(local_note) => {
+            if *local_note.updated_at.as_ref() < updated_at {
+                note_service
+                    .update_note(UpdateNote {
+                        id: id.into(),
+                        text: text.into(),
+                        files: note_files,
+                    })
+                    .await?;
 
-    try_join_all(space_futures).await?;
+                emit_data_event(
+                    emitter,
+                    DataEvent::NoteUpdated(note_service.note_by_id(id.into()).await?),
+                )?;
+            }
+        }
+    }
 
     Ok(())
 }
 
-pub async fn import<D: Db, R: Runtime, E: Emitter<R>>(
+pub async fn import<D: Db, O: OperationDb, R: Runtime, E: Emitter<R>>(
     emitter: &E,
     file_path: &Path,
     file_service: &FileService<D>,
     space_service: &SpaceService<D>,
     note_service: &NoteService<D>,
+    operation_db: &O,
 ) -> Result<(), DataansError> {
     let file = File::open(file_path)?;
     let schema: Schema = serde_json::from_reader(file)?;
 
-    match schema {
-        Schema::V1(schema_v1) => import_v1(schema_v1, emitter, file_service, space_service, note_service).await,
+    import_schema(schema, emitter, file_service, space_service, note_service, operation_db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use common::export::SchemaVersion;
+    use common::note::CreateNoteOwned;
+    use common::space::{Avatar, CreateSpaceOwned, DeleteSpace};
+    use common::{DEFAULT_SPACE_AVATAR_ID, DEFAULT_SPACE_AVATAR_PATH};
+    use serde_json::Value;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use tauri::test::{MockRuntime, mock_app};
+    use uuid::Uuid;
+
+    use super::import;
+    use crate::dataans::FileService;
+    use crate::dataans::command::export::json::export;
+    use crate::dataans::db::OperationLogger;
+    use crate::dataans::db::sqlite::SqliteDb;
+    use crate::dataans::service::note::NoteService;
+    use crate::dataans::service::space::SpaceService;
+
+    struct Vault {
+        dir: PathBuf,
+        operation_logger: Arc<OperationLogger>,
+        file_service: FileService<SqliteDb>,
+        space_service: Arc<SpaceService<SqliteDb>>,
+        note_service: NoteService<SqliteDb>,
+    }
+
+    impl Vault {
+        /// Opens a new vault. Vaults share the files directory, so imported files are already in place.
+        async fn new(dir: &Path, files_path: &Arc<Path>) -> Self {
+            let dir = dir.join(Uuid::new_v4().to_string());
+            fs::create_dir(&dir).unwrap();
+
+            let pool = SqlitePoolOptions::new()
+                .max_connections(4)
+                .connect_with(
+                    SqliteConnectOptions::new()
+                        .filename(dir.join("dataans.sqlite"))
+                        .create_if_missing(true),
+                )
+                .await
+                .unwrap();
+            sqlx::migrate!().run(&pool).await.unwrap();
+
+            let operation_logger = Arc::new(OperationLogger::new(pool));
+            let db = Arc::new(SqliteDb::new(Arc::clone(&operation_logger)));
+            let space_service = Arc::new(SpaceService::new(Arc::clone(&db)));
+
+            Self {
+                dir,
+                operation_logger,
+                file_service: FileService::new(Arc::clone(&db), Arc::clone(files_path)),
+                note_service: NoteService::new(db, Arc::clone(&space_service), Arc::clone(files_path)),
+                space_service,
+            }
+        }
+
+        /// Exports the vault data and returns the path to the json file.
+        async fn export(&self, version: SchemaVersion) -> PathBuf {
+            let backups_dir = self.dir.join(Uuid::new_v4().to_string());
+            fs::create_dir(&backups_dir).unwrap();
+
+            export(
+                version,
+                &backups_dir,
+                self.space_service.spaces().await.unwrap(),
+                &self.note_service,
+                self.operation_logger.as_ref(),
+            )
+            .await
+            .unwrap();
+
+            fs::read_dir(&backups_dir).unwrap().next().unwrap().unwrap().path()
+        }
+
+        async fn import(&self, path: &Path) {
+            let app = mock_app();
+
+            import::<_, _, MockRuntime, _>(
+                &app,
+                path,
+                &self.file_service,
+                &self.space_service,
+                &self.note_service,
+                self.operation_logger.as_ref(),
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    async fn round_trip(version: SchemaVersion) {
+        let dir = std::env::temp_dir().join(format!("dataans-json-{}", Uuid::new_v4()));
+        let files_path: Arc<Path> = dir.join("files").into();
+        fs::create_dir_all(&files_path).unwrap();
+
+        let source = Vault::new(&dir, &files_path).await;
+        let space_id = Uuid::new_v4();
+        let deleted_space_id = Uuid::new_v4();
+        for (id, name) in [(space_id, "Work"), (deleted_space_id, "Archive")] {
+            source
+                .space_service
+                .create_space(CreateSpaceOwned {
+                    id: id.into(),
+                    name: name.into(),
+                    avatar: Avatar::new(DEFAULT_SPACE_AVATAR_ID.into(), DEFAULT_SPACE_AVATAR_PATH),
+                })
+                .await
+                .unwrap();
+        }
+        let file = source
+            .file_service
+            .upload_file(Uuid::new_v4(), "plans.txt".into(), b"Quarterly plans")
+            .await
+            .unwrap();
+        let deleted_file = source
+            .file_service
+            .upload_file(Uuid::new_v4(), "old-plans.txt".into(), b"Old plans")
+            .await
+            .unwrap();
+        let mut note_ids = Vec::new();
+        for (text, files, space_id) in [
+            ("Plans are attached", vec![file], space_id),
+            ("# Ideas\nMore ideas", Vec::new(), space_id),
+            ("Draft", Vec::new(), space_id),
+            ("Old ideas", Vec::new(), deleted_space_id),
+        ] {
+            let note = source
+                .note_service
+                .create_note(CreateNoteOwned {
+                    id: Uuid::new_v4().into(),
+                    text: text.into(),
+                    space_id: space_id.into(),
+                    files,
+                })
+                .await
+                .unwrap();
+            note_ids.push(note.id);
+        }
+        source.note_service.delete_note(note_ids[2]).await.unwrap();
+        source
+            .file_service
+            .delete_file(*deleted_file.id.as_ref())
+            .await
+            .unwrap();
+        source
+            .space_service
+            .delete_space(DeleteSpace {
+                id: deleted_space_id.into(),
+            })
+            .await
+            .unwrap();
+
+        let exported = source.export(version).await;
+
+        let target = Vault::new(&dir, &files_path).await;
+        target.import(&exported).await;
+        let reexported = target.export(version).await;
+
+        let exported = fs::read_to_string(&exported).unwrap();
+        let value = serde_json::from_str::<Value>(&exported).unwrap();
+        assert_eq!(value["version"], version.variant_name());
+        if version == SchemaVersion::V2 {
+            // Deleted records are exported and imported too.
+            assert_eq!(value["spaces"].as_array().unwrap().len(), 2);
+            assert_eq!(value["notes"].as_array().unwrap().len(), 4);
+            assert_eq!(value["files"].as_array().unwrap().len(), 4);
+        }
+        assert_eq!(exported, fs::read_to_string(&reexported).unwrap());
+
+        // The import is idempotent.
+        target.import(&target.export(version).await).await;
+        assert_eq!(exported, fs::read_to_string(target.export(version).await).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn v1_round_trip() {
+        round_trip(SchemaVersion::V1).await;
+    }
+
+    #[tokio::test]
+    async fn v2_round_trip() {
+        round_trip(SchemaVersion::V2).await;
     }
 }
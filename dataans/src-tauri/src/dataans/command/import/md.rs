@@ -0,0 +1,780 @@
+//! Import of a directory with Markdown files (e.g. an Obsidian or Logseq vault).
+//!
+//! Every directory with Markdown files becomes a space and every file becomes a note (or several notes when the file
+//! is split by headings). Linked local images and attachments are copied into the files directory and registered.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::event::{DATA_EVENT, DataEvent};
+use common::import::{ImportReport, MdImportConfig, SkippedItem};
+use common::note::{CreateNoteOwned, File};
+use common::space::{Avatar, CreateSpaceOwned, Id as SpaceId};
+use common::{DEFAULT_SPACE_AVATAR_ID, DEFAULT_SPACE_AVATAR_PATH};
+use tauri::{Emitter, Runtime};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime};
+use uuid::Uuid;
+
+use crate::dataans::db::Db;
+use crate::dataans::service::note::NoteService;
+use crate::dataans::service::space::SpaceService;
+use crate::dataans::{DataansError, FileService};
+
+/// Front matter keys with the note creation date.
+const CREATED_KEYS: &[&str] = &["created", "created_at", "date", "date created", "creation date"];
+
+/// Front matter keys with the note update date.
+const UPDATED_KEYS: &[&str] = &["updated", "updated_at", "modified", "date modified", "last modified"];
+
+/// Name of the space for Markdown files in the root of the imported directory if the root has no name.
+const DEFAULT_SPACE_NAME: &str = "Imported";
+
+/// Markdown files of the imported directory grouped by their parent directories.
+#[derive(Debug, Default)]
+struct DirEntries {
+    md_files: BTreeMap<PathBuf, Vec<PathBuf>>,
+    other_files: Vec<PathBuf>,
+}
+
+/// Collects files of the directory recursively. Hidden files and directories (e.g. `.obsidian`) are ignored.
+fn collect_entries(dir: &Path, entries: &mut DirEntries) -> Result<(), DataansError> {
+    let mut dir_entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    dir_entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in dir_entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_entries(&path, entries)?;
+        } else if is_md_file(&path) {
+            entries.md_files.entry(dir.to_path_buf()).or_default().push(path);
+        } else {
+            entries.other_files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_md_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown"))
+}
+
+fn is_image(name: &str) -> bool {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    matches!(
+        extension.as_str(),
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "svg" | "avif"
+    )
+}
+
+/// Returns the space name for the directory: its path relative to the imported directory.
+fn space_name(root: &Path, dir: &Path) -> String {
+    let relative = dir
+        .strip_prefix(root)
+        .unwrap_or(dir)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+
+    if relative.is_empty() {
+        root.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| DEFAULT_SPACE_NAME.to_owned())
+    } else {
+        relative.join(" / ")
+    }
+}
+
+/// Note dates specified in the front matter.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FrontMatter {
+    created: Option<OffsetDateTime>,
+    updated: Option<OffsetDateTime>,
+}
+
+/// Splits the YAML front matter from the note text.
+///
+/// Only simple `key: value` pairs are parsed. Other front matter fields are dropped.
+fn split_front_matter(text: &str) -> (FrontMatter, &str) {
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return (FrontMatter::default(), text);
+    };
+
+    let mut front_matter = FrontMatter::default();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+
+        let line = line.trim_end();
+        if line == "---" {
+            return (front_matter, &rest[offset..]);
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim().trim_matches(['"', '\'']);
+
+        if CREATED_KEYS.contains(&key.as_str()) {
+            front_matter.created = parse_date(value).or(front_matter.created);
+        } else if UPDATED_KEYS.contains(&key.as_str()) {
+            front_matter.updated = parse_date(value).or(front_matter.updated);
+        }
+    }
+
+    // The front matter is not closed, so it is a regular text.
+    (FrontMatter::default(), text)
+}
+
+/// Parses dates in formats commonly used in front matter and journal file names.
+///
+/// Dates without the time zone are treated as UTC.
+fn parse_date(value: &str) -> Option<OffsetDateTime> {
+    if let Ok(date) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Some(date);
+    }
+
+    let date_time_formats = [
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+        format_description!("[year]-[month]-[day]T[hour]:[minute]"),
+        format_description!("[year]-[month]-[day] [hour]:[minute]"),
+    ];
+    if let Some(date) = date_time_formats
+        .iter()
+        .find_map(|format| PrimitiveDateTime::parse(value, format).ok())
+    {
+        return Some(date.assume_utc());
+    }
+
+    let date_formats = [
+        format_description!("[year]-[month]-[day]"),
+        format_description!("[year]_[month]_[day]"),
+        format_description!("[year].[month].[day]"),
+    ];
+    date_formats
+        .iter()
+        .find_map(|format| Date::parse(value, format).ok())
+        .map(|date| date.midnight().assume_utc())
+}
+
+/// Returns the level of the ATX heading (`# Heading`).
+fn heading_level(line: &str) -> Option<usize> {
+    let line = line
+        .strip_prefix("   ")
+        .or_else(|| line.strip_prefix("  "))
+        .or_else(|| line.strip_prefix(' '))
+        .unwrap_or(line);
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t']))).then_some(level)
+}
+
+/// Returns byte offsets and levels of the headings outside of fenced code blocks.
+fn headings(text: &str) -> Vec<(usize, usize)> {
+    let mut headings = Vec::new();
+    let mut in_code_block = false;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        } else if !in_code_block && let Some(level) = heading_level(line.trim_end()) {
+            headings.push((offset, level));
+        }
+        offset += line.len();
+    }
+
+    headings
+}
+
+/// Splits the text into parts by its top-level headings. Every part starts with its heading.
+///
+/// The text before the first heading becomes a separate part.
+fn split_by_headings(text: &str) -> Vec<&str> {
+    let headings = headings(text);
+    let Some(top_level) = headings.iter().map(|(_, level)| *level).min() else {
+        return vec![text];
+    };
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (offset, _) in headings.into_iter().filter(|(_, level)| *level == top_level) {
+        parts.push(&text[start..offset]);
+        start = offset;
+    }
+    parts.push(&text[start..]);
+
+    parts.into_iter().filter(|part| !part.trim().is_empty()).collect()
+}
+
+/// A Markdown link or an Obsidian wikilink.
+#[derive(Debug, PartialEq, Eq)]
+struct Link<'text> {
+    /// Link text (or image alt text).
+    text: &'text str,
+    /// Link destination.
+    target: &'text str,
+    /// `![...](...)` or `![[...]]`.
+    is_embed: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'text> {
+    Text(&'text str),
+    Link(Link<'text>, &'text str),
+}
+
+/// Splits the text into plain text and links. Fenced code blocks are not parsed.
+fn parse_links(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut in_code_block = false;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            segments.push(Segment::Text(line));
+        } else if in_code_block {
+            segments.push(Segment::Text(line));
+        } else {
+            parse_line_links(line, &mut segments);
+        }
+    }
+
+    segments
+}
+
+fn parse_line_links<'text>(line: &'text str, segments: &mut Vec<Segment<'text>>) {
+    let mut text_start = 0;
+    let mut position = 0;
+
+    while let Some(found) = line[position..].find('[') {
+        let start = position + found;
+        let is_embed = line[..start].ends_with('!');
+        let link_start = if is_embed { start - 1 } else { start };
+
+        match parse_link(&line[start..], is_embed) {
+            Some((link, len)) => {
+                segments.push(Segment::Text(&line[text_start..link_start]));
+                segments.push(Segment::Link(link, &line[link_start..start + len]));
+
+                position = start + len;
+                text_start = position;
+            }
+            None => position = start + 1,
+        }
+    }
+
+    segments.push(Segment::Text(&line[text_start..]));
+}
+
+/// Parses the link that starts at the beginning of `text` (with `[`). Returns the link and its length.
+fn parse_link(text: &str, is_embed: bool) -> Option<(Link<'_>, usize)> {
+    if let Some(wikilink) = text.strip_prefix("[[") {
+        // `[[file.pdf]]`, `[[file.pdf|alias]]`, or `[[image.png|300]]`.
+        let end = wikilink.find("]]")?;
+        let inner = &wikilink[..end];
+        let (target, alias) = inner.split_once('|').unwrap_or((inner, inner));
+        let target = target.split_once('#').map(|(target, _)| target).unwrap_or(target);
+
+        return Some((
+            Link {
+                text: alias,
+                target,
+                is_embed,
+            },
+            end + 4,
+        ));
+    }
+
+    let text_end = text.find("](")?;
+    let link_text = &text[1..text_end];
+    if link_text.contains(['[', ']']) {
+        return None;
+    }
+
+    let destination = &text[text_end + 2..];
+    let (target, target_len) = if let Some(wrapped) = destination.strip_prefix('<') {
+        let end = wrapped.find('>')?;
+        (&wrapped[..end], end + 2)
+    } else {
+        let end = destination.find(|c: char| c == ')' || c.is_whitespace())?;
+        (&destination[..end], end)
+    };
+    // Skip the optional link title.
+    let close = destination[target_len..].find(')')? + target_len;
+
+    Some((
+        Link {
+            text: link_text,
+            target,
+            is_embed,
+        },
+        text_end + 2 + close + 1,
+    ))
+}
+
+/// Decodes `%XX` sequences in the link destination.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Checks whether the link points to a local file (not a web page, email, or a heading in the same note).
+fn is_local_target(target: &str) -> bool {
+    !target.is_empty() && !target.starts_with('#') && !target.contains("://") && !target.starts_with("mailto:")
+}
+
+/// Returns the image link destination understood by the notes renderer.
+fn local_link_destination(file_name: &str) -> String {
+    if file_name.contains([' ', '(', ')']) {
+        format!("<{file_name}>")
+    } else {
+        file_name.to_owned()
+    }
+}
+
+fn file_dates(path: &Path, front_matter: &FrontMatter) -> (OffsetDateTime, OffsetDateTime) {
+    let metadata = fs::metadata(path).ok();
+    let modified = metadata
+        .as_ref()
+        .and_then(|metadata| metadata.modified().ok())
+        .map(OffsetDateTime::from);
+    let file_created = metadata
+        .as_ref()
+        .and_then(|metadata| metadata.created().ok())
+        .map(OffsetDateTime::from);
+    // Journal pages are usually named by their date (e.g. `2024_01_15.md` in Logseq).
+    let name_date = path.file_stem().and_then(|stem| stem.to_str()).and_then(parse_date);
+
+    let created = front_matter
+        .created
+        .or(name_date)
+        .or(file_created)
+        .or(modified)
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    let updated = front_matter.updated.or(modified).unwrap_or(created).max(created);
+
+    (created, updated)
+}
+
+struct Importer<'a, D, E> {
+    root: PathBuf,
+    emitter: &'a E,
+    file_service: &'a FileService<D>,
+    space_service: &'a SpaceService<D>,
+    note_service: &'a NoteService<D>,
+    /// Non-Markdown files of the imported directory by their names. Used to resolve wikilinks.
+    files_by_name: HashMap<String, Vec<PathBuf>>,
+    /// Already imported attachments.
+    imported_files: HashMap<PathBuf, File>,
+    report: ImportReport,
+}
+
+impl<D: Db, E> Importer<'_, D, E> {
+    fn skip(&mut self, path: &Path, reason: impl Into<String>) {
+        self.report.skipped.push(SkippedItem {
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        });
+    }
+
+    fn emit<R: Runtime>(&self, event: DataEvent) -> Result<(), DataansError>
+    where
+        E: Emitter<R>,
+    {
+        self.emitter.emit(DATA_EVENT, event)?;
+
+        Ok(())
+    }
+
+    /// Returns the existing space with the same name or creates a new one.
+    async fn space<R: Runtime>(&mut self, name: String) -> Result<SpaceId, DataansError>
+    where
+        E: Emitter<R>,
+    {
+        let existing_space = self
+            .space_service
+            .spaces()
+            .await?
+            .into_iter()
+            .find(|space| space.name.as_ref() == name);
+        if let Some(space) = existing_space {
+            return Ok(space.id);
+        }
+
+        let space = self
+            .space_service
+            .create_space(CreateSpaceOwned {
+                id: Uuid::new_v4().into(),
+                name: name.into(),
+                avatar: Avatar::new(DEFAULT_SPACE_AVATAR_ID.into(), DEFAULT_SPACE_AVATAR_PATH),
+            })
+            .await?;
+        let space_id = space.id;
+
+        self.emit(DataEvent::SpaceAdded(space))?;
+        self.report.spaces += 1;
+
+        Ok(space_id)
+    }
+
+    /// Resolves the link target to a local file inside the imported directory.
+    fn resolve(&self, note_path: &Path, target: &str) -> Option<PathBuf> {
+        let target = percent_decode(target);
+        let note_dir = note_path.parent().unwrap_or(&self.root);
+
+        let path = [note_dir.join(&target), self.root.join(&target)]
+            .into_iter()
+            .find(|path| path.is_file())
+            // Obsidian resolves wikilinks by the file name.
+            .or_else(|| self.files_by_name.get(&target).and_then(|paths| paths.first().cloned()))?;
+
+        // Links must not point outside of the imported directory.
+        path.canonicalize()
+            .ok()
+            .filter(|path| path.starts_with(&self.root) && !is_md_file(path))
+    }
+
+    async fn import_attachment<R: Runtime>(&mut self, source: &Path) -> Result<File, DataansError>
+    where
+        E: Emitter<R>,
+    {
+        if let Some(file) = self.imported_files.get(source) {
+            return Ok(file.clone());
+        }
+
+        let file = self.file_service.import_file(Uuid::new_v4(), source, |_, _| {}).await?;
+
+        self.emit(DataEvent::FileAdded(file.clone()))?;
+        self.report.files += 1;
+        self.imported_files.insert(source.to_path_buf(), file.clone());
+
+        Ok(file)
+    }
+
+    /// Imports linked attachments and rewrites the links.
+    ///
+    /// Images are rendered from the local files. Other attachments are attached to the note and their links are
+    /// replaced with the link text.
+    async fn note_text<R: Runtime>(&mut self, note_path: &Path, text: &str) -> Result<(String, Vec<File>), DataansError>
+    where
+        E: Emitter<R>,
+    {
+        let mut note_text = String::with_capacity(text.len());
+        let mut files = Vec::<File>::new();
+
+        for segment in parse_links(text) {
+            let (link, source) = match segment {
+                Segment::Text(text) => {
+                    note_text.push_str(text);
+                    continue;
+                }
+                Segment::Link(link, source) => (link, source),
+            };
+
+            if !is_local_target(link.target) {
+                note_text.push_str(source);
+                continue;
+            }
+
+            let Some(path) = self.resolve(note_path, link.target) else {
+                // Links to other notes are kept as is.
+                if !is_md_file(Path::new(link.target)) && Path::new(link.target).extension().is_some() {
+                    self.skip(note_path, format!("linked file is not found: {}", link.target));
+                }
+                note_text.push_str(source);
+                continue;
+            };
+
+            let file = match self.import_attachment(&path).await {
+                Ok(file) => file,
+                Err(err) => {
+                    warn!(?err, ?path, "Failed to import the attachment");
+                    self.skip(&path, format!("failed to import the attachment: {err}"));
+                    note_text.push_str(source);
+                    continue;
+                }
+            };
+
+            let file_name = file.path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            if link.is_embed && is_image(&file.name) {
+                let alt = if link.text == link.target || link.text.parse::<u32>().is_ok() {
+                    ""
+                } else {
+                    link.text
+                };
+                note_text.push_str(&format!("![{alt}]({})", local_link_destination(file_name)));
+            } else {
+                note_text.push_str(if link.text.is_empty() {
+                    file.name.as_str()
+                } else {
+                    link.text
+                });
+            }
+
+            if !files.iter().any(|attached| attached.id == file.id) {
+                files.push(file);
+            }
+        }
+
+        Ok((note_text, files))
+    }
+
+    async fn import_md_file<R: Runtime>(
+        &mut self,
+        space_id: SpaceId,
+        path: &Path,
+        config: MdImportConfig,
+    ) -> Result<(), DataansError>
+    where
+        E: Emitter<R>,
+    {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                self.skip(path, format!("can not read the file: {err}"));
+                return Ok(());
+            }
+        };
+
+        let (front_matter, body) = split_front_matter(&text);
+        let (created_at, updated_at) = file_dates(path, &front_matter);
+
+        let parts = if config.split_by_headings {
+            split_by_headings(body)
+        } else {
+            vec![body]
+        };
+        let parts = parts
+            .into_iter()
+            .filter(|part| !part.trim().is_empty())
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            self.skip(path, "the file is empty");
+            return Ok(());
+        }
+
+        for (index, part) in parts.into_iter().enumerate() {
+            let (text, files) = self.note_text(path, part.trim()).await?;
+
+            // Parts of the same file keep their order in the space.
+            let offset = Duration::milliseconds(i64::try_from(index).expect("usize -> i64 conversion should not fail"));
+            let note = self
+                .note_service
+                .create_note_with_dates(
+                    CreateNoteOwned {
+                        id: Uuid::new_v4().into(),
+                        text: text.into(),
+                        space_id,
+                        files,
+                    },
+                    created_at + offset,
+                    updated_at.max(created_at + offset),
+                )
+                .await?;
+
+            self.emit(DataEvent::NoteAdded(note))?;
+            self.report.notes += 1;
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn import<D: Db, R: Runtime, E: Emitter<R>>(
+    emitter: &E,
+    dir: &Path,
+    config: MdImportConfig,
+    file_service: &FileService<D>,
+    space_service: &SpaceService<D>,
+    note_service: &NoteService<D>,
+) -> Result<ImportReport, DataansError> {
+    let root = dir.canonicalize()?;
+
+    let mut entries = DirEntries::default();
+    collect_entries(&root, &mut entries)?;
+
+    let mut files_by_name = HashMap::<String, Vec<PathBuf>>::new();
+    for path in &entries.other_files {
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            files_by_name.entry(name.to_owned()).or_default().push(path.clone());
+        }
+    }
+
+    let mut importer = Importer {
+        root: root.clone(),
+        emitter,
+        file_service,
+        space_service,
+        note_service,
+        files_by_name,
+        imported_files: HashMap::new(),
+        report: ImportReport::default(),
+    };
+
+    for (dir, md_files) in entries.md_files {
+        let space_id = importer.space(space_name(&root, &dir)).await?;
+
+        for path in md_files {
+            importer.import_md_file(space_id, &path, config).await?;
+        }
+    }
+
+    let imported_files = importer.imported_files.keys().cloned().collect::<HashSet<_>>();
+    for path in entries.other_files {
+        if !imported_files.contains(&path) {
+            importer.skip(&path, "not a Markdown file and not linked from any note");
+        }
+    }
+
+    Ok(importer.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use time::macros::datetime;
+
+    use super::{
+        FrontMatter, Link, Segment, parse_date, parse_links, space_name, split_by_headings, split_front_matter,
+    };
+
+    #[test]
+    fn front_matter() {
+        let text =
+            "---\nid: 1\ncreated: 2024-01-15T10:20:30+02:00\nupdated: \"2024-02-01\"\ntags: [a, b]\n---\n# Title\n";
+
+        assert_eq!(
+            split_front_matter(text),
+            (
+                FrontMatter {
+                    created: Some(datetime!(2024-01-15 10:20:30 +02:00)),
+                    updated: Some(datetime!(2024-02-01 00:00:00 UTC)),
+                },
+                "# Title\n"
+            )
+        );
+
+        // Not closed front matter is a regular text.
+        let text = "---\ncreated: 2024-01-15\n";
+        assert_eq!(split_front_matter(text), (FrontMatter::default(), text));
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("2024-01-15 08:30"), Some(datetime!(2024-01-15 08:30:00 UTC)));
+        assert_eq!(parse_date("2024_01_15"), Some(datetime!(2024-01-15 00:00:00 UTC)));
+        assert_eq!(parse_date("Meeting notes"), None);
+    }
+
+    #[test]
+    fn headings_split() {
+        let text = "intro\n## First\na\n```\n## not a heading\n```\n### Sub\nb\n## Second\nc\n";
+
+        assert_eq!(
+            split_by_headings(text),
+            vec![
+                "intro\n",
+                "## First\na\n```\n## not a heading\n```\n### Sub\nb\n",
+                "## Second\nc\n"
+            ]
+        );
+        assert_eq!(split_by_headings("no headings"), vec!["no headings"]);
+    }
+
+    #[test]
+    fn links() {
+        let segments =
+            parse_links("See ![alt](img/a%20b.png \"title\") and [[doc.pdf|the doc]], ![[c.png]] [x](https://x.y)\n");
+
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("See "),
+                Segment::Link(
+                    Link {
+                        text: "alt",
+                        target: "img/a%20b.png",
+                        is_embed: true
+                    },
+                    "![alt](img/a%20b.png \"title\")"
+                ),
+                Segment::Text(" and "),
+                Segment::Link(
+                    Link {
+                        text: "the doc",
+                        target: "doc.pdf",
+                        is_embed: false
+                    },
+                    "[[doc.pdf|the doc]]"
+                ),
+                Segment::Text(", "),
+                Segment::Link(
+                    Link {
+                        text: "c.png",
+                        target: "c.png",
+                        is_embed: true
+                    },
+                    "![[c.png]]"
+                ),
+                Segment::Text(" "),
+                Segment::Link(
+                    Link {
+                        text: "x",
+                        target: "https://x.y",
+                        is_embed: false
+                    },
+                    "[x](https://x.y)"
+                ),
+                Segment::Text("\n"),
+            ]
+        );
+
+        assert_eq!(
+            parse_links("[not a link] [a](b"),
+            vec![Segment::Text("[not a link] [a](b")]
+        );
+    }
+
+    #[test]
+    fn space_names() {
+        let root = Path::new("/home/user/vault");
+
+        assert_eq!(space_name(root, root), "vault");
+        assert_eq!(space_name(root, &root.join("Projects").join("Work")), "Projects / Work");
+    }
+}
@@ -1,19 +1,28 @@
+mod archive;
 mod json;
+mod md;
+mod telegram;
 
 use std::path::PathBuf;
 
 use common::error::CommandResult;
+use common::import::{ImportReport, MdImportConfig};
 use tauri::{AppHandle, Runtime, State};
 
 use crate::dataans::{DataansError, DataansState};
 
-#[instrument(level = "trace", ret, skip(state))]
+/// Imports the app data from the json export or the backup archive.
+///
+/// The passphrase is needed only for encrypted archives.
+#[instrument(level = "trace", ret, skip(state, passphrase))]
 #[tauri::command]
 pub async fn import_app_data<R: Runtime>(
     app: AppHandle<R>,
     state: State<'_, DataansState>,
     path: PathBuf,
+    passphrase: Option<String>,
 ) -> CommandResult<()> {
+    let state = state.vault();
     let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
 
     if extension == "json" {
@@ -24,6 +33,22 @@ pub async fn import_app_data<R: Runtime>(
             &state.file_service,
             &state.space_service,
             &state.note_service,
+            state.operation_logger.as_ref(),
+        )
+        .await?;
+
+        Ok(())
+    } else if extension == "zip" {
+        info!(?path, "Processing archive import...");
+        archive::import(
+            &app,
+            &path,
+            passphrase.as_deref(),
+            &state.files_path,
+            &state.file_service,
+            &state.space_service,
+            &state.note_service,
+            state.operation_logger.as_ref(),
         )
         .await?;
 
@@ -33,3 +58,69 @@ pub async fn import_app_data<R: Runtime>(
         Err(DataansError::IncorrectImportFileType(extension.to_string()).into())
     }
 }
+
+/// Imports a directory with Markdown files (e.g. an Obsidian or Logseq vault).
+///
+/// Directories become spaces, Markdown files become notes, and linked local files become attachments.
+#[instrument(level = "trace", ret, skip(state))]
+#[tauri::command]
+pub async fn import_md_dir<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, DataansState>,
+    path: PathBuf,
+    config: MdImportConfig,
+) -> CommandResult<ImportReport> {
+    let state = state.vault();
+
+    info!(?path, ?config, "Processing Markdown directory import...");
+    let report = md::import(
+        &app,
+        &path,
+        config,
+        &state.file_service,
+        &state.space_service,
+        &state.note_service,
+    )
+    .await?;
+    info!(
+        spaces = report.spaces,
+        notes = report.notes,
+        files = report.files,
+        skipped = report.skipped.len(),
+        "Markdown directory has been imported"
+    );
+
+    Ok(report)
+}
+
+/// Imports the Telegram Desktop JSON export (`result.json`).
+///
+/// Chats and channels become spaces, messages become notes, and exported photos and documents become attachments.
+#[instrument(level = "trace", ret, skip(state))]
+#[tauri::command]
+pub async fn import_telegram<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, DataansState>,
+    path: PathBuf,
+) -> CommandResult<ImportReport> {
+    let state = state.vault();
+
+    info!(?path, "Processing Telegram export import...");
+    let report = telegram::import(
+        &app,
+        &path,
+        &state.file_service,
+        &state.space_service,
+        &state.note_service,
+    )
+    .await?;
+    info!(
+        spaces = report.spaces,
+        notes = report.notes,
+        files = report.files,
+        skipped = report.skipped.len(),
+        "Telegram export has been imported"
+    );
+
+    Ok(report)
+}
@@ -0,0 +1,592 @@
+//! Import of the Telegram Desktop JSON export (`result.json`).
+//!
+//! Both single chat exports and full account exports are supported. Every chat or channel becomes a space and every
+//! message becomes a note. Space and note ids are derived from Telegram ids, so the same export can be imported
+//! several times without duplicates.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use common::event::{DATA_EVENT, DataEvent};
+use common::import::{ImportReport, SkippedItem};
+use common::note::{CreateNoteOwned, File as NoteFile};
+use common::space::{Avatar, CreateSpaceOwned, Id as SpaceId};
+use common::{DEFAULT_SPACE_AVATAR_ID, DEFAULT_SPACE_AVATAR_PATH};
+use serde::Deserialize;
+use tauri::{Emitter, Runtime};
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use uuid::{Uuid, uuid};
+
+use crate::dataans::db::Db;
+use crate::dataans::service::note::{NoteService, NoteServiceError};
+use crate::dataans::service::space::{SpaceService, SpaceServiceError};
+use crate::dataans::{DataansError, FileService};
+
+/// Namespace for space and note ids derived from Telegram chat and message ids.
+const TELEGRAM_NAMESPACE: Uuid = uuid!("0b6f3c6e-8f57-4c55-9d3a-1f1c7d2e9a41");
+
+/// Telegram Desktop writes this text instead of the file path when the file has not been exported.
+const FILE_NOT_INCLUDED_PREFIX: &str = "(File not included.";
+
+/// Full account export.
+#[derive(Debug, Deserialize)]
+struct AccountExport {
+    chats: ChatList,
+    #[serde(default)]
+    left_chats: Option<ChatList>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatList {
+    list: Vec<Chat>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Account(AccountExport),
+    Chat(Chat),
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    id: i64,
+    #[serde(default)]
+    messages: Vec<Message>,
+}
+
+impl Chat {
+    fn space_name(&self) -> String {
+        match &self.name {
+            Some(name) if !name.trim().is_empty() => name.clone(),
+            _ if self.kind == "saved_messages" => "Saved Messages".to_owned(),
+            _ => format!("Telegram chat {}", self.id),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    date: String,
+    #[serde(default)]
+    date_unixtime: Option<String>,
+    #[serde(default)]
+    edited_unixtime: Option<String>,
+    #[serde(default)]
+    text: Text,
+    /// Newer exports duplicate the message text as the list of entities.
+    #[serde(default)]
+    text_entities: Vec<TextEntity>,
+    #[serde(default)]
+    photo: Option<String>,
+    #[serde(default)]
+    file: Option<String>,
+}
+
+impl Message {
+    fn entities(&self) -> Vec<TextEntity> {
+        if !self.text_entities.is_empty() {
+            return self.text_entities.clone();
+        }
+
+        match &self.text {
+            Text::Plain(text) => vec![TextEntity::plain(text.clone())],
+            Text::Parts(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    TextPart::Plain(text) => TextEntity::plain(text.clone()),
+                    TextPart::Entity(entity) => entity.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    fn created_at(&self) -> Option<OffsetDateTime> {
+        parse_unixtime(self.date_unixtime.as_deref()).or_else(|| {
+            // Old exports contain only the local time without the time zone.
+            PrimitiveDateTime::parse(
+                &self.date,
+                format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+            )
+            .ok()
+            .map(PrimitiveDateTime::assume_utc)
+        })
+    }
+
+    /// Paths of the exported photo and document relative to the export directory.
+    fn media(&self) -> impl Iterator<Item = &str> {
+        [self.photo.as_deref(), self.file.as_deref()].into_iter().flatten()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Plain(String),
+    Parts(Vec<TextPart>),
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self::Plain(String::new())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TextPart {
+    Plain(String),
+    Entity(TextEntity),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TextEntity {
+    #[serde(rename = "type")]
+    kind: String,
+    text: String,
+    #[serde(default)]
+    href: Option<String>,
+    #[serde(default)]
+    language: Option<String>,
+}
+
+impl TextEntity {
+    fn plain(text: String) -> Self {
+        Self {
+            kind: "plain".to_owned(),
+            text,
+            href: None,
+            language: None,
+        }
+    }
+}
+
+fn parse_unixtime(value: Option<&str>) -> Option<OffsetDateTime> {
+    value
+        .and_then(|value| value.parse::<i64>().ok())
+        .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())
+}
+
+/// Wraps the text into the Markdown emphasis markers.
+///
+/// Markers must be adjacent to the text, so leading and trailing whitespaces are moved outside.
+fn emphasis(text: &str, marker: &str, md: &mut String) {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        md.push_str(text);
+        return;
+    }
+
+    let start = text.len() - text.trim_start().len();
+    let end = start + trimmed.len();
+
+    md.push_str(&text[..start]);
+    md.push_str(marker);
+    md.push_str(trimmed);
+    md.push_str(marker);
+    md.push_str(&text[end..]);
+}
+
+/// Starts a new line unless the text is empty or already ends with a line break.
+fn start_line(md: &mut String) {
+    if !md.is_empty() && !md.ends_with('\n') {
+        md.push('\n');
+    }
+}
+
+/// Converts Telegram text entities into Markdown.
+///
+/// Entities without the Markdown equivalent (mentions, hashtags, spoilers, etc) are kept as plain text.
+fn entities_to_md(entities: &[TextEntity]) -> String {
+    let mut md = String::new();
+
+    for entity in entities {
+        let text = entity.text.as_str();
+
+        match entity.kind.as_str() {
+            "bold" => emphasis(text, "**", &mut md),
+            "italic" => emphasis(text, "*", &mut md),
+            "strikethrough" => emphasis(text, "~~", &mut md),
+            "code" if text.contains('`') => {
+                md.push_str("`` ");
+                md.push_str(text);
+                md.push_str(" ``");
+            }
+            "code" => {
+                md.push('`');
+                md.push_str(text);
+                md.push('`');
+            }
+            "pre" => {
+                start_line(&mut md);
+                md.push_str("```");
+                md.push_str(entity.language.as_deref().unwrap_or_default());
+                md.push('\n');
+                md.push_str(text.trim_end_matches('\n'));
+                md.push_str("\n```\n");
+            }
+            "text_link" => match &entity.href {
+                Some(href) => {
+                    md.push('[');
+                    md.push_str(&text.replace('[', "\\[").replace(']', "\\]"));
+                    md.push_str("](");
+                    md.push_str(&href.replace(' ', "%20").replace(')', "%29"));
+                    md.push(')');
+                }
+                None => md.push_str(text),
+            },
+            "blockquote" => {
+                start_line(&mut md);
+                for line in text.lines() {
+                    md.push_str("> ");
+                    md.push_str(line);
+                    md.push('\n');
+                }
+            }
+            _ => md.push_str(text),
+        }
+    }
+
+    md.trim().to_owned()
+}
+
+/// Resolves the media file path relative to the export directory.
+///
+/// Paths come from the `result.json` file, so they must not point outside of the (canonical) export directory.
+/// Returns the reason to skip the file otherwise.
+fn media_path(export_dir: &Path, media: &str) -> Result<PathBuf, String> {
+    let path = export_dir
+        .join(media)
+        .canonicalize()
+        .map_err(|err| format!("failed to import {media}: {err}"))?;
+
+    if path.starts_with(export_dir) {
+        Ok(path)
+    } else {
+        Err(format!("{media} is outside of the export directory"))
+    }
+}
+
+struct Importer<'a, D, E> {
+    export_dir: PathBuf,
+    export_path: &'a Path,
+    emitter: &'a E,
+    file_service: &'a FileService<D>,
+    space_service: &'a SpaceService<D>,
+    note_service: &'a NoteService<D>,
+    report: ImportReport,
+}
+
+impl<D: Db, E> Importer<'_, D, E> {
+    fn skip(&mut self, chat: &Chat, message: &Message, reason: impl AsRef<str>) {
+        self.report.skipped.push(SkippedItem {
+            path: self.export_path.to_path_buf(),
+            reason: format!(
+                "message {} in \"{}\": {}",
+                message.id,
+                chat.space_name(),
+                reason.as_ref()
+            ),
+        });
+    }
+
+    fn emit<R: Runtime>(&self, event: DataEvent) -> Result<(), DataansError>
+    where
+        E: Emitter<R>,
+    {
+        self.emitter.emit(DATA_EVENT, event)?;
+
+        Ok(())
+    }
+
+    async fn space<R: Runtime>(&mut self, chat: &Chat) -> Result<SpaceId, DataansError>
+    where
+        E: Emitter<R>,
+    {
+        let space_id = SpaceId::from(Uuid::new_v5(
+            &TELEGRAM_NAMESPACE,
+            format!("chat:{}", chat.id).as_bytes(),
+        ));
+
+        match self.space_service.space_by_id(space_id).await {
+            Ok(_) => {}
+            Err(SpaceServiceError::NotFound) => {
+                let space = self
+                    .space_service
+                    .create_space(CreateSpaceOwned {
+                        id: space_id,
+                        name: chat.space_name().into(),
+                        avatar: Avatar::new(DEFAULT_SPACE_AVATAR_ID.into(), DEFAULT_SPACE_AVATAR_PATH),
+                    })
+                    .await?;
+
+                self.emit(DataEvent::SpaceAdded(space))?;
+                self.report.spaces += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(space_id)
+    }
+
+    async fn import_media<R: Runtime>(&mut self, chat: &Chat, message: &Message) -> Vec<NoteFile>
+    where
+        E: Emitter<R>,
+    {
+        let mut files = Vec::new();
+
+        for media in message.media() {
+            if media.starts_with(FILE_NOT_INCLUDED_PREFIX) {
+                self.skip(chat, message, "the file is not included in the export");
+                continue;
+            }
+
+            let path = match media_path(&self.export_dir, media) {
+                Ok(path) => path,
+                Err(reason) => {
+                    warn!(media, %reason, "Invalid message file path");
+                    self.skip(chat, message, reason);
+                    continue;
+                }
+            };
+            let file = match self.file_service.import_file(Uuid::new_v4(), &path, |_, _| {}).await {
+                Ok(file) => file,
+                Err(err) => {
+                    warn!(?err, ?path, "Failed to import the message file");
+                    self.skip(chat, message, format!("failed to import {media}: {err}"));
+                    continue;
+                }
+            };
+
+            if let Err(err) = self.emit(DataEvent::FileAdded(file.clone())) {
+                warn!(?err, "Failed to emit the file added event");
+            }
+            self.report.files += 1;
+            files.push(file);
+        }
+
+        files
+    }
+
+    async fn import_chat<R: Runtime>(&mut self, chat: &Chat) -> Result<(), DataansError>
+    where
+        E: Emitter<R>,
+    {
+        let messages = chat
+            .messages
+            .iter()
+            // Service messages (pinned messages, joined members, etc) are not the chat content.
+            .filter(|message| message.kind == "message")
+            .collect::<Vec<_>>();
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let space_id = self.space(chat).await?;
+
+        for message in messages {
+            let note_id = Uuid::new_v5(
+                &TELEGRAM_NAMESPACE,
+                format!("chat:{}:message:{}", chat.id, message.id).as_bytes(),
+            );
+            match self.note_service.note_by_id(note_id.into()).await {
+                Ok(_) => {
+                    debug!(?note_id, message_id = message.id, "Message has already been imported");
+                    continue;
+                }
+                Err(NoteServiceError::NotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            let Some(created_at) = message.created_at() else {
+                self.skip(chat, message, format!("invalid message date: {}", message.date));
+                continue;
+            };
+            let updated_at = parse_unixtime(message.edited_unixtime.as_deref())
+                .unwrap_or(created_at)
+                .max(created_at);
+
+            let text = entities_to_md(&message.entities());
+            let files = self.import_media(chat, message).await;
+            if text.is_empty() && files.is_empty() {
+                if message.photo.is_none() && message.file.is_none() {
+                    // E.g. polls, locations, contacts.
+                    self.skip(chat, message, "the message has no text and no supported media");
+                }
+                continue;
+            }
+
+            let note = self
+                .note_service
+                .create_note_with_dates(
+                    CreateNoteOwned {
+                        id: note_id.into(),
+                        text: text.into(),
+                        space_id,
+                        files,
+                    },
+                    created_at,
+                    updated_at,
+                )
+                .await?;
+
+            self.emit(DataEvent::NoteAdded(note))?;
+            self.report.notes += 1;
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn import<D: Db, R: Runtime, E: Emitter<R>>(
+    emitter: &E,
+    export_path: &Path,
+    file_service: &FileService<D>,
+    space_service: &SpaceService<D>,
+    note_service: &NoteService<D>,
+) -> Result<ImportReport, DataansError> {
+    let export: Export = serde_json::from_reader(BufReader::new(File::open(export_path)?))?;
+    let chats = match export {
+        Export::Account(AccountExport { chats, left_chats }) => chats
+            .list
+            .into_iter()
+            .chain(left_chats.into_iter().flat_map(|chats| chats.list))
+            .collect(),
+        Export::Chat(chat) => vec![chat],
+    };
+
+    let mut importer = Importer {
+        export_dir: export_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .canonicalize()?,
+        export_path,
+        emitter,
+        file_service,
+        space_service,
+        note_service,
+        report: ImportReport::default(),
+    };
+
+    for chat in chats {
+        importer.import_chat(&chat).await?;
+    }
+
+    Ok(importer.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use time::macros::datetime;
+    use uuid::Uuid;
+
+    use super::{Export, Text, entities_to_md, media_path};
+
+    #[test]
+    fn entities() {
+        let message = r#"{
+            "id": 7,
+            "type": "message",
+            "date": "2024-01-15T10:20:30",
+            "date_unixtime": "1705314030",
+            "text": "",
+            "text_entities": [
+                { "type": "bold", "text": "Plan " },
+                { "type": "plain", "text": "for " },
+                { "type": "text_link", "text": "the [site]", "href": "https://example.com/a b" },
+                { "type": "plain", "text": ": run " },
+                { "type": "code", "text": "cargo test" },
+                { "type": "pre", "text": "fn main() {}\n", "language": "rust" },
+                { "type": "mention", "text": "@someone" }
+            ]
+        }"#;
+        let message = serde_json::from_str::<super::Message>(message).unwrap();
+
+        assert_eq!(message.created_at(), Some(datetime!(2024-01-15 10:20:30 UTC)));
+        assert_eq!(
+            entities_to_md(&message.entities()),
+            "**Plan** for [the \\[site\\]](https://example.com/a%20b): run `cargo test`\n```rust\nfn main() {}\n```\n@someone"
+        );
+    }
+
+    #[test]
+    fn old_text_format() {
+        let message = r#"{
+            "id": 1,
+            "type": "message",
+            "date": "2019-03-01T08:00:00",
+            "text": ["See ", { "type": "italic", "text": "this" }, " and ", { "type": "code", "text": "a`b" }],
+            "photo": "photos/photo_1@01-03-2019_08-00-00.jpg"
+        }"#;
+        let message = serde_json::from_str::<super::Message>(message).unwrap();
+
+        assert!(matches!(message.text, Text::Parts(_)));
+        assert_eq!(message.created_at(), Some(datetime!(2019-03-01 08:00:00 UTC)));
+        assert_eq!(entities_to_md(&message.entities()), "See *this* and `` a`b ``");
+        assert_eq!(
+            message.media().collect::<Vec<_>>(),
+            vec!["photos/photo_1@01-03-2019_08-00-00.jpg"]
+        );
+    }
+
+    #[test]
+    fn export_kinds() {
+        let chat = r#"{ "type": "saved_messages", "id": 1, "messages": [] }"#;
+        let Export::Chat(chat) = serde_json::from_str::<Export>(chat).unwrap() else {
+            panic!("single chat export expected");
+        };
+        assert_eq!(chat.space_name(), "Saved Messages");
+
+        let account = r#"{
+            "personal_information": { "first_name": "A" },
+            "chats": { "about": "", "list": [{ "name": "Channel", "type": "private_channel", "id": 2, "messages": [] }] }
+        }"#;
+        let Export::Account(account) = serde_json::from_str::<Export>(account).unwrap() else {
+            panic!("account export expected");
+        };
+        assert_eq!(account.chats.list[0].space_name(), "Channel");
+        assert!(account.left_chats.is_none());
+    }
+
+    #[test]
+    fn media_outside_of_export_dir() {
+        let dir = std::env::temp_dir().join(format!("dataans-telegram-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("export/photos")).unwrap();
+        fs::write(dir.join("export/photos/photo_1.jpg"), b"photo").unwrap();
+        fs::write(dir.join("secret.txt"), b"secret").unwrap();
+
+        let export_dir = dir.join("export").canonicalize().unwrap();
+
+        assert_eq!(
+            media_path(&export_dir, "photos/photo_1.jpg").unwrap(),
+            export_dir.join("photos/photo_1.jpg")
+        );
+        assert_eq!(
+            media_path(&export_dir, "photos/../photos/photo_1.jpg").unwrap(),
+            export_dir.join("photos/photo_1.jpg")
+        );
+        assert!(media_path(&export_dir, "../secret.txt").is_err());
+        assert!(
+            media_path(
+                &export_dir,
+                dir.join("secret.txt").canonicalize().unwrap().to_str().unwrap()
+            )
+            .is_err()
+        );
+        assert!(media_path(&export_dir, "photos/missing.jpg").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -3,5 +3,7 @@ pub mod export;
 pub mod file;
 pub mod import;
 pub mod note;
+pub mod recovery_kit;
 pub mod space;
 pub mod sync;
+pub mod vault;
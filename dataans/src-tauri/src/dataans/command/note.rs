@@ -8,24 +8,28 @@ use crate::dataans::DataansState;
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn list_notes(state: State<'_, DataansState>, space_id: SpaceId) -> CommandResult<Vec<OwnedNote>> {
+    let state = state.vault();
     Ok(state.note_service.space_notes(space_id).await?)
 }
 
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn create_note(state: State<'_, DataansState>, note: CreateNoteOwned) -> CommandResult<OwnedNote> {
+    let state = state.vault();
     Ok(state.note_service.create_note(note).await?)
 }
 
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn update_note(state: State<'_, DataansState>, note_data: UpdateNote<'static>) -> CommandResult<OwnedNote> {
+    let state = state.vault();
     Ok(state.note_service.update_note(note_data).await?)
 }
 
 #[instrument(ret, skip(state))]
 #[tauri::command]
 pub async fn delete_note(state: State<'_, DataansState>, note_id: NoteId) -> CommandResultEmpty {
+    let state = state.vault();
     Ok(state.note_service.delete_note(note_id).await?)
 }
 
@@ -36,11 +40,13 @@ pub async fn search_notes_in_space(
     query: String,
     space_id: SpaceId,
 ) -> CommandResult<Vec<NoteFullOwned>> {
+    let state = state.vault();
     Ok(state.note_service.search_notes_in_space(&query, space_id).await?)
 }
 
 #[instrument(level = "trace", ret, skip(state))]
 #[tauri::command]
 pub async fn search_notes(state: State<'_, DataansState>, query: String) -> CommandResult<Vec<NoteFullOwned>> {
+    let state = state.vault();
     Ok(state.note_service.search_notes(&query).await?)
 }
@@ -0,0 +1,255 @@
+//! Recovery kit export and import.
+//!
+//! The recovery kit is a printable HTML page with the sync server URL, unique passphrase (salt),
+//! KDF parameters, and the key fingerprint. The same values are embedded into the page as JSON, so the app can
+//! read them back and pre-fill the sign-in form.
+
+use std::path::PathBuf;
+
+use common::error::{CommandError, CommandResult};
+use common::profile::{KdfParams, RecoveryKit, UserProfile};
+use futures::channel::oneshot;
+use tauri::{AppHandle, Runtime, State};
+use tauri_plugin_dialog::{DialogExt, FilePath};
+
+use crate::dataans::crypto::{EncryptionKey, KDF_ALGORITHM, PBKDF2_ITERATIONS, key_fingerprint};
+use crate::dataans::{DataansError, DataansState};
+
+const RECOVERY_KIT_FILE_NAME: &str = "dataans-recovery-kit.html";
+const RECOVERY_KIT_DATA_START: &str = r#"<script type="application/json" id="dataans-recovery-kit">"#;
+const RECOVERY_KIT_DATA_END: &str = "</script>";
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Renders the recovery kit as a printable HTML page.
+pub fn render(recovery_kit: &RecoveryKit) -> Result<String, DataansError> {
+    // `</` is escaped so the JSON data can not close the `<script>` tag.
+    let data = serde_json::to_string_pretty(recovery_kit)?.replace("</", "<\\/");
+
+    let sync_server = escape_html(recovery_kit.sync_server.as_ref().as_str());
+    let auth_scheme = recovery_kit.auth_scheme.pretty();
+    let salt = escape_html(recovery_kit.salt.as_ref());
+    let algorithm = escape_html(&recovery_kit.kdf.algorithm);
+    let iterations = recovery_kit.kdf.iterations;
+    let key_fingerprint = escape_html(&recovery_kit.key_fingerprint);
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8" />
+  <title>Dataans recovery kit</title>
+  <style>
+    body {{ font-family: sans-serif; max-width: 48em; margin: 2em auto; }}
+    td {{ padding: 0.4em 1em 0.4em 0; vertical-align: top; }}
+    code {{ font-size: 1.2em; word-break: break-all; }}
+  </style>
+</head>
+<body>
+  <h1>Dataans recovery kit</h1>
+  <p>
+    Print this page or store it in a safe place. You need these values (and your password) to access your backup
+    on a new device. The password is not included. Anyone who has this kit <b>and</b> your password can decrypt your data.
+  </p>
+  <table>
+    <tr><td>Sync server</td><td><code>{sync_server}</code></td></tr>
+    <tr><td>Authentication</td><td><code>{auth_scheme}</code></td></tr>
+    <tr><td>Unique passphrase</td><td><code>{salt}</code></td></tr>
+    <tr><td>Key derivation</td><td><code>{algorithm}</code>, <code>{iterations}</code> iterations</td></tr>
+    <tr><td>Key fingerprint</td><td><code>{key_fingerprint}</code></td></tr>
+  </table>
+  <p>To restore access, open the app, go to the sync settings, and import this file.</p>
+  {RECOVERY_KIT_DATA_START}
+{data}
+  {RECOVERY_KIT_DATA_END}
+</body>
+</html>
+"#
+    ))
+}
+
+/// Parses the recovery kit from the HTML page created by [render].
+pub fn parse(page: &str) -> Result<RecoveryKit, DataansError> {
+    let data_start = page
+        .find(RECOVERY_KIT_DATA_START)
+        .ok_or(DataansError::InvalidRecoveryKit("recovery kit data not found"))?
+        + RECOVERY_KIT_DATA_START.len();
+    let data_len = page[data_start..]
+        .find(RECOVERY_KIT_DATA_END)
+        .ok_or(DataansError::InvalidRecoveryKit("recovery kit data is not terminated"))?;
+
+    let recovery_kit: RecoveryKit = serde_json::from_str(&page[data_start..data_start + data_len])?;
+
+    if recovery_kit.kdf.algorithm != KDF_ALGORITHM || recovery_kit.kdf.iterations != PBKDF2_ITERATIONS {
+        return Err(DataansError::InvalidRecoveryKit(
+            "unsupported key derivation parameters",
+        ));
+    }
+
+    Ok(recovery_kit)
+}
+
+/// Exports the recovery kit of the current user.
+///
+/// Returns `None` if the user cancelled the file save dialog.
+#[instrument(level = "trace", ret, skip(app, state))]
+#[tauri::command]
+pub async fn export_recovery_kit<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, DataansState>,
+) -> CommandResult<Option<PathBuf>> {
+    let state = state.vault();
+    let UserProfile {
+        auth_token: _,
+        auth_scheme,
+        secret_key,
+        salt,
+        sync_config,
+    } = state.web_service.load_user_profile().await?;
+
+    let encryption_key =
+        EncryptionKey::try_from(secret_key.as_ref().as_slice()).expect("secret key length is always correct");
+
+    let page = render(&RecoveryKit {
+        sync_server: sync_config.url,
+        auth_scheme,
+        salt,
+        kdf: KdfParams {
+            algorithm: KDF_ALGORITHM.to_owned(),
+            iterations: PBKDF2_ITERATIONS,
+        },
+        key_fingerprint: key_fingerprint(&encryption_key),
+    })?;
+
+    let (tx, rx) = oneshot::channel();
+
+    tauri::async_runtime::spawn(async move {
+        app.dialog()
+            .file()
+            .set_file_name(RECOVERY_KIT_FILE_NAME)
+            .add_filter("Recovery kit", &["html"])
+            .set_title("Save recovery kit")
+            .save_file(move |file_path| {
+                let result = match file_path {
+                    Some(FilePath::Path(p)) => Ok(Some(p)),
+                    Some(FilePath::Url(_)) => {
+                        let err = CommandError::Dataans("URLs are not supported".to_string());
+                        error!(?err, "Failed to select file");
+
+                        Err(err)
+                    }
+                    None => Ok(None),
+                };
+                let _ = tx.send(result);
+            });
+    });
+
+    let Ok(path) = rx.await else {
+        // The sender is dropped: the dialog is closed.
+        return Ok(None);
+    };
+    let Some(path) = path? else {
+        debug!("User cancelled recovery kit save dialog");
+
+        return Ok(None);
+    };
+
+    tokio::fs::write(&path, page).await.map_err(DataansError::from)?;
+
+    info!(?path, "Recovery kit saved");
+
+    Ok(Some(path))
+}
+
+/// Reads the recovery kit selected by the user.
+///
+/// Returns `None` if the user cancelled the file selection.
+#[instrument(level = "trace", skip(app))]
+#[tauri::command]
+pub async fn import_recovery_kit<R: Runtime>(app: AppHandle<R>) -> CommandResult<Option<RecoveryKit>> {
+    let (tx, rx) = oneshot::channel();
+
+    tauri::async_runtime::spawn(async move {
+        app.dialog()
+            .file()
+            .add_filter("Recovery kit", &["html"])
+            .set_title("Select recovery kit")
+            .pick_file(move |file_path| {
+                let result = match file_path {
+                    Some(FilePath::Path(p)) => Ok(Some(p)),
+                    Some(FilePath::Url(_)) => {
+                        let err = CommandError::Dataans("URLs are not supported".to_string());
+                        error!(?err, "Failed to select file");
+
+                        Err(err)
+                    }
+                    None => Ok(None),
+                };
+                let _ = tx.send(result);
+            });
+    });
+
+    let Ok(path) = rx.await else {
+        return Ok(None);
+    };
+    let Some(path) = path? else {
+        debug!("User cancelled recovery kit selection");
+
+        return Ok(None);
+    };
+
+    let page = tokio::fs::read_to_string(&path).await.map_err(DataansError::from)?;
+
+    Ok(Some(parse(&page)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::profile::{AuthScheme, KdfParams, RecoveryKit};
+    use url::Url;
+
+    use super::{parse, render};
+    use crate::dataans::crypto::{KDF_ALGORITHM, PBKDF2_ITERATIONS};
+
+    fn recovery_kit(iterations: u32) -> RecoveryKit {
+        RecoveryKit {
+            sync_server: Url::parse("https://backup.dataans.com/").unwrap().into(),
+            auth_scheme: AuthScheme::ApiToken,
+            salt: String::from("tbt-</script>-<b>salt</b>").into(),
+            kdf: KdfParams {
+                algorithm: KDF_ALGORITHM.to_owned(),
+                iterations,
+            },
+            key_fingerprint: String::from("1a2b-3c4d-5e6f-7a8b"),
+        }
+    }
+
+    #[test]
+    fn recovery_kit_round_trip() {
+        let recovery_kit = recovery_kit(PBKDF2_ITERATIONS);
+
+        let page = render(&recovery_kit).unwrap();
+        assert!(!page.contains("<b>salt</b>"));
+
+        let parsed = parse(&page).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&recovery_kit).unwrap(),
+            serde_json::to_value(&parsed).unwrap()
+        );
+    }
+
+    #[test]
+    fn unsupported_kdf_params() {
+        let page = render(&recovery_kit(1_000)).unwrap();
+
+        assert!(parse(&page).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use common::common_api_types::Usage;
 use common::error::{CommandResult, CommandResultEmpty};
 use common::event::{STATUS_UPDATE_EVENT, StatusUpdateEvent};
 use common::profile::{Sync, UserContext, UserProfile};
@@ -8,9 +9,11 @@ use url::Url;
 
 use crate::dataans::command::auth::emit_user_context;
 use crate::dataans::crypto::EncryptionKey;
+use crate::dataans::service::web::WebService;
+use crate::dataans::sync::client::Client;
+use crate::dataans::sync::token::renew_token;
 use crate::dataans::sync::{SyncError, sync_future};
-use crate::dataans::{DataansError, DataansState};
-use crate::window::cf_auth;
+use crate::dataans::{DataansError, DataansState, VaultState};
 
 #[tauri::command]
 pub async fn set_sync_options<R: Runtime>(
@@ -18,6 +21,7 @@ pub async fn set_sync_options<R: Runtime>(
     state: State<'_, DataansState>,
     sync_config: Sync,
 ) -> CommandResult<UserContext> {
+    let state = state.vault();
     let user_context = state.web_service.set_sync_options(sync_config).await?;
 
     emit_user_context(&app, user_context.clone())?;
@@ -27,15 +31,58 @@ pub async fn set_sync_options<R: Runtime>(
 
 #[tauri::command]
 pub async fn full_sync<R: Runtime>(app: AppHandle<R>, state: State<'_, DataansState>) -> CommandResultEmpty {
+    let state = state.vault();
+    Ok(spawn_full_sync(app, &state)?)
+}
+
+/// Returns the storage usage on the sync server.
+#[instrument(level = "trace", ret, skip(state))]
+#[tauri::command]
+pub async fn sync_usage(state: State<'_, DataansState>) -> CommandResult<Usage> {
+    let state = state.vault();
+    let client = sync_client(&state.web_service)?;
+
+    Ok(client.usage().await.map_err(DataansError::from)?)
+}
+
+/// Creates the sync server client for the signed-in user.
+pub fn sync_client(web_service: &WebService) -> Result<Client, DataansError> {
+    let Some(UserProfile {
+        auth_token,
+        auth_scheme,
+        secret_key,
+        sync_config,
+        salt: _,
+    }) = web_service.user_profile()
+    else {
+        return Err(DataansError::UserNotSignedIn);
+    };
+
+    Ok(Client::new(
+        sync_config.url.into(),
+        EncryptionKey::try_from(secret_key.as_ref().as_slice()).expect("secret key length is always correct"),
+        &auth_token,
+        auth_scheme,
+    )?)
+}
+
+/// Starts the full sync in the background.
+///
+/// If the sync fails because of the expired auth token, then the app tries to renew the token
+/// and the sync is resumed after the re-authentication (see the `sign_in` command).
+pub fn spawn_full_sync<R: Runtime>(app: AppHandle<R>, state: &VaultState) -> Result<(), DataansError> {
     let Some(user_profile) = state.web_service.user_profile() else {
-        return Err(DataansError::UserNotSignedIn.into());
+        return Err(DataansError::UserNotSignedIn);
     };
 
     let operation_logger = Arc::clone(&state.operation_logger);
     let files_path = Arc::clone(&state.files_path);
+    let web_service = Arc::clone(&state.web_service);
+    let file_service = Arc::clone(&state.file_service);
 
     let UserProfile {
         auth_token,
+        auth_scheme,
         secret_key,
         sync_config,
         salt: _,
@@ -49,21 +96,35 @@ pub async fn full_sync<R: Runtime>(app: AppHandle<R>, state: State<'_, DataansSt
             &app,
             files_path,
             &auth_token,
+            auth_scheme,
         )
         .await
         .map(|_| StatusUpdateEvent::SyncSuccessful);
 
+        if sync_result.is_ok() {
+            // Index the text of the downloaded files for the notes search.
+            if let Err(err) = file_service.index_files_text().await {
+                warn!(?err, "Failed to index files text");
+            }
+        }
+
         if let Err(SyncError::TokenExpired) = &sync_result {
+            web_service.interrupt_sync();
+
+            if renew_token(&app, &web_service, auth_scheme, sync_config.url.as_ref(), true).await {
+                // The sync is resumed by the `sign_in` command.
+                return;
+            }
+
             if let Err(err) = app.emit(
                 STATUS_UPDATE_EVENT,
-                StatusUpdateEvent::SyncFailed("Access token is expired. Please, sign-in again".into()),
+                StatusUpdateEvent::SyncFailed(
+                    "Access token is expired. Please, sign-in again. The synchronization will resume automatically"
+                        .into(),
+                ),
             ) {
                 error!(?err, "Failed to emit status update event");
             };
-
-            if let Err(err) = cf_auth(app, Url::from(sync_config.url.clone())).await {
-                error!(?err, "Failed to open CF-Auth window");
-            }
         } else {
             let status_update_event = sync_result.unwrap_or_else(|err| StatusUpdateEvent::SyncFailed(err.to_string()));
 
@@ -0,0 +1,18 @@
+use common::VaultsInfo;
+use common::error::{CommandResult, CommandResultEmpty};
+use tauri::{AppHandle, Runtime, State};
+
+use crate::dataans::DataansState;
+use crate::dataans::vault::switch_vault_and_reload;
+
+#[instrument(level = "trace", ret, skip(state))]
+#[tauri::command]
+pub async fn list_vaults(state: State<'_, DataansState>) -> CommandResult<VaultsInfo> {
+    Ok(state.vaults_info())
+}
+
+#[instrument(level = "trace", ret, skip(app))]
+#[tauri::command]
+pub async fn switch_vault<R: Runtime>(app: AppHandle<R>, name: String) -> CommandResultEmpty {
+    Ok(switch_vault_and_reload(&app, &name).await?)
+}
@@ -19,12 +19,18 @@
 //! The user is responsible for storing their password. The app never stores the user's password anywhere.
 //!
 //! The key derivation algorithm is [PBKDF2](https://en.wikipedia.org/wiki/PBKDF2). Iteration count is hardcoded
-//! and is equal to 1_200_000.
+//! and is equal to 1_200_000 ([PBKDF2_ITERATIONS]).
 //!
 //! The password and the salt can be very long, so they are hashed using the SHA256 before passing into PBKDF2.
 //! ```
 //! let key = pbkdf2(sha256(password), sha256(salt), 1_200_000);
 //! ```
+//!
+//! # File blob ids
+//!
+//! Files with the same content are stored on the sync server only once. The server-side blob id is derived from the
+//! content hash using the HMAC keyed with the encryption key ([file_blob_id]). So, the server can not link
+//! blobs to the known content or to the blobs of other users.
 
 use aes_gcm::aead::Aead;
 use aes_gcm::{AeadCore, Aes256Gcm, Key, KeyInit, KeySizeUser, Nonce};
@@ -37,11 +43,22 @@ use serde::de::DeserializeOwned;
 use sha2::digest::typenum::Unsigned;
 use sha2::{Digest, Sha256};
 use thiserror::Error;
+use uuid::{Builder, Uuid};
 
 /// AES-GCM 96-bit (12-byte) nonce.
 const NONCE_LENGTH: usize = <Aes256Gcm as AeadCore>::NonceSize::USIZE;
 const HMAC_SHA256_CHECKSUM_LENGTH: usize = 32;
 
+/// Encryption key derivation algorithm name.
+pub const KDF_ALGORITHM: &str = "PBKDF2-HMAC-SHA256";
+/// PBKDF2 iterations count.
+pub const PBKDF2_ITERATIONS: u32 = 1_200_000;
+/// Key fingerprint length in bytes.
+///
+/// The fingerprint is intentionally short: it is enough to detect a typo in the password, but it reveals
+/// less information about the key.
+const KEY_FINGERPRINT_LENGTH: usize = 8;
+
 pub type EncryptionKey = Key<Aes256Gcm>;
 
 #[derive(Debug, Error)]
@@ -162,11 +179,46 @@ pub fn derive_encryption_key(password: &[u8], salt: &[u8]) -> CryptoResult<Encry
     let salt = Sha256::digest(salt).to_vec();
 
     let mut key = [0; <Aes256Gcm as KeySizeUser>::KeySize::USIZE];
-    pbkdf2_hmac::<Sha256>(&password, &salt, 1_200_000, &mut key);
+    pbkdf2_hmac::<Sha256>(&password, &salt, PBKDF2_ITERATIONS, &mut key);
 
     Ok(key.into())
 }
 
+/// Generates a random salt for the encryption key derivation (e.g. for encrypted backups).
+pub fn generate_salt() -> String {
+    SaltString::generate(&mut OsRng).as_str().to_owned()
+}
+
+/// Computes the encryption key fingerprint.
+///
+/// The fingerprint is a truncated domain-separated SHA256 hash of the key formatted as hex groups (e.g. `1a2b-3c4d-5e6f-7a8b`).
+pub fn key_fingerprint(key: &EncryptionKey) -> String {
+    let hash = Sha256::new()
+        .chain_update(b"dataans-key-fingerprint")
+        .chain_update(key)
+        .finalize();
+
+    hash[..KEY_FINGERPRINT_LENGTH]
+        .chunks(2)
+        .map(|chunk| chunk.iter().map(|byte| format!("{byte:02x}")).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Computes the sync server blob id of the file with the given content hash.
+///
+/// The blob id is a truncated domain-separated HMAC-SHA256 of the content hash formatted as UUID v8.
+pub fn file_blob_id(key: &EncryptionKey, content_hash: &str) -> Uuid {
+    use hmac::{Hmac, Mac};
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take a key of any size");
+    mac.update(b"dataans-file-blob-id");
+    mac.update(content_hash.as_bytes());
+    let mac = mac.finalize().into_bytes();
+
+    Builder::from_custom_bytes(mac[..16].try_into().expect("HMAC-SHA256 output is 32 bytes long")).into_uuid()
+}
+
 /// Computes argon2 hash of the encryption key.
 pub fn hash_encryption_key(key: &EncryptionKey) -> Result<String, CryptoError> {
     let salt = SaltString::generate(&mut OsRng);
@@ -220,4 +272,35 @@ mod tests {
 
         assert_eq!(note, decrypted_note);
     }
+
+    #[test]
+    fn key_fingerprint_format() {
+        let key = b"oeifvncpfiejnvdjpvnwifvj12345678";
+
+        let fingerprint = key_fingerprint(key.into());
+
+        assert_eq!(fingerprint.len(), 19);
+        assert_eq!(fingerprint, key_fingerprint(key.into()));
+        assert_ne!(fingerprint, key_fingerprint(b"oeifvncpfiejnvdjpvnwifvj12345679".into()));
+    }
+
+    #[test]
+    fn file_blob_id_is_keyed() {
+        let key = b"oeifvncpfiejnvdjpvnwifvj12345678";
+        let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        assert_eq!(file_blob_id(key.into(), hash), file_blob_id(key.into(), hash));
+        assert_eq!(file_blob_id(key.into(), hash).get_version_num(), 8);
+        assert_ne!(
+            file_blob_id(key.into(), hash),
+            file_blob_id(b"oeifvncpfiejnvdjpvnwifvj12345679".into(), hash)
+        );
+        assert_ne!(
+            file_blob_id(key.into(), hash),
+            file_blob_id(
+                key.into(),
+                "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae"
+            )
+        );
+    }
 }
@@ -21,8 +21,22 @@ pub trait Db: Send + Sync {
     #[allow(dead_code)]
     async fn files(&self) -> Result<Vec<File>, DbError>;
     async fn file_by_id(&self, file_id: Uuid) -> Result<File, DbError>;
+    /// Returns a non-deleted file with the given content hash.
+    async fn file_by_hash(&self, hash: &str) -> Result<Option<File>, DbError>;
+    /// Checks whether any non-deleted file is stored in the given local file.
+    async fn is_file_path_used(&self, path: &str) -> Result<bool, DbError>;
     async fn add_file(&self, file: &File) -> Result<(), DbError>;
     async fn remove_file(&self, file_id: Uuid) -> Result<(), DbError>;
+    /// Returns non-deleted files without the extracted text.
+    async fn files_without_text(&self) -> Result<Vec<File>, DbError>;
+    /// Saves the text extracted from the file. The extracted text is local data and is not synchronized.
+    async fn set_file_text(&self, file_id: Uuid, text: &str) -> Result<(), DbError>;
+    /// Saves the size of the local file. The size is local data and is not synchronized.
+    async fn set_file_size(&self, file_id: Uuid, size: i64) -> Result<(), DbError>;
+    /// Returns `(note id, file id)` pairs of non-deleted attachments whose extracted text contains the query.
+    async fn search_files_text(&self, query: &str) -> Result<Vec<(Uuid, Uuid)>, DbError>;
+    /// Returns non-deleted files attached to non-deleted notes of the space. Files of the latest notes go first.
+    async fn space_files(&self, space_id: Uuid) -> Result<Vec<NoteFile>, DbError>;
 
     async fn spaces(&self) -> Result<Vec<Space>, DbError>;
     async fn space_by_id(&self, space_id: Uuid) -> Result<Space, DbError>;
@@ -66,4 +80,16 @@ pub trait OperationDb: Send + Sync {
 
     /// Marks the file as uploaded in the local database.
     async fn mark_file_as_uploaded(&self, file_id: Uuid) -> Result<(), DbError>;
+
+    /// Returns all spaces including deleted ones.
+    async fn all_spaces(&self) -> Result<Vec<Space>, DbError>;
+
+    /// Returns all notes including deleted ones.
+    async fn all_notes(&self) -> Result<Vec<Note>, DbError>;
+
+    /// Returns all files including deleted ones.
+    async fn all_files(&self) -> Result<Vec<File>, DbError>;
+
+    /// Returns all note attachments as `(note_id, file_id)` pairs.
+    async fn notes_files(&self) -> Result<Vec<(Uuid, Uuid)>, DbError>;
 }
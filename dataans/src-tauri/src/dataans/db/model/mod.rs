@@ -99,6 +99,17 @@ pub struct File {
     pub is_deleted: bool,
     #[serde(skip)]
     pub is_uploaded: bool,
+    /// Hex-encoded SHA-256 hash of the file content.
+    ///
+    /// Files with the same hash share the same local file and the same blob on the sync server.
+    /// Files created before the deduplication support do not have the hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Size of the local file in bytes.
+    ///
+    /// Files created before the integrity check support do not have the size until the first check.
+    #[serde(skip)]
+    pub size: Option<i64>,
 }
 
 impl Hash for File {
@@ -109,9 +120,21 @@ impl Hash for File {
         self.created_at.hash(state);
         self.updated_at.hash(state);
         self.is_deleted.hash(state);
+        // Hashes of the operations created before the deduplication support must not change.
+        if let Some(hash) = &self.hash {
+            hash.hash(state);
+        }
     }
 }
 
+/// A file attached to a note.
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct NoteFile {
+    pub note_id: Uuid,
+    #[sqlx(flatten)]
+    pub file: File,
+}
+
 impl File {
     pub fn new(id: Uuid, name: String, path: String, created_at: OffsetDateTime, updated_at: OffsetDateTime) -> Self {
         Self {
@@ -122,6 +145,36 @@ impl File {
             updated_at,
             is_deleted: false,
             is_uploaded: false,
+            hash: None,
+            size: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    use super::File;
+    use crate::dataans::sync::Hash;
+
+    #[test]
+    fn file_without_hash_is_backward_compatible() {
+        let now = OffsetDateTime::now_utc();
+        let file = File::new(Uuid::new_v4(), "tbt.png".into(), "tbt.png".into(), now, now);
+
+        let json = serde_json::to_value(&file).unwrap();
+        assert!(json.get("hash").is_none());
+
+        let parsed: File = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, file);
+
+        let hashed_file = File {
+            hash: Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".into()),
+            ..file.clone()
+        };
+        assert_ne!(file.digest::<Sha256>(), hashed_file.digest::<Sha256>());
+    }
+}
@@ -72,8 +72,14 @@ impl Operation<'_> {
         transaction: &mut Transaction<'_, Sqlite>,
     ) -> Result<Option<DataEvent>, DbError> {
         let event = match self {
+            // Deleted records are created only by the data import, so deletions are not lost. They are not displayed.
+            Operation::CreateNote(note) if note.is_deleted => {
+                SqliteDb::add_note(note.as_ref(), transaction).await?;
+
+                None
+            }
             Operation::CreateNote(note) => {
-                SqliteDb::add_note(note.as_ref(), operation_time, transaction).await?;
+                SqliteDb::add_note(note.as_ref(), transaction).await?;
 
                 let Note {
                     id,
@@ -96,6 +102,8 @@ impl Operation<'_> {
                             updated_at: _,
                             is_deleted: _,
                             is_uploaded,
+                            hash: _,
+                            size: _,
                         } = file;
 
                         let path = PathBuf::from(path);
@@ -146,6 +154,8 @@ impl Operation<'_> {
                                 updated_at: _,
                                 is_deleted: _,
                                 is_uploaded,
+                                hash: _,
+                                size: _,
                             } = file;
 
                             let path = PathBuf::from(path);
@@ -186,6 +196,11 @@ impl Operation<'_> {
                     None
                 }
             }
+            Operation::CreateFile(file) if file.is_deleted => {
+                SqliteDb::add_file(file, transaction).await?;
+
+                None
+            }
             Operation::CreateFile(file) => {
                 // The commented line below is a bug. Previously, we assumed that when we accept the `CreateFile` operation,
                 // it also means that the file is uploaded. But it is not necessarily true. The operation can be synced with
@@ -193,7 +208,7 @@ impl Operation<'_> {
                 // uploading succeeded, or when two sync processes happened concurrently.
                 // file.is_uploaded = true;
 
-                SqliteDb::add_file(file, operation_time, transaction).await?;
+                SqliteDb::add_file(file, transaction).await?;
 
                 let File {
                     id,
@@ -203,6 +218,8 @@ impl Operation<'_> {
                     updated_at: _,
                     is_deleted: _,
                     is_uploaded,
+                    hash: _,
+                    size: _,
                 } = file.as_ref();
 
                 let path = PathBuf::from(path.clone());
@@ -224,8 +241,13 @@ impl Operation<'_> {
 
                 None
             }
+            Operation::CreateSpace(space) if space.is_deleted => {
+                SqliteDb::add_space(space.as_ref(), transaction).await?;
+
+                None
+            }
             Operation::CreateSpace(space) => {
-                SqliteDb::add_space(space.as_ref(), operation_time, transaction).await?;
+                SqliteDb::add_space(space.as_ref(), transaction).await?;
 
                 let Space {
                     id,
@@ -289,7 +311,7 @@ impl Operation<'_> {
                 let local_note = SqliteDb::note_by_id(*note_id, transaction.as_mut()).await?;
 
                 if local_note.updated_at < operation_time {
-                    SqliteDb::set_note_files(*note_id, files.as_ref(), operation_time, transaction).await?;
+                    SqliteDb::set_note_files(*note_id, files.as_ref(), transaction).await?;
 
                     let Note {
                         id,
@@ -312,6 +334,8 @@ impl Operation<'_> {
                                 updated_at: _,
                                 is_deleted: _,
                                 is_uploaded,
+                                hash: _,
+                                size: _,
                             } = file;
 
                             let path = PathBuf::from(path);
@@ -440,6 +464,15 @@ impl OperationLogger {
         Ok(self.pool.acquire().await?)
     }
 
+    /// Returns the direct connection to the database for modifying local-only data.
+    ///
+    /// # Correctness
+    ///
+    /// Only tables that are never synchronized (e.g. the extracted files text) can be modified using this connection.
+    pub async fn local_data_connection(&self) -> Result<PoolConnection<Sqlite>, DbError> {
+        Ok(self.pool.acquire().await?)
+    }
+
     /// Begins a new transaction.
     ///
     /// The returned guard automatically inserts a new operation during transaction committing.
@@ -559,6 +592,48 @@ impl OperationDb for OperationLogger {
 
         Ok(())
     }
+
+    async fn all_spaces(&self) -> Result<Vec<Space>, DbError> {
+        let mut connection = self.pool.acquire().await?;
+
+        let spaces = sqlx::query_as("SELECT id, name, avatar_id, created_at, updated_at, is_deleted FROM spaces")
+            .fetch_all(&mut *connection)
+            .await?;
+
+        Ok(spaces)
+    }
+
+    async fn all_notes(&self) -> Result<Vec<Note>, DbError> {
+        let mut connection = self.pool.acquire().await?;
+
+        let notes = sqlx::query_as("SELECT id, text, created_at, updated_at, space_id, is_deleted FROM notes")
+            .fetch_all(&mut *connection)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn all_files(&self) -> Result<Vec<File>, DbError> {
+        let mut connection = self.pool.acquire().await?;
+
+        let files = sqlx::query_as(
+            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded, hash, size FROM files",
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+
+        Ok(files)
+    }
+
+    async fn notes_files(&self) -> Result<Vec<(Uuid, Uuid)>, DbError> {
+        let mut connection = self.pool.acquire().await?;
+
+        let notes_files = sqlx::query_as("SELECT note_id, file_id FROM notes_files")
+            .fetch_all(&mut *connection)
+            .await?;
+
+        Ok(notes_files)
+    }
 }
 
 /// sqlx transaction wrapper for automatic user operation logging.
@@ -8,7 +8,7 @@ use uuid::Uuid;
 use super::*;
 
 const NOTE_FILES: &str =
-    "SELECT files.id, files.name, files.path, files.created_at, files.updated_at, files.is_deleted, files.is_uploaded
+    "SELECT files.id, files.name, files.path, files.created_at, files.updated_at, files.is_deleted, files.is_uploaded, files.hash, files.size
     FROM files
         LEFT JOIN notes_files ON files.id = notes_files.file_id
     WHERE notes_files.note_id = ?1 AND files.is_deleted = FALSE";
@@ -85,28 +85,28 @@ impl SqliteDb {
         Ok(space)
     }
 
-    pub async fn add_space(
-        space: &Space,
-        now: OffsetDateTime,
-        transaction: &mut Transaction<'_, sqlx::Sqlite>,
-    ) -> Result<(), DbError> {
+    /// Inserts the space.
+    ///
+    /// The space keeps its own dates, so imported spaces are created with their original dates.
+    pub async fn add_space(space: &Space, transaction: &mut Transaction<'_, sqlx::Sqlite>) -> Result<(), DbError> {
         let Space {
             id,
             name,
             avatar_id,
-            created_at: _,
-            updated_at: _,
-            is_deleted: _,
+            created_at,
+            updated_at,
+            is_deleted,
         } = space;
 
-        sqlx::query!(
-            "INSERT INTO spaces (id, name, avatar_id, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-            id,
-            name,
-            avatar_id,
-            now,
-            now,
+        sqlx::query(
+            "INSERT INTO spaces (id, name, avatar_id, created_at, updated_at, is_deleted) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         )
+        .bind(id)
+        .bind(name)
+        .bind(avatar_id)
+        .bind(created_at)
+        .bind(updated_at)
+        .bind(is_deleted)
         .execute(&mut **transaction)
         .await?;
 
@@ -205,28 +205,28 @@ impl SqliteDb {
         Ok(note)
     }
 
-    pub async fn add_note(
-        note: &Note,
-        now: OffsetDateTime,
-        transaction: &mut Transaction<'_, Sqlite>,
-    ) -> Result<(), DbError> {
+    /// Inserts the note.
+    ///
+    /// The note keeps its own dates, so imported notes are created with their original dates.
+    pub async fn add_note(note: &Note, transaction: &mut Transaction<'_, Sqlite>) -> Result<(), DbError> {
         let Note {
             id,
             text,
-            created_at: _,
-            updated_at: _,
+            created_at,
+            updated_at,
             space_id,
-            is_deleted: _,
+            is_deleted,
         } = note;
 
-        sqlx::query!(
-            "INSERT INTO notes (id, text, created_at,  updated_at, space_id) VALUES (?1, ?2, ?3, ?4, ?5)",
-            id,
-            text,
-            now,
-            now,
-            space_id,
+        sqlx::query(
+            "INSERT INTO notes (id, text, created_at, updated_at, space_id, is_deleted) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         )
+        .bind(id)
+        .bind(text)
+        .bind(created_at)
+        .bind(updated_at)
+        .bind(space_id)
+        .bind(is_deleted)
         .execute(&mut **transaction)
         .await?;
 
@@ -265,7 +265,7 @@ impl SqliteDb {
     /// The same as [SqliteDb::file_by_id] but returns the file even if deleted.
     pub async fn absolute_file_by_id(file_id: Uuid, connection: &mut SqliteConnection) -> Result<File, DbError> {
         let file = sqlx::query_as(
-            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded FROM files WHERE id = ?1",
+            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded, hash, size FROM files WHERE id = ?1",
         )
         .bind(file_id)
         .fetch_one(&mut *connection)
@@ -277,7 +277,7 @@ impl SqliteDb {
     /// Returns the file by its id. Returns an error if the file is deleted.
     pub async fn file_by_id(file_id: Uuid, connection: &mut SqliteConnection) -> Result<File, DbError> {
         let file = sqlx::query_as(
-            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded FROM files WHERE id = ?1 AND is_deleted = FALSE",
+            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded, hash, size FROM files WHERE id = ?1 AND is_deleted = FALSE",
         )
         .bind(file_id)
         .fetch_one(&mut *connection)
@@ -306,36 +306,63 @@ impl SqliteDb {
         Ok(files)
     }
 
-    pub async fn add_file(
-        file: &File,
-        now: OffsetDateTime,
-        transaction: &mut Transaction<'_, Sqlite>,
-    ) -> Result<(), DbError> {
+    /// Inserts the file.
+    ///
+    /// The file keeps its own dates, so imported files are created with their original dates.
+    pub async fn add_file(file: &File, transaction: &mut Transaction<'_, Sqlite>) -> Result<(), DbError> {
         let File {
             id,
             name,
             path,
-            created_at: _,
-            updated_at: _,
-            is_deleted: _,
+            created_at,
+            updated_at,
+            is_deleted,
             is_uploaded,
+            hash,
+            size,
         } = file;
 
-        sqlx::query!(
-            "INSERT INTO files (id, name, path, created_at, updated_at, is_uploaded) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            id,
-            name,
-            path,
-            now,
-            now,
-            is_uploaded,
+        sqlx::query(
+            "INSERT INTO files (id, name, path, created_at, updated_at, is_deleted, is_uploaded, hash, size) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         )
+        .bind(id)
+        .bind(name)
+        .bind(path)
+        .bind(created_at)
+        .bind(updated_at)
+        .bind(is_deleted)
+        .bind(is_uploaded)
+        .bind(hash)
+        .bind(size)
         .execute(&mut **transaction)
         .await?;
 
         Ok(())
     }
 
+    /// Returns a non-deleted file with the given content hash.
+    pub async fn file_by_hash(hash: &str, connection: &mut SqliteConnection) -> Result<Option<File>, DbError> {
+        let file = sqlx::query_as(
+            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded, hash, size FROM files WHERE hash = ?1 AND is_deleted = FALSE LIMIT 1",
+        )
+        .bind(hash)
+        .fetch_optional(&mut *connection)
+        .await?;
+
+        Ok(file)
+    }
+
+    /// Checks whether any non-deleted file is stored in the given local file.
+    pub async fn is_file_path_used(path: &str, connection: &mut SqliteConnection) -> Result<bool, DbError> {
+        let (used,): (bool,) =
+            sqlx::query_as("SELECT EXISTS(SELECT 1 FROM files WHERE path = ?1 AND is_deleted = FALSE)")
+                .bind(path)
+                .fetch_one(&mut *connection)
+                .await?;
+
+        Ok(used)
+    }
+
     pub async fn remove_file(
         file_id: Uuid,
         now: OffsetDateTime,
@@ -352,18 +379,15 @@ impl SqliteDb {
         Ok(())
     }
 
+    /// Replaces the note attachments.
+    ///
+    /// The note update date is not changed: attachments are always set together with the note creation or update,
+    /// so created notes keep their original dates.
     pub async fn set_note_files(
         note_id: Uuid,
         files: &[Uuid],
-        now: OffsetDateTime,
         transaction: &mut Transaction<'_, Sqlite>,
     ) -> Result<(), DbError> {
-        sqlx::query("UPDATE notes SET updated_at = ?1 WHERE id = ?2")
-            .bind(now)
-            .bind(note_id)
-            .execute(&mut **transaction)
-            .await?;
-
         sqlx::query("DELETE FROM notes_files WHERE note_id = ?1")
             .bind(note_id)
             .execute(&mut **transaction)
@@ -380,9 +404,77 @@ impl SqliteDb {
         Ok(())
     }
 
+    pub async fn files_without_text(connection: &mut SqliteConnection) -> Result<Vec<File>, DbError> {
+        let files = sqlx::query_as(
+            "SELECT files.id, files.name, files.path, files.created_at, files.updated_at, files.is_deleted, files.is_uploaded, files.hash, files.size
+            FROM files
+                LEFT JOIN files_text ON files.id = files_text.file_id
+            WHERE files_text.file_id IS NULL AND files.is_deleted = FALSE",
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+
+        Ok(files)
+    }
+
+    pub async fn set_file_text(file_id: Uuid, text: &str, connection: &mut SqliteConnection) -> Result<(), DbError> {
+        sqlx::query("INSERT OR REPLACE INTO files_text (file_id, text) VALUES (?1, ?2)")
+            .bind(file_id)
+            .bind(text)
+            .execute(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_file_size(file_id: Uuid, size: i64, connection: &mut SqliteConnection) -> Result<(), DbError> {
+        sqlx::query("UPDATE files SET size = ?1 WHERE id = ?2")
+            .bind(size)
+            .bind(file_id)
+            .execute(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn search_files_text(
+        query: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<Vec<(Uuid, Uuid)>, DbError> {
+        // `instr` is case-sensitive like the notes text search.
+        let matches = sqlx::query_as(
+            "SELECT notes_files.note_id, files.id
+            FROM files_text
+                JOIN files ON files.id = files_text.file_id
+                JOIN notes_files ON notes_files.file_id = files.id
+            WHERE instr(files_text.text, ?1) > 0 AND files.is_deleted = FALSE",
+        )
+        .bind(query)
+        .fetch_all(&mut *connection)
+        .await?;
+
+        Ok(matches)
+    }
+
+    pub async fn space_files(space_id: Uuid, connection: &mut SqliteConnection) -> Result<Vec<NoteFile>, DbError> {
+        let files = sqlx::query_as(
+            "SELECT notes_files.note_id, files.id, files.name, files.path, files.created_at, files.updated_at, files.is_deleted, files.is_uploaded, files.hash, files.size
+            FROM notes
+                JOIN notes_files ON notes_files.note_id = notes.id
+                JOIN files ON files.id = notes_files.file_id
+            WHERE notes.space_id = ?1 AND notes.is_deleted = FALSE AND files.is_deleted = FALSE
+            ORDER BY notes.created_at DESC, files.name",
+        )
+        .bind(space_id)
+        .fetch_all(&mut *connection)
+        .await?;
+
+        Ok(files)
+    }
+
     pub async fn files(connection: &mut SqliteConnection) -> Result<Vec<File>, DbError> {
         let files = sqlx::query_as(
-            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded FROM files WHERE is_deleted = FALSE",
+            "SELECT id, name, path, created_at, updated_at, is_deleted, is_uploaded, hash, size FROM files WHERE is_deleted = FALSE",
         )
         .fetch_all(&mut *connection)
         .await?;
@@ -408,12 +500,25 @@ impl Db for SqliteDb {
         SqliteDb::file_by_id(file_id, &mut connection).await
     }
 
+    #[instrument(ret, skip(self))]
+    async fn file_by_hash(&self, hash: &str) -> Result<Option<File>, DbError> {
+        let mut connection = self.pool.read_only_connection().await?;
+
+        SqliteDb::file_by_hash(hash, &mut connection).await
+    }
+
+    #[instrument(ret, skip(self))]
+    async fn is_file_path_used(&self, path: &str) -> Result<bool, DbError> {
+        let mut connection = self.pool.read_only_connection().await?;
+
+        SqliteDb::is_file_path_used(path, &mut connection).await
+    }
+
     #[instrument(ret, skip(self))]
     async fn add_file(&self, file: &File) -> Result<(), DbError> {
         let mut transaction = self.pool.begin(Operation::CreateFile(Cow::Borrowed(file))).await?;
-        let now = transaction.now();
 
-        Self::add_file(file, now, transaction.transaction()).await?;
+        Self::add_file(file, transaction.transaction()).await?;
         transaction.commit().await?;
 
         Ok(())
@@ -430,6 +535,41 @@ impl Db for SqliteDb {
         Ok(())
     }
 
+    #[instrument(ret, skip(self))]
+    async fn files_without_text(&self) -> Result<Vec<File>, DbError> {
+        let mut connection = self.pool.read_only_connection().await?;
+
+        SqliteDb::files_without_text(&mut connection).await
+    }
+
+    #[instrument(ret, skip(self, text), fields(text_len = text.len()))]
+    async fn set_file_text(&self, file_id: Uuid, text: &str) -> Result<(), DbError> {
+        let mut connection = self.pool.local_data_connection().await?;
+
+        SqliteDb::set_file_text(file_id, text, &mut connection).await
+    }
+
+    #[instrument(ret, skip(self))]
+    async fn set_file_size(&self, file_id: Uuid, size: i64) -> Result<(), DbError> {
+        let mut connection = self.pool.local_data_connection().await?;
+
+        SqliteDb::set_file_size(file_id, size, &mut connection).await
+    }
+
+    #[instrument(ret, skip(self))]
+    async fn search_files_text(&self, query: &str) -> Result<Vec<(Uuid, Uuid)>, DbError> {
+        let mut connection = self.pool.read_only_connection().await?;
+
+        SqliteDb::search_files_text(query, &mut connection).await
+    }
+
+    #[instrument(ret, skip(self))]
+    async fn space_files(&self, space_id: Uuid) -> Result<Vec<NoteFile>, DbError> {
+        let mut connection = self.pool.read_only_connection().await?;
+
+        SqliteDb::space_files(space_id, &mut connection).await
+    }
+
     #[instrument(ret, skip(self))]
     async fn spaces(&self) -> Result<Vec<Space>, DbError> {
         let mut connection = self.pool.read_only_connection().await?;
@@ -452,9 +592,8 @@ impl Db for SqliteDb {
     #[instrument(ret, skip(self))]
     async fn create_space(&self, space: &Space) -> Result<(), DbError> {
         let mut transaction = self.pool.begin(Operation::CreateSpace(Cow::Borrowed(space))).await?;
-        let now = transaction.now();
 
-        SqliteDb::add_space(space, now, transaction.transaction()).await?;
+        SqliteDb::add_space(space, transaction.transaction()).await?;
         transaction.commit().await?;
 
         Ok(())
@@ -517,9 +656,8 @@ impl Db for SqliteDb {
     #[instrument(ret, skip(self))]
     async fn create_note(&self, note: &Note) -> Result<(), DbError> {
         let mut transaction = self.pool.begin(Operation::CreateNote(Cow::Borrowed(note))).await?;
-        let now = transaction.now();
 
-        SqliteDb::add_note(note, now, transaction.transaction()).await?;
+        SqliteDb::add_note(note, transaction.transaction()).await?;
         transaction.commit().await?;
 
         Ok(())
@@ -560,9 +698,8 @@ impl Db for SqliteDb {
             .pool
             .begin(Operation::SetNoteFiles(note_id, Cow::Borrowed(files)))
             .await?;
-        let now = transaction.now();
 
-        SqliteDb::set_note_files(note_id, files, now, transaction.transaction()).await?;
+        SqliteDb::set_note_files(note_id, files, transaction.transaction()).await?;
         transaction.commit().await?;
 
         Ok(())
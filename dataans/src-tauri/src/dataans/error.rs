@@ -43,9 +43,21 @@ pub enum DataansError {
     #[error("can not create an image from raw image data")]
     ImageFromRaw,
 
-    #[error("Incorrect import file type: only `json` is supported: {0}")]
+    #[error("Incorrect import file type: only `json` and `zip` are supported: {0}")]
     IncorrectImportFileType(String),
 
+    #[error("zip error: {0:?}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("invalid backup archive: {0}")]
+    InvalidArchive(&'static str),
+
+    #[error("the backup archive is encrypted: passphrase is required")]
+    ArchivePassphraseRequired,
+
+    #[error("invalid backup archive passphrase")]
+    InvalidArchivePassphrase,
+
     #[error("failed to register the user: {0}")]
     SignUpFailed(String),
 
@@ -67,8 +79,20 @@ pub enum DataansError {
     #[error("invalid credentials: {0}")]
     InvalidCredentials(&'static str),
 
+    #[error("invalid recovery kit: {0}")]
+    InvalidRecoveryKit(&'static str),
+
+    #[error("vault not found: {0}")]
+    VaultNotFound(String),
+
+    #[error("invalid vault '{0}': {1}")]
+    InvalidVault(String, &'static str),
+
     #[error(transparent)]
     Sync(#[from] crate::dataans::sync::SyncError),
+
+    #[error("blocking task failed: {0:?}")]
+    BlockingTask(#[from] tokio::task::JoinError),
 }
 
 impl From<DataansError> for CommandError {
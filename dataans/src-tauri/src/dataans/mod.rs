@@ -8,22 +8,25 @@ use tauri::async_runtime::block_on;
 use tauri::plugin::{Builder, TauriPlugin};
 use tauri::{Manager, Runtime};
 
-use crate::dataans::db::OperationLogger;
 use crate::dataans::db::sqlite::SqliteDb;
-use crate::{CONFIG_FILE_NAME, CONFIGS_DIR, FILES_DIR, PROFILE_DIR};
+use crate::dataans::db::{DbError, OperationLogger};
+use crate::{CONFIG_FILE_NAME, CONFIGS_DIR, DB_DIR, FILES_DIR, PROFILE_DIR};
 
+mod archive;
 mod command;
 mod crypto;
 mod db;
 pub mod error;
 mod service;
 mod sync;
+pub mod vault;
 
 use crate::dataans::error::DataansError;
 use crate::dataans::service::file::FileService;
 use crate::dataans::service::note::NoteService;
 use crate::dataans::service::space::SpaceService;
 use crate::dataans::service::web::WebService;
+pub use crate::dataans::vault::DataansState;
 
 pub struct State<D> {
     base_path: Arc<Path>,
@@ -36,18 +39,33 @@ pub struct State<D> {
     operation_logger: Arc<OperationLogger>,
 }
 
-pub type DataansState = State<SqliteDb>;
+/// State of the opened vault.
+pub type VaultState = State<SqliteDb>;
 
-impl DataansState {
-    pub async fn init(db_dir: PathBuf, base_path: Arc<Path>) -> Self {
-        // It's okay to panic in this function because the app is useless without a working db.
+fn create_dir_if_not_exists(dir: &Path) {
+    if !dir.exists() {
+        match fs::create_dir_all(dir) {
+            Ok(()) => info!(?dir, "Successfully created directory"),
+            Err(err) => error!(?err, ?dir, "Filed to create directory"),
+        }
+    }
+}
+
+impl VaultState {
+    /// Initializes the vault state: creates vault directories, opens the database, loads the user profile, etc.
+    pub async fn init(base_path: Arc<Path>) -> Result<Self, DataansError> {
+        let db_dir = base_path.join(DB_DIR);
+        create_dir_if_not_exists(&base_path);
+        create_dir_if_not_exists(&db_dir);
+        create_dir_if_not_exists(&base_path.join(FILES_DIR));
+        create_dir_if_not_exists(&base_path.join(PROFILE_DIR));
 
         let db_file = db_dir.join("dataans.sqlite");
 
         info!(?db_file, "Database file");
 
         if !db_file.exists() {
-            std::fs::File::create(&db_file).expect("Can not create db file");
+            std::fs::File::create(&db_file)?;
         }
 
         let pool = SqlitePoolOptions::new()
@@ -56,11 +74,16 @@ impl DataansState {
             .acquire_timeout(std::time::Duration::from_secs(5))
             .connect_lazy(&format!(
                 "sqlite://{}",
-                db_file.to_str().expect("Bro, wtf, use UTF-8 paths")
+                db_file
+                    .to_str()
+                    .ok_or_else(|| DataansError::PathIsNotUtf8(db_file.clone()))?
             ))
-            .expect("can not connect to sqlite db");
+            .map_err(DbError::from)?;
 
-        sqlx::migrate!().run(&pool).await.expect("Failed to run migrations");
+        sqlx::migrate!()
+            .run(&pool)
+            .await
+            .map_err(|err| DbError::from(sqlx::Error::from(err)))?;
 
         let operation_logger = Arc::new(OperationLogger::new(pool));
         let sqlite = Arc::new(SqliteDb::new(Arc::clone(&operation_logger)));
@@ -73,13 +96,16 @@ impl DataansState {
             Arc::clone(&files_path),
         ));
         let file_service = Arc::new(FileService::new(Arc::clone(&sqlite), Arc::clone(&files_path)));
-        let web_service = Arc::new(
-            WebService::new(&base_path.join(PROFILE_DIR))
-                .await
-                .expect("can not initiate web service"),
-        );
+        // Files added by older app versions or by an interrupted sync do not have the extracted text yet.
+        let indexed_file_service = Arc::clone(&file_service);
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = indexed_file_service.index_files_text().await {
+                warn!(?err, "Failed to index files text");
+            }
+        });
+        let web_service = Arc::new(WebService::new(&base_path.join(PROFILE_DIR)).await?);
 
-        Self {
+        Ok(Self {
             base_path,
             files_path,
 
@@ -88,7 +114,7 @@ impl DataansState {
             file_service,
             web_service,
             operation_logger,
-        }
+        })
     }
 }
 
@@ -108,18 +134,30 @@ pub fn init_dataans_plugin<R: Runtime>() -> TauriPlugin<R> {
             command::note::search_notes_in_space,
             command::note::search_notes,
             command::file::upload_file,
+            command::file::import_file,
             command::file::delete_file,
+            command::file::thumbnail,
+            command::file::space_files,
+            command::file::verify_files,
+            command::file::repair_files,
             command::file::gen_random_avatar,
             command::file::pick_avatar,
             command::file::handle_clipboard_image,
             command::file::save_file_as,
             command::export::export_app_data,
             command::import::import_app_data,
+            command::import::import_md_dir,
+            command::import::import_telegram,
             command::auth::profile,
             command::auth::sign_in,
             command::auth::sign_out,
+            command::recovery_kit::export_recovery_kit,
+            command::recovery_kit::import_recovery_kit,
             command::sync::set_sync_options,
             command::sync::full_sync,
+            command::sync::sync_usage,
+            command::vault::list_vaults,
+            command::vault::switch_vault,
         ])
         .setup(|app_handle, _api| {
             info!("Starting app setup...");
@@ -127,6 +165,7 @@ pub fn init_dataans_plugin<R: Runtime>() -> TauriPlugin<R> {
             let path_resolver = app_handle.path();
             let config = crate::config::load_config_inner(app_handle).expect("config reading should not fail");
             let app_data = PathBuf::from(config.app.base_path);
+            let vaults = config.app.vaults;
 
             debug!(?app_data);
             if !app_data.exists() {
@@ -136,24 +175,7 @@ pub fn init_dataans_plugin<R: Runtime>() -> TauriPlugin<R> {
                 }
             }
 
-            let db_dir = app_data.join("db");
-            let files_dir = app_data.join(FILES_DIR);
             let configs_dir = app_data.join(CONFIGS_DIR);
-            let profile_dir = app_data.join(PROFILE_DIR);
-
-            if !db_dir.exists() {
-                match fs::create_dir(&db_dir) {
-                    Ok(()) => info!(?db_dir, "Successfully created database directory"),
-                    Err(err) => error!(?err, ?db_dir, "Filed to create database directory"),
-                }
-            }
-
-            if !files_dir.exists() {
-                match fs::create_dir(&files_dir) {
-                    Ok(()) => info!(?files_dir, "Successfully created files directory"),
-                    Err(err) => error!(?err, ?files_dir, "Filed to create files directory"),
-                }
-            }
 
             if !configs_dir.exists() {
                 match fs::create_dir(&configs_dir) {
@@ -162,13 +184,6 @@ pub fn init_dataans_plugin<R: Runtime>() -> TauriPlugin<R> {
                 }
             }
 
-            if !profile_dir.exists() {
-                match fs::create_dir(&profile_dir) {
-                    Ok(()) => info!(?profile_dir, "Successfully created profile directory"),
-                    Err(err) => error!(?err, ?profile_dir, "Filed to create profile directory"),
-                }
-            }
-
             let config_file = configs_dir.join(CONFIG_FILE_NAME);
             if !config_file.exists() {
                 let resource_dir = path_resolver.resource_dir()?.join("resources");
@@ -204,9 +219,13 @@ pub fn init_dataans_plugin<R: Runtime>() -> TauriPlugin<R> {
                 }
             }
 
-            let dataans_state = block_on(DataansState::init(db_dir, app_data.into()));
+            // The default vault lives directly in the app data directory.
+            let dataans_state = block_on(DataansState::init(app_data, vaults))?;
+            vault::allow_vault_files(app_handle, &dataans_state.active_vault_path())?;
             app_handle.manage(dataans_state);
 
+            tauri::async_runtime::spawn(sync::token::watch_token_expiration(app_handle.clone()));
+
             Ok(())
         })
         .build()
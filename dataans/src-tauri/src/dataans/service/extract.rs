@@ -0,0 +1,274 @@
+//! Text extraction from attachments.
+//!
+//! The extracted text is used by the notes search. Office documents (`docx`, `pptx`, `xlsx`, and OpenDocument files)
+//! are zip archives with XML inside, so we only need to strip the XML markup. PDF files are parsed with the `pdf-extract` crate.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+
+use thiserror::Error;
+use zip::ZipArchive;
+
+/// Maximum size (in bytes) of the stored text of one file. Longer texts are truncated.
+const MAX_TEXT_SIZE: usize = 1024 * 1024;
+
+/// Maximum size (in bytes) of one XML document read from an office document.
+///
+/// The markup takes much more space than the text, but documents are compressed, so the size must be limited anyway.
+const MAX_XML_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Maximum size (in bytes) of the PDF file to extract the text from. The PDF parser loads the whole file into memory.
+const MAX_PDF_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Closing tags that end a paragraph (or a spreadsheet string) in supported XML documents.
+const PARAGRAPH_END_TAGS: &[&str] = &["w:p", "a:p", "text:p", "text:h", "si"];
+
+/// Tags that represent a line break.
+const LINE_BREAK_TAGS: &[&str] = &["w:br", "w:cr", "a:br", "text:line-break"];
+
+/// Tags that represent a tab character.
+const TAB_TAGS: &[&str] = &["w:tab", "text:tab"];
+
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    #[error("IO error: {0:?}")]
+    Io(#[from] io::Error),
+
+    #[error("zip error: {0:?}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("PDF error: {0}")]
+    Pdf(String),
+}
+
+/// Document format supported by the text extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    PlainText,
+    Pdf,
+    Docx,
+    Pptx,
+    Xlsx,
+    OpenDocument,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        Some(match extension.as_str() {
+            "txt" | "text" | "md" | "markdown" | "csv" | "tsv" | "log" | "json" | "toml" | "yaml" | "yml" | "xml"
+            | "html" | "htm" | "rst" | "org" | "tex" => Format::PlainText,
+            "pdf" => Format::Pdf,
+            "docx" => Format::Docx,
+            "pptx" => Format::Pptx,
+            "xlsx" => Format::Xlsx,
+            "odt" | "ods" | "odp" => Format::OpenDocument,
+            _ => return None,
+        })
+    }
+}
+
+/// Extracts the text from the file.
+///
+/// Returns `None` if the file format is not supported.
+pub fn extract_text(path: &Path) -> Result<Option<String>, ExtractError> {
+    let Some(format) = Format::from_path(path) else {
+        return Ok(None);
+    };
+
+    let mut text = match format {
+        // Only the beginning of the file is read: the rest would be truncated anyway.
+        Format::PlainText => read_lossy(File::open(path)?, MAX_TEXT_SIZE as u64)?,
+        Format::Pdf if fs::metadata(path)?.len() > MAX_PDF_SIZE => String::new(),
+        // The PDF parser may panic on malformed documents.
+        Format::Pdf => catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text(path)))
+            .map_err(|_| ExtractError::Pdf("PDF parser panicked".into()))?
+            .map_err(|err| ExtractError::Pdf(err.to_string()))?,
+        Format::Docx => zip_xml_text(path, |name| name == "word/document.xml")?,
+        Format::Pptx => zip_xml_text(path, |name| {
+            name.starts_with("ppt/slides/slide") && name.ends_with(".xml")
+        })?,
+        Format::Xlsx => zip_xml_text(path, |name| name == "xl/sharedStrings.xml")?,
+        Format::OpenDocument => zip_xml_text(path, |name| name == "content.xml")?,
+    };
+
+    truncate(&mut text, MAX_TEXT_SIZE);
+
+    Ok(Some(text))
+}
+
+/// Extracts the text from XML files inside the zip archive.
+///
+/// Files are processed in the natural order of their names (`slide2.xml` goes before `slide10.xml`).
+fn zip_xml_text(path: &Path, is_text_file: impl Fn(&str) -> bool) -> Result<String, ExtractError> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    let mut names = archive
+        .file_names()
+        .filter(|name| is_text_file(name))
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    names.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+    let mut text = String::new();
+    for name in names {
+        let xml = read_lossy(archive.by_name(&name)?, MAX_XML_SIZE)?;
+
+        text.push_str(&xml_text(&xml));
+        if text.len() > MAX_TEXT_SIZE {
+            break;
+        }
+    }
+
+    Ok(text)
+}
+
+/// Reads at most `limit` bytes and converts them to a string. Invalid UTF-8 sequences are replaced.
+fn read_lossy(reader: impl Read, limit: u64) -> io::Result<String> {
+    let mut data = Vec::new();
+    reader.take(limit).read_to_end(&mut data)?;
+
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Returns the text content of the XML document.
+///
+/// Tags are removed, entities are decoded, and paragraphs are separated by new lines.
+fn xml_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut rest = xml;
+
+    while let Some(tag_start) = rest.find('<') {
+        push_unescaped(&mut text, &rest[..tag_start]);
+
+        let Some(tag_len) = rest[tag_start..].find('>') else {
+            // Unclosed tag at the end of the document.
+            return text;
+        };
+        let tag = &rest[tag_start + 1..tag_start + tag_len];
+        rest = &rest[tag_start + tag_len + 1..];
+
+        let (is_closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name = tag.split_whitespace().next().unwrap_or_default().trim_end_matches('/');
+
+        let tags = if is_closing {
+            PARAGRAPH_END_TAGS
+        } else {
+            LINE_BREAK_TAGS
+        };
+        if tags.contains(&name) {
+            text.push('\n');
+        } else if !is_closing && TAB_TAGS.contains(&name) {
+            text.push('\t');
+        }
+    }
+    push_unescaped(&mut text, rest);
+
+    text
+}
+
+/// Decodes XML entities and appends the text to `out`.
+fn push_unescaped(out: &mut String, text: &str) {
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        match rest
+            .find(';')
+            .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)))
+        {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok()?
+            } else {
+                entity.strip_prefix('#')?.parse().ok()?
+            };
+
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Truncates the text to at most `max_size` bytes keeping it a valid UTF-8 string.
+fn truncate(text: &mut String, max_size: usize) {
+    if text.len() <= max_size {
+        return;
+    }
+
+    let mut end = max_size;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Format, read_lossy, truncate, xml_text};
+
+    #[test]
+    fn formats() {
+        assert_eq!(Format::from_path(Path::new("notes.md")), Some(Format::PlainText));
+        assert_eq!(Format::from_path(Path::new("Report.PDF")), Some(Format::Pdf));
+        assert_eq!(Format::from_path(Path::new("letter.docx")), Some(Format::Docx));
+        assert_eq!(Format::from_path(Path::new("table.ods")), Some(Format::OpenDocument));
+        assert_eq!(Format::from_path(Path::new("image.png")), None);
+        assert_eq!(Format::from_path(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn docx_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?><w:document><w:body><w:p><w:r><w:t>Tom &amp; Jerry</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">&lt;3 &#x1F600;</w:t></w:r></w:p><w:p><w:r><w:t>Second</w:t><w:br w:type="page"/><w:t>line</w:t></w:r></w:p></w:body></w:document>"#;
+
+        assert_eq!(xml_text(xml), "Tom & Jerry\t<3 😀\nSecond\nline\n");
+    }
+
+    #[test]
+    fn invalid_entities_are_kept() {
+        assert_eq!(xml_text("<t>a & b &unknown; &#xZZ;</t>"), "a & b &unknown; &#xZZ;");
+    }
+
+    #[test]
+    fn truncate_on_char_boundary() {
+        let mut text = String::from("aé");
+        truncate(&mut text, 2);
+
+        assert_eq!(text, "a");
+    }
+
+    #[test]
+    fn read_is_limited() {
+        assert_eq!(read_lossy("Tom & Jerry".as_bytes(), 3).unwrap(), "Tom");
+    }
+}
@@ -1,19 +1,200 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::{fs, io};
 
 use arboard::Clipboard;
-use common::note::{File, FileId, FileStatus};
-use image::{ImageBuffer, Rgba};
+use common::export::FileV2;
+use common::note::{File, FileId, FileStatus, FilesReport};
+use common::space::{Avatar, Id as SpaceId, SpaceFile};
+use common::{DEFAULT_SPACE_AVATAR_PATH, DEFAULT_THUMBNAIL_SIZE};
+use image::{ImageBuffer, ImageFormat, ImageReader, Rgba};
+use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use uuid::Uuid;
 
 use crate::dataans::DataansError;
 use crate::dataans::db::Db;
-use crate::dataans::db::model::File as FileModel;
+use crate::dataans::db::model::{File as FileModel, NoteFile};
+use crate::dataans::service::extract::extract_text;
+use crate::dataans::sync::client::Client;
 
 // TODO: Introduce `FileServiceError`.
 
+/// Returns the hex-encoded SHA-256 hash of the file content.
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Returns the hex-encoded SHA-256 hash of the file content.
+///
+/// The file is read by chunks on the blocking thread pool, so big files neither block the async runtime
+/// nor are fully loaded into memory.
+async fn file_hash(path: PathBuf) -> Result<String, DataansError> {
+    let hash = tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+        Ok::<_, io::Error>(format!("{:x}", hasher.finalize()))
+    })
+    .await??;
+
+    Ok(hash)
+}
+
+/// Converts the local file size into the database representation.
+fn db_file_size(size: u64) -> i64 {
+    i64::try_from(size).expect("file size should fit in i64")
+}
+
+/// Size of the buffer used for streaming imported files.
+const IMPORT_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Reads the file by chunks and returns the hex-encoded SHA-256 hash of its content.
+///
+/// If `destination` is specified, then the content is copied into a new file at this path.
+async fn stream_file(
+    source: &Path,
+    destination: Option<&Path>,
+    total: u64,
+    on_progress: &mut impl FnMut(u64, u64),
+) -> io::Result<String> {
+    let mut reader = tokio::fs::File::open(source).await?;
+    let mut writer = match destination {
+        Some(destination) => Some(tokio::fs::File::create_new(destination).await?),
+        None => None,
+    };
+
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; IMPORT_BUFFER_SIZE];
+    let mut processed = 0;
+
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+        if let Some(writer) = writer.as_mut() {
+            writer.write_all(&buf[..read]).await?;
+        }
+
+        processed += u64::try_from(read).expect("usize -> u64 conversion should not fail");
+        on_progress(processed, total.max(processed));
+    }
+
+    if let Some(mut writer) = writer {
+        writer.flush().await?;
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Directory inside the files directory with cached image thumbnails.
+const THUMBNAILS_DIR: &str = ".thumbnails";
+
+/// Allowed range of the thumbnail size. It keeps the thumbnails cache bounded.
+const MIN_THUMBNAIL_SIZE: u32 = 32;
+const MAX_THUMBNAIL_SIZE: u32 = 2048;
+
+/// Returns the thumbnail path relative to the files directory.
+fn thumbnail_path(path: &str, size: u32) -> PathBuf {
+    Path::new(THUMBNAILS_DIR).join(format!("{path}_{size}.png"))
+}
+
+/// Returns the thumbnail of the image file, generating it if it is not cached yet.
+///
+/// The thumbnail fits into the `size`x`size` square and keeps the image aspect ratio.
+/// Returns the path relative to the files directory. If the image is already small enough,
+/// then the original file path is returned. `None` means the file is not a supported image.
+pub fn generate_thumbnail(files_path: &Path, path: &str, size: u32) -> Result<Option<PathBuf>, DataansError> {
+    let thumbnail = thumbnail_path(path, size);
+    if files_path.join(&thumbnail).exists() {
+        return Ok(Some(thumbnail));
+    }
+
+    let file_path = files_path.join(path);
+    let reader = ImageReader::open(&file_path)?.with_guessed_format()?;
+    match reader.format() {
+        // Animated images lose their animation in thumbnails.
+        None | Some(ImageFormat::Gif) => return Ok(None),
+        Some(_) => {}
+    }
+
+    let (width, height) = reader.into_dimensions()?;
+    if width <= size && height <= size {
+        return Ok(Some(PathBuf::from(path)));
+    }
+
+    let image = ImageReader::open(&file_path)?.with_guessed_format()?.decode()?;
+
+    fs::create_dir_all(files_path.join(THUMBNAILS_DIR))?;
+    image
+        .thumbnail(size, size)
+        .save_with_format(files_path.join(&thumbnail), ImageFormat::Png)?;
+    debug!(?path, size, "Image thumbnail generated");
+
+    Ok(Some(thumbnail))
+}
+
+/// Generates the default thumbnail of the newly saved or downloaded file.
+///
+/// Image decoding is CPU-heavy, so it runs on the blocking thread pool.
+/// Thumbnails are only a cache, so errors are logged and ignored.
+pub async fn cache_thumbnail(files_path: &Path, path: &str) {
+    let (files_path, file_path) = (files_path.to_path_buf(), path.to_owned());
+    let result =
+        tokio::task::spawn_blocking(move || generate_thumbnail(&files_path, &file_path, DEFAULT_THUMBNAIL_SIZE))
+            .await
+            .map_err(DataansError::from)
+            .and_then(|result| result);
+
+    if let Err(err) = result {
+        warn!(?err, ?path, "Failed to generate the image thumbnail");
+    }
+}
+
+/// Removes all cached thumbnails of the file.
+fn remove_thumbnails(files_path: &Path, path: &str) -> Result<(), DataansError> {
+    let thumbnails_path = files_path.join(THUMBNAILS_DIR);
+    if !thumbnails_path.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(thumbnails_path)? {
+        let entry = entry?;
+
+        let is_file_thumbnail = entry.file_name().to_str().is_some_and(|name| {
+            name.strip_prefix(path)
+                .and_then(|name| name.strip_prefix('_'))
+                .and_then(|name| name.strip_suffix(".png"))
+                .is_some_and(|size| size.parse::<u32>().is_ok())
+        });
+        if is_file_thumbnail {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the path points to a file directly inside the files directory and returns its name.
+fn local_file_name(path: &Path) -> Result<&str, DataansError> {
+    match path.file_name() {
+        Some(name) if Path::new(name) == path => name
+            .to_str()
+            .ok_or_else(|| DataansError::PathIsNotUtf8(path.to_path_buf())),
+        _ => Err(DataansError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid file path: {path:?}"),
+        ))),
+    }
+}
+
 pub struct FileService<D> {
     db: Arc<D>,
     files_path: Arc<Path>,
@@ -24,38 +205,110 @@ impl<D: Db> FileService<D> {
         Self { db, files_path }
     }
 
-    pub async fn register_file(&self, file: File) -> Result<(), DataansError> {
-        let File {
+    /// Registers the space avatar file unless it is already registered (e.g. when spaces are imported from a backup).
+    ///
+    /// The default avatar is the app asset, so its path is kept as is. Other avatars are stored in the files directory.
+    pub async fn register_avatar(&self, avatar: &Avatar<'_>) -> Result<(), DataansError> {
+        if self.db.file_by_id(avatar.id()).await.is_ok() {
+            return Ok(());
+        }
+
+        let path = if avatar.path() == DEFAULT_SPACE_AVATAR_PATH {
+            avatar.path()
+        } else {
+            Path::new(avatar.path())
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| DataansError::PathIsNotUtf8(avatar.path().into()))?
+        };
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path)
+            .to_owned();
+        let now = OffsetDateTime::now_utc();
+
+        self.db
+            .add_file(&FileModel::new(avatar.id(), name, path.to_owned(), now, now))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Registers the file record from the json export (schema V2) keeping its upload status and content hash.
+    ///
+    /// The default avatar is the app asset, so its path is kept as is. Other files are stored in the files directory.
+    /// Local files without the content hash (e.g. from the schema V1 export) are hashed. Uploaded files keep the missing
+    /// hash, because their blob id on the sync server is derived from it.
+    ///
+    /// The text of local files is indexed for the notes search. Deleted files are registered as deleted records,
+    /// so deletions of the imported data are synchronized to other devices.
+    pub async fn register_exported_file(&self, file: &FileV2) -> Result<(), DataansError> {
+        let FileV2 {
             id,
             name,
             path,
-            status: _,
+            created_at,
+            updated_at,
+            is_deleted,
+            is_uploaded,
+            hash,
         } = file;
-        let now = OffsetDateTime::now_utc();
+
+        let path = if path == DEFAULT_SPACE_AVATAR_PATH {
+            path.as_str()
+        } else {
+            Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| DataansError::PathIsNotUtf8(path.into()))?
+        };
+
+        let is_local = !is_deleted && path != DEFAULT_SPACE_AVATAR_PATH && self.files_path.join(path).exists();
+        let hash = match hash {
+            Some(hash) => Some(hash.clone()),
+            None if !*is_uploaded && is_local => {
+                Some(stream_file(&self.files_path.join(path), None, 0, &mut |_, _| {}).await?)
+            }
+            None => None,
+        };
 
         self.db
-            .add_file(&FileModel::new(
-                id.into(),
-                name,
-                path.file_name()
-                    .ok_or_else(|| {
-                        DataansError::IoError(io::Error::new(
-                            io::ErrorKind::IsADirectory,
-                            format!("invalid file path: {path:?}"),
-                        ))
-                    })?
-                    .to_str()
-                    .ok_or_else(|| DataansError::PathIsNotUtf8(path.clone()))?
-                    .to_owned(),
-                now,
-                now,
-            ))
+            .add_file(&FileModel {
+                is_deleted: *is_deleted,
+                is_uploaded: *is_uploaded,
+                hash,
+                ..FileModel::new(*id, name.clone(), path.to_owned(), *created_at, *updated_at)
+            })
             .await?;
+        if is_local {
+            self.index_file_text(*id, path).await?;
+        }
 
         Ok(())
     }
 
     pub async fn file_by_id(&self, file_id: FileId) -> Result<File, DataansError> {
+        let file = self.db.file_by_id(*file_id.as_ref()).await?;
+
+        Ok(self.file_from_model(file))
+    }
+
+    /// Returns files attached to the notes of the space.
+    pub async fn space_files(&self, space_id: SpaceId) -> Result<Vec<SpaceFile>, DataansError> {
+        Ok(self
+            .db
+            .space_files(space_id.inner())
+            .await?
+            .into_iter()
+            .map(|NoteFile { note_id, file }| SpaceFile {
+                note_id: note_id.into(),
+                file: self.file_from_model(file),
+            })
+            .collect())
+    }
+
+    fn file_from_model(&self, file: FileModel) -> File {
         let FileModel {
             id,
             name,
@@ -64,50 +317,365 @@ impl<D: Db> FileService<D> {
             updated_at: _,
             is_deleted: _,
             is_uploaded,
-        } = self.db.file_by_id(*file_id.as_ref()).await?;
+            hash: _,
+            size: _,
+        } = file;
 
         let path = self.files_path.join(path);
         let status = FileStatus::status_for_file(&path, is_uploaded);
 
-        Ok(File {
+        File {
             id: id.into(),
             name,
             path,
             status,
-        })
+        }
     }
 
-    pub async fn upload_file(&self, id: Uuid, name: String, data: &[u8]) -> Result<File, DataansError> {
-        let file_name = format!("{id}_{name}");
+    /// Extracts the text of the file and saves it for the notes search.
+    ///
+    /// An empty text is saved for unsupported files, so they are not processed again.
+    async fn index_file_text(&self, id: Uuid, path: &str) -> Result<(), DataansError> {
+        let file_path = self.files_path.join(path);
+        let text = match tokio::task::spawn_blocking(move || extract_text(&file_path)).await? {
+            Ok(text) => text.unwrap_or_default(),
+            Err(err) => {
+                warn!(?err, ?id, ?path, "Failed to extract the file text");
+
+                String::new()
+            }
+        };
+
+        self.db.set_file_text(id, &text).await?;
 
-        let file_path = self.files_path.join(&file_name);
+        Ok(())
+    }
+
+    /// Extracts the text of all files that have not been processed yet.
+    ///
+    /// Files that have not been downloaded yet are skipped. They are processed after the next sync.
+    /// It runs in the background when the vault is opened and after every successful sync.
+    #[instrument(err, skip(self))]
+    pub async fn index_files_text(&self) -> Result<(), DataansError> {
+        for file in self.db.files_without_text().await? {
+            if file.path == DEFAULT_SPACE_AVATAR_PATH || !self.files_path.join(&file.path).exists() {
+                continue;
+            }
+
+            self.index_file_text(file.id, &file.path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the integrity of local files.
+    ///
+    /// Walks all registered files and the files directory and reports missing, corrupted, and orphaned files.
+    #[instrument(err, skip(self))]
+    pub async fn verify_files(&self) -> Result<FilesReport, DataansError> {
+        let mut report = FilesReport::default();
+        let mut known_paths = HashSet::new();
+        // Files with the same content share the same local file. So, we compute every hash only once.
+        let mut hashes = HashMap::new();
+
+        for file in self.db.files().await? {
+            // The default space avatar is a part of the app resources.
+            if file.path == DEFAULT_SPACE_AVATAR_PATH {
+                continue;
+            }
+
+            known_paths.insert(file.path.clone());
+
+            let path = self.files_path.join(&file.path);
+            let size = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => db_file_size(metadata.len()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    report.missing.push(self.file_from_model(file));
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            match file.size {
+                Some(saved_size) if saved_size != size => {
+                    warn!(?file.id, ?file.path, saved_size, size, "File size does not match the saved size");
+
+                    report.corrupted.push(self.file_from_model(file));
+                    continue;
+                }
+                Some(_) => {}
+                // Files created before the integrity check support do not have the saved size.
+                // The current size is saved, so the next checks detect its changes.
+                None => self.db.set_file_size(file.id, size).await?,
+            }
+
+            let Some(hash) = file.hash.as_ref() else {
+                continue;
+            };
+            let actual_hash = match hashes.entry(file.path.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(file_hash(path).await?),
+            };
+            if *actual_hash != *hash {
+                warn!(?file.id, ?file.path, "File content does not match the saved hash");
+
+                report.corrupted.push(self.file_from_model(file));
+            }
+        }
+
+        for entry in fs::read_dir(&self.files_path)? {
+            let entry = entry?;
+
+            // Directories (e.g. the thumbnails cache) are not user files.
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let is_known = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| known_paths.contains(name));
+            if !is_known {
+                report.orphaned.push(PathBuf::from(entry.file_name()));
+            }
+        }
+        report.orphaned.sort();
+
+        info!(
+            missing = report.missing.len(),
+            corrupted = report.corrupted.len(),
+            orphaned = report.orphaned.len(),
+            "Files integrity check finished"
+        );
+
+        Ok(report)
+    }
+
+    /// Downloads the missing or corrupted file from the sync server.
+    #[instrument(err, skip(self, client))]
+    pub async fn redownload_file(&self, client: &Client, file_id: Uuid) -> Result<File, DataansError> {
+        let file = self.db.file_by_id(file_id).await?;
+
+        remove_thumbnails(&self.files_path, &file.path)?;
+        let path = self.files_path.join(&file.path);
+        client.download_file(client.blob_id(&file), &path).await?;
+        self.db
+            .set_file_size(file.id, db_file_size(tokio::fs::metadata(&path).await?.len()))
+            .await?;
+        cache_thumbnail(&self.files_path, &file.path).await;
+        self.index_file_text(file.id, &file.path).await?;
+
+        Ok(self.file_from_model(file))
+    }
+
+    /// Registers the orphaned file.
+    ///
+    /// `path` must be relative to the files directory.
+    #[instrument(err, skip(self))]
+    pub async fn recover_file(&self, path: &Path) -> Result<File, DataansError> {
+        let file_name = local_file_name(path)?;
+        if self.db.is_file_path_used(file_name).await? {
+            return Err(DataansError::IoError(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("file is already registered: {path:?}"),
+            )));
+        }
+
+        let path = self.files_path.join(file_name);
+        let size = db_file_size(tokio::fs::metadata(&path).await?.len());
+        let hash = file_hash(path).await?;
+
+        // Uploaded files are stored as `<file id>_<file name>`. We restore the original file name if possible.
+        let name = match file_name.split_once('_') {
+            Some((id, name)) if Uuid::parse_str(id).is_ok() && !name.is_empty() => name,
+            _ => file_name,
+        };
 
-        fs::write(&file_path, data)?;
+        let id = Uuid::new_v4();
+        let now = OffsetDateTime::now_utc();
+        self.db
+            .add_file(&FileModel {
+                hash: Some(hash),
+                size: Some(size),
+                ..FileModel::new(id, name.to_owned(), file_name.to_owned(), now, now)
+            })
+            .await?;
+        cache_thumbnail(&self.files_path, file_name).await;
+        self.index_file_text(id, file_name).await?;
 
+        self.file_by_id(id.into()).await
+    }
+
+    /// Deletes the orphaned file.
+    ///
+    /// `path` must be relative to the files directory.
+    #[instrument(err, skip(self))]
+    pub async fn delete_orphaned_file(&self, path: &Path) -> Result<(), DataansError> {
+        let file_name = local_file_name(path)?;
+        if self.db.is_file_path_used(file_name).await? {
+            return Err(DataansError::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("file is registered and can not be deleted: {path:?}"),
+            )));
+        }
+
+        fs::remove_file(self.files_path.join(file_name))?;
+        remove_thumbnails(&self.files_path, file_name)?;
+
+        Ok(())
+    }
+
+    /// Saves the file content and registers a new file.
+    ///
+    /// If a file with the same content already exists, then the new file reuses its local file instead of
+    /// writing a copy. Otherwise, the content is written into the `file_name` file.
+    async fn save_file(&self, id: Uuid, name: String, file_name: String, data: &[u8]) -> Result<File, DataansError> {
+        let hash = content_hash(data);
+
+        let (path, is_uploaded) = match self.db.file_by_hash(&hash).await? {
+            Some(existing) if self.files_path.join(&existing.path).exists() => {
+                debug!(?id, existing_id = ?existing.id, "File with the same content already exists");
+
+                (existing.path, existing.is_uploaded)
+            }
+            _ => {
+                fs::write(self.files_path.join(&file_name), data)?;
+                cache_thumbnail(&self.files_path, &file_name).await;
+
+                (file_name, false)
+            }
+        };
+
+        self.add_saved_file(id, name, path, is_uploaded, hash).await
+    }
+
+    /// Registers a new file which content is already saved in the files directory.
+    async fn add_saved_file(
+        &self,
+        id: Uuid,
+        name: String,
+        path: String,
+        is_uploaded: bool,
+        hash: String,
+    ) -> Result<File, DataansError> {
+        let size = db_file_size(tokio::fs::metadata(self.files_path.join(&path)).await?.len());
         let now = OffsetDateTime::now_utc();
         self.db
-            .add_file(&FileModel::new(id, name.clone(), file_name.clone(), now, now))
+            .add_file(&FileModel {
+                is_uploaded,
+                hash: Some(hash),
+                size: Some(size),
+                ..FileModel::new(id, name.clone(), path.clone(), now, now)
+            })
             .await?;
+        if let Err(err) = self.index_file_text(id, &path).await {
+            warn!(?err, ?id, "Failed to save the file text");
+        }
 
-        let status = FileStatus::status_for_file(&file_path, false);
+        let status = FileStatus::status_for_file(&self.files_path.join(&path), is_uploaded);
 
         Ok(File {
             id: id.into(),
             name,
-            path: PathBuf::from(file_name),
+            path: PathBuf::from(path),
             status,
         })
     }
 
-    pub async fn delete_file(&self, file_id: Uuid) -> Result<(), DataansError> {
+    pub async fn upload_file(&self, id: Uuid, name: String, data: &[u8]) -> Result<File, DataansError> {
+        let file_name = format!("{id}_{name}");
+
+        self.save_file(id, name, file_name, data).await
+    }
+
+    /// Imports the file from the local file system and registers it.
+    ///
+    /// The file is copied into the files directory, so later changes of the original file do not affect the attachment.
+    /// Read-only files are hard-linked when possible (e.g. on the same file system) because they are not expected to
+    /// change. The content is streamed, so the file is never fully loaded into memory. `on_progress` receives the
+    /// amount of processed bytes and the file size.
+    #[instrument(err, skip(self, on_progress))]
+    pub async fn import_file(
+        &self,
+        id: Uuid,
+        source: &Path,
+        mut on_progress: impl FnMut(u64, u64),
+    ) -> Result<File, DataansError> {
+        let name = source
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| DataansError::PathIsNotUtf8(source.to_path_buf()))?
+            .to_owned();
+        let file_name = format!("{id}_{name}");
+        let destination = self.files_path.join(&file_name);
+
+        let metadata = tokio::fs::metadata(source).await?;
+        let total = metadata.len();
+        on_progress(0, total);
+
+        let is_linked = metadata.permissions().readonly() && tokio::fs::hard_link(source, &destination).await.is_ok();
+        let hash = if is_linked {
+            debug!(?id, "File has been hard-linked");
+
+            stream_file(&destination, None, total, &mut on_progress).await
+        } else {
+            stream_file(source, Some(&destination), total, &mut on_progress).await
+        };
+        let hash = match hash {
+            Ok(hash) => hash,
+            Err(err) => {
+                if let Err(err) = fs::remove_file(&destination) {
+                    warn!(?err, ?destination, "Failed to remove partially imported file");
+                }
+
+                return Err(err.into());
+            }
+        };
+
+        let (path, is_uploaded) = match self.db.file_by_hash(&hash).await? {
+            Some(existing) if self.files_path.join(&existing.path).exists() => {
+                debug!(?id, existing_id = ?existing.id, "File with the same content already exists");
+                fs::remove_file(&destination)?;
+
+                (existing.path, existing.is_uploaded)
+            }
+            _ => {
+                cache_thumbnail(&self.files_path, &file_name).await;
+
+                (file_name, false)
+            }
+        };
+
+        self.add_saved_file(id, name, path, is_uploaded, hash).await
+    }
+
+    /// Returns the thumbnail of the image file.
+    ///
+    /// `None` means the file is not a supported image or it does not exist locally (yet).
+    pub async fn thumbnail(&self, file_id: Uuid, size: u32) -> Result<Option<PathBuf>, DataansError> {
         let file = self.db.file_by_id(file_id).await?;
 
-        let file_path = self.files_path.join(&file.path);
+        if !self.files_path.join(&file.path).exists() {
+            return Ok(None);
+        }
 
-        fs::remove_file(file_path)?;
+        let files_path = Arc::clone(&self.files_path);
+        let size = size.clamp(MIN_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE);
+
+        tokio::task::spawn_blocking(move || generate_thumbnail(&files_path, &file.path, size)).await?
+    }
+
+    pub async fn delete_file(&self, file_id: Uuid) -> Result<(), DataansError> {
+        let file = self.db.file_by_id(file_id).await?;
 
         self.db.remove_file(file_id).await?;
 
+        // Files with the same content share the same local file.
+        if !self.db.is_file_path_used(&file.path).await? {
+            fs::remove_file(self.files_path.join(&file.path))?;
+            remove_thumbnails(&self.files_path, &file.path)?;
+        }
+
         Ok(())
     }
 
@@ -181,28 +749,119 @@ impl<D: Db> FileService<D> {
         let id = Uuid::new_v4();
         let name = format!("{id}.png");
 
-        let image_path = self.files_path.join(&name);
-
         let img: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(
             image_data.width.try_into().unwrap(),
             image_data.height.try_into().unwrap(),
             image_data.bytes.as_ref(),
         )
         .ok_or_else(|| DataansError::ImageFromRaw)?;
-        img.save(&image_path)?;
 
-        let now = OffsetDateTime::now_utc();
-        self.db
-            .add_file(&FileModel::new(id, name.clone(), name.clone(), now, now))
-            .await?;
+        let mut data = Vec::new();
+        img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
 
-        let status = FileStatus::status_for_file(&image_path, false);
+        self.save_file(id, name.clone(), name, &data).await
+    }
+}
 
-        Ok(File {
-            id: id.into(),
-            name: name.clone(),
-            path: name.into(),
-            status,
-        })
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use image::{ImageBuffer, Rgba};
+    use uuid::Uuid;
+
+    use super::{
+        IMPORT_BUFFER_SIZE, THUMBNAILS_DIR, content_hash, file_hash, generate_thumbnail, local_file_name,
+        remove_thumbnails, stream_file,
+    };
+
+    #[test]
+    fn thumbnails() {
+        let files_path = std::env::temp_dir().join(format!("dataans-thumbnails-{}", Uuid::new_v4()));
+        fs::create_dir_all(&files_path).unwrap();
+
+        ImageBuffer::from_pixel(1000, 500, Rgba([255_u8, 0, 0, 255]))
+            .save(files_path.join("big.png"))
+            .unwrap();
+        ImageBuffer::from_pixel(100, 100, Rgba([0_u8, 255, 0, 255]))
+            .save(files_path.join("small.png"))
+            .unwrap();
+        fs::write(files_path.join("notes.txt"), "not an image").unwrap();
+
+        let thumbnail = generate_thumbnail(&files_path, "big.png", 200).unwrap().unwrap();
+        assert_eq!(thumbnail, PathBuf::from(THUMBNAILS_DIR).join("big.png_200.png"));
+        let dimensions = image::image_dimensions(files_path.join(&thumbnail)).unwrap();
+        assert_eq!(dimensions, (200, 100));
+        // The cached thumbnail is reused.
+        assert_eq!(
+            generate_thumbnail(&files_path, "big.png", 200).unwrap(),
+            Some(thumbnail.clone())
+        );
+
+        // Small images are not upscaled.
+        assert_eq!(
+            generate_thumbnail(&files_path, "small.png", 200).unwrap(),
+            Some(PathBuf::from("small.png"))
+        );
+        assert_eq!(generate_thumbnail(&files_path, "notes.txt", 200).unwrap(), None);
+
+        remove_thumbnails(&files_path, "big.png").unwrap();
+        assert!(!files_path.join(&thumbnail).exists());
+
+        fs::remove_dir_all(&files_path).unwrap();
+    }
+
+    #[test]
+    fn local_file_names() {
+        assert_eq!(local_file_name(Path::new("image.png")).unwrap(), "image.png");
+
+        assert!(local_file_name(Path::new("../image.png")).is_err());
+        assert!(local_file_name(Path::new(".thumbnails/image.png_512.png")).is_err());
+        assert!(local_file_name(Path::new("/etc/passwd")).is_err());
+        assert!(local_file_name(Path::new("..")).is_err());
+    }
+
+    #[tokio::test]
+    async fn streaming_copy() {
+        let dir = std::env::temp_dir().join(format!("dataans-import-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let data = (0..IMPORT_BUFFER_SIZE * 2 + 17)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect::<Vec<_>>();
+        let total = u64::try_from(data.len()).unwrap();
+        fs::write(dir.join("source.bin"), &data).unwrap();
+
+        let mut progress = Vec::new();
+        let hash = stream_file(
+            &dir.join("source.bin"),
+            Some(&dir.join("copy.bin")),
+            total,
+            &mut |imported, total| progress.push((imported, total)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(hash, content_hash(&data));
+        assert_eq!(file_hash(dir.join("source.bin")).await.unwrap(), hash);
+        assert_eq!(fs::read(dir.join("copy.bin")).unwrap(), data);
+        assert!(progress.windows(2).all(|window| window[0].0 < window[1].0));
+        assert!(progress.iter().all(|&(_, progress_total)| progress_total == total));
+        assert_eq!(progress.last(), Some(&(total, total)));
+
+        // The destination file must not exist.
+        assert!(
+            stream_file(
+                &dir.join("source.bin"),
+                Some(&dir.join("copy.bin")),
+                total,
+                &mut |_, _| {}
+            )
+            .await
+            .is_err()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
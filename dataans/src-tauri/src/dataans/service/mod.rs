@@ -1,3 +1,4 @@
+pub mod extract;
 pub mod file;
 pub mod note;
 pub mod space;
@@ -1,12 +1,17 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
 use common::error::CommandError;
-use common::note::{CreateNoteOwned, File, FileStatus, Id as NoteId, Note, NoteFullOwned, OwnedNote, UpdateNote};
+use common::export::NoteV2;
+use common::note::{
+    CreateNoteOwned, File, FileId, FileStatus, Id as NoteId, Note, NoteFullOwned, OwnedNote, UpdateNote,
+};
 use common::space::Id as SpaceId;
 use futures::future::try_join_all;
 use thiserror::Error;
 use time::OffsetDateTime;
+use uuid::Uuid;
 
 use crate::dataans::DataansError;
 use crate::dataans::db::model::{File as FileModel, Note as NoteModel};
@@ -78,6 +83,8 @@ impl<D: Db> NoteService<D> {
                     updated_at: _,
                     is_deleted: _,
                     is_uploaded,
+                    hash: _,
+                    size: _,
                 } = file;
 
                 let path = files_path.join(path);
@@ -135,6 +142,18 @@ impl<D: Db> NoteService<D> {
     }
 
     pub async fn create_note(&self, note: CreateNoteOwned) -> NoteServiceResult<OwnedNote> {
+        let now = OffsetDateTime::now_utc();
+
+        self.create_note_with_dates(note, now, now).await
+    }
+
+    /// Creates a note with the given creation and update dates (e.g. when notes are imported from other apps).
+    pub async fn create_note_with_dates(
+        &self,
+        note: CreateNoteOwned,
+        created_at: OffsetDateTime,
+        updated_at: OffsetDateTime,
+    ) -> NoteServiceResult<OwnedNote> {
         let CreateNoteOwned {
             id,
             text,
@@ -142,14 +161,12 @@ impl<D: Db> NoteService<D> {
             space_id,
         } = note;
 
-        let created_at = OffsetDateTime::now_utc();
-
         self.db
             .create_note(&NoteModel::new(
                 id.inner(),
                 text.clone().into(),
                 created_at,
-                created_at,
+                updated_at,
                 space_id.inner(),
             ))
             .await?;
@@ -166,11 +183,36 @@ impl<D: Db> NoteService<D> {
             text,
             files,
             created_at: created_at.into(),
-            updated_at: created_at.into(),
+            updated_at: updated_at.into(),
             space_id,
         })
     }
 
+    /// Creates the deleted note record from the json export (schema V2).
+    ///
+    /// Deleted records keep deletions of the imported data, so they are synchronized to other devices.
+    /// Attachments of deleted notes are not restored.
+    pub async fn create_deleted_note(&self, note: &NoteV2) -> NoteServiceResult<()> {
+        let NoteV2 {
+            id,
+            text,
+            space_id,
+            created_at,
+            updated_at,
+            is_deleted: _,
+            files: _,
+        } = note;
+
+        self.db
+            .create_note(&NoteModel {
+                is_deleted: true,
+                ..NoteModel::new(*id, text.clone(), *created_at, *updated_at, *space_id)
+            })
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_note(&self, note: UpdateNote<'static>) -> NoteServiceResult<OwnedNote> {
         let UpdateNote {
             id: note_id,
@@ -219,17 +261,34 @@ impl<D: Db> NoteService<D> {
         Ok(())
     }
 
-    pub async fn search_notes_in_space(
-        &self,
-        query: &str,
-        space_id: SpaceId,
-    ) -> Result<Vec<NoteFullOwned>, DataansError> {
+    /// Returns ids of attachments whose extracted text contains the query grouped by note id.
+    async fn matched_files(&self, query: &str) -> Result<HashMap<Uuid, Vec<FileId>>, DataansError> {
+        let mut matched_files: HashMap<Uuid, Vec<FileId>> = HashMap::new();
+
+        if query.is_empty() {
+            return Ok(matched_files);
+        }
+
+        for (note_id, file_id) in self.db.search_files_text(query).await? {
+            matched_files.entry(note_id).or_default().push(file_id.into());
+        }
+
+        Ok(matched_files)
+    }
+
+    /// Returns notes whose text or attachments text contains the query.
+    async fn search(&self, query: &str, notes: Vec<OwnedNote>) -> Result<Vec<NoteFullOwned>, DataansError> {
+        let mut matched_files = self.matched_files(query).await?;
+
         try_join_all(
-            self.space_notes(space_id)
-                .await?
+            notes
                 .into_iter()
-                .filter(|note| note.text.as_ref().contains(query))
-                .map(|note| async move {
+                .filter_map(|note| {
+                    let matched_files = matched_files.remove(&note.id.inner()).unwrap_or_default();
+
+                    (note.text.as_ref().contains(query) || !matched_files.is_empty()).then_some((note, matched_files))
+                })
+                .map(|(note, matched_files)| async move {
                     let Note {
                         id,
                         text,
@@ -244,6 +303,7 @@ impl<D: Db> NoteService<D> {
                         created_at,
                         updated_at,
                         files,
+                        matched_files,
                         space: self.space_service.space_by_id(space_id).await?,
                     })
                 }),
@@ -251,31 +311,15 @@ impl<D: Db> NoteService<D> {
         .await
     }
 
+    pub async fn search_notes_in_space(
+        &self,
+        query: &str,
+        space_id: SpaceId,
+    ) -> Result<Vec<NoteFullOwned>, DataansError> {
+        self.search(query, self.space_notes(space_id).await?).await
+    }
+
     pub async fn search_notes(&self, query: &str) -> Result<Vec<NoteFullOwned>, DataansError> {
-        try_join_all(
-            self.notes()
-                .await?
-                .into_iter()
-                .filter(|note| note.text.as_ref().contains(query))
-                .map(|note| async move {
-                    let Note {
-                        id,
-                        text,
-                        created_at,
-                        updated_at,
-                        space_id,
-                        files,
-                    } = note;
-                    Result::<NoteFullOwned, DataansError>::Ok(NoteFullOwned {
-                        id,
-                        text,
-                        created_at,
-                        updated_at,
-                        files,
-                        space: self.space_service.space_by_id(space_id).await?,
-                    })
-                }),
-        )
-        .await
+        self.search(query, self.notes().await?).await
     }
 }
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use common::error::CommandError;
+use common::export::SpaceV2;
 use common::space::{Avatar, CreateSpaceOwned, DeleteSpace, Id as SpaceId, OwnedSpace, UpdateSpace};
 use futures::future::try_join_all;
 use thiserror::Error;
@@ -48,9 +49,19 @@ impl<D: Db> SpaceService<D> {
     }
 
     pub async fn create_space(&self, space: CreateSpaceOwned) -> SpaceServiceResult<OwnedSpace> {
-        let CreateSpaceOwned { id, name, avatar } = space;
+        let now = OffsetDateTime::now_utc();
 
-        let created_at = OffsetDateTime::now_utc();
+        self.create_space_with_dates(space, now, now).await
+    }
+
+    /// Creates a space with the given creation and update dates (e.g. when spaces are imported from the json export).
+    pub async fn create_space_with_dates(
+        &self,
+        space: CreateSpaceOwned,
+        created_at: OffsetDateTime,
+        updated_at: OffsetDateTime,
+    ) -> SpaceServiceResult<OwnedSpace> {
+        let CreateSpaceOwned { id, name, avatar } = space;
 
         let avatar_id = if avatar.id() == common::DEFAULT_SPACE_AVATAR_ID {
             // If the user decided to use the default avatar, we should create a new avatar file with a default image path in the database.
@@ -78,7 +89,7 @@ impl<D: Db> SpaceService<D> {
                 name.clone().into(),
                 avatar_id,
                 created_at,
-                created_at,
+                updated_at,
             ))
             .await?;
 
@@ -87,10 +98,33 @@ impl<D: Db> SpaceService<D> {
             name,
             avatar,
             created_at: created_at.into(),
-            updated_at: created_at.into(),
+            updated_at: updated_at.into(),
         })
     }
 
+    /// Creates the deleted space record from the json export (schema V2).
+    ///
+    /// Deleted records keep deletions of the imported data, so they are synchronized to other devices.
+    pub async fn create_deleted_space(&self, space: &SpaceV2) -> SpaceServiceResult<()> {
+        let SpaceV2 {
+            id,
+            name,
+            avatar_id,
+            created_at,
+            updated_at,
+            is_deleted: _,
+        } = space;
+
+        self.db
+            .create_space(&SpaceModel {
+                is_deleted: true,
+                ..SpaceModel::new(*id, name.clone(), *avatar_id, *created_at, *updated_at)
+            })
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_space(&self, space_data: UpdateSpace<'static>) -> SpaceServiceResult<OwnedSpace> {
         let UpdateSpace {
             id: space_id,
@@ -152,6 +186,8 @@ impl<D: Db> SpaceService<D> {
             updated_at: _,
             is_deleted: _,
             is_uploaded: _,
+            hash: _,
+            size: _,
         } = db.file_by_id(avatar_id).await?;
 
         Ok(OwnedSpace {
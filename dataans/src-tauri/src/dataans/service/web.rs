@@ -1,8 +1,11 @@
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use common::profile::{Sync, UserContext, UserProfile};
 use tokio::fs;
+use tokio::sync::Notify;
+use tokio::sync::futures::Notified;
 
 use crate::dataans::DataansError;
 
@@ -13,6 +16,12 @@ const PROFILE_FILE_NAME: &str = "profile.json";
 pub struct WebService {
     profile_path: PathBuf,
     user_profile: Mutex<Option<UserProfile>>,
+    /// `true` if the last sync was interrupted because of the expired auth token.
+    ///
+    /// The sync is resumed automatically after the re-authentication.
+    sync_interrupted: AtomicBool,
+    /// Notifies waiters every time the user profile is authorized (sign-in or token renewal).
+    authorized: Notify,
 }
 
 impl WebService {
@@ -28,14 +37,16 @@ impl WebService {
         Ok(Self {
             profile_path,
             user_profile: Mutex::new(user_profile),
+            sync_interrupted: AtomicBool::new(false),
+            authorized: Notify::new(),
         })
     }
 
     pub async fn authorize(&self, profile: UserProfile) -> Result<(), DataansError> {
         fs::write(&self.profile_path, serde_json::to_vec(&profile)?).await?;
 
-        let mut user_profile = self.user_profile.lock().unwrap();
-        *user_profile = Some(profile);
+        *self.user_profile.lock().unwrap() = Some(profile);
+        self.authorized.notify_waiters();
 
         Ok(())
     }
@@ -47,6 +58,7 @@ impl WebService {
 
         fs::remove_file(&self.profile_path).await?;
         *self.user_profile.lock().unwrap() = None;
+        self.sync_interrupted.store(false, Ordering::Release);
 
         Ok(())
     }
@@ -59,6 +71,23 @@ impl WebService {
         Ok(serde_json::from_slice(&fs::read(&self.profile_path).await?)?)
     }
 
+    /// Remembers that the sync has been interrupted due to the expired auth token.
+    pub fn interrupt_sync(&self) {
+        self.sync_interrupted.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` if the sync has been interrupted and resets the flag.
+    pub fn take_interrupted_sync(&self) -> bool {
+        self.sync_interrupted.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns a future that completes when the user profile is authorized next time.
+    ///
+    /// The future receives the notification even if it has not been polled yet.
+    pub fn authorized(&self) -> Notified<'_> {
+        self.authorized.notified()
+    }
+
     pub fn user_profile(&self) -> Option<UserProfile> {
         self.user_profile.lock().unwrap().clone()
     }
@@ -68,11 +97,15 @@ impl WebService {
             let UserProfile {
                 sync_config,
                 auth_token: _,
+                auth_scheme,
                 secret_key: _,
                 salt: _,
             } = serde_json::from_slice(&fs::read(&self.profile_path).await?)?;
 
-            Ok(Some(UserContext { sync_config }))
+            Ok(Some(UserContext {
+                sync_config,
+                auth_scheme,
+            }))
         } else {
             Ok(None)
         }
@@ -86,6 +119,7 @@ impl WebService {
 
         let user_context = UserContext {
             sync_config: user_profile.sync_config.clone(),
+            auth_scheme: user_profile.auth_scheme,
         };
 
         *self.user_profile.lock().unwrap() = Some(user_profile);
@@ -2,25 +2,33 @@ use std::path::Path;
 use std::time::Duration;
 
 use base64ct::{Base64, Encoding};
-use common::profile::AuthorizationToken;
+use common::profile::{AuthScheme, AuthorizationToken};
 use reqwest::ClientBuilder;
 use reqwest::header::{HeaderMap, HeaderValue};
 use sha2::Sha256;
 use time::OffsetDateTime;
 use url::Url;
 use uuid::Uuid;
-use web_api_types::{Blocks, Operation, User};
+use web_api_types::{
+    Blocks, ContinuationToken, FileGcReport, FileGcRequest, MAX_OPERATIONS_DATA_PER_REQUEST,
+    MAX_OPERATIONS_PER_REQUEST, Operation, OperationRejection, OperationsPage, RateLimited, Usage, User,
+};
 
 use super::SyncError;
-use crate::dataans::crypto::{EncryptionKey, decrypt, decrypt_data, encrypt, encrypt_data};
-use crate::dataans::db::{OperationRecord, OperationRecordOwned};
+use crate::dataans::crypto::{EncryptionKey, decrypt, decrypt_data, encrypt, encrypt_data, file_blob_id};
+use crate::dataans::db::{File, OperationRecord, OperationRecordOwned};
 use crate::dataans::sync::hash::Hash;
 
+/// How many times the rate-limited request is retried.
+const MAX_RATE_LIMIT_RETRIES: usize = 3;
+
 macro_rules! check_token_expiration {
     ($expired_at:expr) => {
-        let now = time::OffsetDateTime::now_utc();
-        if now >= $expired_at {
-            return Err(super::SyncError::TokenExpired);
+        if let Some(expired_at) = $expired_at {
+            let now = time::OffsetDateTime::now_utc();
+            if now >= expired_at {
+                return Err(super::SyncError::TokenExpired);
+            }
         }
     };
 }
@@ -33,7 +41,9 @@ pub struct Client {
     sync_server: Url,
     encryption_key: EncryptionKey,
     /// Authorization token expiration time.
-    expires_at: OffsetDateTime,
+    ///
+    /// API tokens do not expire, so the value is absent for them.
+    expires_at: Option<OffsetDateTime>,
 }
 
 impl Client {
@@ -42,17 +52,28 @@ impl Client {
         sync_server: Url,
         encryption_key: EncryptionKey,
         auth_token: &AuthorizationToken,
+        auth_scheme: AuthScheme,
     ) -> Result<Self, SyncError> {
-        let expires_at = extract_expiration_time(auth_token)?;
+        let expires_at = token_expiration(auth_token, auth_scheme)?;
 
         let client = ClientBuilder::new()
             .default_headers({
                 let mut headers = HeaderMap::new();
 
-                headers.insert(
-                    "Cookie",
-                    HeaderValue::from_str(&format!("CF_Authorization={}", auth_token.as_ref()))?,
-                );
+                match auth_scheme {
+                    AuthScheme::CloudflareAccess => {
+                        headers.insert(
+                            "Cookie",
+                            HeaderValue::from_str(&format!("CF_Authorization={}", auth_token.as_ref()))?,
+                        );
+                    }
+                    AuthScheme::ApiToken | AuthScheme::Oidc => {
+                        let mut value = HeaderValue::from_str(&format!("Bearer {}", auth_token.as_ref()))?;
+                        value.set_sensitive(true);
+
+                        headers.insert("Authorization", value);
+                    }
+                }
 
                 headers
             })
@@ -108,26 +129,35 @@ impl Client {
         Ok(blocks)
     }
 
-    /// Requests operation stored on the server.
+    /// Requests one page of operations stored on the server.
     ///
-    /// The server will skip the first `operations_to_skip` operations and will return the rest of them.
+    /// The first page starts after `operations_to_skip` operations. Next pages are requested using
+    /// the continuation token returned with the previous page. `None` token means there are no more pages.
     /// This method automatically decrypt the received operation.
     #[instrument(err, skip(self))]
-    pub async fn operations(&self, operations_to_skip: usize) -> Result<Vec<OperationRecordOwned>, SyncError> {
+    pub async fn operations_page(
+        &self,
+        operations_to_skip: usize,
+        continuation: Option<&ContinuationToken>,
+    ) -> Result<(Vec<OperationRecordOwned>, Option<ContinuationToken>), SyncError> {
         check_token_expiration!(self.expires_at);
 
-        let mut operations_url = self.sync_server.join("data/operation")?;
-        operations_url
-            .query_pairs_mut()
-            .append_pair("operations_to_skip", &operations_to_skip.to_string());
+        let mut operations_url = self.sync_server.join("data/operation/page")?;
+        {
+            let mut query = operations_url.query_pairs_mut();
+            query.append_pair("operations_to_skip", &operations_to_skip.to_string());
+            if let Some(continuation) = continuation {
+                query.append_pair("continuation", continuation.as_str());
+            }
+        }
 
-        let operations = self
+        let OperationsPage { operations, next } = self
             .client
             .get(operations_url)
             .send()
             .await?
             .error_for_status()?
-            .json::<Vec<Operation>>()
+            .json::<OperationsPage>()
             .await?;
         let operations = operations
             .into_iter()
@@ -136,12 +166,13 @@ impl Client {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(operations)
+        Ok((operations, next))
     }
 
     /// Sends the provided operations to the server.
     ///
-    /// This method automatically encrypts provided operations.
+    /// This method automatically encrypts provided operations. Big uploads are split into several requests,
+    /// so every request fits into the server limits.
     #[instrument(err, skip(self, operations))]
     pub async fn upload_operations(&self, operations: &[OperationRecord<'_>]) -> Result<(), SyncError> {
         check_token_expiration!(self.expires_at);
@@ -159,17 +190,72 @@ impl Client {
             })
             .collect::<Result<Vec<_>, SyncError>>()?;
 
-        let _ = self
-            .client
-            .post(self.sync_server.join("data/operation")?)
-            .json(&operations)
-            .send()
-            .await?
-            .error_for_status()?;
+        let mut batch_start = 0;
+        let mut batch_data_size = 0;
+        for (index, operation) in operations.iter().enumerate() {
+            let data_size = operation.data.as_ref().len();
+
+            if index - batch_start == MAX_OPERATIONS_PER_REQUEST
+                || (index > batch_start && batch_data_size + data_size > MAX_OPERATIONS_DATA_PER_REQUEST)
+            {
+                self.upload_operations_batch(&operations[batch_start..index]).await?;
+
+                batch_start = index;
+                batch_data_size = 0;
+            }
+
+            batch_data_size += data_size;
+        }
+
+        if batch_start < operations.len() {
+            self.upload_operations_batch(&operations[batch_start..]).await?;
+        }
 
         Ok(())
     }
 
+    /// Sends one batch of encrypted operations to the server.
+    ///
+    /// Rate-limited requests are retried after the delay requested by the server.
+    async fn upload_operations_batch(&self, operations: &[Operation]) -> Result<(), SyncError> {
+        let url = self.sync_server.join("data/operation")?;
+
+        for _ in 0..MAX_RATE_LIMIT_RETRIES {
+            let response = self.client.post(url.clone()).json(&operations).send().await?;
+
+            if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let RateLimited { retry_after } = response.json().await?;
+                warn!(retry_after, "Operations upload is rate limited");
+
+                tokio::time::sleep(Duration::from_secs(retry_after)).await;
+
+                continue;
+            }
+
+            if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+                let rejection: OperationRejection = response.json().await?;
+
+                return Err(SyncError::OperationsRejected(rejection));
+            }
+
+            let _ = error_for_quota(response).await?.error_for_status()?;
+
+            return Ok(());
+        }
+
+        Err(SyncError::SyncFailed("operations upload is rate limited"))
+    }
+
+    /// Returns the id of the file blob on the sync server.
+    ///
+    /// Files with the same content share the same blob. Files without the content hash are stored under their own id.
+    pub fn blob_id(&self, file: &File) -> Uuid {
+        match &file.hash {
+            Some(hash) => file_blob_id(&self.encryption_key, hash),
+            None => file.id,
+        }
+    }
+
     /// Uploads the file to the server.
     ///
     /// The provided path must be absolute in the file system.
@@ -181,13 +267,13 @@ impl Client {
 
         let data = encrypt_data(&file_data, &self.encryption_key)?;
 
-        let _ = self
+        let response = self
             .client
             .post(self.sync_server.join("file/")?.join(&id.to_string())?)
             .body(data)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+        let _ = error_for_quota(response).await?.error_for_status()?;
 
         Ok(())
     }
@@ -232,6 +318,27 @@ impl Client {
         Ok(())
     }
 
+    /// Asks the server to collect the user's files that are no longer referenced.
+    ///
+    /// `live_files` must contain all files that are still in use. Other files stored on the server
+    /// are marked as orphaned and deleted after the server's grace period.
+    #[instrument(err, skip(self, live_files), fields(live_files = live_files.len()))]
+    pub async fn collect_garbage(&self, live_files: &[Uuid]) -> Result<FileGcReport, SyncError> {
+        check_token_expiration!(self.expires_at);
+
+        let response = self
+            .client
+            .post(self.sync_server.join("file/")?.join("gc")?)
+            .json(&FileGcRequest {
+                live_files: live_files.to_vec(),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<FileGcReport>().await?)
+    }
+
     /// Returns the [User] object from the sync server.
     ///
     /// There is noting special about the [User] object. It is only used to validate the user's
@@ -250,6 +357,21 @@ impl Client {
         Ok(response.json::<User>().await?)
     }
 
+    /// Returns the user's storage usage on the sync server.
+    #[instrument(err, skip(self))]
+    pub async fn usage(&self) -> Result<Usage, SyncError> {
+        check_token_expiration!(self.expires_at);
+
+        let response = self
+            .client
+            .get(self.sync_server.join("usage/")?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Usage>().await?)
+    }
+
     /// Initializes the user on the sync server.
     ///
     /// This method **must** be called only _once_.
@@ -269,6 +391,32 @@ impl Client {
     }
 }
 
+/// Converts the storage quota rejection into [SyncError::QuotaExceeded].
+///
+/// The server explains the rejection in the response body, so the user can see why the upload failed.
+async fn error_for_quota(response: reqwest::Response) -> Result<reqwest::Response, SyncError> {
+    if response.status() == reqwest::StatusCode::PAYLOAD_TOO_LARGE {
+        let message = response.text().await?;
+
+        return Err(SyncError::QuotaExceeded(message));
+    }
+
+    Ok(response)
+}
+
+/// Returns the authorization token expiration time.
+///
+/// API tokens do not expire, so `None` is returned for them.
+pub fn token_expiration(
+    auth_token: &AuthorizationToken,
+    auth_scheme: AuthScheme,
+) -> Result<Option<OffsetDateTime>, SyncError> {
+    match auth_scheme {
+        AuthScheme::CloudflareAccess | AuthScheme::Oidc => Ok(Some(extract_expiration_time(auth_token)?)),
+        AuthScheme::ApiToken => Ok(None),
+    }
+}
+
 fn extract_expiration_time(auth_token: &AuthorizationToken) -> Result<OffsetDateTime, SyncError> {
     let mut token_parts = auth_token.as_ref().split('.');
 
@@ -41,13 +41,10 @@
 //! the first pair of blocks with different hashes.
 //!
 //! **Step 3.** Now the app knows the amount of the common blocks and can skip operations that belong
-//! to these blocks. The app skips them and requests all other operations that the server has.
+//! to these blocks. The app skips them and requests all other operations that the server has page by page.
+//! Remote operations that the app does not have are applied as soon as their page arrives.
 //!
-//! **Step 4.** The app has two sets of operations: local one and remote one. It finds the difference
-//! between them. The first set will contain operations to upload and the second one will contain
-//! operations to apply.
-//!
-//! **Step 5.** The app uploads and applies operations from the corresponding sets.
+//! **Step 4.** The app uploads local operations that are not present among the remote ones.
 //!
 //! Basically, that's all.
 //!
@@ -64,6 +61,7 @@
 
 pub mod client;
 mod hash;
+pub mod token;
 
 use std::collections::HashSet;
 use std::path::Path;
@@ -71,7 +69,7 @@ use std::sync::Arc;
 
 use common::event::{DATA_EVENT, DataEvent};
 use common::note::{FileId, FileStatus};
-use common::profile::AuthorizationToken;
+use common::profile::{AuthScheme, AuthorizationToken};
 use futures::StreamExt;
 use futures::stream::FuturesUnordered;
 pub use hash::{Hash, Hasher};
@@ -82,9 +80,11 @@ use thiserror::Error;
 use tokio_stream::wrappers::ReceiverStream;
 use url::Url;
 use uuid::Uuid;
+use web_api_types::OperationRejection;
 
 use crate::dataans::crypto::{CryptoError, EncryptionKey};
-use crate::dataans::db::{DbError, OperationDb};
+use crate::dataans::db::{DbError, File, OperationDb};
+use crate::dataans::service::file::cache_thumbnail;
 use crate::dataans::sync::client::Client;
 
 const OPERATIONS_PER_BLOCK: usize = 16;
@@ -124,6 +124,12 @@ pub enum SyncError {
 
     #[error("invalid authorization token: {0}")]
     InvalidAuthToken(String),
+
+    #[error("{0}")]
+    QuotaExceeded(String),
+
+    #[error("the server rejected operations: {0}")]
+    OperationsRejected(OperationRejection),
 }
 
 impl SyncError {
@@ -140,8 +146,9 @@ pub async fn sync_future<D: OperationDb, R: Runtime, E: Emitter<R>>(
     emitter: &E,
     files_path: Arc<Path>,
     auth_token: &AuthorizationToken,
+    auth_scheme: AuthScheme,
 ) -> Result<(), SyncError> {
-    let synchronizer = Synchronizer::new(db, sync_server, encryption_key, files_path, auth_token)?;
+    let synchronizer = Synchronizer::new(db, sync_server, encryption_key, files_path, auth_token, auth_scheme)?;
 
     let (sender, receiver) = channel::<FileId>(CHANNEL_BUFFER_SIZE);
 
@@ -154,6 +161,11 @@ pub async fn sync_future<D: OperationDb, R: Runtime, E: Emitter<R>>(
         (Ok(_), Ok(_)) => {
             info!("Synchronization successful.");
 
+            // The file GC is an optimization. The sync is successful even if it fails.
+            if let Err(err) = synchronizer.collect_garbage().await {
+                warn!(?err, "Failed to collect orphaned files on the sync server");
+            }
+
             Ok(())
         }
         (Err(main_err), Err(file_err)) => {
@@ -178,6 +190,13 @@ pub async fn sync_future<D: OperationDb, R: Runtime, E: Emitter<R>>(
     }
 }
 
+/// Returns the server-side (blob) ids of the given files.
+///
+/// Files with the same content share one blob, so every id is returned only once.
+fn live_blob_ids(files: &[File], blob_id: impl Fn(&File) -> Uuid) -> Vec<Uuid> {
+    files.iter().map(blob_id).collect::<HashSet<_>>().into_iter().collect()
+}
+
 /// Does all the synchronization work.
 struct Synchronizer<D> {
     db: Arc<D>,
@@ -193,10 +212,11 @@ impl<D: OperationDb> Synchronizer<D> {
         encryption_key: EncryptionKey,
         files_path: Arc<Path>,
         auth_token: &AuthorizationToken,
+        auth_scheme: AuthScheme,
     ) -> Result<Self, SyncError> {
         Ok(Self {
             db,
-            client: Client::new(sync_server, encryption_key, auth_token)?,
+            client: Client::new(sync_server, encryption_key, auth_token, auth_scheme)?,
             files_path,
         })
     }
@@ -210,12 +230,14 @@ impl<D: OperationDb> Synchronizer<D> {
     async fn handle_file<R: Runtime, E: Emitter<R>>(&self, file_id: Uuid, emitter: &E) -> Result<(), SyncError> {
         let file = self.db.file_by_id(*file_id.as_ref()).await?;
         let file_path = self.files_path.join(&file.path);
+        let blob_id = self.client.blob_id(&file);
 
         if file.is_uploaded {
             if !file_path.exists() {
                 debug!(?file.id, ?file.path, "File does not exist locally, but is uploaded. Downloading...");
 
-                self.client.download_file(file.id, &file_path).await?;
+                self.client.download_file(blob_id, &file_path).await?;
+                cache_thumbnail(&self.files_path, &file.path).await;
                 emitter
                     .emit(
                         DATA_EVENT,
@@ -231,7 +253,12 @@ impl<D: OperationDb> Synchronizer<D> {
         } else if file_path.exists() {
             debug!(?file.id, ?file.path, "File exists locally, but is not uploaded. Uploading...");
 
-            self.client.upload_file(file.id, &file_path).await?;
+            // The file with the same content may already be uploaded by this or another device.
+            if file.hash.is_some() && self.client.exists(blob_id).await? {
+                debug!(?file.id, %blob_id, "File content is already uploaded");
+            } else {
+                self.client.upload_file(blob_id, &file_path).await?;
+            }
             self.db.mark_file_as_uploaded(file.id).await?;
             emitter
                 .emit(
@@ -249,13 +276,14 @@ impl<D: OperationDb> Synchronizer<D> {
             // When we accept the `CreateFile` operation from the sync server, we do not know if the file is already uploaded or not.
             // Obviously, the file does not exist locally. Also, file's `is_uploaded` property is false by default.
             // Here we try to download the file. But if the file is not found on the server, then [_there is nothing we can do_](https://knowyourmeme.com/memes/napoleon-there-is-nothing-we-can-do).
-            match self.client.exists(file.id).await {
+            match self.client.exists(blob_id).await {
                 Ok(true) => {
                     self.db.mark_file_as_uploaded(file.id).await?;
 
                     debug!(?file.id, ?file.path, "File does not exist locally, but is uploaded. Downloading...");
 
-                    self.client.download_file(file.id, &file_path).await?;
+                    self.client.download_file(blob_id, &file_path).await?;
+                    cache_thumbnail(&self.files_path, &file.path).await;
                     emitter
                         .emit(
                             DATA_EVENT,
@@ -346,6 +374,19 @@ impl<D: OperationDb> Synchronizer<D> {
         result
     }
 
+    /// Tells the server which files are still in use, so it can delete orphaned ones.
+    ///
+    /// It must be called only after a fully successful sync. Otherwise, the server may delete files
+    /// that are referenced by operations the app has not uploaded yet.
+    async fn collect_garbage(&self) -> Result<(), SyncError> {
+        let live_files = live_blob_ids(&self.db.files().await?, |file| self.client.blob_id(file));
+
+        let report = self.client.collect_garbage(&live_files).await?;
+        info!(?report, "Orphaned files collected");
+
+        Ok(())
+    }
+
     /// Does local and remote databases synchronization.
     #[instrument(err, skip(self, emitter))]
     async fn synchronize<R: Runtime, E: Emitter<R>>(
@@ -358,7 +399,8 @@ impl<D: OperationDb> Synchronizer<D> {
             futures::join!(self.db.operations(), self.client.blocks(OPERATIONS_PER_BLOCK),);
 
         let mut local_operations = local_operations?;
-        local_operations.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        // The server orders operations by creation time and id.
+        local_operations.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
         let remote_blocks = remote_blocks?;
 
         let local_blocks = local_operations
@@ -395,82 +437,152 @@ impl<D: OperationDb> Synchronizer<D> {
         }
 
         let local_operations = &local_operations[blocks_to_skip * OPERATIONS_PER_BLOCK..];
-        // Step 3: Request operations from the server.
-        let remote_operations = self.client.operations(blocks_to_skip * OPERATIONS_PER_BLOCK).await?;
-
-        // Step 4: Find the difference between local and remote operations.
-        // Yes, the algorithm below can be optimized. But I do not want to make it complicated unless there is a real need for that.
         let local_operations_set = local_operations
             .iter()
             .map(|operation| operation.id)
             .collect::<HashSet<_>>();
-        let remote_operations_set = remote_operations.iter().map(|operation| operation.id).collect();
-        let common_operation = local_operations_set
-            .intersection(&remote_operations_set)
-            .collect::<HashSet<_>>();
 
-        let mut operations_to_upload = local_operations.to_vec();
-        let mut operations_to_apply = remote_operations;
+        // Step 3: Request operations from the server page by page and apply remote operations
+        // on the local database that the current user does not have. Pages are applied as they arrive.
+        let remote_operations_set = self
+            .apply_remote_operations(
+                blocks_to_skip * OPERATIONS_PER_BLOCK,
+                &local_operations_set,
+                emitter,
+                &sender,
+            )
+            .await
+            .map_err(|err| {
+                error!(?err, "Failed to apply remote operations");
 
-        operations_to_upload.retain(|operation| !common_operation.contains(&operation.id));
-        operations_to_apply.retain(|operation| !common_operation.contains(&operation.id));
+                if err.is_token_expired() {
+                    err
+                } else {
+                    SyncError::SyncFailed("failed to apply remote operations")
+                }
+            })?;
 
+        // Step 4: Upload local operations that the server does not have.
+        let operations_to_upload = local_operations
+            .iter()
+            .filter(|operation| !remote_operations_set.contains(&operation.id))
+            .cloned()
+            .collect::<Vec<_>>();
         trace!(?operations_to_upload);
-        trace!(?operations_to_apply);
-
-        // Step 5: Upload local operations that the server does not have and apply remote operations
-        // on the local database that the current user does not have.
-        let result = futures::join!(
-            async {
-                for operation in operations_to_apply {
-                    if let Some(event) = self.db.apply_operation(&operation).await.inspect_err(|err| {
-                        error!(?err, ?operation, "Failed to apply operation");
-                    })? {
-                        if let DataEvent::FileAdded(file) = &event {
-                            sender.send(file.id).await.map_err(|err| {
-                                error!(?err, "Failed to send file id into the channel");
-                                SyncError::Event("failed to send file id into the channel")
-                            })?;
-                        }
-                        emitter.emit(DATA_EVENT, event).map_err(|err| {
-                            error!(?err, "Failed to emit data event");
-                            SyncError::Event("failed to emit data event")
-                        })?;
-                    }
+
+        self.client
+            .upload_operations(&operations_to_upload)
+            .await
+            .map_err(|err| {
+                error!(?err, "Failed to upload local operations");
+
+                match err {
+                    SyncError::TokenExpired | SyncError::QuotaExceeded(_) | SyncError::OperationsRejected(_) => err,
+                    _ => SyncError::SyncFailed("failed to upload local operations"),
                 }
+            })?;
+
+        info!("Synchronization successful.");
+
+        Ok(())
+    }
 
-                Result::<_, SyncError>::Ok(())
-            },
-            self.client.upload_operations(&operations_to_upload)
-        );
+    /// Requests remote operations page by page and applies the ones that are not present locally.
+    ///
+    /// Returns ids of all requested remote operations.
+    async fn apply_remote_operations<R: Runtime, E: Emitter<R>>(
+        &self,
+        operations_to_skip: usize,
+        local_operations: &HashSet<Uuid>,
+        emitter: &E,
+        sender: &Sender<FileId>,
+    ) -> Result<HashSet<Uuid>, SyncError> {
+        let mut remote_operations = HashSet::new();
+        let mut continuation = None;
 
-        match result {
-            (Ok(_), Ok(_)) => {
-                info!("Synchronization successful.");
+        loop {
+            let (operations, next) = self
+                .client
+                .operations_page(operations_to_skip, continuation.as_ref())
+                .await?;
 
-                Ok(())
-            }
-            (Err(apply_err), Err(upload_err)) => {
-                error!(
-                    ?apply_err,
-                    ?upload_err,
-                    "Failed to apply remote operations and upload local operations"
-                );
-
-                Err(SyncError::SyncFailed(
-                    "failed to apply remote operations and upload local operations",
-                ))
-            }
-            (Err(err), _) => {
-                error!(?err, "Failed to apply remote operations");
+            for operation in operations {
+                remote_operations.insert(operation.id);
+
+                if local_operations.contains(&operation.id) {
+                    continue;
+                }
+
+                trace!(?operation, "Applying remote operation");
 
-                Err(SyncError::SyncFailed("failed to apply remote operations"))
+                if let Some(event) = self.db.apply_operation(&operation).await.inspect_err(|err| {
+                    error!(?err, ?operation, "Failed to apply operation");
+                })? {
+                    if let DataEvent::FileAdded(file) = &event {
+                        sender.send(file.id).await.map_err(|err| {
+                            error!(?err, "Failed to send file id into the channel");
+                            SyncError::Event("failed to send file id into the channel")
+                        })?;
+                    }
+                    emitter.emit(DATA_EVENT, event).map_err(|err| {
+                        error!(?err, "Failed to emit data event");
+                        SyncError::Event("failed to emit data event")
+                    })?;
+                }
             }
-            (_, Err(err)) => {
-                error!(?err, "Failed to upload remote operations");
 
-                Err(SyncError::SyncFailed("failed to upload remote operations"))
+            match next {
+                Some(next) => continuation = Some(next),
+                None => break,
             }
         }
+
+        Ok(remote_operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    use super::live_blob_ids;
+    use crate::dataans::crypto::file_blob_id;
+    use crate::dataans::db::File;
+
+    fn file(hash: Option<&str>) -> File {
+        File {
+            id: Uuid::new_v4(),
+            name: String::from("tbt.png"),
+            path: String::from("tbt.png"),
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: OffsetDateTime::now_utc(),
+            is_deleted: false,
+            is_uploaded: true,
+            hash: hash.map(ToOwned::to_owned),
+            size: None,
+        }
+    }
+
+    #[test]
+    fn hashed_files_are_live_by_blob_id() {
+        let key = b"oeifvncpfiejnvdjpvnwifvj12345678";
+        let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let blob_id = |file: &File| match &file.hash {
+            Some(hash) => file_blob_id(key.into(), hash),
+            None => file.id,
+        };
+
+        let (hashed, duplicate, legacy) = (file(Some(hash)), file(Some(hash)), file(None));
+
+        let mut live = live_blob_ids(&[hashed.clone(), duplicate, legacy.clone()], blob_id);
+        live.sort();
+
+        let mut expected = vec![file_blob_id(key.into(), hash), legacy.id];
+        expected.sort();
+
+        // The hashed file is uploaded under its blob id. Its own id is unknown to the server.
+        assert_eq!(live, expected);
+        assert!(!live.contains(&hashed.id));
     }
 }
@@ -0,0 +1,149 @@
+//! Authorization token lifecycle.
+//!
+//! JWT-based tokens (Cloudflare Access and OIDC) expire. The app tracks the token expiration time and:
+//!
+//! * Tries to renew the token silently when it is about to expire. Only Cloudflare Access supports it:
+//!   the CF-Auth page is loaded in a hidden window and, if the Cloudflare session is still alive,
+//!   the page signs in without user interaction.
+//! * Warns the user when the token cannot be renewed silently.
+//!
+//! If the sync fails because of the expired token, the sync is resumed automatically after the re-authentication.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::event::{STATUS_UPDATE_EVENT, StatusUpdateEvent};
+use common::profile::{AuthScheme, UserProfile};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use time::OffsetDateTime;
+use url::Url;
+
+use crate::dataans::DataansState;
+use crate::dataans::service::web::WebService;
+use crate::dataans::sync::client::token_expiration;
+use crate::window::{CF_WINDOW_TITLE, cf_auth, cf_silent_renew};
+
+/// How long before the expiration the app starts renewing the token.
+const RENEW_BEFORE_EXPIRATION: time::Duration = time::Duration::minutes(10);
+/// How often the app checks the token expiration time.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// The maximum time the app waits for the silent renewal.
+const SILENT_RENEW_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Periodically checks the auth token expiration time and renews the token if possible.
+///
+/// This future never ends. It should be spawned once on the app start. It always checks the token
+/// of the currently opened vault.
+pub async fn watch_token_expiration<R: Runtime>(app: AppHandle<R>) {
+    // Expiration time of the token we have already handled. It prevents repeated warnings and renewals.
+    let mut handled: Option<OffsetDateTime> = None;
+
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let web_service = Arc::clone(&app.state::<DataansState>().vault().web_service);
+
+        let Some(UserProfile {
+            auth_token,
+            auth_scheme,
+            sync_config,
+            ..
+        }) = web_service.user_profile()
+        else {
+            continue;
+        };
+
+        let expires_at = match token_expiration(&auth_token, auth_scheme) {
+            Ok(Some(expires_at)) => expires_at,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!(?err, "Failed to extract auth token expiration time");
+                continue;
+            }
+        };
+
+        if handled == Some(expires_at) || expires_at - OffsetDateTime::now_utc() > RENEW_BEFORE_EXPIRATION {
+            continue;
+        }
+        handled = Some(expires_at);
+
+        info!(?expires_at, "Auth token expires soon");
+
+        if renew_token(&app, &web_service, auth_scheme, sync_config.url.as_ref(), false).await {
+            continue;
+        }
+
+        let minutes_left = (expires_at - OffsetDateTime::now_utc()).whole_minutes().max(0);
+        if let Err(err) = app.emit(
+            STATUS_UPDATE_EVENT,
+            StatusUpdateEvent::AuthTokenExpiresSoon(minutes_left as u64),
+        ) {
+            error!(?err, "Failed to emit status update event");
+        }
+    }
+}
+
+/// Renews the auth token.
+///
+/// Returns `true` if the token has been renewed without user interaction. If the silent renewal is
+/// not possible and `interactive` is `true`, then the sign-in window is opened.
+pub async fn renew_token<R: Runtime>(
+    app: &AppHandle<R>,
+    web_service: &WebService,
+    auth_scheme: AuthScheme,
+    sync_server: &Url,
+    interactive: bool,
+) -> bool {
+    match auth_scheme {
+        AuthScheme::CloudflareAccess => {
+            let old_token = web_service.user_profile().map(|profile| profile.auth_token);
+            // Subscribe before opening the window, so the notification can not be missed.
+            let authorized = web_service.authorized();
+
+            match cf_silent_renew(app, sync_server) {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("CF-Auth window is already opened");
+                    return false;
+                }
+                Err(err) => {
+                    error!(?err, "Failed to open the CF-Auth window for silent renewal");
+                    return false;
+                }
+            }
+
+            if tokio::time::timeout(SILENT_RENEW_TIMEOUT, authorized).await.is_err() {
+                debug!("Silent auth token renewal timed out");
+            }
+
+            let new_token = web_service.user_profile().map(|profile| profile.auth_token);
+            if new_token.is_some() && new_token != old_token {
+                info!("Auth token has been renewed silently");
+                return true;
+            }
+
+            warn!("Silent auth token renewal failed");
+
+            if interactive {
+                // The Cloudflare session is expired too. The user must sign in again.
+                if let Some(window) = app.webview_windows().get(CF_WINDOW_TITLE) {
+                    if let Err(err) = window.show().and_then(|_| window.set_focus()) {
+                        error!(?err, "Failed to show CF-Auth window");
+                    }
+                } else if let Err(err) = cf_auth(app.clone(), sync_server.clone()).await {
+                    error!(?err, "Failed to open CF-Auth window");
+                }
+            } else if let Some(window) = app.webview_windows().get(CF_WINDOW_TITLE)
+                && let Err(err) = window.destroy()
+            {
+                error!(?err, "Failed to destroy CF-Auth window");
+            }
+
+            false
+        }
+        // The app does not have a refresh token, so the user must paste a new access token in the sync settings.
+        AuthScheme::Oidc => false,
+        // API tokens do not expire.
+        AuthScheme::ApiToken => true,
+    }
+}
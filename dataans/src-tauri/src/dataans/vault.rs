@@ -0,0 +1,228 @@
+//! Vaults support.
+//!
+//! A vault is a separate notebook with its own database, files, user profile, and sync server.
+//! The default vault lives directly in the app base path. Additional vaults are configured in the
+//! `[[app.vaults]]` config section and live in `<base path>/vaults/<name>` (or in the configured path).
+//!
+//! Only one vault is opened at a time. Switching the vault re-initializes the [VaultState].
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use common::{DEFAULT_VAULT_NAME, Vault, VaultsInfo};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::dataans::{DataansError, VaultState};
+use crate::window::CF_WINDOW_TITLE;
+use crate::{ACTIVE_VAULT_FILE_NAME, FILES_DIR, VAULTS_DIR};
+
+struct ActiveVault {
+    name: String,
+    state: Arc<VaultState>,
+}
+
+/// The app state.
+///
+/// Holds the state of the currently opened vault.
+pub struct DataansState {
+    base_path: PathBuf,
+    vaults: Vec<Vault>,
+    active: RwLock<ActiveVault>,
+}
+
+impl DataansState {
+    /// Opens the last used vault (or the default one).
+    pub async fn init(base_path: PathBuf, vaults: Vec<Vault>) -> Result<Self, DataansError> {
+        validate_vaults(&vaults)?;
+
+        let active_vault_file = base_path.join(ACTIVE_VAULT_FILE_NAME);
+
+        let name = fs::read_to_string(&active_vault_file)
+            .map(|name| name.trim().to_owned())
+            .ok()
+            .filter(|name| name == DEFAULT_VAULT_NAME || vaults.iter().any(|vault| &vault.name == name))
+            .unwrap_or_else(|| DEFAULT_VAULT_NAME.to_owned());
+
+        let path = vault_path(&base_path, &vaults, &name)?;
+        info!(name, ?path, "Opening vault");
+
+        let state = Arc::new(VaultState::init(path.into()).await?);
+
+        Ok(Self {
+            base_path,
+            vaults,
+            active: RwLock::new(ActiveVault { name, state }),
+        })
+    }
+
+    /// Returns the state of the currently opened vault.
+    pub fn vault(&self) -> Arc<VaultState> {
+        Arc::clone(&self.active.read().unwrap().state)
+    }
+
+    /// Returns the name of the currently opened vault.
+    pub fn active_vault_name(&self) -> String {
+        self.active.read().unwrap().name.clone()
+    }
+
+    /// Returns the data directory of the currently opened vault.
+    pub fn active_vault_path(&self) -> PathBuf {
+        self.vault().base_path.to_path_buf()
+    }
+
+    /// Returns the information about all vaults.
+    pub fn vaults_info(&self) -> VaultsInfo {
+        VaultsInfo {
+            names: std::iter::once(DEFAULT_VAULT_NAME.to_owned())
+                .chain(self.vaults.iter().map(|vault| vault.name.clone()))
+                .collect(),
+            active: self.active_vault_name(),
+        }
+    }
+
+    /// Closes the current vault and opens the vault with the given name.
+    ///
+    /// Background tasks (like the sync) that have already started keep using the previous vault until they finish.
+    pub async fn switch_vault(&self, name: &str) -> Result<(), DataansError> {
+        if self.active_vault_name() == name {
+            return Ok(());
+        }
+
+        let path = vault_path(&self.base_path, &self.vaults, name)?;
+        info!(name, ?path, "Switching vault");
+
+        let state = Arc::new(VaultState::init(path.into()).await?);
+
+        *self.active.write().unwrap() = ActiveVault {
+            name: name.to_owned(),
+            state,
+        };
+
+        tokio::fs::write(self.base_path.join(ACTIVE_VAULT_FILE_NAME), name).await?;
+
+        Ok(())
+    }
+}
+
+/// Switches the vault and reloads all app windows, so they display the new vault data.
+pub async fn switch_vault_and_reload<R: Runtime>(app: &AppHandle<R>, name: &str) -> Result<(), DataansError> {
+    let state = app.state::<DataansState>();
+
+    if state.active_vault_name() == name {
+        return Ok(());
+    }
+
+    state.switch_vault(name).await?;
+    allow_vault_files(app, &state.active_vault_path())?;
+
+    for (label, window) in app.webview_windows() {
+        if label == CF_WINDOW_TITLE {
+            // The sign-in window belongs to the previous vault.
+            window.destroy()?;
+        } else {
+            window.reload()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Allows the webview to load vault files using the asset protocol.
+///
+/// The vault can be located outside the app data directory, so it is not covered by the static asset protocol scope.
+pub fn allow_vault_files<R: Runtime>(app: &AppHandle<R>, vault_path: &Path) -> Result<(), DataansError> {
+    app.asset_protocol_scope()
+        .allow_directory(vault_path.join(FILES_DIR), true)?;
+
+    Ok(())
+}
+
+/// Checks the configured vaults.
+///
+/// Vault names are used as directory names, so they must be plain file names. Also, they must be unique
+/// and must not clash with the default vault name.
+fn validate_vaults(vaults: &[Vault]) -> Result<(), DataansError> {
+    for (index, vault) in vaults.iter().enumerate() {
+        let name = vault.name.as_str();
+        let invalid = |reason| DataansError::InvalidVault(name.to_owned(), reason);
+
+        if name.trim().is_empty() {
+            return Err(invalid("the name is empty"));
+        }
+        if name == DEFAULT_VAULT_NAME {
+            return Err(invalid("the name is reserved for the default vault"));
+        }
+        if Path::new(name).file_name() != Some(OsStr::new(name)) || name.contains(['/', '\\']) {
+            return Err(invalid("the name must be a plain file name"));
+        }
+        if vaults[..index].iter().any(|other| other.name == name) {
+            return Err(invalid("the name is duplicated"));
+        }
+    }
+
+    Ok(())
+}
+
+fn vault_path(base_path: &Path, vaults: &[Vault], name: &str) -> Result<PathBuf, DataansError> {
+    if name == DEFAULT_VAULT_NAME {
+        return Ok(base_path.to_path_buf());
+    }
+
+    let vault = vaults
+        .iter()
+        .find(|vault| vault.name == name)
+        .ok_or_else(|| DataansError::VaultNotFound(name.to_owned()))?;
+
+    if vault.path.is_empty() {
+        Ok(base_path.join(VAULTS_DIR).join(&vault.name))
+    } else {
+        Ok(PathBuf::from(&vault.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{DEFAULT_VAULT_NAME, Vault};
+
+    use super::validate_vaults;
+    use crate::dataans::DataansError;
+
+    fn vaults(names: &[&str]) -> Vec<Vault> {
+        names
+            .iter()
+            .map(|name| Vault {
+                name: (*name).to_owned(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_vault_names() {
+        assert!(validate_vaults(&vaults(&["work", "home notes", "a.b"])).is_ok());
+    }
+
+    #[test]
+    fn invalid_vault_names() {
+        for names in [
+            &["work", ""][..],
+            &[DEFAULT_VAULT_NAME],
+            &[".."],
+            &["."],
+            &["../work"],
+            &["work/notes"],
+            &["work\\notes"],
+            &["/work"],
+            &["work", "home", "work"],
+        ] {
+            let bad_name = names.last().unwrap();
+
+            match validate_vaults(&vaults(names)) {
+                Err(DataansError::InvalidVault(name, _)) => assert_eq!(&name, bad_name),
+                result => panic!("{names:?} must be rejected, got {result:?}"),
+            }
+        }
+    }
+}
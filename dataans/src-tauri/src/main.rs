@@ -14,6 +14,7 @@ use std::str::FromStr;
 
 use tauri::menu::{MenuBuilder, MenuItemBuilder};
 use tauri::{AppHandle, Manager, Result, RunEvent};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
 
 const LOGGING_ENV_VAR_NAME: &str = "DATAANS_LOG";
@@ -27,6 +28,9 @@ const WINDOW_VISIBILITY_TITLE: &str = "Toggle";
 const WINDOW_QUIT_TITLE: &str = "Quit";
 
 const PROFILE_DIR: &str = "profile";
+const DB_DIR: &str = "db";
+const VAULTS_DIR: &str = "vaults";
+const ACTIVE_VAULT_FILE_NAME: &str = "active-vault";
 const FILES_DIR: &str = "files";
 const CONFIGS_DIR: &str = "configs";
 const CONFIG_FILE_NAME: &str = "config.toml";
@@ -50,6 +54,45 @@ fn toggle_app_visibility(app: &AppHandle) -> Result<()> {
     Ok(())
 }
 
+/// Shows the error message to the user.
+///
+/// It is used for errors that happen outside of commands, so the frontend can not report them.
+fn show_error(app: &AppHandle, message: String) {
+    app.dialog()
+        .message(message)
+        .title("Dataans")
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
+}
+
+/// Handles the vault toggle shortcut.
+///
+/// If the vault is already opened, then it toggles the app visibility. Otherwise, it switches to the vault and shows the app.
+fn toggle_vault(app: &AppHandle, name: String) -> Result<()> {
+    if app.state::<dataans::DataansState>().active_vault_name() == name {
+        return toggle_app_visibility(app);
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = dataans::vault::switch_vault_and_reload(&app, &name).await {
+            error!(?err, name, "Failed to switch vault");
+            show_error(&app, format!("Failed to open the '{name}' vault: {err}"));
+            return;
+        }
+
+        if let Some(window) = app.get_webview_window(MAIN_WINDOW_NAME) {
+            if let Err(err) = window.show().and_then(|_| window.set_focus()) {
+                error!(?err, "Failed to show main window");
+            }
+        } else {
+            error!("{MAIN_WINDOW_NAME} window not found!");
+        }
+    });
+
+    Ok(())
+}
+
 fn init_tracing(app_data: &Path) {
     use std::fs::OpenOptions;
     use std::{fs, io};
@@ -137,6 +180,30 @@ fn main() {
             let visibility_shortcut = Shortcut::from_str(&config.app.app_toggle).unwrap();
             debug!(?visibility_shortcut);
 
+            let vault_shortcuts = config
+                .app
+                .vaults
+                .iter()
+                .filter(|vault| !vault.toggle.is_empty())
+                .filter_map(|vault| match Shortcut::from_str(&vault.toggle) {
+                    Ok(shortcut) => Some((shortcut, vault.name.clone())),
+                    Err(err) => {
+                        error!(?err, vault = vault.name, "Invalid vault toggle shortcut");
+                        show_error(
+                            app.handle(),
+                            format!("Invalid toggle shortcut of the '{}' vault: {err}", vault.name),
+                        );
+
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            debug!(?vault_shortcuts);
+            let registered_vault_shortcuts = vault_shortcuts
+                .iter()
+                .map(|(shortcut, name)| (*shortcut, name.clone()))
+                .collect::<Vec<_>>();
+
             app.handle().plugin(
                 tauri_plugin_global_shortcut::Builder::new()
                     .with_handler(move |app, shortcut, event| {
@@ -151,12 +218,32 @@ fn main() {
                                     debug!("Global visibility shortcut has been released.");
                                 }
                             }
+                        } else if let Some((_, vault_name)) = vault_shortcuts
+                            .iter()
+                            .find(|(vault_shortcut, _)| vault_shortcut == shortcut)
+                            && event.state() == ShortcutState::Pressed
+                        {
+                            debug!(vault_name, "Global vault shortcut has been pressed.");
+                            if let Err(err) = toggle_vault(app, vault_name.clone()) {
+                                error!(?err, vault_name, "Failed to toggle vault");
+                            }
                         }
                     })
                     .build(),
             )?;
 
             app.global_shortcut().register(visibility_shortcut)?;
+            for (vault_shortcut, vault_name) in registered_vault_shortcuts {
+                if let Err(err) = app.global_shortcut().register(vault_shortcut) {
+                    error!(?err, vault = vault_name, "Failed to register vault toggle shortcut");
+                    show_error(
+                        app.handle(),
+                        format!("Failed to register the toggle shortcut of the '{vault_name}' vault: {err}"),
+                    );
+
+                    continue;
+                }
+            }
 
             if config.app.always_on_top {
                 if let Some(window) = app.handle().get_webview_window(MAIN_WINDOW_NAME) {
@@ -200,6 +287,7 @@ fn main() {
             config::open_theme_file,
             code_block::parse_code,
             window::select_import_file,
+            window::select_import_dir,
             window::open_app_info_window,
             window::cf_auth,
         ])
@@ -71,9 +71,33 @@ pub async fn cf_auth<R: Runtime>(app: AppHandle<R>, url: Url) -> CommandResultEm
     Ok(())
 }
 
+/// Opens the hidden CF-Auth window for the silent token renewal.
+///
+/// If the Cloudflare Access session is still alive, then Cloudflare redirects to the authorization page
+/// without any user interaction, and the page signs in automatically. The window is closed by the `sign_in` command.
+///
+/// Returns `false` if the CF-Auth window is already opened.
+pub fn cf_silent_renew<R: Runtime>(app: &AppHandle<R>, url: &Url) -> Result<bool, DataansError> {
+    if app.webview_windows().contains_key(CF_WINDOW_TITLE) {
+        return Ok(false);
+    }
+
+    let mut renew_url = url.join("health/authorize.html")?;
+    renew_url.query_pairs_mut().append_pair("renew", "true");
+
+    // Browsing data is not cleared on purpose: we need the Cloudflare session cookies.
+    WebviewWindowBuilder::new(app, CF_WINDOW_TITLE, WebviewUrl::External(renew_url))
+        .visible(false)
+        .inner_size(800.0, 800.0)
+        .title(CF_WINDOW_TITLE)
+        .build()?;
+
+    Ok(true)
+}
+
 /// Selects the data file for importing into the app.
 ///
-/// Currently, only the json import is supported.
+/// Currently, the json export and the backup archive are supported.
 #[tauri::command]
 pub async fn select_import_file(app: AppHandle) -> CommandResult<Option<PathBuf>> {
     let (tx, rx) = oneshot::channel();
@@ -81,7 +105,7 @@ pub async fn select_import_file(app: AppHandle) -> CommandResult<Option<PathBuf>
     tauri::async_runtime::spawn(async move {
         app.dialog()
             .file()
-            .add_filter("Notes", &["json"])
+            .add_filter("Notes", &["json", "zip"])
             .pick_file(move |file_path| {
                 let result = match file_path {
                     Some(FilePath::Path(p)) => Ok(Some(p)),
@@ -105,3 +129,33 @@ pub async fn select_import_file(app: AppHandle) -> CommandResult<Option<PathBuf>
         }
     }
 }
+
+/// Selects the directory with Markdown files for importing into the app.
+#[tauri::command]
+pub async fn select_import_dir(app: AppHandle) -> CommandResult<Option<PathBuf>> {
+    let (tx, rx) = oneshot::channel();
+
+    tauri::async_runtime::spawn(async move {
+        app.dialog().file().pick_folder(move |dir_path| {
+            let result = match dir_path {
+                Some(FilePath::Path(p)) => Ok(Some(p)),
+                Some(_) => {
+                    let err = CommandError::Dataans("unsupported directory selected".to_string());
+                    error!(?err, "Failed to select directory");
+                    Err(err)
+                }
+                None => Ok(None),
+            };
+            let _ = tx.send(result);
+        });
+    });
+
+    match rx.await {
+        Ok(result) => result,
+        Err(e) => {
+            let err = CommandError::Dataans(format!("failed to receive directory path: {e}"));
+            error!(?err, "failed to select directory");
+            Err(err)
+        }
+    }
+}
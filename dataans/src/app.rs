@@ -23,6 +23,10 @@ pub enum FindNoteMode {
     FindNote {
         space: Option<OwnedSpace>,
     },
+    /// Browse files attached to the notes of the space.
+    Gallery {
+        space: OwnedSpace,
+    },
 }
 
 #[derive(Debug, Clone)]
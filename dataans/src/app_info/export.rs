@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use common::export::SchemaVersion;
+use common::export::{ArchiveExportOption, SchemaVersion};
 use common::{DataExportConfig, NotesExportOption};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
@@ -91,11 +91,26 @@ pub fn Export() -> impl IntoView {
                                 value=version.variant_name()
                                 selected=version.variant_name() == schema_version.variant_name()
                             >
-                                {schema_version.to_string()}
+                                {version.to_string()}
                             </option>
                         }).collect_view()}
                     </select>
                 }.into_any(),
+                DataExportConfig::Archive(archive_option) => view! {
+                    <input
+                        type="password"
+                        class="input"
+                        placeholder="Passphrase (optional)"
+                        title="The archive is encrypted when the passphrase is set"
+                        prop:value=archive_option.passphrase.unwrap_or_default()
+                        on:change=move |ev| {
+                            let passphrase = event_target_value(&ev);
+                            set_export_config.set(DataExportConfig::Archive(ArchiveExportOption {
+                                passphrase: Some(passphrase).filter(|passphrase| !passphrase.is_empty()),
+                            }));
+                        }
+                    />
+                }.into_any(),
             }}
             <button class="button_cancel" on:click=move |_| { export_data_action.dispatch(export_config.get()); }>"Export"</button>
             <Show when=move || backup_dir.get().is_some()>
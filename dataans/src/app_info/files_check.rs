@@ -0,0 +1,100 @@
+use common::note::{FileRepair, FilesReport};
+use leptos::prelude::*;
+
+use crate::backend::file::{repair_files, verify_files};
+
+#[component]
+pub fn FilesCheck() -> impl IntoView {
+    let toaster = leptoaster::expect_toaster();
+
+    let (report, set_report) = signal(None::<FilesReport>);
+
+    let t = toaster.clone();
+    let check_files = Action::new_unsync(move |_: &()| {
+        let toaster = t.clone();
+        async move {
+            set_report.set(Some(try_exec!(verify_files().await, "Failed to check files", toaster)));
+        }
+    });
+
+    let repair = Action::new_unsync(move |repairs: &Vec<FileRepair>| {
+        let toaster = toaster.clone();
+        let repairs = repairs.clone();
+        async move {
+            set_report.set(Some(try_exec!(
+                repair_files(repairs).await,
+                "Failed to repair files",
+                toaster
+            )));
+        }
+    });
+
+    view! {
+        <div class="app-info-files-check">
+            <div class="horizontal">
+                <button
+                    class="button_cancel"
+                    disabled=move || check_files.pending().get() || repair.pending().get()
+                    on:click=move |_| { check_files.dispatch(()); }
+                >
+                    {move || if check_files.pending().get() { "Checking files..." } else { "Check files" }}
+                </button>
+                {move || report.get().filter(FilesReport::is_ok).map(|_| view! { <span>"No problems found."</span> })}
+            </div>
+            {move || report.get().filter(|report| !report.is_ok()).map(|report| {
+                let FilesReport { missing, corrupted, orphaned } = report;
+
+                let broken_files = (!missing.is_empty() || !corrupted.is_empty()).then(|| {
+                    let repairs = missing
+                        .iter()
+                        .chain(&corrupted)
+                        .map(|file| FileRepair::Redownload(file.id))
+                        .collect::<Vec<_>>();
+
+                    view! {
+                        <span>"Missing or corrupted files. They can be downloaded again from the sync server:"</span>
+                        <ul class="app-info-files-check-list">
+                            {missing.iter().map(|file| view! { <li>{format!("{} (missing)", file.name)}</li> }).collect_view()}
+                            {corrupted.iter().map(|file| view! { <li>{format!("{} (content mismatch)", file.name)}</li> }).collect_view()}
+                        </ul>
+                        <button class="button_ok" on:click=move |_| { repair.dispatch(repairs.clone()); }>"Re-download"</button>
+                    }
+                });
+
+                let orphaned_files = (!orphaned.is_empty()).then(|| {
+                    let recover_all = orphaned.iter().cloned().map(FileRepair::Recover).collect::<Vec<_>>();
+
+                    view! {
+                        <span>"Files that are not registered in the app:"</span>
+                        <ul class="app-info-files-check-list">
+                            {orphaned.iter().map(|path| {
+                                let recover = vec![FileRepair::Recover(path.clone())];
+                                let delete = vec![FileRepair::Delete(path.clone())];
+
+                                view! {
+                                    <li>
+                                        {path.to_string_lossy().into_owned()}
+                                        <button
+                                            class="button_ok"
+                                            title="Attach the file to a new note in the \"Recovered\" space"
+                                            on:click=move |_| { repair.dispatch(recover.clone()); }
+                                        >
+                                            "Recover"
+                                        </button>
+                                        <button class="button_cancel" on:click=move |_| { repair.dispatch(delete.clone()); }>"Delete"</button>
+                                    </li>
+                                }
+                            }).collect_view()}
+                        </ul>
+                        <button class="button_ok" on:click=move |_| { repair.dispatch(recover_all.clone()); }>"Recover all"</button>
+                    }
+                });
+
+                view! {
+                    {broken_files}
+                    {orphaned_files}
+                }
+            })}
+        </div>
+    }
+}
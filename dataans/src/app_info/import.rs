@@ -1,20 +1,24 @@
+use common::import::{ImportReport, MdImportConfig};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 
-use crate::backend::import::{import_app_data, select_import_file};
+use crate::backend::import::{import_app_data, import_md_dir, import_telegram, select_import_dir, select_import_file};
 
 #[component]
 pub fn Import() -> impl IntoView {
     let toaster = leptoaster::expect_toaster();
     let (is_importing, set_is_importing) = signal(false);
+    let (passphrase, set_passphrase) = signal(String::new());
 
     let import_data = move |_| {
         let toaster_clone = toaster.clone();
         set_is_importing.set(true);
+        // Only encrypted backup archives need the passphrase.
+        let passphrase = Some(passphrase.get_untracked()).filter(|passphrase| !passphrase.is_empty());
 
         spawn_local(async move {
             match select_import_file().await {
-                Ok(Some(path)) => match import_app_data(path).await {
+                Ok(Some(path)) => match import_app_data(path, passphrase).await {
                     Ok(_) => toaster_clone.success("Import successful!"),
                     Err(e) => toaster_clone.error(format!("Import failed: {e}")),
                 },
@@ -30,8 +34,102 @@ pub fn Import() -> impl IntoView {
     };
 
     view! {
-        <button on:click=import_data disabled=is_importing.get() style="cursor: pointer;">
-            {move || if is_importing.get() { "Importing..." } else { "Import" }}
-        </button>
+        <div class="horizontal">
+            <input
+                type="password"
+                class="input"
+                placeholder="Archive passphrase (optional)"
+                prop:value=passphrase
+                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+            />
+            <button on:click=import_data disabled=is_importing.get() style="cursor: pointer;">
+                {move || if is_importing.get() { "Importing..." } else { "Import" }}
+            </button>
+        </div>
+    }
+}
+
+fn import_summary(report: &ImportReport) -> String {
+    format!(
+        "Imported {} notes and {} files into {} new spaces",
+        report.notes, report.files, report.spaces
+    )
+}
+
+/// Imports notes from other apps: Markdown folders (e.g. Obsidian or Logseq vaults) and Telegram exports.
+#[component]
+pub fn ExternalImport() -> impl IntoView {
+    let toaster = leptoaster::expect_toaster();
+
+    let (config, set_config) = signal(MdImportConfig::default());
+    let (report, set_report) = signal(None::<ImportReport>);
+
+    let t = toaster.clone();
+    let import_dir = Action::new_unsync(move |config: &MdImportConfig| {
+        let toaster = t.clone();
+        let config = *config;
+        async move {
+            let Some(path) = try_exec!(select_import_dir().await, "Cannot select a directory", toaster) else {
+                // User canceled the dialog, do nothing
+                return;
+            };
+
+            let report = try_exec!(import_md_dir(path, config).await, "Import failed", toaster);
+            toaster.success(import_summary(&report));
+            set_report.set(Some(report));
+        }
+    });
+
+    let import_telegram_export = Action::new_unsync(move |_: &()| {
+        let toaster = toaster.clone();
+        async move {
+            let Some(path) = try_exec!(select_import_file().await, "Cannot select a file", toaster) else {
+                // User canceled the dialog, do nothing
+                return;
+            };
+
+            let report = try_exec!(import_telegram(path).await, "Import failed", toaster);
+            toaster.success(import_summary(&report));
+            set_report.set(Some(report));
+        }
+    });
+    let is_importing = move || import_dir.pending().get() || import_telegram_export.pending().get();
+
+    view! {
+        <div class="app-info-files-check">
+            <div class="horizontal">
+                <button
+                    class="button_cancel"
+                    disabled=is_importing
+                    on:click=move |_| { import_dir.dispatch(config.get_untracked()); }
+                >
+                    {move || if import_dir.pending().get() { "Importing..." } else { "Import Markdown folder" }}
+                </button>
+                <label title="Every top-level heading starts a new note">
+                    <input
+                        type="checkbox"
+                        prop:checked=move || config.get().split_by_headings
+                        on:change=move |ev| set_config.set(MdImportConfig { split_by_headings: event_target_checked(&ev) })
+                    />
+                    "Split by headings"
+                </label>
+                <button
+                    class="button_cancel"
+                    title="Select result.json from the Telegram Desktop chat or account export (JSON format)"
+                    disabled=is_importing
+                    on:click=move |_| { import_telegram_export.dispatch(()); }
+                >
+                    {move || if import_telegram_export.pending().get() { "Importing..." } else { "Import Telegram export" }}
+                </button>
+            </div>
+            {move || report.get().filter(|report| !report.skipped.is_empty()).map(|report| view! {
+                <span>"Skipped items:"</span>
+                <ul class="app-info-files-check-list">
+                    {report.skipped.into_iter().map(|item| view! {
+                        <li>{format!("{}: {}", item.path.to_string_lossy(), item.reason)}</li>
+                    }).collect_view()}
+                </ul>
+            })}
+        </div>
     }
 }
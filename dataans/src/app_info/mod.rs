@@ -1,6 +1,8 @@
 mod export;
+mod files_check;
 mod import;
 mod sync_settings;
+mod vault;
 
 use std::path::PathBuf;
 
@@ -10,8 +12,10 @@ use leptos::prelude::*;
 use leptos::task::spawn_local;
 
 use self::sync_settings::SyncState;
+use self::vault::VaultSwitcher;
 use crate::app_info::export::Export;
-use crate::app_info::import::Import;
+use crate::app_info::files_check::FilesCheck;
+use crate::app_info::import::{ExternalImport, Import};
 use crate::backend::{open_config_file, open_config_file_folder, open_theme_file};
 use crate::notes::md_node::InlineCode;
 
@@ -57,6 +61,7 @@ pub fn AppInfo() -> impl IntoView {
             <span>"Source code: "<a href="https://github.com/TheBestTvarynka/Dataans" target="_blank">"GitHub/TbeBestTvarynka/Dataans"</a>"."</span>
             <span class="icons-by-icons8">"Icons by "<a href="https://icons8.com" target="_blank">"Icons8"</a>"."</span>
             <hr style="width: 100%" />
+            <VaultSwitcher />
             <SyncState />
             <hr style="width: 80%" />
             <div class="horizontal">
@@ -79,7 +84,7 @@ pub fn AppInfo() -> impl IntoView {
 
                 let KeyBindings { toggle_spaces_bar, create_space, edit_current_space, delete_current_space, select_next_list_item, select_prev_list_item, find_note, find_note_in_selected_space, regenerate_space_avatar } = key_bindings;
                 let Appearance { theme } = appearance;
-                let App { app_toggle, always_on_top, hide_window_decorations, hide_taskbar_icon, base_path } = app;
+                let App { app_toggle, always_on_top, hide_window_decorations, hide_taskbar_icon, base_path, vaults } = app;
 
                 view!{
                     <table class="app-window-config-table">
@@ -94,11 +99,19 @@ pub fn AppInfo() -> impl IntoView {
                             </td>
                         </tr>
                         <tr>
-                            <td>"App data folder"</td>
+                            <td>"Vault data folder"</td>
                             <td>
                                 <InlineCode code=base_path />
                             </td>
                         </tr>
+                        {vaults.into_iter().filter(|vault| !vault.toggle.is_empty()).map(|vault| view! {
+                            <tr>
+                                <td>{format!("Vault '{}' toggle", vault.name)}</td>
+                                <td>
+                                    <InlineCode code=vault.toggle />
+                                </td>
+                            </tr>
+                        }).collect_view()}
                         <tr>
                             <td>"Always on top"</td>
                             <td>
@@ -202,6 +215,8 @@ pub fn AppInfo() -> impl IntoView {
                 <Export />
                 <Import />
             </div>
+            <ExternalImport />
+            <FilesCheck />
         </div>
     }
 }
@@ -1,22 +1,120 @@
-use common::profile::{Sync, SyncMode, UserContext};
+use common::common_api_types::Usage;
+use common::profile::{AuthScheme, RecoveryKit, Sync, SyncMode, UserContext};
 use leptos::html;
 use leptos::prelude::*;
 use leptos::task::spawn_local;
+use url::Url;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlSelectElement;
+
+/// Formats the number of bytes in a human-readable form. For example, `1.5 MiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[component]
+fn StorageUsage() -> impl IntoView {
+    let (usage, set_usage) = signal(Option::<Usage>::None);
+
+    spawn_local(async move {
+        // The usage is informational. The sync server may be unreachable at the moment, so we do not bother the user.
+        match crate::backend::sync::sync_usage().await {
+            Ok(usage) => set_usage.set(Some(usage)),
+            Err(err) => warn!(?err, "Failed to load the sync server storage usage"),
+        }
+    });
+
+    move || {
+        usage.get().map(|usage| {
+            let used = format_bytes(usage.total_bytes());
+            let total = match usage.quota {
+                Some(quota) => format!("{used} of {} used", format_bytes(quota)),
+                None => format!("{used} used"),
+            };
+
+            view! {
+                <span title=format!("Operations: {}. Files: {}.", format_bytes(usage.operation_bytes), format_bytes(usage.file_bytes))>
+                    {format!("Storage: {total} ({} operations, {} files).", usage.operations, usage.files)}
+                </span>
+            }
+        })
+    }
+}
 
 #[component]
 pub fn SyncSettings(context: UserContext) -> impl IntoView {
     let toaster = leptoaster::expect_toaster();
 
+    let t = toaster.clone();
     let sign_out = Callback::new(move |_: ()| {
-        let t = toaster.clone();
+        let t = t.clone();
         spawn_local(async move {
             try_exec!(crate::backend::auth::sign_out().await, "Failed to sign out", t);
         })
     });
 
+    let t = toaster.clone();
+    let export_recovery_kit = Callback::new(move |_: ()| {
+        let t = t.clone();
+        spawn_local(async move {
+            if let Some(path) = try_exec!(
+                crate::backend::auth::export_recovery_kit().await,
+                "Failed to export the recovery kit",
+                t
+            ) {
+                t.toast(
+                    leptoaster::ToastBuilder::new(format!("Recovery kit saved to {}", path.display()))
+                        .with_level(leptoaster::ToastLevel::Success)
+                        .with_position(leptoaster::ToastPosition::BottomRight)
+                        .with_expiry(Some(5000)),
+                );
+            }
+        })
+    });
+
     let UserContext {
         sync_config: Sync { url, mode },
+        auth_scheme,
     } = context;
+
+    // Only Cloudflare Access tokens can be renewed by the app. For other schemes, the user enters a new token here.
+    // The sync interrupted by the expired token is resumed after the token update.
+    let token_ref: NodeRef<html::Input> = NodeRef::new();
+    let sync_server = url.as_ref().clone();
+    let update_token = Callback::new(move |_: ()| {
+        let t = toaster.clone();
+        let token = token_ref.get().expect("<input> should be mounted").value();
+        let sync_server = sync_server.clone();
+
+        spawn_local(async move {
+            try_exec!(
+                crate::backend::auth::sign_in(&token, &sync_server, None, None, auth_scheme, None).await,
+                "Failed to update the authorization token",
+                t
+            );
+
+            t.toast(
+                leptoaster::ToastBuilder::new("Authorization token has been updated")
+                    .with_level(leptoaster::ToastLevel::Success)
+                    .with_position(leptoaster::ToastPosition::BottomRight)
+                    .with_expiry(Some(3000)),
+            );
+        })
+    });
+
     let url = url.as_ref().to_string();
 
     view! {
@@ -27,6 +125,20 @@ pub fn SyncSettings(context: UserContext) -> impl IntoView {
                     <img alt="cloud-icon" src="/public/icons/sign-out.png" />
                 </button>
             </div>
+            <div class="horizontal">
+                <button class="button_cancel" title="Save the unique passphrase and other sign-in values to a printable file" on:click=move |_| export_recovery_kit.run(())>
+                    "Export recovery kit"
+                </button>
+            </div>
+            {(auth_scheme != AuthScheme::CloudflareAccess).then(|| view! {
+                <div class="horizontal">
+                    <input type="password" class="input" placeholder="New token" style="flex-grow: 1;" node_ref=token_ref />
+                    <button class="button_ok" title="Re-authenticate with a new token" on:click=move |_| update_token.run(())>
+                        "Update token"
+                    </button>
+                </div>
+            })}
+            <StorageUsage />
             {match mode {
                 SyncMode::Manual => view! {
                     <form>
@@ -48,9 +160,17 @@ pub fn SyncSettings(context: UserContext) -> impl IntoView {
 pub fn SetUpSync() -> impl IntoView {
     let toaster = leptoaster::expect_toaster();
 
+    let (auth_scheme, set_auth_scheme) = signal(AuthScheme::default());
+    let recovery_kit: RwSignal<Option<RecoveryKit>> = RwSignal::new(None);
+
     let web_server_url_ref: NodeRef<html::Input> = NodeRef::new();
+    let token_ref: NodeRef<html::Input> = NodeRef::new();
+    let password_ref: NodeRef<html::Input> = NodeRef::new();
+    let salt_ref: NodeRef<html::Input> = NodeRef::new();
+
+    let t = toaster.clone();
     let show_auth_window = Callback::new(move |_: ()| {
-        let t = toaster.clone();
+        let t = t.clone();
         let url = web_server_url_ref.get().expect("<input> should be mounted").value();
         let url = try_exec!(url.parse(), "Failed to parse the web server URL", t);
         spawn_local(async move {
@@ -62,12 +182,109 @@ pub fn SetUpSync() -> impl IntoView {
         })
     });
 
+    let t = toaster.clone();
+    let import_recovery_kit = Callback::new(move |_: ()| {
+        let t = t.clone();
+        spawn_local(async move {
+            let Some(kit) = try_exec!(
+                crate::backend::auth::import_recovery_kit().await,
+                "Failed to import the recovery kit",
+                t
+            ) else {
+                return;
+            };
+
+            web_server_url_ref
+                .get()
+                .expect("<input> should be mounted")
+                .set_value(kit.sync_server.as_ref().as_str());
+            set_auth_scheme.set(kit.auth_scheme);
+            recovery_kit.set(Some(kit));
+        })
+    });
+
+    let sign_in_with_token = Callback::new(move |_: ()| {
+        let t = toaster.clone();
+        let url = web_server_url_ref.get().expect("<input> should be mounted").value();
+        let url: Url = try_exec!(url.parse(), "Failed to parse the web server URL", t);
+        let token = token_ref.get().expect("<input> should be mounted").value();
+        let password = password_ref.get().expect("<input> should be mounted").value();
+        let salt = salt_ref.get().expect("<input> should be mounted").value();
+        let auth_scheme = auth_scheme.get();
+        // The fingerprint is checked only if the passphrase from the recovery kit is used.
+        let key_fingerprint = recovery_kit
+            .get_untracked()
+            .filter(|kit| kit.salt.as_ref() == &salt)
+            .map(|kit| kit.key_fingerprint);
+
+        spawn_local(async move {
+            try_exec!(
+                crate::backend::auth::sign_in(
+                    &token,
+                    &url,
+                    Some(password.as_str()).filter(|password| !password.is_empty()),
+                    Some(salt.as_str()).filter(|salt| !salt.is_empty()),
+                    auth_scheme,
+                    key_fingerprint.as_deref(),
+                )
+                .await,
+                "Failed to sign in",
+                t
+            );
+        })
+    });
+
     view! {
-        <div class="horizontal">
-            <input type="text" class="input" value="https://backup.dataans.com/" style="flex-grow: 1;" node_ref=web_server_url_ref />
-            <button on:click=move |_| show_auth_window.run(()) title="Set up back up & sync" class="tool">
-                <img alt="cloud-icon" src="/public/icons/cloud-backup-light.png" />
-            </button>
+        <div class="app-info-sync-config">
+            <div class="horizontal" style="width: 100%;">
+                <select
+                    class="input"
+                    on:change=move |ev: leptos::ev::Event| {
+                        let select: HtmlSelectElement = ev.target().unwrap().unchecked_into();
+                        if let Ok(scheme) = select.value().parse::<AuthScheme>() {
+                            set_auth_scheme.set(scheme);
+                        }
+                    }
+                >
+                    {AuthScheme::variants().iter().map(|scheme| view! {
+                        <option
+                            value=scheme.variant_name()
+                            selected=move || *scheme == auth_scheme.get()
+                        >
+                            {scheme.pretty()}
+                        </option>
+                    }).collect_view()}
+                </select>
+                <input type="text" class="input" value="https://backup.dataans.com/" style="flex-grow: 1;" node_ref=web_server_url_ref />
+                <Show when=move || auth_scheme.get() == AuthScheme::CloudflareAccess>
+                    <button on:click=move |_| show_auth_window.run(()) title="Set up back up & sync" class="tool">
+                        <img alt="cloud-icon" src="/public/icons/cloud-backup-light.png" />
+                    </button>
+                </Show>
+                <button class="button_cancel" title="Pre-fill sign-in values from the recovery kit" on:click=move |_| import_recovery_kit.run(())>
+                    "Import recovery kit"
+                </button>
+            </div>
+            <Show when=move || auth_scheme.get() == AuthScheme::CloudflareAccess && recovery_kit.get().is_some()>
+                <span>
+                    "Unique passphrase from the recovery kit: "
+                    <code>{move || recovery_kit.get().map(|kit| kit.salt.as_ref().clone()).unwrap_or_default()}</code>
+                    ". Enter it alongside your password in the authorization window."
+                </span>
+            </Show>
+            <Show when=move || auth_scheme.get() != AuthScheme::CloudflareAccess>
+                <input type="password" class="input" placeholder="Token" style="width: 100%;" node_ref=token_ref />
+                <input type="password" class="input" placeholder="Password" style="width: 100%;" node_ref=password_ref />
+                <input
+                    type="text"
+                    class="input"
+                    placeholder="Unique passphrase (leave empty on the first sign in)"
+                    style="width: 100%;"
+                    node_ref=salt_ref
+                    prop:value=move || recovery_kit.get().map(|kit| kit.salt.as_ref().clone()).unwrap_or_default()
+                />
+                <button class="button_ok" on:click=move |_| sign_in_with_token.run(())>"Sign in"</button>
+            </Show>
         </div>
     }
 }
@@ -0,0 +1,49 @@
+use common::VaultsInfo;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlSelectElement;
+
+use crate::backend::vault::{list_vaults, switch_vault};
+
+#[component]
+pub fn VaultSwitcher() -> impl IntoView {
+    let toaster = leptoaster::expect_toaster();
+
+    let (vaults, set_vaults) = signal(Option::<VaultsInfo>::None);
+
+    let t = toaster.clone();
+    spawn_local(async move {
+        set_vaults.set(Some(try_exec!(list_vaults().await, "Failed to list vaults", t)));
+    });
+
+    let switch_vault = Callback::new(move |name: String| {
+        let t = toaster.clone();
+        spawn_local(async move {
+            // All windows are reloaded after the successful switch.
+            try_exec!(switch_vault(&name).await, "Failed to switch vault", t);
+        })
+    });
+
+    view! {
+        <Show when=move || vaults.get().map(|vaults| vaults.names.len() > 1).unwrap_or(false)>
+            <div class="horizontal">
+                <span>"Vault:"</span>
+                <select
+                    class="input"
+                    on:change=move |ev: leptos::ev::Event| {
+                        let select: HtmlSelectElement = ev.target().unwrap().unchecked_into();
+                        switch_vault.run(select.value());
+                    }
+                >
+                    {move || vaults.get().map(|VaultsInfo { names, active }| names.into_iter().map(|name| {
+                        let selected = name == active;
+                        view! {
+                            <option value=name.clone() selected=selected>{name}</option>
+                        }
+                    }).collect_view())}
+                </select>
+            </div>
+        </Show>
+    }
+}
@@ -1,6 +1,10 @@
+use std::path::PathBuf;
+
 use common::APP_PLUGIN_NAME;
 use common::error::{CommandResult, CommandResultEmpty};
-use common::profile::UserContext;
+use common::profile::{AuthScheme, RecoveryKit, UserContext};
+use serde::Serialize;
+use url::Url;
 
 use crate::backend::{EmptyArgs, invoke_command};
 
@@ -11,3 +15,44 @@ pub async fn profile() -> CommandResult<Option<UserContext>> {
 pub async fn sign_out() -> CommandResultEmpty {
     invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|sign_out"), &EmptyArgs {}).await
 }
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignInArgs<'a> {
+    token: &'a str,
+    url: &'a Url,
+    password: Option<&'a str>,
+    salt: Option<&'a str>,
+    auth_scheme: AuthScheme,
+    key_fingerprint: Option<&'a str>,
+}
+
+pub async fn sign_in(
+    token: &str,
+    url: &Url,
+    password: Option<&str>,
+    salt: Option<&str>,
+    auth_scheme: AuthScheme,
+    key_fingerprint: Option<&str>,
+) -> CommandResultEmpty {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|sign_in"),
+        &SignInArgs {
+            token,
+            url,
+            password,
+            salt,
+            auth_scheme,
+            key_fingerprint,
+        },
+    )
+    .await
+}
+
+pub async fn export_recovery_kit() -> CommandResult<Option<PathBuf>> {
+    invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|export_recovery_kit"), &EmptyArgs {}).await
+}
+
+pub async fn import_recovery_kit() -> CommandResult<Option<RecoveryKit>> {
+    invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|import_recovery_kit"), &EmptyArgs {}).await
+}
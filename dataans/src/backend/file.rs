@@ -1,8 +1,9 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use common::APP_PLUGIN_NAME;
 use common::error::{CommandResult, CommandResultEmpty};
-use common::note::File;
+use common::note::{File, FileRepair, FilesReport};
+use common::space::{Id as SpaceId, SpaceFile};
 use serde::Serialize;
 use uuid::Uuid;
 
@@ -24,6 +25,24 @@ pub async fn upload_file(id: Uuid, name: &str, data: &[u8]) -> CommandResult<Fil
     .await
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportFileArgs<'path> {
+    id: Uuid,
+    path: &'path Path,
+}
+
+/// Imports the file from the local file system.
+///
+/// Unlike [upload_file], the file content is not sent over IPC, so it is suitable for large files.
+pub async fn import_file(id: Uuid, path: &Path) -> CommandResult<File> {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|import_file"),
+        &ImportFileArgs { id, path },
+    )
+    .await
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct FileId {
@@ -34,6 +53,59 @@ pub async fn remove_file(id: Uuid) -> CommandResultEmpty {
     invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|delete_file"), &FileId { id }).await
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThumbnailArgs {
+    file_id: Uuid,
+    size: u32,
+}
+
+/// Returns the image thumbnail path relative to the files directory.
+///
+/// `None` means the file is not a supported image or it is not downloaded yet.
+pub async fn thumbnail(file_id: Uuid, size: u32) -> CommandResult<Option<PathBuf>> {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|thumbnail"),
+        &ThumbnailArgs { file_id, size },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpaceFilesArgs {
+    space_id: SpaceId,
+}
+
+/// Returns files attached to the notes of the space.
+pub async fn space_files(space_id: SpaceId) -> CommandResult<Vec<SpaceFile>> {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|space_files"),
+        &SpaceFilesArgs { space_id },
+    )
+    .await
+}
+
+/// Checks the integrity of local files.
+pub async fn verify_files() -> CommandResult<FilesReport> {
+    invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|verify_files"), &EmptyArgs {}).await
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RepairFilesArgs {
+    repairs: Vec<FileRepair>,
+}
+
+/// Applies the repairs and returns the updated files integrity report.
+pub async fn repair_files(repairs: Vec<FileRepair>) -> CommandResult<FilesReport> {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|repair_files"),
+        &RepairFilesArgs { repairs },
+    )
+    .await
+}
+
 pub async fn gen_avatar() -> CommandResult<File> {
     invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|gen_random_avatar"), &EmptyArgs {}).await
 }
@@ -1,5 +1,6 @@
 use common::APP_PLUGIN_NAME;
 use common::error::CommandResult;
+use common::import::{ImportReport, MdImportConfig};
 use serde::Serialize;
 
 use super::EmptyArgs;
@@ -11,13 +12,47 @@ struct ImportConfig {
     path: String,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppDataImportArgs {
+    path: String,
+    passphrase: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MdImportArgs {
+    path: String,
+    config: MdImportConfig,
+}
+
 pub async fn select_import_file() -> CommandResult<Option<String>> {
     invoke_command("select_import_file", &EmptyArgs {}).await
 }
 
-pub async fn import_app_data(path: String) -> CommandResult<()> {
+pub async fn import_app_data(path: String, passphrase: Option<String>) -> CommandResult<()> {
     invoke_command(
         &format!("plugin:{APP_PLUGIN_NAME}|import_app_data"),
+        &AppDataImportArgs { path, passphrase },
+    )
+    .await
+}
+
+pub async fn select_import_dir() -> CommandResult<Option<String>> {
+    invoke_command("select_import_dir", &EmptyArgs {}).await
+}
+
+pub async fn import_md_dir(path: String, config: MdImportConfig) -> CommandResult<ImportReport> {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|import_md_dir"),
+        &MdImportArgs { path, config },
+    )
+    .await
+}
+
+pub async fn import_telegram(path: String) -> CommandResult<ImportReport> {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|import_telegram"),
         &ImportConfig { path },
     )
     .await
@@ -6,6 +6,7 @@ pub mod import;
 pub mod notes;
 pub mod spaces;
 pub mod sync;
+pub mod vault;
 pub mod window;
 
 use std::path::Path;
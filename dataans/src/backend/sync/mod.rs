@@ -1,15 +1,19 @@
 mod event;
 
+use std::path::PathBuf;
+
 use common::APP_PLUGIN_NAME;
+use common::common_api_types::Usage;
 use common::error::{CommandResult, CommandResultEmpty};
 use common::event::{
-    DATA_EVENT, DataEvent, STATUS_UPDATE_EVENT, StatusUpdateEvent, USER_CONTEXT_EVENT, UserContextEvent,
+    DATA_EVENT, DataEvent, FILE_IMPORT_PROGRESS_EVENT, FileImportProgress, STATUS_UPDATE_EVENT, StatusUpdateEvent,
+    USER_CONTEXT_EVENT, UserContextEvent,
 };
 use common::profile::{Sync, UserContext};
 use futures::StreamExt;
 use leptoaster::ToasterContext;
 use leptos::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::GlobalState;
 use crate::backend::{EmptyArgs, invoke_command};
@@ -60,6 +64,22 @@ pub async fn on_status_update(toaster: ToasterContext) -> CommandResultEmpty {
                         .with_expiry(Some(5000)),
                 );
             }
+            StatusUpdateEvent::AuthTokenExpiresSoon(minutes_left) => {
+                warn!(minutes_left, "Auth token expires soon");
+                let message = if minutes_left == 0 {
+                    "Authorization token has expired. Please, sign in again or update the token in the sync settings to continue synchronization.".to_owned()
+                } else {
+                    format!(
+                        "Authorization token expires in {minutes_left} minute(s). Please, sign in again or update the token in the sync settings to continue synchronization."
+                    )
+                };
+                toaster.toast(
+                    leptoaster::ToastBuilder::new(message)
+                        .with_level(leptoaster::ToastLevel::Warn)
+                        .with_position(leptoaster::ToastPosition::BottomRight)
+                        .with_expiry(Some(10000)),
+                );
+            }
         }
     }
 
@@ -138,6 +158,45 @@ pub async fn on_data(data: RwSignal<GlobalState>) -> CommandResultEmpty {
     Ok(())
 }
 
+/// Files dropped onto the app window.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FilesDrop {
+    /// Paths of the dropped files.
+    pub paths: Vec<PathBuf>,
+    /// Drop position in physical pixels relative to the window.
+    pub position: DropPosition,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DropPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+pub async fn on_files_drop(handle: impl Fn(FilesDrop)) -> CommandResultEmpty {
+    let mut events = event::listen::<FilesDrop>(event::DRAG_DROP).await?;
+
+    while let Some(event) = events.next().await {
+        debug!(?event, "Event received:");
+
+        handle(event.payload);
+    }
+
+    Ok(())
+}
+
+pub async fn on_file_import_progress(handle: impl Fn(FileImportProgress)) -> CommandResultEmpty {
+    let mut events = event::listen::<FileImportProgress>(FILE_IMPORT_PROGRESS_EVENT).await?;
+
+    while let Some(event) = events.next().await {
+        trace!(?event, "Event received:");
+
+        handle(event.payload);
+    }
+
+    Ok(())
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncConfigArgs<'a> {
@@ -156,3 +215,8 @@ pub async fn set_sync_options(sync_config: &Sync) -> CommandResult<UserContext>
 pub async fn trigger_full_sync() -> CommandResultEmpty {
     invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|full_sync"), &EmptyArgs {}).await
 }
+
+/// Returns the storage usage on the sync server.
+pub async fn sync_usage() -> CommandResult<Usage> {
+    invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|sync_usage"), &EmptyArgs {}).await
+}
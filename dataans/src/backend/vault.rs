@@ -0,0 +1,26 @@
+use common::error::{CommandResult, CommandResultEmpty};
+use common::{APP_PLUGIN_NAME, VaultsInfo};
+use serde::Serialize;
+
+use crate::backend::{EmptyArgs, invoke_command};
+
+pub async fn list_vaults() -> CommandResult<VaultsInfo> {
+    invoke_command(&format!("plugin:{APP_PLUGIN_NAME}|list_vaults"), &EmptyArgs {}).await
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SwitchVaultArgs<'a> {
+    name: &'a str,
+}
+
+/// Switches the opened vault.
+///
+/// All app windows are reloaded after the switch.
+pub async fn switch_vault(name: &str) -> CommandResultEmpty {
+    invoke_command(
+        &format!("plugin:{APP_PLUGIN_NAME}|switch_vault"),
+        &SwitchVaultArgs { name },
+    )
+    .await
+}
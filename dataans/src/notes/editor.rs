@@ -1,14 +1,29 @@
+use std::path::PathBuf;
+
 use common::note::{CreateNote, DraftNote, File, Note};
 use common::space::Id as SpaceId;
+use futures::FutureExt;
+use futures::future::{AbortHandle, Abortable};
 use gloo_storage::{LocalStorage, Storage};
+use leptos::html;
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 use uuid::Uuid;
 use web_sys::KeyboardEvent;
 
-use crate::backend::file::remove_file;
+use crate::backend::file::{import_file, remove_file};
+use crate::backend::sync::{DropPosition, on_file_import_progress, on_files_drop};
 use crate::common::{Attachment, Files, TextArea};
 
+/// A file which is being imported into the draft note.
+#[derive(Debug, Clone, PartialEq)]
+struct FileImport {
+    id: Uuid,
+    name: String,
+    imported: u64,
+    total: u64,
+}
+
 #[component]
 pub fn Editor(space_id: SpaceId, #[prop(into)] create_note: Callback<(Note<'static>,), ()>) -> impl IntoView {
     let toaster = leptoaster::expect_toaster();
@@ -57,6 +72,87 @@ pub fn Editor(space_id: SpaceId, #[prop(into)] create_note: Callback<(Note<'stat
         }
     };
 
+    let editor_ref = NodeRef::<html::Div>::new();
+    let (imports, set_imports) = signal(Vec::<FileImport>::new());
+
+    let t = toaster.clone();
+    let import_files = move |paths: Vec<PathBuf>| {
+        for path in paths {
+            let id = Uuid::new_v4();
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            set_imports.update(|imports| {
+                imports.push(FileImport {
+                    id,
+                    name,
+                    imported: 0,
+                    total: 0,
+                })
+            });
+
+            let toaster = t.clone();
+            spawn_local(async move {
+                let file = import_file(id, &path).await;
+                set_imports.update(|imports| imports.retain(|import| import.id != id));
+
+                let file = try_exec!(file, "Failed to import file", toaster);
+                if let Some(DraftNote { text, mut files }) = draft_note.try_get_untracked() {
+                    files.push(file);
+                    set_draft_note(DraftNote { text, files });
+                }
+            });
+        }
+    };
+
+    // Tauri reports the drop position in physical pixels.
+    let is_over_editor = move |position: DropPosition| {
+        let Some(editor) = editor_ref.get_untracked() else {
+            return false;
+        };
+        let rect = editor.get_bounding_client_rect();
+        let scale = window().device_pixel_ratio();
+        let (x, y) = (position.x / scale, position.y / scale);
+
+        rect.left() <= x && x <= rect.right() && rect.top() <= y && y <= rect.bottom()
+    };
+
+    // Listeners are stopped when the editor is unmounted.
+    let (listeners, registration) = AbortHandle::new_pair();
+    let t = toaster.clone();
+    spawn_local(
+        Abortable::new(
+            async move {
+                let files_drop = on_files_drop(move |files_drop| {
+                    if is_over_editor(files_drop.position) {
+                        import_files(files_drop.paths);
+                    }
+                });
+                let import_progress = on_file_import_progress(move |progress| {
+                    set_imports.update(|imports| {
+                        if let Some(import) = imports
+                            .iter_mut()
+                            .find(|import| import.id == *progress.file_id.as_ref())
+                        {
+                            import.imported = progress.imported;
+                            import.total = progress.total;
+                        }
+                    });
+                });
+
+                try_exec!(
+                    futures::try_join!(files_drop, import_progress),
+                    "Failed to listen on file drop events",
+                    t
+                );
+            },
+            registration,
+        )
+        .map(|_| ()),
+    );
+    on_cleanup(move || listeners.abort());
+
     let toaster = toaster.clone();
     let remove_file = Callback::<(File,), ()>::new(
         move |(File {
@@ -94,7 +190,7 @@ pub fn Editor(space_id: SpaceId, #[prop(into)] create_note: Callback<(Note<'stat
     };
 
     view! {
-        <div class="editor-container">
+        <div class="editor-container" node_ref=editor_ref>
             <div class="horizontal">
                 <TextArea
                     id="create_note".to_owned()
@@ -116,6 +212,19 @@ pub fn Editor(space_id: SpaceId, #[prop(into)] create_note: Callback<(Note<'stat
             <div class="editor-meta">
                 {move || view!{ <Files files=draft_note.get().files.clone() remove_file edit_mode=true /> }}
             </div>
+            {move || {
+                let imports = imports.get();
+                (!imports.is_empty()).then(|| view! {
+                    <div class="file-imports">
+                        {imports.into_iter().map(|FileImport { id: _, name, imported, total }| view! {
+                            <div class="file-import" title=name.clone()>
+                                <span>{name}</span>
+                                <progress max=total.max(1).to_string() value=imported.to_string() />
+                            </div>
+                        }).collect_view()}
+                    </div>
+                })
+            }}
         </div>
     }
 }
@@ -17,6 +17,7 @@ pub fn Info(
     set_spaces: SignalSetter<Vec<OwnedSpace>>,
     delete_state_space: SignalSetter<SpaceId>,
     #[prop(into)] toggle_note_search: Callback<(), ()>,
+    #[prop(into)] show_gallery: Callback<(), ()>,
     #[prop(into)] set_selected_space: Callback<(OwnedSpace,), ()>,
     config: Config,
 ) -> impl IntoView {
@@ -71,6 +72,13 @@ pub fn Info(
                     >
                         <img alt="find note" src="/public/icons/search.svg" />
                     </button>
+                    <button
+                        class="tool"
+                        title="Space files"
+                        on:click=move |_| show_gallery.run(())
+                    >
+                        <img alt="space files" src="/public/icons/attachment.png" />
+                    </button>
                     <button
                         class="tool"
                         title="Edit space info"
@@ -1,3 +1,72 @@
+use std::path::Path;
+
+use common::DEFAULT_THUMBNAIL_SIZE;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use uuid::Uuid;
+
+use crate::backend::convert_file_src;
+use crate::backend::file::{open, thumbnail};
+
+/// Renders the note image.
+///
+/// Images in the notes list are rendered from their thumbnails. The original image is opened on click.
+#[component]
+pub fn NoteImage(url: String, alt: String, title: Option<String>, base_path: String) -> impl IntoView {
+    let style = parse_image_size(&alt);
+
+    // Images with a custom size are rendered from the original file: the thumbnail can be too small for them.
+    let file_id = if style.is_none() { file_id_from_path(&url) } else { None };
+    let image_thumbnail = LocalResource::new(move || async move {
+        let file_id = file_id?;
+
+        match thumbnail(file_id, DEFAULT_THUMBNAIL_SIZE).await {
+            Ok(thumbnail) => thumbnail,
+            Err(err) => {
+                warn!(?err, ?file_id, "Failed to load the image thumbnail");
+
+                None
+            }
+        }
+    });
+
+    let original_src = convert_file_src(&url, &base_path);
+    let src = move || {
+        image_thumbnail
+            .get()
+            .flatten()
+            .and_then(|thumbnail| {
+                thumbnail
+                    .to_str()
+                    .map(|thumbnail| convert_file_src(thumbnail, &base_path))
+            })
+            .unwrap_or_else(|| original_src.clone())
+    };
+
+    let open_image = move |_| {
+        let path = url.clone();
+        spawn_local(async move {
+            open(Path::new(&path)).await;
+        })
+    };
+
+    view! {
+        <img src=src alt=alt class="note-image" style=style on:click=open_image title=title />
+    }
+}
+
+/// Extracts the file id from the attachment file path.
+///
+/// Attachment files are stored as `<file id>_<file name>` or `<file id>.<extension>`.
+fn file_id_from_path(path: &str) -> Option<Uuid> {
+    let id = path.get(..36)?;
+
+    match path.get(36..37) {
+        Some("_") | Some(".") => Uuid::parse_str(id).ok(),
+        _ => None,
+    }
+}
+
 /// Parses the image alt text to extract an information about its size.
 ///
 /// Originally, the MD does not allow resizing images.
@@ -116,7 +185,9 @@ fn is_valid_dimension(dimension: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_image_size;
+    use uuid::uuid;
+
+    use super::{file_id_from_path, parse_image_size};
 
     #[test]
     fn parse_valid_image_size() {
@@ -144,4 +215,19 @@ mod tests {
         // Empty dimensions.
         assert_eq!(parse_image_size("="), None);
     }
+
+    #[test]
+    fn parse_file_id_from_path() {
+        let id = uuid!("54d49bda-644e-44a9-a1ad-4a8fa5f368a5");
+
+        assert_eq!(file_id_from_path("54d49bda-644e-44a9-a1ad-4a8fa5f368a5.png"), Some(id));
+        assert_eq!(
+            file_id_from_path("54d49bda-644e-44a9-a1ad-4a8fa5f368a5_screenshot.jpg"),
+            Some(id)
+        );
+
+        assert_eq!(file_id_from_path("54d49bda-644e-44a9-a1ad-4a8fa5f368a5"), None);
+        assert_eq!(file_id_from_path("https://example.com/image.png"), None);
+        assert_eq!(file_id_from_path("/public/default_space_avatar.png"), None);
+    }
 }
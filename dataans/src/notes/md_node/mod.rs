@@ -3,18 +3,13 @@ mod image;
 mod inline_code;
 mod list_item;
 
-use std::path::Path;
-
 pub use inline_code::InlineCode;
 use leptos::prelude::*;
-use leptos::task::spawn_local;
 use markdown::mdast::Node;
 
 use self::code_block::CodeBlock;
+use self::image::NoteImage;
 use self::list_item::ListItem;
-use crate::backend::convert_file_src;
-use crate::backend::file::open;
-use crate::notes::md_node::image::parse_image_size;
 
 pub fn render_md_node(node: &Node, base_path: &str) -> AnyView {
     match node {
@@ -152,19 +147,8 @@ pub fn render_md_node(node: &Node, base_path: &str) -> AnyView {
             </div>
         }
         .into_any(),
-        Node::Image(image) => {
-            let image_path = image.url.clone();
-            let open_image = move |_| {
-                let path = image_path.clone();
-                spawn_local(async move {
-                    open(Path::new(&path)).await;
-                })
-            };
-            let alt = image.alt.clone();
-            let style = parse_image_size(&alt);
-            view! {
-                <img src=convert_file_src(&image.url, base_path) alt={alt} class="note-image" style={style} on:click=open_image title={image.title.clone()} />
-            }
+        Node::Image(image) => view! {
+            <NoteImage url=image.url.clone() alt=image.alt.clone() title=image.title.clone() base_path=base_path.to_owned() />
         }
         .into_any(),
         Node::Table(table) => view! {
@@ -138,6 +138,12 @@ pub fn Notes() -> impl IntoView {
                             });
                             focus_element(SEARCH_NOTE_INPUT_ID);
                         }
+                        show_gallery=move || {
+                            set_spaces_minimized.set(false);
+                            set_find_node_mode.set(FindNoteMode::Gallery {
+                                space: current_space.get().unwrap(),
+                            });
+                        }
                         set_selected_space
                         config=config.get()
                     />
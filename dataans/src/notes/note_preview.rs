@@ -18,6 +18,13 @@ pub fn NotePreview(
     };
 
     let note_id = note.id;
+    let matched_files = note
+        .files
+        .iter()
+        .filter(|file| note.matched_files.contains(&file.id))
+        .map(|file| file.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
 
     view! {
         <div class=class on:click=move |_| set_selected_note.run((note_id,))>
@@ -26,6 +33,11 @@ pub fn NotePreview(
                 <div class="vertical">
                     <span class="note-preview-space-name">{note.space.name.to_string()}</span>
                     <span class="note-preview-note-text">{note_preview_text(note.text.as_ref())}</span>
+                    {(!matched_files.is_empty()).then(|| view! {
+                        <span class="note-preview-matched-files" title="Attachments that match the query">
+                            {format!("Found in: {matched_files}")}
+                        </span>
+                    })}
                 </div>
             </Show>
         </div>
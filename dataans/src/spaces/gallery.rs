@@ -0,0 +1,167 @@
+use common::note::{File, Id as NoteId};
+use common::space::{OwnedSpace, SpaceFile};
+use common::{Config, DEFAULT_THUMBNAIL_SIZE};
+use leptos::callback::Callback;
+use leptos::prelude::*;
+
+use crate::backend::convert_file_src;
+use crate::backend::file::{space_files, thumbnail};
+use crate::spaces::Space;
+
+/// Kind of the attached file. Used to filter files in the gallery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    Image,
+    Document,
+    Other,
+}
+
+impl FileKind {
+    fn from_name(name: &str) -> Self {
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "svg" | "tiff" | "ico" | "avif" => FileKind::Image,
+            "pdf" | "txt" | "md" | "rtf" | "doc" | "docx" | "odt" | "xls" | "xlsx" | "ods" | "csv" | "ppt" | "pptx"
+            | "odp" | "epub" => FileKind::Document,
+            _ => FileKind::Other,
+        }
+    }
+}
+
+/// Gallery filters. `None` means all files.
+const FILTERS: [(Option<FileKind>, &str); 4] = [
+    (None, "All"),
+    (Some(FileKind::Image), "Images"),
+    (Some(FileKind::Document), "Documents"),
+    (Some(FileKind::Other), "Other"),
+];
+
+/// Renders all files attached to the notes of the space.
+#[component]
+pub fn Gallery(
+    space: OwnedSpace,
+    spaces_minimized: Signal<bool>,
+    #[prop(into)] focus_note: Callback<(NoteId, OwnedSpace), ()>,
+) -> impl IntoView {
+    let global_config = expect_context::<RwSignal<Config>>();
+    let (filter, set_filter) = signal(None::<FileKind>);
+
+    let space_id = space.id;
+    let files = LocalResource::new(move || async move { space_files(space_id).await });
+
+    let space_data = space.clone();
+
+    view! {
+        <div class="spaces-scroll-area">
+            <div class="note-search-options">
+                <span class="note-search-label">"Files in:"</span>
+                <Space
+                    space=space_data
+                    base_path=global_config.get_untracked().app.base_path
+                    set_selected_space=|_: OwnedSpace| {}
+                    selected=true
+                    minimized=spaces_minimized
+                />
+            </div>
+            <div class="gallery-filters">
+                {FILTERS.into_iter().map(|(kind, label)| view! {
+                    <button
+                        class=move || if filter.get() == kind { "gallery-filter gallery-filter-selected" } else { "gallery-filter" }
+                        on:click=move |_| set_filter.set(kind)
+                    >
+                        {label}
+                    </button>
+                }).collect_view()}
+            </div>
+            <Suspense fallback=move || view! { <span class="note-search-label">"Loading files..."</span> }>
+                {move || files.get().map(|files| match files {
+                    Ok(files) => {
+                        let filter = filter.get();
+                        let files = files
+                            .into_iter()
+                            .filter(|space_file| filter.is_none_or(|kind| FileKind::from_name(&space_file.file.name) == kind))
+                            .collect::<Vec<_>>();
+
+                        if files.is_empty() {
+                            return view! { <span class="note-search-label">"No files."</span> }.into_any();
+                        }
+
+                        let base_path = global_config.get().app.base_path;
+                        view! {
+                            <div class="gallery-grid">
+                                {files.into_iter().map(|SpaceFile { note_id, file }| {
+                                    let space = space.clone();
+                                    view! {
+                                        <GalleryItem
+                                            file
+                                            base_path=base_path.clone()
+                                            open_note=move || focus_note.run((note_id, space.clone()))
+                                        />
+                                    }
+                                }).collect_view()}
+                            </div>
+                        }.into_any()
+                    }
+                    Err(err) => view! {
+                        <span class="note-search-label">{format!("Failed to load files: {err}")}</span>
+                    }.into_any(),
+                })}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn GalleryItem(file: File, base_path: String, #[prop(into)] open_note: Callback<(), ()>) -> impl IntoView {
+    let is_image = FileKind::from_name(&file.name) == FileKind::Image;
+    let file_id = *file.id.as_ref();
+
+    // The default thumbnail size is used to reuse thumbnails cached for the notes list.
+    let image_thumbnail = LocalResource::new(move || async move {
+        if !is_image {
+            return None;
+        }
+
+        match thumbnail(file_id, DEFAULT_THUMBNAIL_SIZE).await {
+            Ok(thumbnail) => thumbnail,
+            Err(err) => {
+                warn!(?err, ?file_id, "Failed to load the image thumbnail");
+
+                None
+            }
+        }
+    });
+
+    let preview = move || match image_thumbnail.get().flatten().and_then(|thumbnail| {
+        thumbnail
+            .to_str()
+            .map(|thumbnail| convert_file_src(thumbnail, &base_path))
+    }) {
+        Some(src) => view! { <img class="gallery-item-image" src=src alt="" /> }.into_any(),
+        None => view! { <img class="gallery-item-icon" src="/public/icons/file.png" alt="" /> }.into_any(),
+    };
+
+    view! {
+        <button class="gallery-item" title=format!("{}\nClick to jump to the note", file.name) on:click=move |_| open_note.run(())>
+            {preview}
+            <span class="gallery-item-name">{file.name.clone()}</span>
+        </button>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileKind;
+
+    #[test]
+    fn file_kinds() {
+        assert_eq!(FileKind::from_name("screenshot.PNG"), FileKind::Image);
+        assert_eq!(FileKind::from_name("report.final.pdf"), FileKind::Document);
+        assert_eq!(FileKind::from_name("archive.tar.gz"), FileKind::Other);
+        assert_eq!(FileKind::from_name("Makefile"), FileKind::Other);
+    }
+}
@@ -1,4 +1,5 @@
 mod found_notes_list;
+mod gallery;
 mod space;
 pub mod space_form;
 mod spaces_list;
@@ -14,6 +15,7 @@ use leptos::task::spawn_local;
 use leptos_use::{use_document, use_event_listener};
 
 use self::found_notes_list::FoundNotesList;
+use self::gallery::Gallery;
 use self::space::Space;
 use self::spaces_list::SpacesList;
 use self::tools::Tools;
@@ -99,26 +101,30 @@ pub fn Spaces(spaces: Signal<Vec<OwnedSpace>>, set_spaces: SignalSetter<Vec<Owne
             }}
             {move || {
                 let config = global_config.get();
-                match find_note_mode.get() {
+                let find_note_mode = find_note_mode.get();
+                if find_note_mode != FindNoteMode::None {
+                    let _ = use_event_listener(use_document(), keydown, move |ev| {
+                        if ev.key() == "Escape" {
+                            ev.prevent_default();
+                            set_find_node_mode.set(FindNoteMode::None)
+                        }
+                    });
+                }
+
+                match find_note_mode {
                     FindNoteMode::None => view!{
                         <SpacesList config selected_space spaces spaces_minimized set_selected_space />
                     }.into_any(),
-                    FindNoteMode::FindNote { space } => {
-                        let _ = use_event_listener(use_document(), keydown, move |ev| {
-                            if ev.key() == "Escape" {
-                                ev.prevent_default();
-                                set_find_node_mode.set(FindNoteMode::None)
-                            }
-                        });
-
-                        view! {
-                            <FoundNotesList config query search_in_space=space spaces_minimized focus_note />
-                        }
+                    FindNoteMode::FindNote { space } => view! {
+                        <FoundNotesList config query search_in_space=space spaces_minimized focus_note />
+                    }.into_any(),
+                    FindNoteMode::Gallery { space } => view! {
+                        <Gallery space spaces_minimized focus_note />
                     }.into_any(),
                 }
             }}
             <div style="flex-grow: 1; align-content: end; display: flex; flex-direction: column; align-items: center; justify-content: flex-end;">
-                {move || if let Some(UserContext { sync_config: Sync { mode: SyncMode::Manual, .. } }) = user_context.get() {
+                {move || if let Some(UserContext { sync_config: Sync { mode: SyncMode::Manual, .. }, .. }) = user_context.get() {
                     let sync_toaster = toaster.clone();
                     let start_full_sync = move |_| {
                         let t = sync_toaster.clone();